@@ -2,13 +2,15 @@ use anyhow::Result;
 use std::{collections::HashSet, env, path::PathBuf, sync::Arc, time::Duration};
 
 use screenpipe_core::Language;
-use screenpipe_db::DatabaseManager;
+use screenpipe_db::{DatabaseManager, DEFAULT_SPEAKER_MATCH_THRESHOLD};
 
 use crate::{
     core::{
         device::{default_input_device, default_output_device},
-        engine::AudioTranscriptionEngine,
+        encoding::AudioEncoding,
+        engine::{AudioTranscriptionEngine, RealtimeTranscriptionEngine},
     },
+    processing::AudioProcessingConfig,
     transcription::deepgram::CUSTOM_DEEPGRAM_API_TOKEN,
     vad::{VadEngineEnum, VadSensitivity},
 };
@@ -23,6 +25,7 @@ pub struct AudioManagerOptions {
     pub deepgram_api_key: Option<String>,
     pub enable_diarization: bool,
     pub enable_realtime: bool,
+    pub realtime_engine: RealtimeTranscriptionEngine,
     pub audio_chunk_duration: Duration,
     pub vad_sensitivity: VadSensitivity,
     pub health_check_grace_period: u64,
@@ -31,6 +34,15 @@ pub struct AudioManagerOptions {
     pub db_path: Option<String>,
     pub deepgram_url: Option<String>,
     pub deepgram_websocket_url: Option<String>,
+    pub openai_compatible_url: Option<String>,
+    pub openai_compatible_model: Option<String>,
+    pub openai_compatible_api_key: Option<String>,
+    /// Cosine distance under which a voice is attributed to a known speaker
+    pub speaker_match_threshold: f64,
+    /// Gain control, noise suppression and echo cancellation applied per device
+    pub audio_processing: AudioProcessingConfig,
+    /// Codec and bitrate audio chunks are stored with
+    pub audio_encoding: AudioEncoding,
     pub output_path: Option<PathBuf>,
 }
 
@@ -48,6 +60,7 @@ impl Default for AudioManagerOptions {
             deepgram_api_key,
             enable_diarization: true,
             enable_realtime: false,
+            realtime_engine: RealtimeTranscriptionEngine::default(),
             audio_chunk_duration: Duration::from_secs(30),
             vad_sensitivity: VadSensitivity::High,
             health_check_grace_period: 15,
@@ -56,6 +69,12 @@ impl Default for AudioManagerOptions {
             db_path: None,
             deepgram_url,
             deepgram_websocket_url,
+            openai_compatible_url: None,
+            openai_compatible_model: None,
+            openai_compatible_api_key: None,
+            speaker_match_threshold: DEFAULT_SPEAKER_MATCH_THRESHOLD,
+            audio_processing: AudioProcessingConfig::default(),
+            audio_encoding: AudioEncoding::default(),
         }
     }
 }
@@ -102,6 +121,11 @@ impl AudioManagerBuilder {
         self
     }
 
+    pub fn realtime_engine(mut self, realtime_engine: RealtimeTranscriptionEngine) -> Self {
+        self.options.realtime_engine = realtime_engine;
+        self
+    }
+
     pub fn audio_chunk_duration(mut self, audio_chunk_duration: Duration) -> Self {
         self.options.audio_chunk_duration = audio_chunk_duration;
         self
@@ -137,6 +161,37 @@ impl AudioManagerBuilder {
         self
     }
 
+    /// Server used by [`AudioTranscriptionEngine::OpenAiCompatible`].
+    pub fn openai_compatible_url(mut self, openai_compatible_url: Option<String>) -> Self {
+        self.options.openai_compatible_url = openai_compatible_url;
+        self
+    }
+
+    pub fn openai_compatible_model(mut self, openai_compatible_model: Option<String>) -> Self {
+        self.options.openai_compatible_model = openai_compatible_model;
+        self
+    }
+
+    pub fn openai_compatible_api_key(mut self, openai_compatible_api_key: Option<String>) -> Self {
+        self.options.openai_compatible_api_key = openai_compatible_api_key;
+        self
+    }
+
+    pub fn speaker_match_threshold(mut self, speaker_match_threshold: f64) -> Self {
+        self.options.speaker_match_threshold = speaker_match_threshold;
+        self
+    }
+
+    pub fn audio_processing(mut self, audio_processing: AudioProcessingConfig) -> Self {
+        self.options.audio_processing = audio_processing;
+        self
+    }
+
+    pub fn audio_encoding(mut self, audio_encoding: AudioEncoding) -> Self {
+        self.options.audio_encoding = audio_encoding;
+        self
+    }
+
     pub async fn build(&mut self, db: Arc<DatabaseManager>) -> Result<AudioManager> {
         self.validate_options()?;
         let options = &mut self.options;
@@ -166,15 +221,24 @@ impl AudioManagerBuilder {
             ));
         }
 
+        if self.options.transcription_engine == Arc::new(AudioTranscriptionEngine::OpenAiCompatible)
+            && self.options.openai_compatible_url.is_none()
+        {
+            return Err(anyhow::anyhow!(
+                "A server url is required for the OpenAI compatible transcription engine"
+            ));
+        }
+
         if self.options.output_path.is_none() {
             return Err(anyhow::anyhow!("Output path is required for audio manager"));
         }
 
         if self.options.enable_realtime
+            && self.options.realtime_engine == RealtimeTranscriptionEngine::Deepgram
             && (self.options.deepgram_api_key.is_none() && CUSTOM_DEEPGRAM_API_TOKEN.is_empty())
         {
             return Err(anyhow::anyhow!(
-                "Deepgram API key is required for realtime transcription"
+                "Deepgram API key is required for realtime transcription with Deepgram"
             ));
         }
 
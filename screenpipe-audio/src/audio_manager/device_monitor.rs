@@ -4,7 +4,10 @@ use anyhow::Result;
 use tokio::{sync::Mutex, task::JoinHandle, time::sleep};
 use tracing::{error, info};
 
-use crate::{core::device::parse_audio_device, device::device_manager::DeviceManager};
+use crate::{
+    core::device::{parse_audio_device, DeviceType},
+    device::device_manager::DeviceManager,
+};
 
 use super::{AudioManager, AudioManagerStatus};
 
@@ -23,7 +26,15 @@ pub async fn start_device_monitor(
         loop {
             if audio_manager.status().await == AudioManagerStatus::Running {
                 let currently_available_devices = device_manager.devices().await;
-                let enabled_devices = audio_manager.enabled_devices().await;
+                // output devices first, so that inputs can cancel their echo from the start
+                let mut enabled_devices: Vec<String> =
+                    audio_manager.enabled_devices().await.into_iter().collect();
+                enabled_devices.sort_by_key(|name| {
+                    !matches!(
+                        parse_audio_device(name),
+                        Ok(device) if device.device_type == DeviceType::Output
+                    )
+                });
                 for device_name in disconnected_devices.clone() {
                     let device = match parse_audio_device(&device_name) {
                         Ok(device) => device,
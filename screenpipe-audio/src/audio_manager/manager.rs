@@ -2,32 +2,39 @@ use anyhow::{anyhow, Result};
 use dashmap::DashMap;
 use std::{
     collections::HashSet,
-    path::PathBuf,
     sync::{atomic::Ordering, Arc},
 };
 use tokio::{
     join,
-    sync::{Mutex, RwLock},
+    sync::{mpsc, Mutex, RwLock},
     task::JoinHandle,
 };
 use tracing::{error, info, warn};
-use whisper_rs::WhisperContext;
 
-use screenpipe_db::DatabaseManager;
+use screenpipe_db::{DatabaseManager, Speaker};
 
 use super::{start_device_monitor, stop_device_monitor, AudioManagerOptions};
 use crate::{
     core::{
-        device::{parse_audio_device, AudioDevice},
+        device::{parse_audio_device, AudioDevice, DeviceType},
+        engine::{AudioTranscriptionEngine, RealtimeTranscriptionEngine},
         record_and_transcribe,
     },
     device::device_manager::DeviceManager,
+    processing::echo_cancellation::EchoSources,
     segmentation::segmentation_manager::SegmentationManager,
+    speaker::enrollment::{compute_clip_embedding, SpeakerClip},
     transcription::{
+        create_transcription_engine, create_whisper_engine,
         deepgram::streaming::stream_transcription_deepgram,
         handle_new_transcript,
+        retranscription::{
+            create_retranscription_worker, RetranscriptionCommand, RetranscriptionResponse,
+            RetranscriptionStatus,
+        },
         stt::process_audio_input,
-        whisper::model::{create_whisper_context_parameters, download_whisper_model},
+        whisper::streaming::stream_transcription_whisper,
+        TranscriptionEngine,
     },
     vad::{silero::SileroVad, webrtc::WebRtcVad, VadEngine, VadEngineEnum},
     AudioInput, TranscriptionResult,
@@ -57,7 +64,34 @@ pub struct AudioManager {
     transcription_sender: Arc<crossbeam::channel::Sender<TranscriptionResult>>,
     transcription_receiver_handle: Arc<RwLock<Option<JoinHandle<()>>>>,
     recording_receiver_handle: Arc<RwLock<Option<JoinHandle<()>>>>,
-    stt_model_path: PathBuf,
+    /// Local Whisper model, shared by batch and realtime transcription. Not loaded when
+    /// transcribing with a server and realtime transcription doesn't run locally.
+    whisper_engine: Option<Arc<dyn TranscriptionEngine>>,
+    /// Whisper model publishing live transcriptions, shared by all devices. Only set when
+    /// realtime transcription runs locally.
+    realtime_transcription_engine: Option<Arc<dyn TranscriptionEngine>>,
+    retranscription: Arc<Mutex<Option<RetranscriptionJob>>>,
+}
+
+/// A re-transcription worker and the engine it re-transcribes with.
+struct RetranscriptionJob {
+    engine: Arc<AudioTranscriptionEngine>,
+    commands: mpsc::Sender<RetranscriptionCommand>,
+    responses: mpsc::Receiver<RetranscriptionResponse>,
+}
+
+impl RetranscriptionJob {
+    async fn send(&mut self, command: RetranscriptionCommand) -> Result<RetranscriptionStatus> {
+        self.commands
+            .send(command)
+            .await
+            .map_err(|_| anyhow!("re-transcription worker stopped"))?;
+        self.responses
+            .recv()
+            .await
+            .map(|response| response.status)
+            .ok_or_else(|| anyhow!("re-transcription worker stopped"))
+    }
 }
 
 impl AudioManager {
@@ -74,10 +108,27 @@ impl AudioManager {
         let (transcription_sender, transcription_receiver) = crossbeam::channel::bounded(1000);
 
         let recording_handles = DashMap::new();
-        let stt_model_path = download_whisper_model(options.transcription_engine.clone())?;
-
         whisper_rs::install_logging_hooks();
 
+        let realtime_whisper = options.enable_realtime
+            && options.realtime_engine == RealtimeTranscriptionEngine::Whisper;
+        // loaded once, a second context would hold another copy of the model in memory
+        let whisper_engine = if realtime_whisper
+            || *options.transcription_engine != AudioTranscriptionEngine::OpenAiCompatible
+        {
+            Some(create_whisper_engine(
+                options.transcription_engine.clone(),
+                None,
+            )?)
+        } else {
+            None
+        };
+        let realtime_transcription_engine = if realtime_whisper {
+            whisper_engine.clone()
+        } else {
+            None
+        };
+
         let manager = Self {
             options: Arc::new(RwLock::new(options)),
             device_manager: Arc::new(device_manager),
@@ -92,7 +143,9 @@ impl AudioManager {
             recording_handles: Arc::new(recording_handles),
             recording_receiver_handle: Arc::new(RwLock::new(None)),
             transcription_receiver_handle: Arc::new(RwLock::new(None)),
-            stt_model_path,
+            whisper_engine,
+            realtime_transcription_engine,
+            retranscription: Arc::new(Mutex::new(None)),
         };
 
         Ok(manager)
@@ -243,23 +296,62 @@ impl AudioManager {
         let languages = options.languages.clone();
         let deepgram_api_key = options.deepgram_api_key.clone();
         let realtime_enabled = options.enable_realtime;
+        let realtime_transcription_engine = self.realtime_transcription_engine.clone();
+        let vad_engine = options.vad_engine.clone();
         let device_clone = device.clone();
 
+        let processing = options.audio_processing.for_device(device);
+        // echo is cancelled against the enabled output devices, whichever stream records them
+        let echo_sources =
+            if processing.echo_cancellation && device.device_type == DeviceType::Input {
+                let devices: Vec<_> = self
+                    .enabled_devices()
+                    .await
+                    .iter()
+                    .filter_map(|name| parse_audio_device(name).ok())
+                    .filter(|d| d.device_type == DeviceType::Output)
+                    .collect();
+                if devices.is_empty() {
+                    warn!(
+                        "no output device is enabled, echo cancellation is off for {}",
+                        device
+                    );
+                }
+                let device_manager = self.device_manager.clone();
+                Some(EchoSources {
+                    devices,
+                    lookup: Arc::new(move |d: &AudioDevice| device_manager.stream(d)),
+                })
+            } else {
+                None
+            };
+
         let recording_handle = tokio::spawn(async move {
             let record_and_transcribe_handle = tokio::spawn(record_and_transcribe(
                 stream.clone(),
                 audio_chunk_duration,
                 recording_sender.clone(),
                 is_running.clone(),
+                processing,
+                echo_sources,
             ));
 
             let realtime_handle = if realtime_enabled {
-                Some(tokio::spawn(stream_transcription_deepgram(
-                    stream,
-                    languages,
-                    is_running,
-                    deepgram_api_key,
-                )))
+                Some(match realtime_transcription_engine {
+                    Some(transcription_engine) => tokio::spawn(stream_transcription_whisper(
+                        stream,
+                        languages,
+                        is_running,
+                        transcription_engine,
+                        vad_engine,
+                    )),
+                    None => tokio::spawn(stream_transcription_deepgram(
+                        stream,
+                        languages,
+                        is_running,
+                        deepgram_api_key,
+                    )),
+                })
             } else {
                 None
             };
@@ -308,17 +400,11 @@ impl AudioManager {
         let options = self.options.read().await;
         let output_path = options.output_path.clone();
         let languages = options.languages.clone();
-        let deepgram_api_key = options.deepgram_api_key.clone();
-        let audio_transcription_engine = options.transcription_engine.clone();
+        let audio_encoding = options.audio_encoding;
         let vad_engine = self.vad_engine.clone();
         let whisper_receiver = self.recording_receiver.clone();
-        let context_param = create_whisper_context_parameters(audio_transcription_engine.clone())?;
-
-        let quantized_path = self.stt_model_path.clone();
-        let whisper_context = Arc::new(
-            WhisperContext::new_with_params(&quantized_path.to_string_lossy(), context_param)
-                .expect("failed to load model"),
-        );
+        let transcription_engine =
+            create_transcription_engine(&options, self.whisper_engine.clone())?;
 
         Ok(tokio::spawn(async move {
             while let Ok(audio) = whisper_receiver.recv() {
@@ -330,11 +416,10 @@ impl AudioManager {
                     embedding_manager.clone(),
                     embedding_extractor.clone(),
                     &output_path.clone().unwrap(),
-                    audio_transcription_engine.clone(),
-                    deepgram_api_key.clone(),
+                    transcription_engine.clone(),
                     languages.clone(),
                     &transcription_sender.clone(),
-                    whisper_context.clone(),
+                    audio_encoding,
                 )
                 .await
                 {
@@ -347,11 +432,12 @@ impl AudioManager {
     async fn start_transcription_receiver_handler(&self) -> Result<JoinHandle<()>> {
         let transcription_receiver = self.transcription_receiver.clone();
         let db = self.db.clone();
-        let transcription_engine = self.options.read().await.transcription_engine.clone();
+        let options = self.options.read().await;
         Ok(tokio::spawn(handle_new_transcript(
             db,
             transcription_receiver,
-            transcription_engine,
+            options.transcription_engine.clone(),
+            options.speaker_match_threshold,
         )))
     }
 
@@ -386,6 +472,82 @@ impl AudioManager {
     pub async fn enabled_devices(&self) -> HashSet<String> {
         self.options.read().await.enabled_devices.clone()
     }
+
+    /// Sends `command` to the job re-transcribing stored audio. `Start` creates the job if
+    /// there is none, re-transcribing with `engine`, or the configured engine if not given.
+    pub async fn retranscribe(
+        &self,
+        command: RetranscriptionCommand,
+        engine: Option<AudioTranscriptionEngine>,
+    ) -> Result<RetranscriptionStatus> {
+        let mut job = self.retranscription.lock().await;
+
+        if matches!(command, RetranscriptionCommand::Start) {
+            let engine = match engine {
+                Some(engine) => Arc::new(engine),
+                None => self.options.read().await.transcription_engine.clone(),
+            };
+
+            if let Some(current) = job.as_mut() {
+                if current.engine != engine {
+                    match current.send(RetranscriptionCommand::Status).await? {
+                        RetranscriptionStatus::Running { .. }
+                        | RetranscriptionStatus::Paused { .. } => {
+                            return Err(anyhow!(
+                                "audio is being re-transcribed with {}, stop it first",
+                                current.engine
+                            ));
+                        }
+                        _ => *job = None,
+                    }
+                }
+            }
+
+            if job.is_none() {
+                let mut options = self.options.read().await.clone();
+                options.transcription_engine = engine.clone();
+                let (commands, responses, _) =
+                    create_retranscription_worker(self.db.clone(), options, None);
+                *job = Some(RetranscriptionJob {
+                    engine,
+                    commands,
+                    responses,
+                });
+            }
+        }
+
+        match job.as_mut() {
+            Some(job) => job.send(command).await,
+            None => Ok(RetranscriptionStatus::NotStarted),
+        }
+    }
+
+    /// Enrolls the speaker heard in `clips`, adding their voice to `speaker_id` or to a new
+    /// speaker, so that they are recognised in later recordings.
+    pub async fn enroll_speaker(
+        &self,
+        speaker_id: Option<i64>,
+        name: Option<String>,
+        clips: Vec<SpeakerClip>,
+    ) -> Result<Speaker> {
+        if clips.is_empty() {
+            return Err(anyhow!("no audio clips to enroll the speaker from"));
+        }
+
+        let embedding_extractor = self.segmentation_manager.embedding_extractor.clone();
+        let embeddings = tokio::task::spawn_blocking(move || {
+            clips
+                .iter()
+                .map(|clip| compute_clip_embedding(&embedding_extractor, clip))
+                .collect::<Result<Vec<_>>>()
+        })
+        .await??;
+
+        Ok(self
+            .db
+            .enroll_speaker(speaker_id, name.as_deref(), &embeddings)
+            .await?)
+    }
 }
 
 impl Drop for AudioManager {
@@ -0,0 +1,143 @@
+use anyhow::{bail, Result};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{pcm_decode, utils::ffmpeg::write_audio_to_file};
+
+/// Codec audio chunks are stored with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum AudioCodec {
+    /// AAC in an mp4 container
+    #[default]
+    Aac,
+    /// Opus in an ogg container, the smallest files for speech
+    Opus,
+    /// Lossless
+    Flac,
+    Mp3,
+    /// Uncompressed 16-bit PCM
+    Wav,
+}
+
+impl AudioCodec {
+    pub const ALL: [AudioCodec; 5] = [
+        AudioCodec::Aac,
+        AudioCodec::Opus,
+        AudioCodec::Flac,
+        AudioCodec::Mp3,
+        AudioCodec::Wav,
+    ];
+
+    /// Extension of the files written with this codec, also the ffmpeg muxer writing them.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "mp4",
+            AudioCodec::Opus => "ogg",
+            AudioCodec::Flac => "flac",
+            AudioCodec::Mp3 => "mp3",
+            AudioCodec::Wav => "wav",
+        }
+    }
+
+    /// Codec of an audio file, from its extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        AudioCodec::ALL
+            .into_iter()
+            .find(|codec| codec.extension() == extension)
+    }
+
+    /// Bitrate used when none is configured, in kbit/s. Lossless codecs have none.
+    pub fn default_bitrate(&self) -> Option<u32> {
+        match self {
+            AudioCodec::Aac => Some(64),
+            // opus keeps speech intelligible far below what the other codecs need
+            AudioCodec::Opus => Some(24),
+            AudioCodec::Mp3 => Some(64),
+            AudioCodec::Flac | AudioCodec::Wav => None,
+        }
+    }
+}
+
+impl fmt::Display for AudioCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioCodec::Aac => write!(f, "aac"),
+            AudioCodec::Opus => write!(f, "opus"),
+            AudioCodec::Flac => write!(f, "flac"),
+            AudioCodec::Mp3 => write!(f, "mp3"),
+            AudioCodec::Wav => write!(f, "wav"),
+        }
+    }
+}
+
+impl FromStr for AudioCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AudioCodec::ALL
+            .into_iter()
+            .find(|codec| codec.to_string() == s.to_lowercase())
+            .ok_or_else(|| format!("unknown audio codec: {}", s))
+    }
+}
+
+/// How audio chunks are encoded when they are written to disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AudioEncoding {
+    pub codec: AudioCodec,
+    /// In kbit/s, ignored by lossless codecs
+    pub bitrate: Option<u32>,
+}
+
+impl AudioEncoding {
+    /// Uses the default bitrate of the codec when `bitrate` is `None`.
+    pub fn new(codec: AudioCodec, bitrate: Option<u32>) -> Self {
+        Self {
+            codec,
+            bitrate: bitrate
+                .filter(|_| codec.default_bitrate().is_some())
+                .or(codec.default_bitrate()),
+        }
+    }
+}
+
+impl Default for AudioEncoding {
+    fn default() -> Self {
+        Self::new(AudioCodec::default(), None)
+    }
+}
+
+impl fmt::Display for AudioEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bitrate {
+            Some(bitrate) => write!(f, "{} {}k", self.codec, bitrate),
+            None => write!(f, "{}", self.codec),
+        }
+    }
+}
+
+/// Re-encodes the audio file at `path` with `encoding` into a file next to it, named after
+/// the new codec, and returns its path. The original file is left in place, unless only the
+/// bitrate changes, then the new file replaces it.
+pub fn recompress_audio_file(path: &Path, encoding: AudioEncoding) -> Result<PathBuf> {
+    let new_path = path.with_extension(encoding.codec.extension());
+    if new_path == path && encoding.bitrate.is_none() {
+        bail!("{:?} is already encoded with {}", path, encoding.codec);
+    }
+
+    let (samples, sample_rate) = pcm_decode(path)?;
+    if new_path != path {
+        write_audio_to_file(&samples, sample_rate, &new_path, false, encoding)?;
+        return Ok(new_path);
+    }
+
+    // written aside first, the original is never left half overwritten
+    let temp_path = path.with_extension(format!("recompressed.{}", encoding.codec.extension()));
+    write_audio_to_file(&samples, sample_rate, &temp_path, false, encoding)?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(new_path)
+}
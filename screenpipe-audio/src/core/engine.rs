@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
 #[derive(Clone, Debug, PartialEq, Default)]
 pub enum AudioTranscriptionEngine {
@@ -10,6 +10,8 @@ pub enum AudioTranscriptionEngine {
     WhisperLargeV3TurboQuantized,
     WhisperLargeV3,
     WhisperLargeV3Quantized,
+    /// Any server implementing OpenAI's `/v1/audio/transcriptions` endpoint
+    OpenAiCompatible,
 }
 
 impl fmt::Display for AudioTranscriptionEngine {
@@ -26,6 +28,47 @@ impl fmt::Display for AudioTranscriptionEngine {
             AudioTranscriptionEngine::WhisperLargeV3TurboQuantized => {
                 write!(f, "WhisperLargeV3TurboQuantized")
             }
+            AudioTranscriptionEngine::OpenAiCompatible => write!(f, "OpenAiCompatible"),
+        }
+    }
+}
+
+/// Parses the names written by `Display`, which are stored with each transcription.
+impl FromStr for AudioTranscriptionEngine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Deepgram" => Ok(AudioTranscriptionEngine::Deepgram),
+            "WhisperTiny" => Ok(AudioTranscriptionEngine::WhisperTiny),
+            "WhisperTinyQuantized" => Ok(AudioTranscriptionEngine::WhisperTinyQuantized),
+            "WhisperLargeV3" => Ok(AudioTranscriptionEngine::WhisperLargeV3),
+            "WhisperLargeV3Quantized" => Ok(AudioTranscriptionEngine::WhisperLargeV3Quantized),
+            "WhisperLargeV3Turbo" => Ok(AudioTranscriptionEngine::WhisperLargeV3Turbo),
+            "WhisperLargeV3TurboQuantized" => {
+                Ok(AudioTranscriptionEngine::WhisperLargeV3TurboQuantized)
+            }
+            "OpenAiCompatible" => Ok(AudioTranscriptionEngine::OpenAiCompatible),
+            _ => Err(format!("unknown transcription engine: {}", s)),
+        }
+    }
+}
+
+/// Engine publishing live `transcription` events while audio is recorded.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum RealtimeTranscriptionEngine {
+    /// Streams the audio to Deepgram
+    #[default]
+    Deepgram,
+    /// Runs the local Whisper model on the speech as it is detected, no network needed
+    Whisper,
+}
+
+impl fmt::Display for RealtimeTranscriptionEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RealtimeTranscriptionEngine::Deepgram => write!(f, "Deepgram"),
+            RealtimeTranscriptionEngine::Whisper => write!(f, "Whisper"),
         }
     }
 }
@@ -1,7 +1,9 @@
 pub mod device;
+pub mod encoding;
 pub mod engine;
 mod run_record_and_transcribe;
 pub mod stream;
+use crate::processing::{echo_cancellation::EchoSources, AudioProcessing};
 use crate::transcription::deepgram::streaming::stream_transcription_deepgram;
 use crate::AudioInput;
 use anyhow::Result;
@@ -56,11 +58,16 @@ fn is_normal_shutdown(is_running: &Arc<AtomicBool>) -> bool {
     !is_running.load(Ordering::Relaxed)
 }
 
+/// Records `audio_stream` in chunks of `duration` and sends them to be transcribed.
+/// `echo_sources` are the output devices whose audio is cancelled from an input device when
+/// `processing` enables echo cancellation.
 pub async fn record_and_transcribe(
     audio_stream: Arc<AudioStream>,
     duration: Duration,
     whisper_sender: Arc<crossbeam::channel::Sender<AudioInput>>,
     is_running: Arc<AtomicBool>,
+    processing: AudioProcessing,
+    echo_sources: Option<EchoSources>,
 ) -> Result<()> {
     while is_running.load(Ordering::Relaxed) {
         match run_record_and_transcribe::run_record_and_transcribe(
@@ -68,6 +75,8 @@ pub async fn record_and_transcribe(
             duration,
             whisper_sender.clone(),
             is_running.clone(),
+            processing,
+            echo_sources.clone(),
         )
         .await
         {
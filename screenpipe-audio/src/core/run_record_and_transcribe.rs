@@ -7,9 +7,17 @@ use std::{
 };
 
 use anyhow::{anyhow, Result};
+use chrono::Utc;
 use tracing::{debug, error, info, warn};
 
-use crate::{core::update_device_capture_time, AudioInput};
+use crate::{
+    core::update_device_capture_time,
+    processing::{
+        echo_cancellation::{EchoReference, EchoSources},
+        AudioProcessing, AudioProcessor,
+    },
+    AudioInput,
+};
 
 use super::AudioStream;
 
@@ -18,10 +26,26 @@ pub async fn run_record_and_transcribe(
     duration: Duration,
     whisper_sender: Arc<crossbeam::channel::Sender<AudioInput>>,
     is_running: Arc<AtomicBool>,
+    processing: AudioProcessing,
+    echo_sources: Option<EchoSources>,
 ) -> Result<()> {
     let mut receiver = audio_stream.subscribe().await;
     let device_name = audio_stream.device.to_string();
 
+    let echo_reference = match &echo_sources {
+        Some(sources) if processing.echo_cancellation => Some(EchoReference::from_sources(sources)),
+        _ => None,
+    };
+    let mut processor = processing.is_enabled().then(|| {
+        info!("processing audio of {} with {}", device_name, processing);
+        AudioProcessor::new(
+            processing,
+            &audio_stream.device.device_type,
+            audio_stream.device_config.sample_rate().0,
+            echo_reference,
+        )
+    });
+
     info!(
         "starting continuous recording for {} ({}s segments)",
         device_name,
@@ -40,7 +64,12 @@ pub async fn run_record_and_transcribe(
     {
         while collected_audio.len() < max_samples && is_running.load(Ordering::Relaxed) {
             match receiver.recv().await {
-                Ok(chunk) => {
+                Ok(mut chunk) => {
+                    if let Some(processor) = &mut processor {
+                        if let Err(e) = processor.process(&mut chunk) {
+                            warn!("audio processing failed for {}: {}", device_name, e);
+                        }
+                    }
                     collected_audio.extend(chunk);
                     update_device_capture_time(&device_name);
                 }
@@ -53,11 +82,15 @@ pub async fn run_record_and_transcribe(
 
         if !collected_audio.is_empty() {
             debug!("sending audio segment to audio model");
+            let recorded_for =
+                chrono::Duration::milliseconds((collected_audio.len() * 1000 / sample_rate) as i64);
             match whisper_sender.try_send(AudioInput {
                 data: Arc::new(collected_audio.clone()),
                 device: audio_stream.device.clone(),
                 sample_rate: audio_stream.device_config.sample_rate().0,
                 channels: audio_stream.device_config.channels(),
+                processed: processor.is_some(),
+                recorded_at: Utc::now() - recorded_for,
             }) {
                 Ok(_) => {
                     debug!("sent audio segment to audio model");
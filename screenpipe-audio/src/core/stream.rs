@@ -106,6 +106,11 @@ impl AudioStream {
     }
 
     pub async fn subscribe(&self) -> broadcast::Receiver<Vec<f32>> {
+        self.receiver()
+    }
+
+    /// Same as [`Self::subscribe`], for callers that can't await.
+    pub fn receiver(&self) -> broadcast::Receiver<Vec<f32>> {
         self.transmitter.subscribe()
     }
 
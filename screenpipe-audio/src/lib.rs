@@ -2,11 +2,13 @@ pub mod core;
 mod utils;
 pub mod vad;
 pub use transcription::stt::stt;
-pub use transcription::{AudioInput, TranscriptionResult};
+pub use transcription::{AudioInput, Transcript, TranscriptionResult};
 pub mod speaker;
 pub mod transcription;
 pub use utils::audio::pcm_decode;
 pub use utils::audio::resample;
+pub use utils::ffmpeg::write_audio_to_file;
 pub mod audio_manager;
+pub mod processing;
 mod device;
 mod segmentation;
@@ -0,0 +1,490 @@
+use anyhow::Result;
+use realfft::num_complex::Complex32;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use std::collections::VecDeque;
+use std::sync::{Arc, Weak};
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+use crate::core::{device::AudioDevice, stream::AudioStream};
+
+/// Longest echo path cancelled, long enough for speakers and a mic in the same room
+const TAIL_SECONDS: f32 = 0.25;
+/// How much earlier output audio may reach the canceller than the mic audio it is heard in.
+/// The mic is delayed by as much, so that the echo never comes before its reference
+const LOOKAHEAD_SECONDS: f32 = 0.05;
+/// Longest delay between the reference and its echo that is found and made up for
+const MAX_DELAY_SECONDS: f32 = 1.0;
+/// Audio correlated to find the delay, which is estimated again as often
+const DELAY_WINDOW_SECONDS: f32 = 0.5;
+/// Correlation between the mic and the delayed reference needed to trust a delay
+const DELAY_CONFIDENCE: f32 = 0.3;
+/// Adaptation step of the filter
+const STEP_SIZE: f32 = 0.5;
+/// The mic is louder than this share of the speaker audio only when someone talks over it,
+/// the filter is frozen then so that it doesn't learn to cancel the local voice
+const DOUBLE_TALK_RATIO: f32 = 0.5;
+/// Speaker audio quieter than this holds nothing to learn the echo from
+const SILENT_REFERENCE: f32 = 1e-3;
+
+/// Acoustic echo canceller removing what the speakers play from the audio of a microphone.
+/// An adaptive filter, split in blocks and run in the frequency domain, learns the path from
+/// the speakers to the mic and subtracts the echo it predicts from the reference audio.
+/// The output and mic streams aren't in step, so the delay of the echo is estimated by
+/// cross-correlation and the reference delayed to bring the echo within the filter.
+///
+/// Output lags input by one block (~10ms) and the lookahead (50ms).
+pub struct EchoCanceller {
+    block: usize,
+    /// Mic audio waiting out the lookahead
+    near_delay: VecDeque<f32>,
+    /// Latest reference audio, long enough to delay it by up to the longest delay
+    far_history: VecDeque<f32>,
+    /// Latest mic audio after the lookahead, correlated with the reference to find the delay
+    near_history: VecDeque<f32>,
+    /// Samples the reference is delayed by before the filter
+    delay: usize,
+    delay_estimator: DelayEstimator,
+    since_estimate: usize,
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+    reference: Vec<f32>,
+    /// Spectra of the latest reference blocks, newest first
+    partitions: VecDeque<Vec<Complex32>>,
+    weights: Vec<Vec<Complex32>>,
+    /// Loudest reference sample of the latest blocks, newest first
+    reference_peaks: VecDeque<f32>,
+    scratch: Vec<f32>,
+    spectrum: Vec<Complex32>,
+    echo: Vec<Complex32>,
+    near_pending: Vec<f32>,
+    far_pending: Vec<f32>,
+    output: VecDeque<f32>,
+    next_constraint: usize,
+}
+
+impl EchoCanceller {
+    pub fn new(sample_rate: u32) -> Self {
+        let block = (sample_rate as usize / 100).next_power_of_two();
+        let partition_count = ((TAIL_SECONDS * sample_rate as f32) / block as f32).ceil() as usize;
+        let lookahead = (LOOKAHEAD_SECONDS * sample_rate as f32) as usize;
+        let max_delay = (MAX_DELAY_SECONDS * sample_rate as f32) as usize;
+        let window = (DELAY_WINDOW_SECONDS * sample_rate as f32) as usize;
+
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(2 * block);
+        let inverse = planner.plan_fft_inverse(2 * block);
+        let spectrum = forward.make_output_vec();
+        let zeros = vec![Complex32::default(); spectrum.len()];
+
+        Self {
+            block,
+            near_delay: VecDeque::from(vec![0.0; lookahead]),
+            far_history: VecDeque::with_capacity(max_delay + window),
+            near_history: VecDeque::with_capacity(window),
+            delay: 0,
+            delay_estimator: DelayEstimator::new(&mut planner, window, max_delay),
+            since_estimate: 0,
+            forward,
+            inverse,
+            reference: vec![0.0; 2 * block],
+            partitions: VecDeque::from(vec![zeros.clone(); partition_count]),
+            weights: vec![zeros.clone(); partition_count],
+            reference_peaks: VecDeque::from(vec![0.0; partition_count]),
+            scratch: vec![0.0; 2 * block],
+            spectrum,
+            echo: zeros,
+            near_pending: Vec::with_capacity(block),
+            far_pending: Vec::with_capacity(block),
+            output: VecDeque::from(vec![0.0; block]),
+            next_constraint: 0,
+        }
+    }
+
+    /// Removes the echo of `far`, the audio played at the same time, from the mic audio
+    /// `near` in place. Missing reference samples are taken as silence.
+    pub fn process(&mut self, near: &mut [f32], far: &[f32]) -> Result<()> {
+        let window = self.delay_estimator.window;
+        for (i, &sample) in near.iter().enumerate() {
+            self.near_delay.push_back(sample);
+            let near_sample = self.near_delay.pop_front().unwrap_or(0.0);
+
+            if self.far_history.len() == window + self.delay_estimator.max_delay {
+                self.far_history.pop_front();
+            }
+            self.far_history
+                .push_back(far.get(i).copied().unwrap_or(0.0));
+            if self.near_history.len() == window {
+                self.near_history.pop_front();
+            }
+            self.near_history.push_back(near_sample);
+
+            let far_sample = self
+                .far_history
+                .len()
+                .checked_sub(self.delay + 1)
+                .map_or(0.0, |index| self.far_history[index]);
+            self.near_pending.push(near_sample);
+            self.far_pending.push(far_sample);
+            if self.near_pending.len() == self.block {
+                self.process_block()?;
+            }
+
+            self.since_estimate += 1;
+            if self.since_estimate == window {
+                self.since_estimate = 0;
+                self.update_delay()?;
+            }
+        }
+
+        for sample in near.iter_mut() {
+            *sample = self.output.pop_front().unwrap_or(0.0);
+        }
+        Ok(())
+    }
+
+    /// Moves the delay of the reference when the echo no longer falls within the filter, and
+    /// starts learning the echo path again since the filter was learnt at the old delay.
+    fn update_delay(&mut self) -> Result<()> {
+        let Some(lag) = self
+            .delay_estimator
+            .estimate(&self.near_history, &self.far_history)?
+        else {
+            return Ok(());
+        };
+        let tail = self.weights.len() * self.block;
+        if lag >= self.delay && lag - self.delay < tail - self.block {
+            return Ok(());
+        }
+
+        // leave room in the filter for the echo arriving a bit earlier or later
+        self.delay = lag.saturating_sub(tail / 4);
+        for (weights, partition) in self.weights.iter_mut().zip(&mut self.partitions) {
+            weights.fill(Complex32::default());
+            partition.fill(Complex32::default());
+        }
+        self.reference.fill(0.0);
+        self.reference_peaks.iter_mut().for_each(|peak| *peak = 0.0);
+        Ok(())
+    }
+
+    fn process_block(&mut self) -> Result<()> {
+        let block = self.block;
+        let scale = 1.0 / (2 * block) as f32;
+
+        // overlap-save: each transform covers the previous block and the new one
+        self.reference.copy_within(block.., 0);
+        self.reference[block..].copy_from_slice(&self.far_pending);
+        self.scratch.copy_from_slice(&self.reference);
+        self.forward
+            .process(&mut self.scratch, &mut self.spectrum)?;
+        let mut newest = self.partitions.pop_back().unwrap_or_default();
+        newest.copy_from_slice(&self.spectrum);
+        self.partitions.push_front(newest);
+
+        // predicted echo, the last half of the circular convolution is the linear one
+        self.echo.fill(Complex32::default());
+        for (weights, partition) in self.weights.iter().zip(&self.partitions) {
+            for ((echo, w), x) in self.echo.iter_mut().zip(weights).zip(partition) {
+                *echo += w * x;
+            }
+        }
+        clear_edge_phases(&mut self.echo);
+        self.inverse.process(&mut self.echo, &mut self.scratch)?;
+
+        let mut error = vec![0.0; block];
+        for (i, e) in error.iter_mut().enumerate() {
+            *e = self.near_pending[i] - self.scratch[block + i] * scale;
+        }
+        self.output.extend(&error);
+
+        let near_peak = self.near_pending.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        let far_peak = self.far_pending.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        self.reference_peaks.pop_back();
+        self.reference_peaks.push_front(far_peak);
+        self.near_pending.clear();
+        self.far_pending.clear();
+
+        let reference_peak = self.reference_peaks.iter().fold(0.0f32, |m, &p| m.max(p));
+        if reference_peak < SILENT_REFERENCE || near_peak > DOUBLE_TALK_RATIO * reference_peak {
+            return Ok(());
+        }
+
+        self.scratch[..block].fill(0.0);
+        self.scratch[block..].copy_from_slice(&error);
+        self.forward
+            .process(&mut self.scratch, &mut self.spectrum)?;
+
+        // normalized update, each bin scaled by the reference power over the whole filter
+        let regularization = (2 * block) as f32 * SILENT_REFERENCE * SILENT_REFERENCE;
+        for (k, error) in self.spectrum.iter().enumerate() {
+            let power: f32 = self.partitions.iter().map(|p| p[k].norm_sqr()).sum();
+            let step = error * (STEP_SIZE / (power + regularization));
+            for (weights, partition) in self.weights.iter_mut().zip(&self.partitions) {
+                weights[k] += partition[k].conj() * step;
+            }
+        }
+
+        // keep one partition a linear filter per block, the others follow in turn
+        let weights = &mut self.weights[self.next_constraint];
+        self.echo.copy_from_slice(weights);
+        clear_edge_phases(&mut self.echo);
+        self.inverse.process(&mut self.echo, &mut self.scratch)?;
+        for sample in self.scratch[..block].iter_mut() {
+            *sample *= scale;
+        }
+        self.scratch[block..].fill(0.0);
+        self.forward.process(&mut self.scratch, weights)?;
+        self.next_constraint = (self.next_constraint + 1) % self.weights.len();
+
+        Ok(())
+    }
+}
+
+/// The DC and nyquist bins of a real signal have no imaginary part.
+fn clear_edge_phases(spectrum: &mut [Complex32]) {
+    let last = spectrum.len() - 1;
+    spectrum[0].im = 0.0;
+    spectrum[last].im = 0.0;
+}
+
+/// Finds the delay between the reference and its echo in the mic audio by cross-correlating
+/// them in the frequency domain.
+struct DelayEstimator {
+    window: usize,
+    max_delay: usize,
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+}
+
+impl DelayEstimator {
+    fn new(planner: &mut RealFftPlanner<f32>, window: usize, max_delay: usize) -> Self {
+        // long enough that correlating at every delay doesn't wrap around
+        let size = (window + max_delay).next_power_of_two();
+        Self {
+            window,
+            max_delay,
+            forward: planner.plan_fft_forward(size),
+            inverse: planner.plan_fft_inverse(size),
+        }
+    }
+
+    /// Samples by which the echo in the latest `window` samples of `near` follows `far`, both
+    /// ending at the same time. `None` when there is too little audio to tell.
+    fn estimate(&self, near: &VecDeque<f32>, far: &VecDeque<f32>) -> Result<Option<usize>> {
+        let window = self.window;
+        if near.len() < window || far.len() < window {
+            return Ok(None);
+        }
+        let max_lag = far.len() - window;
+
+        let near_energy: f32 = near.iter().map(|s| s * s).sum();
+        // energy of each window of the reference, from the prefix sums of its power
+        let mut prefix = Vec::with_capacity(far.len() + 1);
+        prefix.push(0.0f64);
+        for s in far {
+            prefix.push(prefix[prefix.len() - 1] + (s * s) as f64);
+        }
+        let silent = (window as f32 * SILENT_REFERENCE * SILENT_REFERENCE) as f64;
+        if (near_energy as f64) < silent || prefix[far.len()] - prefix[max_lag] < silent {
+            return Ok(None);
+        }
+
+        let mut near_input = self.forward.make_input_vec();
+        let mut far_input = self.forward.make_input_vec();
+        for (input, s) in near_input.iter_mut().zip(near) {
+            *input = *s;
+        }
+        for (input, s) in far_input.iter_mut().zip(far) {
+            *input = *s;
+        }
+        let mut near_spectrum = self.forward.make_output_vec();
+        let mut far_spectrum = self.forward.make_output_vec();
+        self.forward.process(&mut near_input, &mut near_spectrum)?;
+        self.forward.process(&mut far_input, &mut far_spectrum)?;
+        for (f, n) in far_spectrum.iter_mut().zip(&near_spectrum) {
+            *f *= n.conj();
+        }
+        clear_edge_phases(&mut far_spectrum);
+        let mut correlation = self.inverse.make_output_vec();
+        self.inverse.process(&mut far_spectrum, &mut correlation)?;
+
+        // correlation[offset] pairs the mic window with the reference window starting at
+        // `offset`, which the mic follows by `max_lag - offset` samples
+        let scale = 1.0 / correlation.len() as f64;
+        let mut best: Option<(usize, f64)> = None;
+        for offset in 0..=max_lag {
+            let far_energy = prefix[offset + window] - prefix[offset];
+            if far_energy < silent {
+                continue;
+            }
+            let score = (correlation[offset] as f64 * scale).abs()
+                / (near_energy as f64 * far_energy).sqrt();
+            if best.map_or(true, |(_, best_score)| score > best_score) {
+                best = Some((offset, score));
+            }
+        }
+
+        Ok(best
+            .filter(|&(_, score)| score >= DELAY_CONFIDENCE as f64)
+            .map(|(offset, _)| max_lag - offset))
+    }
+}
+
+/// Finds the stream an output device is currently recorded with.
+pub type StreamLookup = Arc<dyn Fn(&AudioDevice) -> Option<Arc<AudioStream>> + Send + Sync>;
+
+/// Output devices whose audio is cancelled from an input device. Their streams are looked up
+/// rather than held, since an output device is restarted on its own when it disconnects.
+#[derive(Clone)]
+pub struct EchoSources {
+    pub devices: Vec<AudioDevice>,
+    pub lookup: StreamLookup,
+}
+
+/// An output device followed across restarts of its stream.
+struct FollowedDevice {
+    device: AudioDevice,
+    lookup: StreamLookup,
+    stream: Weak<AudioStream>,
+}
+
+struct ReferenceSource {
+    receiver: Option<broadcast::Receiver<Vec<f32>>>,
+    sample_rate: u32,
+    samples: VecDeque<f32>,
+    /// Fraction of a sample left over when resampling the previous chunk
+    carry: f64,
+    followed: Option<FollowedDevice>,
+}
+
+impl ReferenceSource {
+    /// Subscribes to the current stream of the followed device when it was restarted or the
+    /// stream subscribed to closed.
+    fn follow_device(&mut self) {
+        let Some(followed) = &mut self.followed else {
+            return;
+        };
+        match (followed.lookup)(&followed.device) {
+            Some(stream)
+                if self.receiver.is_none()
+                    || !Weak::ptr_eq(&followed.stream, &Arc::downgrade(&stream)) =>
+            {
+                self.receiver = Some(stream.receiver());
+                self.sample_rate = stream.device_config.sample_rate().0;
+                self.samples.clear();
+                self.carry = 0.0;
+                followed.stream = Arc::downgrade(&stream);
+            }
+            Some(_) => {}
+            None => self.receiver = None,
+        }
+    }
+}
+
+/// Audio played by the output devices, the reference an [`EchoCanceller`] cancels. Every
+/// time the mic delivers a chunk the same duration of output audio is taken, so the two
+/// stay in step. Output audio buffered beyond that is kept within the canceller's lookahead,
+/// the canceller makes up for the reference being ahead of the echo.
+#[derive(Default)]
+pub struct EchoReference {
+    sources: Vec<ReferenceSource>,
+}
+
+impl EchoReference {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follows the audio of the given output devices from now on, through restarts of their
+    /// streams.
+    pub fn from_sources(sources: &EchoSources) -> Self {
+        let mut reference = Self::new();
+        for device in &sources.devices {
+            let mut source = ReferenceSource {
+                receiver: None,
+                sample_rate: 0,
+                samples: VecDeque::new(),
+                carry: 0.0,
+                followed: Some(FollowedDevice {
+                    device: device.clone(),
+                    lookup: sources.lookup.clone(),
+                    stream: Weak::new(),
+                }),
+            };
+            source.follow_device();
+            reference.sources.push(source);
+        }
+        reference
+    }
+
+    pub fn add_source(&mut self, receiver: broadcast::Receiver<Vec<f32>>, sample_rate: u32) {
+        self.sources.push(ReferenceSource {
+            receiver: Some(receiver),
+            sample_rate,
+            samples: VecDeque::new(),
+            carry: 0.0,
+            followed: None,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// The next `len` samples of output audio at `sample_rate`, all output devices mixed.
+    pub fn take(&mut self, len: usize, sample_rate: u32) -> Vec<f32> {
+        let mut mixed = vec![0.0; len];
+
+        for source in &mut self.sources {
+            source.follow_device();
+            let Some(receiver) = &mut source.receiver else {
+                continue;
+            };
+            loop {
+                match receiver.try_recv() {
+                    Ok(chunk) => source.samples.extend(chunk),
+                    // what was buffered is out of step with the mic now
+                    Err(TryRecvError::Lagged(_)) => source.samples.clear(),
+                    Err(TryRecvError::Closed) => {
+                        // the stream stopped, the next take picks up the one replacing it
+                        source.receiver = None;
+                        break;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                }
+            }
+
+            let exact = len as f64 * source.sample_rate as f64 / sample_rate as f64 + source.carry;
+            let needed = exact.floor() as usize;
+            source.carry = exact - needed as f64;
+
+            // output left over after this take is older than the mic audio it's taken for, past
+            // the lookahead the echo would come before its reference and can't be cancelled
+            let lookahead = (LOOKAHEAD_SECONDS * source.sample_rate as f32) as usize;
+            if source.samples.len() > needed + lookahead {
+                let excess = source.samples.len() - needed - lookahead / 2;
+                source.samples.drain(..excess);
+            }
+            if needed == 0 {
+                continue;
+            }
+
+            let available = needed.min(source.samples.len());
+            let mut samples: Vec<f32> = source.samples.drain(..available).collect();
+            samples.resize(needed, 0.0);
+
+            // linear interpolation is plenty for a reference the filter adapts to anyway
+            let ratio = needed as f64 / len as f64;
+            for (i, out) in mixed.iter_mut().enumerate() {
+                let position = i as f64 * ratio;
+                let index = position as usize;
+                let fraction = (position - index as f64) as f32;
+                let a = samples[index.min(needed - 1)];
+                let b = samples[(index + 1).min(needed - 1)];
+                *out += a + (b - a) * fraction;
+            }
+        }
+
+        mixed
+    }
+}
@@ -0,0 +1,59 @@
+/// RMS level speech is brought to
+const TARGET_RMS: f32 = 0.1;
+const MIN_GAIN: f32 = 0.1;
+/// +20dB, enough for a quiet laptop mic without blowing up the noise floor
+const MAX_GAIN: f32 = 10.0;
+/// Chunks quieter than this are silence and leave the gain where it is
+const NOISE_GATE_RMS: f32 = 0.001;
+/// How fast the gain drops when the audio gets loud, in seconds
+const ATTACK_SECONDS: f32 = 0.05;
+/// How fast the gain rises again when the audio gets quiet, in seconds
+const RELEASE_SECONDS: f32 = 2.0;
+
+/// Automatic gain control bringing speech to a steady level. The gain follows the loudness
+/// of the audio slowly, so quiet voices are raised and loud ones tamed without pumping on
+/// every syllable.
+#[derive(Debug, Clone)]
+pub struct GainControl {
+    sample_rate: f32,
+    gain: f32,
+}
+
+impl GainControl {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as f32,
+            gain: 1.0,
+        }
+    }
+
+    /// Gain currently applied to the audio.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    pub fn process(&mut self, audio: &mut [f32]) {
+        if audio.is_empty() {
+            return;
+        }
+
+        let rms = (audio.iter().map(|s| s * s).sum::<f32>() / audio.len() as f32).sqrt();
+        let previous = self.gain;
+        if rms > NOISE_GATE_RMS {
+            let desired = (TARGET_RMS / rms).clamp(MIN_GAIN, MAX_GAIN);
+            let time_constant = if desired < self.gain {
+                ATTACK_SECONDS
+            } else {
+                RELEASE_SECONDS
+            };
+            let duration = audio.len() as f32 / self.sample_rate;
+            self.gain += (desired - self.gain) * (1.0 - (-duration / time_constant).exp());
+        }
+
+        // ramp from the previous gain so that changes don't click
+        let step = (self.gain - previous) / audio.len() as f32;
+        for (i, sample) in audio.iter_mut().enumerate() {
+            *sample = (*sample * (previous + step * (i + 1) as f32)).clamp(-1.0, 1.0);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+pub mod echo_cancellation;
+pub mod gain_control;
+pub mod noise_suppression;
+
+use anyhow::Result;
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::core::device::{AudioDevice, DeviceType};
+use echo_cancellation::{EchoCanceller, EchoReference};
+use gain_control::GainControl;
+use noise_suppression::NoiseSuppressor;
+
+/// Processing applied to the audio of a device as it is recorded, before it is stored and
+/// transcribed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AudioProcessing {
+    /// Brings speech to a steady level
+    pub gain_control: bool,
+    /// Removes steady background noise
+    pub noise_suppression: bool,
+    /// Removes what the output devices play from an input device, so that audio coming out of
+    /// the speakers isn't transcribed a second time as the user speaking. Ignored on output
+    /// devices.
+    pub echo_cancellation: bool,
+}
+
+impl AudioProcessing {
+    pub fn is_enabled(&self) -> bool {
+        self.gain_control || self.noise_suppression || self.echo_cancellation
+    }
+}
+
+/// Parses a comma separated list of `agc`, `denoise` and `aec`, or `none`.
+impl FromStr for AudioProcessing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut processing = AudioProcessing::default();
+        for step in s.split(',').map(|step| step.trim().to_lowercase()) {
+            match step.as_str() {
+                "agc" => processing.gain_control = true,
+                "denoise" => processing.noise_suppression = true,
+                "aec" => processing.echo_cancellation = true,
+                "none" | "" => {}
+                _ => {
+                    return Err(format!(
+                        "unknown audio processing step: {} (expected agc, denoise, aec or none)",
+                        step
+                    ))
+                }
+            }
+        }
+        Ok(processing)
+    }
+}
+
+impl fmt::Display for AudioProcessing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps: Vec<&str> = [
+            (self.gain_control, "agc"),
+            (self.noise_suppression, "denoise"),
+            (self.echo_cancellation, "aec"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, name)| enabled.then_some(name))
+        .collect();
+
+        if steps.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", steps.join(","))
+        }
+    }
+}
+
+/// Processing of every device, with overrides for some of them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AudioProcessingConfig {
+    pub default: AudioProcessing,
+    /// Keyed by device name, with or without the `(input)`/`(output)` suffix
+    pub devices: HashMap<String, AudioProcessing>,
+}
+
+impl AudioProcessingConfig {
+    /// Parses entries like `agc,denoise` setting the default and `DEVICE=aec` overriding it
+    /// for one device.
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> Result<Self, String> {
+        let mut config = AudioProcessingConfig::default();
+        for entry in entries {
+            match entry.as_ref().rsplit_once('=') {
+                Some((device, processing)) => {
+                    config
+                        .devices
+                        .insert(device.trim().to_string(), processing.parse()?);
+                }
+                None => config.default = entry.as_ref().parse()?,
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn for_device(&self, device: &AudioDevice) -> AudioProcessing {
+        self.devices
+            .get(&device.to_string())
+            .or_else(|| self.devices.get(&device.name))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Runs the processing chain of one device over its audio: echo cancellation first, while
+/// the mic audio is still a plain sum of the echo and the room, then noise suppression and
+/// gain control last so that the noise isn't raised with the speech.
+pub struct AudioProcessor {
+    sample_rate: u32,
+    echo_cancellation: Option<(EchoCanceller, EchoReference)>,
+    noise_suppression: Option<NoiseSuppressor>,
+    gain_control: Option<GainControl>,
+}
+
+impl AudioProcessor {
+    /// `echo_reference` is only used on input devices with echo cancellation enabled.
+    pub fn new(
+        processing: AudioProcessing,
+        device_type: &DeviceType,
+        sample_rate: u32,
+        echo_reference: Option<EchoReference>,
+    ) -> Self {
+        let echo_cancellation = match echo_reference {
+            Some(reference)
+                if processing.echo_cancellation
+                    && *device_type == DeviceType::Input
+                    && !reference.is_empty() =>
+            {
+                Some((EchoCanceller::new(sample_rate), reference))
+            }
+            _ => None,
+        };
+
+        Self {
+            sample_rate,
+            echo_cancellation,
+            noise_suppression: processing
+                .noise_suppression
+                .then(|| NoiseSuppressor::new(sample_rate)),
+            gain_control: processing
+                .gain_control
+                .then(|| GainControl::new(sample_rate)),
+        }
+    }
+
+    pub fn process(&mut self, audio: &mut [f32]) -> Result<()> {
+        if let Some((canceller, reference)) = &mut self.echo_cancellation {
+            let far = reference.take(audio.len(), self.sample_rate);
+            canceller.process(audio, &far)?;
+        }
+        if let Some(suppressor) = &mut self.noise_suppression {
+            suppressor.process(audio)?;
+        }
+        if let Some(gain_control) = &mut self.gain_control {
+            gain_control.process(audio);
+        }
+        Ok(())
+    }
+}
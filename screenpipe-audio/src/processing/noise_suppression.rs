@@ -0,0 +1,165 @@
+use anyhow::Result;
+use realfft::num_complex::Complex32;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// Frames at the start whose average is taken as the first noise estimate
+const INIT_FRAMES: usize = 25;
+/// A bin this much louder than the noise estimate holds more than noise
+const SPEECH_TO_NOISE: f32 = 2.5;
+/// How much of the gap to the current spectrum the noise estimate covers per frame
+const NOISE_ADAPTATION: f32 = 0.05;
+/// How fast the noise estimate creeps up while a bin holds speech, so that it catches up
+/// with noise that got louder
+const NOISE_RISE_PER_SECOND: f32 = 1.5;
+/// Smoothing of the spectrum the noise estimate follows
+const SPECTRUM_SMOOTHING: f32 = 0.8;
+/// Weight of the previous frame in the a priori SNR, higher means less musical noise
+const DECISION_DIRECTED: f32 = 0.98;
+/// Bins are never attenuated more than -20dB so that the noise left sounds natural
+const GAIN_FLOOR: f32 = 0.1;
+
+/// Streaming noise suppressor. The spectrum of ~20ms overlapping frames is compared to a
+/// running estimate of the background noise, learned on the frames and bins that don't hold
+/// speech, and each bin is attenuated with a Wiener gain. Stationary noise like fans, hum
+/// and hiss is removed while speech passes through.
+///
+/// Output lags input by one frame.
+pub struct NoiseSuppressor {
+    frame_size: usize,
+    hop: usize,
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+    window: Vec<f32>,
+    frame: Vec<f32>,
+    scratch: Vec<f32>,
+    spectrum: Vec<Complex32>,
+    overlap: Vec<f32>,
+    pending: Vec<f32>,
+    output: VecDeque<f32>,
+    smoothed: Vec<f32>,
+    noise: Vec<f32>,
+    clean: Vec<f32>,
+    noise_rise: f32,
+    frames: usize,
+}
+
+impl NoiseSuppressor {
+    pub fn new(sample_rate: u32) -> Self {
+        let frame_size = (sample_rate as usize / 50).next_power_of_two();
+        let hop = frame_size / 2;
+
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(frame_size);
+        let inverse = planner.plan_fft_inverse(frame_size);
+        let spectrum = forward.make_output_vec();
+        let bins = spectrum.len();
+
+        // square root of a periodic hann window, applied before and after the transform so
+        // that overlapping frames add back up to the original signal
+        let window = (0..frame_size)
+            .map(|i| (0.5 - 0.5 * (2.0 * PI * i as f32 / frame_size as f32).cos()).sqrt())
+            .collect();
+
+        Self {
+            frame_size,
+            hop,
+            forward,
+            inverse,
+            window,
+            frame: vec![0.0; frame_size],
+            scratch: vec![0.0; frame_size],
+            spectrum,
+            overlap: vec![0.0; frame_size],
+            pending: Vec::with_capacity(hop),
+            output: VecDeque::from(vec![0.0; hop]),
+            smoothed: vec![0.0; bins],
+            noise: vec![0.0; bins],
+            clean: vec![0.0; bins],
+            noise_rise: NOISE_RISE_PER_SECOND.powf(hop as f32 / sample_rate as f32),
+            frames: 0,
+        }
+    }
+
+    /// Denoises `audio` in place.
+    pub fn process(&mut self, audio: &mut [f32]) -> Result<()> {
+        for &sample in audio.iter() {
+            self.pending.push(sample);
+            if self.pending.len() == self.hop {
+                self.process_frame()?;
+            }
+        }
+
+        for sample in audio.iter_mut() {
+            *sample = self.output.pop_front().unwrap_or(0.0);
+        }
+        Ok(())
+    }
+
+    fn process_frame(&mut self) -> Result<()> {
+        let (frame_size, hop) = (self.frame_size, self.hop);
+
+        self.frame.copy_within(hop.., 0);
+        self.frame[frame_size - hop..].copy_from_slice(&self.pending);
+        self.pending.clear();
+
+        for ((scratch, sample), window) in
+            self.scratch.iter_mut().zip(&self.frame).zip(&self.window)
+        {
+            *scratch = sample * window;
+        }
+        self.forward
+            .process(&mut self.scratch, &mut self.spectrum)?;
+
+        for (k, bin) in self.spectrum.iter_mut().enumerate() {
+            let power = bin.norm_sqr();
+            self.smoothed[k] = if self.frames == 0 {
+                power
+            } else {
+                SPECTRUM_SMOOTHING * self.smoothed[k] + (1.0 - SPECTRUM_SMOOTHING) * power
+            };
+
+            let smoothed = self.smoothed[k];
+            let noise = &mut self.noise[k];
+            if self.frames < INIT_FRAMES {
+                *noise += (smoothed - *noise) / (self.frames + 1) as f32;
+            } else if smoothed < SPEECH_TO_NOISE * *noise {
+                *noise += (smoothed - *noise) * NOISE_ADAPTATION;
+            } else {
+                *noise *= self.noise_rise;
+            }
+            *noise = noise.max(f32::EPSILON);
+
+            // decision directed a priori SNR, smoothing the gain over time
+            let posterior_snr = power / *noise;
+            let prior_snr = DECISION_DIRECTED * self.clean[k] / *noise
+                + (1.0 - DECISION_DIRECTED) * (posterior_snr - 1.0).max(0.0);
+            let gain = (prior_snr / (1.0 + prior_snr)).max(GAIN_FLOOR);
+
+            self.clean[k] = gain * gain * power;
+            *bin *= gain;
+        }
+        self.frames += 1;
+
+        // the DC and nyquist bins of a real signal have no imaginary part
+        let last = self.spectrum.len() - 1;
+        self.spectrum[0].im = 0.0;
+        self.spectrum[last].im = 0.0;
+        self.inverse
+            .process(&mut self.spectrum, &mut self.scratch)?;
+
+        let scale = 1.0 / frame_size as f32;
+        for ((overlap, sample), window) in
+            self.overlap.iter_mut().zip(&self.scratch).zip(&self.window)
+        {
+            *overlap += sample * window * scale;
+        }
+        self.output.extend(&self.overlap[..hop]);
+        self.overlap.copy_within(hop.., 0);
+        self.overlap[frame_size - hop..].fill(0.0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+use anyhow::Result;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use screenpipe_db::{DatabaseManager, SpeakerCentroid};
+use serde::Serialize;
+use tracing::info;
+
+/// Average cosine distance under which speakers are considered the same person.
+pub const DEFAULT_CLUSTERING_THRESHOLD: f64 = 0.4;
+
+/// Speakers that are the same person according to their voice.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpeakerMergeSet {
+    /// Speaker the others are merged into: a named or enrolled one if any, else the oldest
+    pub speaker_to_keep_id: i64,
+    pub speaker_ids_to_merge: Vec<i64>,
+}
+
+/// Most clusters compared at once, the distance matrix of a block takes `n²` floats.
+pub const CLUSTER_BLOCK_SIZE: usize = 1000;
+
+/// Groups speakers with agglomerative clustering, using average linkage over the cosine
+/// distance of their centroids and stopping once the closest clusters are further apart than
+/// `threshold`. Speakers with different names are never grouped together.
+///
+/// Only groups of more than one speaker are returned. See [`cluster_speakers_in_blocks`] for
+/// how many speakers are compared at once.
+pub fn cluster_speakers(speakers: &[SpeakerCentroid], threshold: f64) -> Vec<SpeakerMergeSet> {
+    cluster_speakers_in_blocks(speakers, threshold, CLUSTER_BLOCK_SIZE)
+}
+
+/// [`cluster_speakers`], comparing at most `block_size` clusters at once. The first pass splits
+/// speakers into blocks in the order they were created. Later passes cluster the clusters found
+/// again by their mean voice, ordered so that similar voices share a block and with the block
+/// boundaries shifted by half a block every other pass, until two passes in a row merge
+/// nothing. Up to `block_size` speakers this is exact, beyond it close voices can still end up
+/// in different blocks and stay apart.
+pub fn cluster_speakers_in_blocks(
+    speakers: &[SpeakerCentroid],
+    threshold: f64,
+    block_size: usize,
+) -> Vec<SpeakerMergeSet> {
+    let block_size = block_size.max(2);
+    let mut clusters: Vec<Cluster> = speakers
+        .iter()
+        .enumerate()
+        .map(|(i, speaker)| Cluster {
+            members: vec![i],
+            name: speaker.speaker.name.as_str(),
+            centroid: normalized(&speaker.centroid),
+        })
+        .collect();
+
+    let mut pass = 0;
+    let mut passes_without_merges = 0;
+    loop {
+        let count = clusters.len();
+        if pass > 0 {
+            let planes = random_hyperplanes(clusters.first().map_or(0, |c| c.centroid.len()));
+            clusters.sort_by_cached_key(|cluster| similarity_hash(&planes, &cluster.centroid));
+        }
+        let first_block_size = if count > block_size && pass % 2 == 1 {
+            block_size / 2
+        } else {
+            block_size
+        };
+
+        let mut merged = Vec::with_capacity(count);
+        let mut rest = clusters.into_iter();
+        let mut size = first_block_size;
+        loop {
+            let block: Vec<Cluster> = rest.by_ref().take(size).collect();
+            if block.is_empty() {
+                break;
+            }
+            merged.extend(cluster_block(block, threshold));
+            size = block_size;
+        }
+        clusters = merged;
+
+        if count <= block_size {
+            break;
+        }
+        if pass > 0 && clusters.len() == count {
+            passes_without_merges += 1;
+            if passes_without_merges == 2 {
+                break;
+            }
+        } else {
+            passes_without_merges = 0;
+        }
+        pass += 1;
+    }
+    clusters.sort_by_key(|cluster| cluster.members.iter().min().copied());
+
+    clusters
+        .into_iter()
+        .filter(|cluster| cluster.members.len() > 1)
+        .map(|cluster| {
+            let members = cluster.members;
+            let keep = members
+                .iter()
+                .copied()
+                .min_by_key(|&i| {
+                    let speaker = &speakers[i];
+                    (
+                        speaker.speaker.name.is_empty(),
+                        !speaker.enrolled,
+                        speaker.speaker.id,
+                    )
+                })
+                .unwrap_or(members[0]);
+            let mut speaker_ids_to_merge: Vec<i64> = members
+                .iter()
+                .filter(|&&i| i != keep)
+                .map(|&i| speakers[i].speaker.id)
+                .collect();
+            speaker_ids_to_merge.sort_unstable();
+            SpeakerMergeSet {
+                speaker_to_keep_id: speakers[keep].speaker.id,
+                speaker_ids_to_merge,
+            }
+        })
+        .collect()
+}
+
+/// Speakers grouped so far, with the mean of their normalized centroids.
+struct Cluster<'a> {
+    members: Vec<usize>,
+    name: &'a str,
+    centroid: Vec<f32>,
+}
+
+/// Clusters `block`, returning the clusters left in the order of the first cluster of each.
+fn cluster_block<'a>(block: Vec<Cluster<'a>>, threshold: f64) -> Vec<Cluster<'a>> {
+    let n = block.len();
+    let mut clusters: Vec<Option<Cluster>> = block.into_iter().map(Some).collect();
+
+    let mut distances = vec![vec![f32::INFINITY; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let (a, b) = (clusters[i].as_ref().unwrap(), clusters[j].as_ref().unwrap());
+            if !conflicting_names(a.name, b.name) {
+                let distance = cosine_distance(&a.centroid, &b.centroid);
+                distances[i][j] = distance;
+                distances[j][i] = distance;
+            }
+        }
+    }
+    let mut nearest: Vec<(usize, f32)> = (0..n)
+        .map(|i| nearest_cluster(&distances, &clusters, i))
+        .collect();
+
+    loop {
+        let closest = (0..n)
+            .filter(|&i| clusters[i].is_some())
+            .map(|i| (i, nearest[i]))
+            .min_by(|a, b| a.1 .1.total_cmp(&b.1 .1));
+        let Some((a, (b, distance))) = closest else {
+            break;
+        };
+        if !distance.is_finite() || distance as f64 > threshold {
+            break;
+        }
+
+        let merged = clusters[b].take().expect("nearest cluster is live");
+        let cluster = clusters[a].as_mut().expect("closest cluster is live");
+        let (size_a, size_b) = (cluster.members.len() as f32, merged.members.len() as f32);
+        for (value, other) in cluster.centroid.iter_mut().zip(&merged.centroid) {
+            *value = (size_a * *value + size_b * other) / (size_a + size_b);
+        }
+        cluster.members.extend(merged.members);
+        if cluster.name.is_empty() {
+            cluster.name = merged.name;
+        }
+        let name = cluster.name;
+
+        for k in 0..n {
+            if k == a {
+                continue;
+            }
+            let Some(other) = &clusters[k] else {
+                continue;
+            };
+            let distance = if conflicting_names(name, other.name) {
+                f32::INFINITY
+            } else {
+                (size_a * distances[a][k] + size_b * distances[b][k]) / (size_a + size_b)
+            };
+            distances[a][k] = distance;
+            distances[k][a] = distance;
+        }
+
+        nearest[a] = nearest_cluster(&distances, &clusters, a);
+        for k in 0..n {
+            if k == a || clusters[k].is_none() {
+                continue;
+            }
+            if nearest[k].0 == a || nearest[k].0 == b {
+                nearest[k] = nearest_cluster(&distances, &clusters, k);
+            } else if distances[k][a] < nearest[k].1 {
+                nearest[k] = (a, distances[k][a]);
+            }
+        }
+    }
+
+    clusters.into_iter().flatten().collect()
+}
+
+/// Clusters all stored speakers and, unless `dry_run`, merges each set into one speaker, all
+/// sets in one transaction. Returns the merge sets either way.
+pub async fn recluster_speakers(
+    db: &DatabaseManager,
+    threshold: f64,
+    dry_run: bool,
+) -> Result<Vec<SpeakerMergeSet>> {
+    let speakers = db.get_speaker_centroids().await?;
+    let speaker_count = speakers.len();
+    let merge_sets =
+        tokio::task::spawn_blocking(move || cluster_speakers(&speakers, threshold)).await?;
+    info!(
+        "found {} sets of speakers to merge among {} speakers",
+        merge_sets.len(),
+        speaker_count
+    );
+
+    if !dry_run {
+        let merge_sets: Vec<(i64, Vec<i64>)> = merge_sets
+            .iter()
+            .map(|set| (set.speaker_to_keep_id, set.speaker_ids_to_merge.clone()))
+            .collect();
+        db.merge_speaker_sets(&merge_sets).await?;
+    }
+    Ok(merge_sets)
+}
+
+fn conflicting_names(a: &str, b: &str) -> bool {
+    !a.is_empty() && !b.is_empty() && a != b
+}
+
+/// Bits of the similarity hash, clusters on the same side of all the hyperplanes share a block.
+const SIMILARITY_HASH_BITS: usize = 16;
+
+/// Fixed pseudo random hyperplanes through the origin, the same on every run.
+fn random_hyperplanes(dimensions: usize) -> Vec<Vec<f32>> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..SIMILARITY_HASH_BITS)
+        .map(|_| {
+            (0..dimensions)
+                .map(|_| rng.random_range(-1.0..1.0))
+                .collect()
+        })
+        .collect()
+}
+
+/// Which side of each hyperplane `vector` is on, vectors at a small angle mostly agree.
+fn similarity_hash(planes: &[Vec<f32>], vector: &[f32]) -> u32 {
+    planes.iter().fold(0, |hash, plane| {
+        let dot: f32 = plane.iter().zip(vector).map(|(a, b)| a * b).sum();
+        (hash << 1) | (dot >= 0.0) as u32
+    })
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    1.0 - dot / (norm_a * norm_b)
+}
+
+fn nearest_cluster(
+    distances: &[Vec<f32>],
+    clusters: &[Option<Cluster<'_>>],
+    i: usize,
+) -> (usize, f32) {
+    (0..clusters.len())
+        .filter(|&j| j != i && clusters[j].is_some())
+        .map(|j| (j, distances[i][j]))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((i, f32::INFINITY))
+}
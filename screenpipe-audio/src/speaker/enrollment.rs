@@ -0,0 +1,52 @@
+use anyhow::{bail, Result};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use super::embedding::EmbeddingExtractor;
+use crate::{pcm_decode, resample, transcription::stt::SAMPLE_RATE};
+
+/// Shortest clip a voice is reliably recognised from, in seconds.
+const MIN_CLIP_DURATION: f64 = 1.0;
+
+/// Audio of a single speaker, used to enroll them.
+#[derive(Debug, Clone)]
+pub struct SpeakerClip {
+    pub path: PathBuf,
+    /// Seconds from the start of the file, the whole file is used if missing
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+}
+
+/// Computes the voice embedding of a clip, to be stored with the speaker heard in it.
+pub fn compute_clip_embedding(
+    embedding_extractor: &Arc<Mutex<EmbeddingExtractor>>,
+    clip: &SpeakerClip,
+) -> Result<Vec<f32>> {
+    let (samples, sample_rate) = pcm_decode(&clip.path)?;
+    let samples = if sample_rate != SAMPLE_RATE {
+        resample(&samples, sample_rate, SAMPLE_RATE)?
+    } else {
+        samples
+    };
+
+    let to_index =
+        |seconds: f64| ((seconds.max(0.0) * SAMPLE_RATE as f64) as usize).min(samples.len());
+    let start = clip.start_time.map_or(0, to_index);
+    let end = clip.end_time.map_or(samples.len(), to_index);
+    if end <= start || ((end - start) as f64) < MIN_CLIP_DURATION * SAMPLE_RATE as f64 {
+        bail!(
+            "clip of {:?} is shorter than {} second",
+            clip.path,
+            MIN_CLIP_DURATION
+        );
+    }
+
+    let embedding = embedding_extractor
+        .lock()
+        .unwrap()
+        .compute(&samples[start..end])?
+        .collect();
+    Ok(embedding)
+}
@@ -13,7 +13,9 @@ pub fn create_session<P: AsRef<Path>>(path: P) -> Result<Session> {
         .commit_from_file(path.as_ref())?;
     Ok(session)
 }
+pub mod clustering;
 pub mod embedding_manager;
+pub mod enrollment;
 pub mod models;
 mod prepare_segments;
 pub use prepare_segments::prepare_segments;
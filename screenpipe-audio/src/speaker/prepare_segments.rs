@@ -13,6 +13,8 @@ use super::{
     embedding::EmbeddingExtractor, embedding_manager::EmbeddingManager, segment::SpeechSegment,
 };
 
+/// Audio that is `processed` went through the processing chain of its device already, so it
+/// isn't normalized and denoised a second time.
 pub async fn prepare_segments(
     audio_data: &[f32],
     vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>>,
@@ -20,8 +22,13 @@ pub async fn prepare_segments(
     embedding_manager: EmbeddingManager,
     embedding_extractor: Arc<StdMutex<EmbeddingExtractor>>,
     device: &str,
+    processed: bool,
 ) -> Result<(tokio::sync::mpsc::Receiver<SpeechSegment>, bool)> {
-    let audio_data = normalize_v2(audio_data);
+    let audio_data = if processed {
+        audio_data.to_vec()
+    } else {
+        normalize_v2(audio_data)
+    };
 
     let frame_size = 1600;
     let vad_engine = vad_engine.clone();
@@ -37,13 +44,14 @@ pub async fn prepare_segments(
         let mut new_chunk = chunk.to_vec();
         let status = vad_engine.lock().await.audio_type(chunk);
         match status {
+            Ok(VadStatus::Speech) if processed => speech_frame_count += 1,
             Ok(VadStatus::Speech) => {
                 if let Ok(processed_audio) = spectral_subtraction(chunk, noise) {
                     new_chunk = processed_audio;
                     speech_frame_count += 1;
                 }
             }
-            Ok(VadStatus::Unknown) => {
+            Ok(VadStatus::Unknown) if !processed => {
                 noise = average_noise_spectrum(chunk);
             }
             _ => {}
@@ -1,12 +1,71 @@
 use anyhow::Result;
+use futures::future::BoxFuture;
 use hound::{WavSpec, WavWriter};
 use reqwest::{Client, Response};
 use screenpipe_core::Language;
+use screenpipe_db::{TranscriptionSegment, TranscriptionWord};
 use serde_json::Value;
 use std::io::Cursor;
+use std::sync::Arc;
 use tracing::{debug, error, info};
 
+use crate::core::engine::AudioTranscriptionEngine;
 use crate::transcription::deepgram::{CUSTOM_DEEPGRAM_API_TOKEN, DEEPGRAM_API_URL};
+use crate::transcription::{Transcript, TranscriptionEngine};
+
+/// Transcribes with Deepgram, using `fallback` when a request fails. Transcripts made by the
+/// fallback are tagged with the engine it runs.
+pub struct DeepgramEngine {
+    api_key: String,
+    fallback: Option<(AudioTranscriptionEngine, Arc<dyn TranscriptionEngine>)>,
+}
+
+impl DeepgramEngine {
+    pub fn new(
+        api_key: String,
+        fallback: Option<(AudioTranscriptionEngine, Arc<dyn TranscriptionEngine>)>,
+    ) -> Self {
+        Self { api_key, fallback }
+    }
+}
+
+impl TranscriptionEngine for DeepgramEngine {
+    fn transcribe<'a>(
+        &'a self,
+        audio: &'a [f32],
+        sample_rate: u32,
+        device: &'a str,
+        languages: &'a [Language],
+    ) -> BoxFuture<'a, Result<Transcript>> {
+        Box::pin(async move {
+            match transcribe_with_deepgram(
+                &self.api_key,
+                audio,
+                device,
+                sample_rate,
+                languages.to_vec(),
+            )
+            .await
+            {
+                Ok(transcript) => Ok(transcript),
+                Err(e) => match &self.fallback {
+                    Some((fallback_engine, fallback)) => {
+                        error!(
+                            "device: {}, deepgram transcription failed, falling back to {}: {:?}",
+                            device, fallback_engine, e
+                        );
+                        let mut transcript = fallback
+                            .transcribe(audio, sample_rate, device, languages)
+                            .await?;
+                        transcript.fallback_engine = Some(fallback_engine.clone());
+                        Ok(transcript)
+                    }
+                    None => Err(e),
+                },
+            }
+        })
+    }
+}
 
 pub async fn transcribe_with_deepgram(
     api_key: &str,
@@ -14,7 +73,7 @@ pub async fn transcribe_with_deepgram(
     device: &str,
     sample_rate: u32,
     languages: Vec<Language>,
-) -> Result<String> {
+) -> Result<Transcript> {
     debug!("starting deepgram transcription");
 
     // Use token from env var
@@ -113,7 +172,7 @@ async fn get_deepgram_response(
 async fn handle_deepgram_response(
     response: Result<Response, reqwest::Error>,
     device: &str,
-) -> Result<String> {
+) -> Result<Transcript> {
     match response {
         Ok(resp) => {
             debug!("received response from deepgram api");
@@ -127,10 +186,8 @@ async fn handle_deepgram_response(
                         );
                         return Err(anyhow::anyhow!("Deepgram API error: {:?}", result));
                     }
-                    let transcription = result["results"]["channels"][0]["alternatives"][0]
-                        ["transcript"]
-                        .as_str()
-                        .unwrap_or("");
+                    let alternative = &result["results"]["channels"][0]["alternatives"][0];
+                    let transcription = alternative["transcript"].as_str().unwrap_or("");
 
                     if transcription.is_empty() {
                         info!("device: {}, transcription is empty.", device);
@@ -142,7 +199,11 @@ async fn handle_deepgram_response(
                         );
                     }
 
-                    Ok(transcription.to_string())
+                    Ok(Transcript {
+                        text: transcription.to_string(),
+                        segments: deepgram_segments(alternative),
+                        fallback_engine: None,
+                    })
                 }
                 Err(e) => {
                     error!("Failed to parse JSON response: {:?}", e);
@@ -159,3 +220,71 @@ async fn handle_deepgram_response(
         }
     }
 }
+
+/// Builds segments from the sentences of the response, or one segment over all words when the
+/// response has no paragraphs.
+fn deepgram_segments(alternative: &Value) -> Vec<TranscriptionSegment> {
+    let words: Vec<TranscriptionWord> = alternative["words"]
+        .as_array()
+        .map(|words| {
+            words
+                .iter()
+                .filter_map(|word| {
+                    Some(TranscriptionWord {
+                        start_time: word["start"].as_f64()?,
+                        end_time: word["end"].as_f64()?,
+                        text: word["punctuated_word"]
+                            .as_str()
+                            .or_else(|| word["word"].as_str())?
+                            .to_string(),
+                        confidence: word["confidence"].as_f64(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let sentences: Vec<(f64, f64, String)> = alternative["paragraphs"]["paragraphs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|paragraph| paragraph["sentences"].as_array().into_iter().flatten())
+        .filter_map(|sentence| {
+            Some((
+                sentence["start"].as_f64()?,
+                sentence["end"].as_f64()?,
+                sentence["text"].as_str()?.to_string(),
+            ))
+        })
+        .collect();
+
+    if sentences.is_empty() {
+        let (Some(first), Some(last)) = (words.first(), words.last()) else {
+            return Vec::new();
+        };
+        return vec![TranscriptionSegment {
+            start_time: first.start_time,
+            end_time: last.end_time,
+            text: words
+                .iter()
+                .map(|word| word.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            words,
+        }];
+    }
+
+    sentences
+        .into_iter()
+        .map(|(start_time, end_time, text)| TranscriptionSegment {
+            start_time,
+            end_time,
+            text,
+            words: words
+                .iter()
+                .filter(|word| word.start_time >= start_time && word.end_time <= end_time)
+                .cloned()
+                .collect(),
+        })
+        .collect()
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use screenpipe_core::Language;
+use std::{path::PathBuf, sync::Arc};
+use whisper_rs::WhisperContext;
+
+use super::{
+    deepgram::batch::DeepgramEngine,
+    openai_compatible::OpenAiCompatibleEngine,
+    whisper::{
+        batch::WhisperEngine,
+        model::{create_whisper_context_parameters, download_whisper_model},
+    },
+    Transcript,
+};
+use crate::{audio_manager::AudioManagerOptions, core::engine::AudioTranscriptionEngine};
+
+/// Turns speech into text.
+///
+/// Implemented by the local Whisper model, Deepgram and servers compatible with OpenAI's
+/// transcription API. Which one is used is picked with
+/// [`AudioTranscriptionEngine`](crate::core::engine::AudioTranscriptionEngine).
+pub trait TranscriptionEngine: Send + Sync {
+    /// Transcribes mono `audio` sampled at `sample_rate`. `device` is only used for logging.
+    fn transcribe<'a>(
+        &'a self,
+        audio: &'a [f32],
+        sample_rate: u32,
+        device: &'a str,
+        languages: &'a [Language],
+    ) -> BoxFuture<'a, Result<Transcript>>;
+}
+
+/// Creates the engine selected in `options`. `whisper` is used as the local Whisper model (or as
+/// Deepgram's fallback) when given, otherwise the model is loaded, downloading it if needed.
+pub fn create_transcription_engine(
+    options: &AudioManagerOptions,
+    whisper: Option<Arc<dyn TranscriptionEngine>>,
+) -> Result<Arc<dyn TranscriptionEngine>> {
+    if *options.transcription_engine == AudioTranscriptionEngine::OpenAiCompatible {
+        let url = options
+            .openai_compatible_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("no server url for the OpenAI compatible engine"))?;
+        return Ok(Arc::new(OpenAiCompatibleEngine::new(
+            url,
+            options.openai_compatible_model.clone(),
+            options.openai_compatible_api_key.clone(),
+        )?));
+    }
+
+    let whisper = match whisper {
+        Some(whisper) => whisper,
+        None => create_whisper_engine(options.transcription_engine.clone(), None)?,
+    };
+
+    if *options.transcription_engine == AudioTranscriptionEngine::Deepgram {
+        // the model create_whisper_engine loads for engines that aren't Whisper models
+        return Ok(Arc::new(DeepgramEngine::new(
+            options.deepgram_api_key.clone().unwrap_or_default(),
+            Some((
+                AudioTranscriptionEngine::WhisperLargeV3TurboQuantized,
+                whisper,
+            )),
+        )));
+    }
+    Ok(whisper)
+}
+
+/// Loads the local Whisper model of `engine`, or Whisper Large V3 Turbo (quantized) for engines
+/// that aren't Whisper models. The model is downloaded unless `model_path` points at it already.
+pub fn create_whisper_engine(
+    engine: Arc<AudioTranscriptionEngine>,
+    model_path: Option<PathBuf>,
+) -> Result<Arc<dyn TranscriptionEngine>> {
+    let model_path = match model_path {
+        Some(model_path) => model_path,
+        None => download_whisper_model(engine.clone())?,
+    };
+    let context_param = create_whisper_context_parameters(engine)?;
+    let whisper_context = Arc::new(
+        WhisperContext::new_with_params(&model_path.to_string_lossy(), context_param)
+            .expect("failed to load model"),
+    );
+    Ok(Arc::new(WhisperEngine::new(whisper_context)))
+}
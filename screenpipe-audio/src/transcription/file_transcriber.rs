@@ -0,0 +1,139 @@
+use anyhow::Result;
+use screenpipe_db::{DatabaseManager, RetranscribedAudio, SpeakerSpan};
+use std::{path::Path, sync::Arc};
+use tokio::sync::Mutex;
+
+use super::{
+    create_transcription_engine, stt::SAMPLE_RATE,
+    transcription_result::get_or_create_speaker_from_embedding, TranscriptionEngine,
+};
+use crate::{
+    audio_manager::AudioManagerOptions,
+    pcm_decode, resample,
+    segmentation::segmentation_manager::SegmentationManager,
+    speaker::prepare_segments,
+    vad::{silero::SileroVad, webrtc::WebRtcVad, VadEngine, VadEngineEnum},
+};
+
+/// Transcribes audio files outside of recording, running them through the same VAD,
+/// diarization and transcription as recorded audio. The models are loaded once and reused
+/// for every file.
+pub struct FileTranscriber {
+    options: AudioManagerOptions,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
+    vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>>,
+    segmentation_manager: SegmentationManager,
+}
+
+impl FileTranscriber {
+    /// Loads the transcription engine, VAD and diarization models selected in `options`.
+    pub async fn new(options: AudioManagerOptions) -> Result<Self> {
+        // loading the model can take a while and blocks
+        let engine_options = options.clone();
+        let transcription_engine =
+            tokio::task::spawn_blocking(move || create_transcription_engine(&engine_options, None))
+                .await??;
+        let vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>> = match options.vad_engine {
+            VadEngineEnum::Silero => Arc::new(Mutex::new(Box::new(SileroVad::new().await?))),
+            VadEngineEnum::WebRtc => Arc::new(Mutex::new(Box::new(WebRtcVad::new()))),
+        };
+        let segmentation_manager = SegmentationManager::new().await?;
+
+        Ok(Self {
+            options,
+            transcription_engine,
+            vad_engine,
+            segmentation_manager,
+        })
+    }
+
+    /// Decodes the file at `path` and transcribes every speech segment in it. Each segment keeps
+    /// the speaker of the span in `speakers` it overlaps most, or else is matched to a known
+    /// speaker or a new one. Times are seconds from the start of the file. Returns nothing if
+    /// the file holds too little speech.
+    pub async fn transcribe(
+        &self,
+        db: &DatabaseManager,
+        path: &Path,
+        device_name: &str,
+        speakers: &[SpeakerSpan],
+    ) -> Result<Vec<RetranscribedAudio>> {
+        let path = path.to_path_buf();
+        let (samples, sample_rate) =
+            tokio::task::spawn_blocking(move || pcm_decode(path)).await??;
+        let samples = if sample_rate != SAMPLE_RATE {
+            resample(&samples, sample_rate, SAMPLE_RATE)?
+        } else {
+            samples
+        };
+
+        let (mut segments, speech_ratio_ok) = prepare_segments(
+            &samples,
+            self.vad_engine.clone(),
+            &self.segmentation_manager.segmentation_model_path,
+            self.segmentation_manager.embedding_manager.clone(),
+            self.segmentation_manager.embedding_extractor.clone(),
+            device_name,
+            false,
+        )
+        .await?;
+        if !speech_ratio_ok {
+            return Ok(Vec::new());
+        }
+
+        let mut transcriptions = Vec::new();
+        while let Some(segment) = segments.recv().await {
+            let transcript = self
+                .transcription_engine
+                .transcribe(
+                    &segment.samples,
+                    segment.sample_rate,
+                    device_name,
+                    &self.options.languages,
+                )
+                .await?
+                .offset_by(segment.start);
+            if transcript.text.trim().is_empty() {
+                continue;
+            }
+
+            let speaker_id = match overlapping_speaker(speakers, segment.start, segment.end) {
+                Some(speaker_id) => speaker_id,
+                None => {
+                    get_or_create_speaker_from_embedding(
+                        db,
+                        &segment.embedding,
+                        self.options.speaker_match_threshold,
+                    )
+                    .await?
+                    .id
+                }
+            };
+            transcriptions.push(RetranscribedAudio {
+                transcription: transcript.text,
+                speaker_id: Some(speaker_id),
+                start_time: segment.start,
+                end_time: segment.end,
+                segments: transcript.segments,
+                fallback_engine: transcript.fallback_engine.map(|engine| engine.to_string()),
+            });
+        }
+
+        Ok(transcriptions)
+    }
+}
+
+/// The speaker of the span overlapping `start..end` the longest, if any does.
+fn overlapping_speaker(speakers: &[SpeakerSpan], start: f64, end: f64) -> Option<i64> {
+    speakers
+        .iter()
+        .map(|span| {
+            (
+                span.speaker_id,
+                end.min(span.end_time) - start.max(span.start_time),
+            )
+        })
+        .filter(|(_, overlap)| *overlap > 0.0)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(speaker_id, _)| speaker_id)
+}
@@ -4,14 +4,16 @@ use crate::{core::engine::AudioTranscriptionEngine, transcription::process_trans
 use screenpipe_db::DatabaseManager;
 use tracing::{error, info};
 
-use super::TranscriptionResult;
+use super::{retain_segment_words, TranscriptionResult};
 
 pub async fn handle_new_transcript(
     db: Arc<DatabaseManager>,
     transcription_receiver: Arc<crossbeam::channel::Receiver<TranscriptionResult>>,
     transcription_engine: Arc<AudioTranscriptionEngine>,
+    speaker_match_threshold: f64,
 ) {
     let mut previous_transcript = "".to_string();
+    let mut previous_segments = Vec::new();
     let mut previous_transcript_id: Option<i64> = None;
     while let Ok(mut transcription) = transcription_receiver.recv() {
         if transcription
@@ -35,11 +37,19 @@ pub async fn handle_new_transcript(
         {
             if !previous.is_empty() && !current.is_empty() {
                 if previous != previous_transcript {
+                    // the overlap is cut from the end of the previous transcript
+                    previous_segments =
+                        retain_segment_words(&previous_segments, 0..word_count(&previous));
                     processed_previous = Some(previous);
                 }
                 if current_transcript.is_some()
                     && current != current_transcript.clone().unwrap_or_default()
                 {
+                    // and from the start of the new one
+                    let removed = word_count(&current_transcript.clone().unwrap_or_default())
+                        - word_count(&current);
+                    transcription.segments =
+                        retain_segment_words(&transcription.segments, removed..usize::MAX);
                     current_transcript = Some(current);
                 }
             }
@@ -51,13 +61,16 @@ pub async fn handle_new_transcript(
         } else {
             continue;
         }
+        let segments = transcription.segments.clone();
         // Process the transcription result
         match process_transcription_result(
             &db,
             transcription,
             transcription_engine.clone(),
             processed_previous,
+            std::mem::replace(&mut previous_segments, segments),
             previous_transcript_id,
+            speaker_match_threshold,
         )
         .await
         {
@@ -66,3 +79,7 @@ pub async fn handle_new_transcript(
         }
     }
 }
+
+fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
@@ -1,17 +1,55 @@
+use chrono::{DateTime, Utc};
 use std::sync::Arc;
 
-use crate::core::device::AudioDevice;
+use crate::core::{device::AudioDevice, engine::AudioTranscriptionEngine};
+use screenpipe_db::TranscriptionSegment;
 
 pub mod deepgram;
+mod engine;
+pub mod file_transcriber;
+pub mod openai_compatible;
+pub mod retranscription;
 pub mod stt;
 pub mod whisper;
 
+pub use engine::{create_transcription_engine, create_whisper_engine, TranscriptionEngine};
+
+/// Text transcribed from a piece of audio, with the segment and word timings the engine
+/// reported. Times are seconds from the start of that audio.
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    pub text: String,
+    pub segments: Vec<TranscriptionSegment>,
+    /// Engine that made the transcript when it isn't the selected one, e.g. Whisper after a
+    /// failed Deepgram request
+    pub fallback_engine: Option<AudioTranscriptionEngine>,
+}
+
+impl Transcript {
+    /// Shifts all timings by `offset` seconds, e.g. to make them relative to the audio chunk file.
+    pub fn offset_by(mut self, offset: f64) -> Self {
+        for segment in &mut self.segments {
+            segment.start_time += offset;
+            segment.end_time += offset;
+            for word in &mut segment.words {
+                word.start_time += offset;
+                word.end_time += offset;
+            }
+        }
+        self
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AudioInput {
     pub data: Arc<Vec<f32>>,
     pub sample_rate: u32,
     pub channels: u16,
     pub device: Arc<AudioDevice>,
+    /// Went through the processing chain of the device already
+    pub processed: bool,
+    /// When the first sample was captured
+    pub recorded_at: DateTime<Utc>,
 }
 
 mod text_utils;
@@ -19,6 +57,7 @@ mod text_utils;
 mod transcription_result;
 
 pub use transcription_result::process_transcription_result;
+pub use transcription_result::retain_segment_words;
 pub use transcription_result::TranscriptionResult;
 mod handle_new_transcript;
 pub use handle_new_transcript::handle_new_transcript;
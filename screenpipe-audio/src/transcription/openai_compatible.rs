@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use hound::{WavSpec, WavWriter};
+use reqwest::multipart::{Form, Part};
+use reqwest::Client;
+use screenpipe_core::Language;
+use screenpipe_db::{TranscriptionSegment, TranscriptionWord};
+use serde_json::Value;
+use std::io::Cursor;
+use std::time::Duration;
+use tracing::debug;
+
+use super::{Transcript, TranscriptionEngine};
+
+pub const DEFAULT_OPENAI_COMPATIBLE_MODEL: &str = "whisper-1";
+const TRANSCRIPTIONS_PATH: &str = "/v1/audio/transcriptions";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Long enough for a CPU-only server to transcribe a chunk, short enough that an unresponsive
+/// server doesn't hold up the chunks behind it forever
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Transcribes by posting WAV chunks to a server implementing OpenAI's
+/// `/v1/audio/transcriptions` endpoint, e.g. faster-whisper-server or the whisper.cpp server.
+pub struct OpenAiCompatibleEngine {
+    client: Client,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiCompatibleEngine {
+    /// `url` is the base URL of the server, `/v1/audio/transcriptions` is appended unless the
+    /// URL already points at the endpoint.
+    pub fn new(url: &str, model: Option<String>, api_key: Option<String>) -> Result<Self> {
+        let client = Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        Ok(Self {
+            client,
+            url: transcriptions_url(url),
+            model: model.unwrap_or_else(|| DEFAULT_OPENAI_COMPATIBLE_MODEL.to_string()),
+            api_key,
+        })
+    }
+
+    async fn request(
+        &self,
+        audio: &[f32],
+        sample_rate: u32,
+        device: &str,
+        languages: &[Language],
+    ) -> Result<Transcript> {
+        let file = Part::bytes(create_wav_file(audio, sample_rate)?)
+            .file_name("audio.wav")
+            .mime_str("audio/wav")?;
+        let mut form = Form::new()
+            .part("file", file)
+            .text("model", self.model.clone())
+            .text("response_format", "verbose_json")
+            .text("timestamp_granularities[]", "segment")
+            .text("timestamp_granularities[]", "word");
+        // the api takes a single language, let the server detect it otherwise
+        if let [language] = languages {
+            form = form.text("language", language.as_lang_code().to_string());
+        }
+
+        let mut request = self.client.post(&self.url).multipart(form);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        debug!("device: {}, sending audio to {}", device, self.url);
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "transcription server returned {}: {}",
+                status,
+                body
+            ));
+        }
+
+        Ok(parse_transcription_response(
+            &response.json::<Value>().await?,
+        ))
+    }
+}
+
+impl TranscriptionEngine for OpenAiCompatibleEngine {
+    fn transcribe<'a>(
+        &'a self,
+        audio: &'a [f32],
+        sample_rate: u32,
+        device: &'a str,
+        languages: &'a [Language],
+    ) -> BoxFuture<'a, Result<Transcript>> {
+        Box::pin(self.request(audio, sample_rate, device, languages))
+    }
+}
+
+fn transcriptions_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    if url.ends_with("/audio/transcriptions") {
+        url.to_string()
+    } else if let Some(base) = url.strip_suffix("/v1") {
+        format!("{}{}", base, TRANSCRIPTIONS_PATH)
+    } else {
+        format!("{}{}", url, TRANSCRIPTIONS_PATH)
+    }
+}
+
+fn create_wav_file(audio: &[f32], sample_rate: u32) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = WavWriter::new(&mut cursor, spec)?;
+        for &sample in audio {
+            writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+        }
+        writer.finalize()?;
+    }
+    Ok(cursor.into_inner())
+}
+
+/// Reads a `verbose_json` response. Servers that only return `text` give a transcript without
+/// timings.
+fn parse_transcription_response(response: &Value) -> Transcript {
+    let parse_word = |word: &Value| {
+        Some(TranscriptionWord {
+            start_time: word["start"].as_f64()?,
+            end_time: word["end"].as_f64()?,
+            text: word["word"].as_str()?.trim().to_string(),
+            confidence: word["probability"].as_f64(),
+        })
+    };
+    let words: Vec<TranscriptionWord> = response["words"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(parse_word)
+        .collect();
+
+    let segments = response["segments"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|segment| {
+            let start_time = segment["start"].as_f64()?;
+            let end_time = segment["end"].as_f64()?;
+            let text = segment["text"].as_str()?.trim().to_string();
+            // some servers nest the words in their segment
+            let segment_words: Vec<TranscriptionWord> = match segment["words"].as_array() {
+                Some(segment_words) => segment_words.iter().filter_map(parse_word).collect(),
+                None => words
+                    .iter()
+                    .filter(|word| word.start_time >= start_time && word.end_time <= end_time)
+                    .cloned()
+                    .collect(),
+            };
+            Some(TranscriptionSegment {
+                start_time,
+                end_time,
+                text,
+                words: segment_words,
+            })
+        })
+        .filter(|segment| !segment.text.is_empty())
+        .collect();
+
+    Transcript {
+        text: response["text"]
+            .as_str()
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        segments,
+        fallback_engine: None,
+    }
+}
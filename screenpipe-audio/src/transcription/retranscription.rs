@@ -0,0 +1,491 @@
+use anyhow::{anyhow, Result};
+use screenpipe_db::{AudioChunkToRetranscribe, DatabaseManager};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tokio::{
+    sync::{mpsc, RwLock},
+    task::JoinHandle,
+    time,
+};
+use tracing::{debug, error, info, warn};
+
+use super::file_transcriber::FileTranscriber;
+use crate::audio_manager::AudioManagerOptions;
+
+/// Status of a re-transcription job. `engine` is the engine the audio is re-transcribed with.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum RetranscriptionStatus {
+    /// Audio chunks are being re-transcribed
+    Running {
+        engine: String,
+        total_chunks: i64,
+        processed_chunks: i64,
+        failed_chunks: i64,
+    },
+    /// Re-transcription is paused and can be resumed
+    Paused {
+        engine: String,
+        total_chunks: i64,
+        processed_chunks: i64,
+        failed_chunks: i64,
+    },
+    /// All audio chunks were processed
+    Completed {
+        engine: String,
+        total_chunks: i64,
+        failed_chunks: i64,
+        duration_secs: u64,
+    },
+    /// Re-transcription was stopped
+    Stopped {
+        engine: String,
+        total_chunks: i64,
+        processed_chunks: i64,
+        failed_chunks: i64,
+    },
+    /// Re-transcription failed with an error
+    Failed {
+        engine: String,
+        total_chunks: i64,
+        processed_chunks: i64,
+        error: String,
+    },
+    /// Re-transcription has not been started yet
+    NotStarted,
+}
+
+/// Commands that can be sent to control the re-transcription worker
+#[derive(Debug, Clone)]
+pub enum RetranscriptionCommand {
+    /// Start or resume the re-transcription
+    Start,
+    /// Pause the re-transcription (can be resumed later)
+    Pause,
+    /// Stop the re-transcription, starting it again picks up the remaining chunks
+    Stop,
+    /// Request current re-transcription status
+    Status,
+}
+
+/// Response to a command sent to the re-transcription worker
+#[derive(Serialize)]
+pub struct RetranscriptionResponse {
+    pub status: RetranscriptionStatus,
+}
+
+/// Configuration for the re-transcription worker
+#[derive(Debug, Clone)]
+pub struct RetranscriptionConfig {
+    /// Number of audio chunks fetched from the database at once
+    pub batch_size: i64,
+    /// Delay between audio chunks to leave room for recording
+    pub chunk_delay_ms: u64,
+    /// Whether to continue with the next chunk if one fails
+    pub continue_on_error: bool,
+}
+
+impl Default for RetranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 20,
+            chunk_delay_ms: 100,
+            continue_on_error: true,
+        }
+    }
+}
+
+impl RetranscriptionConfig {
+    pub fn new(batch_size: i64, chunk_delay_ms: u64, continue_on_error: bool) -> Self {
+        Self {
+            batch_size,
+            chunk_delay_ms,
+            continue_on_error,
+        }
+    }
+}
+
+/// Worker that re-transcribes stored audio with the engine of its `AudioManagerOptions`,
+/// replacing transcriptions that were made by other engines.
+pub struct RetranscriptionWorker {
+    db: Arc<DatabaseManager>,
+    options: AudioManagerOptions,
+    config: RetranscriptionConfig,
+    status: Arc<RwLock<RetranscriptionStatus>>,
+    is_running: Arc<AtomicBool>,
+    is_paused: Arc<AtomicBool>,
+    cmd_rx: mpsc::Receiver<RetranscriptionCommand>,
+    status_tx: mpsc::Sender<RetranscriptionResponse>,
+    worker_handle: Option<JoinHandle<()>>,
+}
+
+impl RetranscriptionWorker {
+    /// Create a new re-transcription worker
+    pub fn new(
+        db: Arc<DatabaseManager>,
+        options: AudioManagerOptions,
+        cmd_rx: mpsc::Receiver<RetranscriptionCommand>,
+        status_tx: mpsc::Sender<RetranscriptionResponse>,
+        config: RetranscriptionConfig,
+    ) -> Self {
+        Self {
+            db,
+            options,
+            config,
+            status: Arc::new(RwLock::new(RetranscriptionStatus::NotStarted)),
+            is_running: Arc::new(AtomicBool::new(false)),
+            is_paused: Arc::new(AtomicBool::new(false)),
+            cmd_rx,
+            status_tx,
+            worker_handle: None,
+        }
+    }
+
+    /// Start the worker to process commands. Every command is answered with the current status.
+    pub fn start(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            info!("Re-transcription worker started");
+            while let Some(cmd) = self.cmd_rx.recv().await {
+                match cmd {
+                    RetranscriptionCommand::Start => self.start_retranscription().await,
+                    RetranscriptionCommand::Pause => self.pause_retranscription().await,
+                    RetranscriptionCommand::Stop => self.stop_retranscription().await,
+                    RetranscriptionCommand::Status => {}
+                }
+
+                let _ = self
+                    .status_tx
+                    .send(RetranscriptionResponse {
+                        status: self.status.read().await.clone(),
+                    })
+                    .await;
+            }
+            info!("Re-transcription worker stopped");
+        })
+    }
+
+    async fn start_retranscription(&mut self) {
+        if self.is_running.load(Ordering::SeqCst) {
+            if self.is_paused.load(Ordering::SeqCst) {
+                info!("Resuming re-transcription");
+                self.is_paused.store(false, Ordering::SeqCst);
+                let mut status = self.status.write().await;
+                if let RetranscriptionStatus::Paused {
+                    engine,
+                    total_chunks,
+                    processed_chunks,
+                    failed_chunks,
+                } = status.clone()
+                {
+                    *status = RetranscriptionStatus::Running {
+                        engine,
+                        total_chunks,
+                        processed_chunks,
+                        failed_chunks,
+                    };
+                }
+                return;
+            }
+            warn!("Re-transcription is already running");
+            return;
+        }
+
+        self.is_running.store(true, Ordering::SeqCst);
+        self.is_paused.store(false, Ordering::SeqCst);
+        *self.status.write().await = RetranscriptionStatus::Running {
+            engine: self.options.transcription_engine.to_string(),
+            total_chunks: 0,
+            processed_chunks: 0,
+            failed_chunks: 0,
+        };
+
+        let db = self.db.clone();
+        let options = self.options.clone();
+        let config = self.config.clone();
+        let status = self.status.clone();
+        let is_running = self.is_running.clone();
+        let is_paused = self.is_paused.clone();
+
+        let handle = tokio::spawn(async move {
+            let engine = options.transcription_engine.to_string();
+            if let Err(e) = retranscribe_audio(
+                &db,
+                &options,
+                &config,
+                status.clone(),
+                is_running.clone(),
+                is_paused,
+            )
+            .await
+            {
+                error!("Re-transcription failed: {}", e);
+                let mut status = status.write().await;
+                let (total_chunks, processed_chunks) = match &*status {
+                    RetranscriptionStatus::Running {
+                        total_chunks,
+                        processed_chunks,
+                        ..
+                    }
+                    | RetranscriptionStatus::Paused {
+                        total_chunks,
+                        processed_chunks,
+                        ..
+                    } => (*total_chunks, *processed_chunks),
+                    _ => (0, 0),
+                };
+                *status = RetranscriptionStatus::Failed {
+                    engine,
+                    total_chunks,
+                    processed_chunks,
+                    error: e.to_string(),
+                };
+            }
+
+            is_running.store(false, Ordering::SeqCst);
+        });
+
+        self.worker_handle = Some(handle);
+    }
+
+    async fn pause_retranscription(&self) {
+        if !self.is_running.load(Ordering::SeqCst) {
+            warn!("Cannot pause re-transcription: not running");
+            return;
+        }
+
+        info!("Pausing re-transcription");
+        self.is_paused.store(true, Ordering::SeqCst);
+        let mut status = self.status.write().await;
+        if let RetranscriptionStatus::Running {
+            engine,
+            total_chunks,
+            processed_chunks,
+            failed_chunks,
+        } = status.clone()
+        {
+            *status = RetranscriptionStatus::Paused {
+                engine,
+                total_chunks,
+                processed_chunks,
+                failed_chunks,
+            };
+        }
+    }
+
+    async fn stop_retranscription(&self) {
+        if !self.is_running.load(Ordering::SeqCst) {
+            return;
+        }
+
+        info!("Stopping re-transcription");
+        self.is_running.store(false, Ordering::SeqCst);
+        if let Some(handle) = &self.worker_handle {
+            handle.abort();
+        }
+
+        let mut status = self.status.write().await;
+        if let RetranscriptionStatus::Running {
+            engine,
+            total_chunks,
+            processed_chunks,
+            failed_chunks,
+        }
+        | RetranscriptionStatus::Paused {
+            engine,
+            total_chunks,
+            processed_chunks,
+            failed_chunks,
+        } = status.clone()
+        {
+            *status = RetranscriptionStatus::Stopped {
+                engine,
+                total_chunks,
+                processed_chunks,
+                failed_chunks,
+            };
+        }
+    }
+}
+
+/// Re-transcribes every audio chunk that has transcriptions made by another engine than the
+/// selected one. Chunks are replaced one at a time, so a stopped job loses no work.
+async fn retranscribe_audio(
+    db: &DatabaseManager,
+    options: &AudioManagerOptions,
+    config: &RetranscriptionConfig,
+    status: Arc<RwLock<RetranscriptionStatus>>,
+    is_running: Arc<AtomicBool>,
+    is_paused: Arc<AtomicBool>,
+) -> Result<()> {
+    let start_time = std::time::Instant::now();
+    let engine = options.transcription_engine.to_string();
+    let total_chunks = db.count_audio_chunks_to_retranscribe(&engine, 0).await?;
+    let mut processed_chunks = 0;
+    let mut failed_chunks = 0;
+
+    *status.write().await = progress_status(
+        is_paused.load(Ordering::SeqCst),
+        &engine,
+        total_chunks,
+        processed_chunks,
+        failed_chunks,
+    );
+
+    info!(
+        "Starting re-transcription with {}: {} audio chunks",
+        engine, total_chunks
+    );
+    if total_chunks == 0 {
+        *status.write().await = RetranscriptionStatus::Completed {
+            engine,
+            total_chunks,
+            failed_chunks,
+            duration_secs: 0,
+        };
+        return Ok(());
+    }
+
+    let transcriber = FileTranscriber::new(options.clone()).await?;
+
+    let mut last_id = 0;
+    while is_running.load(Ordering::SeqCst) {
+        let chunks = db
+            .get_audio_chunks_to_retranscribe(&engine, last_id, config.batch_size)
+            .await?;
+        if chunks.is_empty() {
+            break;
+        }
+
+        for chunk in chunks {
+            while is_paused.load(Ordering::SeqCst) && is_running.load(Ordering::SeqCst) {
+                time::sleep(Duration::from_millis(500)).await;
+            }
+            if !is_running.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+
+            last_id = chunk.id;
+            match retranscribe_chunk(db, &chunk, &engine, &transcriber).await {
+                Ok(count) => debug!(
+                    "re-transcribed audio chunk {} into {} transcriptions",
+                    chunk.id, count
+                ),
+                Err(e) => {
+                    error!("Failed to re-transcribe audio chunk {}: {}", chunk.id, e);
+                    if !config.continue_on_error {
+                        return Err(anyhow!(
+                            "Failed to re-transcribe audio chunk {}: {}",
+                            chunk.id,
+                            e
+                        ));
+                    }
+                    failed_chunks += 1;
+                }
+            }
+
+            processed_chunks += 1;
+            *status.write().await = progress_status(
+                is_paused.load(Ordering::SeqCst),
+                &engine,
+                total_chunks,
+                processed_chunks,
+                failed_chunks,
+            );
+            time::sleep(Duration::from_millis(config.chunk_delay_ms)).await;
+        }
+    }
+
+    if is_running.load(Ordering::SeqCst) {
+        info!(
+            "Re-transcription completed: {} audio chunks, {} failed",
+            processed_chunks, failed_chunks
+        );
+        *status.write().await = RetranscriptionStatus::Completed {
+            engine,
+            total_chunks,
+            failed_chunks,
+            duration_secs: start_time.elapsed().as_secs(),
+        };
+    }
+
+    Ok(())
+}
+
+fn progress_status(
+    paused: bool,
+    engine: &str,
+    total_chunks: i64,
+    processed_chunks: i64,
+    failed_chunks: i64,
+) -> RetranscriptionStatus {
+    let engine = engine.to_string();
+    if paused {
+        RetranscriptionStatus::Paused {
+            engine,
+            total_chunks,
+            processed_chunks,
+            failed_chunks,
+        }
+    } else {
+        RetranscriptionStatus::Running {
+            engine,
+            total_chunks,
+            processed_chunks,
+            failed_chunks,
+        }
+    }
+}
+
+/// Decodes an audio chunk and runs it through VAD, diarization and transcription again.
+/// Returns the number of transcriptions stored. New transcriptions keep the speakers of the
+/// ones they overlap. Chunks in which no speech is found keep their transcriptions. Either way
+/// the chunk is marked so it isn't picked up again for `engine`, even when a fallback engine
+/// transcribed it.
+async fn retranscribe_chunk(
+    db: &DatabaseManager,
+    chunk: &AudioChunkToRetranscribe,
+    engine: &str,
+    transcriber: &FileTranscriber,
+) -> Result<usize> {
+    let speakers = db.get_audio_chunk_speakers(chunk.id).await?;
+    let transcriptions = transcriber
+        .transcribe(
+            db,
+            Path::new(&chunk.file_path),
+            &chunk.device_name,
+            &speakers,
+        )
+        .await?;
+    if transcriptions.is_empty() {
+        db.mark_audio_chunk_retranscribed(chunk.id, engine).await?;
+        return Ok(0);
+    }
+    db.replace_audio_transcriptions(chunk, engine, &transcriptions)
+        .await?;
+    Ok(transcriptions.len())
+}
+
+/// Create a re-transcription worker and return channels to control it
+pub fn create_retranscription_worker(
+    db: Arc<DatabaseManager>,
+    options: AudioManagerOptions,
+    config: Option<RetranscriptionConfig>,
+) -> (
+    mpsc::Sender<RetranscriptionCommand>,
+    mpsc::Receiver<RetranscriptionResponse>,
+    JoinHandle<()>,
+) {
+    let (cmd_tx, cmd_rx) = mpsc::channel(100);
+    let (status_tx, status_rx) = mpsc::channel(100);
+
+    let worker =
+        RetranscriptionWorker::new(db, options, cmd_rx, status_tx, config.unwrap_or_default());
+
+    let handle = worker.start();
+
+    (cmd_tx, status_rx, handle)
+}
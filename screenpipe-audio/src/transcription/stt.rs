@@ -1,15 +1,14 @@
 use crate::core::device::AudioDevice;
-use crate::core::engine::AudioTranscriptionEngine;
+use crate::core::encoding::AudioEncoding;
 use crate::speaker::embedding::EmbeddingExtractor;
 use crate::speaker::embedding_manager::EmbeddingManager;
 use crate::speaker::prepare_segments;
 use crate::speaker::segment::SpeechSegment;
-use crate::transcription::deepgram::batch::transcribe_with_deepgram;
-use crate::transcription::whisper::batch::process_with_whisper;
 use crate::utils::audio::resample;
 use crate::utils::ffmpeg::{get_new_file_path, write_audio_to_file};
 use crate::vad::VadEngine;
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 #[cfg(target_os = "macos")]
 use objc::rc::autoreleasepool;
 use screenpipe_core::Language;
@@ -21,22 +20,19 @@ use std::{
 };
 use tokio::sync::Mutex;
 use tracing::error;
-use whisper_rs::WhisperContext;
 
+use crate::transcription::{Transcript, TranscriptionEngine};
 use crate::{AudioInput, TranscriptionResult};
 
 pub const SAMPLE_RATE: u32 = 16000;
 
-#[allow(clippy::too_many_arguments)]
 pub async fn stt_sync(
     audio: &[f32],
     sample_rate: u32,
     device: &str,
-    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
-    deepgram_api_key: Option<String>,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
     languages: Vec<Language>,
-    whisper_context: Arc<WhisperContext>,
-) -> Result<String> {
+) -> Result<Transcript> {
     let audio = audio.to_vec();
 
     let device = device.to_string();
@@ -45,48 +41,22 @@ pub async fn stt_sync(
         &audio,
         sample_rate,
         &device,
-        audio_transcription_engine,
-        deepgram_api_key,
+        transcription_engine,
         languages,
-        whisper_context,
     )
     .await
 }
 
-#[allow(clippy::too_many_arguments)]
 pub async fn stt(
     audio: &[f32],
     sample_rate: u32,
     device: &str,
-    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
-    deepgram_api_key: Option<String>,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
     languages: Vec<Language>,
-    whisper_context: Arc<WhisperContext>,
-) -> Result<String> {
-    let transcription: Result<String> =
-        if audio_transcription_engine == AudioTranscriptionEngine::Deepgram.into() {
-            // Deepgram implementation
-            let api_key = deepgram_api_key.unwrap_or_default();
-
-            match transcribe_with_deepgram(&api_key, audio, device, sample_rate, languages.clone())
-                .await
-            {
-                Ok(transcription) => Ok(transcription),
-                Err(e) => {
-                    error!(
-                        "device: {}, deepgram transcription failed, falling back to Whisper: {:?}",
-                        device, e
-                    );
-                    // Fallback to Whisper
-                    process_with_whisper(audio, languages.clone(), whisper_context).await
-                }
-            }
-        } else {
-            // Existing Whisper implementation
-            process_with_whisper(audio, languages, whisper_context).await
-        };
-
-    transcription
+) -> Result<Transcript> {
+    transcription_engine
+        .transcribe(audio, sample_rate, device, &languages)
+        .await
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -97,11 +67,10 @@ pub async fn process_audio_input(
     embedding_manager: EmbeddingManager,
     embedding_extractor: Arc<StdMutex<EmbeddingExtractor>>,
     output_path: &PathBuf,
-    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
-    deepgram_api_key: Option<String>,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
     languages: Vec<Language>,
     output_sender: &crossbeam::channel::Sender<TranscriptionResult>,
-    whisper_context: Arc<WhisperContext>,
+    audio_encoding: AudioEncoding,
 ) -> Result<()> {
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -127,6 +96,7 @@ pub async fn process_audio_input(
         embedding_manager,
         embedding_extractor,
         &audio.device.to_string(),
+        audio.processed,
     )
     .await?;
 
@@ -134,13 +104,15 @@ pub async fn process_audio_input(
         return Ok(());
     }
 
-    let new_file_path = get_new_file_path(&audio.device.to_string(), output_path);
+    let new_file_path =
+        get_new_file_path(&audio.device.to_string(), output_path, audio_encoding.codec);
 
     if let Err(e) = write_audio_to_file(
         &audio.data.to_vec(),
         audio.sample_rate,
         &PathBuf::from(&new_file_path),
         false,
+        audio_encoding,
     ) {
         error!("Error writing audio to file: {:?}", e);
     }
@@ -155,12 +127,11 @@ pub async fn process_audio_input(
                     run_stt(
                         segment,
                         audio.device.clone(),
-                        audio_transcription_engine.clone(),
-                        deepgram_api_key.clone(),
+                        transcription_engine.clone(),
                         languages.clone(),
                         path,
                         timestamp,
-                        whisper_context.clone(),
+                        audio.recorded_at,
                     )
                 })
                 .await?
@@ -173,16 +144,19 @@ pub async fn process_audio_input(
             run_stt(
                 segment,
                 audio.device.clone(),
-                audio_transcription_engine.clone(),
-                deepgram_api_key.clone(),
+                transcription_engine.clone(),
                 languages.clone(),
                 path,
                 timestamp,
-                whisper_context.clone(),
+                audio.recorded_at,
             )
             .await?
         };
 
+        let transcription_result = TranscriptionResult {
+            encoding: Some(audio_encoding),
+            ..transcription_result
+        };
         if output_sender.send(transcription_result).is_err() {
             break;
         }
@@ -191,16 +165,14 @@ pub async fn process_audio_input(
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
 pub async fn run_stt(
     segment: SpeechSegment,
     device: Arc<AudioDevice>,
-    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
-    deepgram_api_key: Option<String>,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
     languages: Vec<Language>,
     path: String,
     timestamp: u64,
-    whisper_context: Arc<WhisperContext>,
+    recorded_at: DateTime<Utc>,
 ) -> Result<TranscriptionResult> {
     let audio = segment.samples.clone();
     let sample_rate = segment.sample_rate;
@@ -208,28 +180,36 @@ pub async fn run_stt(
         &audio,
         sample_rate,
         &device.to_string(),
-        audio_transcription_engine.clone(),
-        deepgram_api_key.clone(),
+        transcription_engine,
         languages.clone(),
-        whisper_context,
     )
     .await
     {
-        Ok(transcription) => Ok(TranscriptionResult {
-            input: AudioInput {
-                data: Arc::new(audio),
-                sample_rate,
-                channels: 1,
-                device: device.clone(),
-            },
-            transcription: Some(transcription),
-            path,
-            timestamp,
-            error: None,
-            speaker_embedding: segment.embedding.clone(),
-            start_time: segment.start,
-            end_time: segment.end,
-        }),
+        Ok(transcript) => {
+            // engines report times from the start of the segment, stored times are from the
+            // start of the audio file
+            let transcript = transcript.offset_by(segment.start);
+            Ok(TranscriptionResult {
+                input: AudioInput {
+                    data: Arc::new(audio),
+                    sample_rate,
+                    channels: 1,
+                    device: device.clone(),
+                    processed: false,
+                    recorded_at,
+                },
+                transcription: Some(transcript.text),
+                segments: transcript.segments,
+                fallback_engine: transcript.fallback_engine,
+                path,
+                encoding: None,
+                timestamp,
+                error: None,
+                speaker_embedding: segment.embedding.clone(),
+                start_time: segment.start,
+                end_time: segment.end,
+            })
+        }
         Err(e) => {
             error!("STT error for input {}: {:?}", device, e);
             Ok(TranscriptionResult {
@@ -238,9 +218,14 @@ pub async fn run_stt(
                     sample_rate: segment.sample_rate,
                     channels: 1,
                     device: device.clone(),
+                    processed: false,
+                    recorded_at,
                 },
                 transcription: None,
+                segments: Vec::new(),
+                fallback_engine: None,
                 path,
+                encoding: None,
                 timestamp,
                 error: Some(e.to_string()),
                 speaker_embedding: Vec::new(),
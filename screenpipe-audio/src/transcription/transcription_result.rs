@@ -1,18 +1,24 @@
-use std::sync::Arc;
+use std::{ops::Range, sync::Arc};
 
-use screenpipe_db::{DatabaseManager, Speaker};
+use screenpipe_db::{DatabaseManager, Speaker, TranscriptionSegment};
 use tracing::{debug, error, info};
 
-use crate::core::engine::AudioTranscriptionEngine;
+use crate::core::{encoding::AudioEncoding, engine::AudioTranscriptionEngine};
 
 use super::{text_utils::longest_common_word_substring, AudioInput};
 
 #[derive(Debug, Clone)]
 pub struct TranscriptionResult {
     pub path: String,
+    /// Codec and bitrate the audio file at `path` was written with
+    pub encoding: Option<AudioEncoding>,
     pub input: AudioInput,
     pub speaker_embedding: Vec<f32>,
     pub transcription: Option<String>,
+    /// Segment and word timings of the transcription, relative to the audio file at `path`.
+    pub segments: Vec<TranscriptionSegment>,
+    /// Engine that made the transcription when it isn't the selected one
+    pub fallback_engine: Option<AudioTranscriptionEngine>,
     pub timestamp: u64,
     pub error: Option<String>,
     pub start_time: f64,
@@ -44,12 +50,55 @@ impl TranscriptionResult {
     }
 }
 
+/// Keeps the words of `segments` in `keep`, counting words across segments as in the joined
+/// transcription, so that segments follow a transcription trimmed by
+/// [`TranscriptionResult::cleanup_overlap`]. Segments left without words are dropped.
+pub fn retain_segment_words(
+    segments: &[TranscriptionSegment],
+    keep: Range<usize>,
+) -> Vec<TranscriptionSegment> {
+    let mut retained = Vec::new();
+    let mut offset = 0;
+    for segment in segments {
+        let words: Vec<&str> = segment.text.split_whitespace().collect();
+        let start = keep.start.saturating_sub(offset).min(words.len());
+        let end = keep.end.saturating_sub(offset).min(words.len());
+        offset += words.len();
+        if start >= end {
+            continue;
+        }
+        if start == 0 && end == words.len() {
+            retained.push(segment.clone());
+            continue;
+        }
+
+        let mut trimmed = TranscriptionSegment {
+            text: words[start..end].join(" "),
+            ..segment.clone()
+        };
+        // word timings only line up with the text when whisper split it the same way
+        if segment.words.len() == words.len() {
+            trimmed.words = segment.words[start..end].to_vec();
+            trimmed.start_time = trimmed.words[0].start_time;
+            trimmed.end_time = trimmed.words[trimmed.words.len() - 1].end_time;
+        } else {
+            trimmed.words.clear();
+        }
+        retained.push(trimmed);
+    }
+    retained
+}
+
+/// Stores `result`. `previous_transcript` and `previous_segments` replace the transcription of
+/// the chunk `previous_transcript_id` when overlap with `result` was trimmed from it.
 pub async fn process_transcription_result(
     db: &DatabaseManager,
     result: TranscriptionResult,
     audio_transcription_engine: Arc<AudioTranscriptionEngine>,
     previous_transcript: Option<String>,
+    previous_segments: Vec<TranscriptionSegment>,
     previous_transcript_id: Option<i64>,
+    speaker_match_threshold: f64,
 ) -> Result<Option<i64>, anyhow::Error> {
     if result.error.is_some() || result.transcription.is_none() {
         error!(
@@ -59,12 +108,21 @@ pub async fn process_transcription_result(
         return Ok(None);
     }
 
-    let speaker = get_or_create_speaker_from_embedding(db, &result.speaker_embedding).await?;
+    let speaker = get_or_create_speaker_from_embedding(
+        db,
+        &result.speaker_embedding,
+        speaker_match_threshold,
+    )
+    .await?;
 
     info!("Detected speaker: {:?}", speaker);
 
     let transcription = result.transcription.unwrap();
-    let transcription_engine = audio_transcription_engine.to_string();
+    let transcription_engine = result
+        .fallback_engine
+        .as_ref()
+        .unwrap_or(&audio_transcription_engine)
+        .to_string();
     let mut chunk_id: Option<i64> = None;
 
     info!(
@@ -77,7 +135,17 @@ pub async fn process_transcription_result(
                 .update_audio_transcription(id, prev_transcript.as_str())
                 .await
             {
-                Ok(_) => {}
+                Ok(_) => {
+                    if let Err(e) = db
+                        .update_transcription_segments(id, &previous_segments)
+                        .await
+                    {
+                        error!(
+                            "Failed to update transcription segments for {}: {}",
+                            result.input.device, e
+                        );
+                    }
+                }
                 Err(e) => error!(
                     "Failed to update transcription for {}: audio_chunk_id {}",
                     result.input.device, e
@@ -85,13 +153,23 @@ pub async fn process_transcription_result(
             }
         }
     }
-    match db.get_or_insert_audio_chunk(&result.path).await {
+    match db
+        .get_or_insert_audio_chunk(
+            &result.path,
+            result.input.recorded_at,
+            result
+                .encoding
+                .map(|encoding| encoding.to_string())
+                .as_deref(),
+        )
+        .await
+    {
         Ok(audio_chunk_id) => {
             if transcription.is_empty() {
                 return Ok(Some(audio_chunk_id));
             }
 
-            if let Err(e) = db
+            match db
                 .insert_audio_transcription(
                     audio_chunk_id,
                     &transcription,
@@ -114,17 +192,31 @@ pub async fn process_transcription_result(
                 )
                 .await
             {
-                error!(
-                    "Failed to insert audio transcription for device {}: {}",
-                    result.input.device, e
-                );
-                return Ok(Some(audio_chunk_id));
-            } else {
-                debug!(
-                    "Inserted audio transcription for chunk {} from device {} using {}",
-                    audio_chunk_id, result.input.device, transcription_engine
-                );
-                chunk_id = Some(audio_chunk_id);
+                Err(e) => {
+                    error!(
+                        "Failed to insert audio transcription for device {}: {}",
+                        result.input.device, e
+                    );
+                    return Ok(Some(audio_chunk_id));
+                }
+                Ok(audio_transcription_id) => {
+                    debug!(
+                        "Inserted audio transcription for chunk {} from device {} using {}",
+                        audio_chunk_id, result.input.device, transcription_engine
+                    );
+                    if !result.segments.is_empty() {
+                        if let Err(e) = db
+                            .insert_transcription_segments(audio_transcription_id, &result.segments)
+                            .await
+                        {
+                            error!(
+                                "Failed to insert transcription segments for device {}: {}",
+                                result.input.device, e
+                            );
+                        }
+                    }
+                    chunk_id = Some(audio_chunk_id);
+                }
             }
         }
         Err(e) => error!(
@@ -135,13 +227,19 @@ pub async fn process_transcription_result(
     Ok(chunk_id)
 }
 
-async fn get_or_create_speaker_from_embedding(
+/// Attributes a voice to the closest known speaker, or to a new one if it's unknown.
+pub(crate) async fn get_or_create_speaker_from_embedding(
     db: &DatabaseManager,
     embedding: &[f32],
+    threshold: f64,
 ) -> Result<Speaker, anyhow::Error> {
-    let speaker = db.get_speaker_from_embedding(embedding).await?;
-    if let Some(speaker) = speaker {
-        Ok(speaker)
+    let speaker_match = db.match_speaker(embedding, threshold).await?;
+    if let Some(speaker_match) = speaker_match {
+        debug!(
+            "matched speaker {} at distance {:.3}",
+            speaker_match.speaker.id, speaker_match.distance
+        );
+        Ok(speaker_match.speaker)
     } else {
         let speaker = db.insert_speaker(embedding).await?;
         Ok(speaker)
@@ -1,25 +1,64 @@
 use super::detect_language;
+use crate::transcription::{Transcript, TranscriptionEngine};
 use anyhow::Result;
+use futures::future::BoxFuture;
 use screenpipe_core::Language;
+use screenpipe_db::{TranscriptionSegment, TranscriptionWord};
 use std::sync::Arc;
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperState};
+
+/// Transcribes with a local Whisper model.
+pub struct WhisperEngine {
+    context: Arc<WhisperContext>,
+}
+
+impl WhisperEngine {
+    pub fn new(context: Arc<WhisperContext>) -> Self {
+        Self { context }
+    }
+}
+
+impl TranscriptionEngine for WhisperEngine {
+    fn transcribe<'a>(
+        &'a self,
+        audio: &'a [f32],
+        _sample_rate: u32,
+        _device: &'a str,
+        languages: &'a [Language],
+    ) -> BoxFuture<'a, Result<Transcript>> {
+        Box::pin(process_with_whisper(
+            audio,
+            languages.to_vec(),
+            self.context.clone(),
+        ))
+    }
+}
+
 /// Processes audio data using the Whisper model to generate transcriptions.
 ///
 /// # Returns
-/// A string containing the processed transcript
+/// The processed transcript with its segment and word timings
 pub async fn process_with_whisper(
     audio: &[f32],
     languages: Vec<Language>,
     whisper_context: Arc<WhisperContext>,
-) -> Result<String> {
+) -> Result<Transcript> {
+    let audio = audio.to_vec();
+    // inference is CPU bound and takes seconds, it would stall the runtime's workers
+    tokio::task::spawn_blocking(move || run_whisper(audio, languages, whisper_context)).await?
+}
+
+fn run_whisper(
+    mut audio: Vec<f32>,
+    languages: Vec<Language>,
+    whisper_context: Arc<WhisperContext>,
+) -> Result<Transcript> {
     let mut whisper_state = whisper_context
         .create_state()
         .expect("failed to create key");
 
     let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 0 });
 
-    let mut audio = audio.to_vec();
-
     if audio.len() < 16000 {
         audio.resize(16000, 0.0);
     }
@@ -50,16 +89,74 @@ pub async fn process_with_whisper(
         .full_n_segments()
         .expect("failed to get number of segments");
 
-    let mut transcript = String::new();
+    let mut transcript = Transcript::default();
 
     for i in 0..num_segments {
         // Get the transcribed text and timestamps for the current segment.
         let segment = whisper_state
             .full_get_segment_text(i)
             .expect("failed to get segment");
+        transcript.text.push_str(&segment);
 
-        transcript.push_str(&segment);
+        // whisper reports times in centiseconds
+        let start_time = whisper_state.full_get_segment_t0(i)? as f64 / 100.0;
+        let end_time = whisper_state.full_get_segment_t1(i)? as f64 / 100.0;
+        let text = segment.trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+        transcript.segments.push(TranscriptionSegment {
+            start_time,
+            end_time,
+            text,
+            words: segment_words(&whisper_state, i)?,
+        });
     }
 
     Ok(transcript)
 }
+
+/// Joins the tokens of a segment into words, a token starting with a space starts a new word.
+fn segment_words(state: &WhisperState, segment: i32) -> Result<Vec<TranscriptionWord>> {
+    let mut words = Vec::new();
+    let mut bytes = Vec::new();
+    let mut current: Option<(f64, f64, f64, usize)> = None;
+
+    let mut finish = |bytes: &mut Vec<u8>, current: Option<(f64, f64, f64, usize)>| {
+        let text = String::from_utf8_lossy(bytes).trim().to_string();
+        bytes.clear();
+        if let Some((start_time, end_time, probability, tokens)) = current {
+            if !text.is_empty() {
+                words.push(TranscriptionWord {
+                    start_time,
+                    end_time,
+                    text,
+                    confidence: Some(probability / tokens as f64),
+                });
+            }
+        }
+    };
+
+    for j in 0..state.full_n_tokens(segment)? {
+        let token = state.full_get_token_bytes(segment, j)?;
+        // special tokens such as [_BEG_] or <|en|>
+        if token.starts_with(b"[_") || token.starts_with(b"<|") {
+            continue;
+        }
+        let data = state.full_get_token_data(segment, j)?;
+        let (t0, t1) = (data.t0 as f64 / 100.0, data.t1 as f64 / 100.0);
+
+        if token.starts_with(b" ") || current.is_none() {
+            finish(&mut bytes, current.take());
+            current = Some((t0, t1, data.p as f64, 1));
+        } else if let Some((_, end_time, probability, tokens)) = current.as_mut() {
+            *end_time = t1;
+            *probability += data.p as f64;
+            *tokens += 1;
+        }
+        bytes.extend_from_slice(token);
+    }
+    finish(&mut bytes, current);
+
+    Ok(words)
+}
@@ -0,0 +1,252 @@
+use anyhow::{anyhow, Result};
+use screenpipe_core::Language;
+use screenpipe_events::send_event;
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, error, info, warn};
+use vad_rs::VadStatus;
+
+use crate::{
+    core::{
+        device::{AudioDevice, DeviceType},
+        stream::AudioStream,
+    },
+    resample,
+    transcription::{
+        deepgram::streaming::RealtimeTranscriptionEvent, stt::SAMPLE_RATE, TranscriptionEngine,
+    },
+    vad::{create_vad_engine, VadEngineEnum},
+};
+
+/// Samples fed to the VAD at once, 100ms at 16kHz like when segmenting recorded audio
+const FRAME_SIZE: usize = 1600;
+/// New audio after which an utterance that is still going is transcribed again
+const PARTIAL_INTERVAL: usize = SAMPLE_RATE as usize;
+/// Silent frames after which an utterance is over
+const END_OF_SPEECH_FRAMES: usize = 8;
+/// Utterances are cut at this length so that Whisper keeps running on a short window
+const MAX_UTTERANCE: usize = 15 * SAMPLE_RATE as usize;
+/// Frames kept from before speech starts so the first word isn't clipped
+const PRE_SPEECH_FRAMES: usize = 3;
+
+/// What an [`UtteranceWindow`] wants transcribed after a frame was pushed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UtteranceUpdate {
+    /// The utterance is still going, its audio so far can be transcribed as a partial result
+    Partial,
+    /// The utterance is over, take its audio with [`UtteranceWindow::take`]
+    Final,
+}
+
+/// Sliding window over live audio holding the utterance currently spoken. Frames are
+/// collected from the moment the VAD hears speech; a partial result is due every second of
+/// new audio and a final one after a pause or once the utterance gets too long.
+#[derive(Debug, Default)]
+pub struct UtteranceWindow {
+    audio: Vec<f32>,
+    pre_speech: VecDeque<Vec<f32>>,
+    in_speech: bool,
+    silent_frames: usize,
+    since_partial: usize,
+}
+
+impl UtteranceWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a 16kHz frame and whether the VAD classified it as speech.
+    pub fn push(&mut self, frame: &[f32], is_speech: bool) -> Option<UtteranceUpdate> {
+        if !self.in_speech {
+            if !is_speech {
+                self.pre_speech.push_back(frame.to_vec());
+                if self.pre_speech.len() > PRE_SPEECH_FRAMES {
+                    self.pre_speech.pop_front();
+                }
+                return None;
+            }
+
+            self.in_speech = true;
+            self.silent_frames = 0;
+            self.since_partial = frame.len();
+            self.audio = self.pre_speech.drain(..).flatten().collect();
+            self.audio.extend_from_slice(frame);
+            return None;
+        }
+
+        self.audio.extend_from_slice(frame);
+        self.since_partial += frame.len();
+        if is_speech {
+            self.silent_frames = 0;
+        } else {
+            self.silent_frames += 1;
+        }
+
+        if self.silent_frames >= END_OF_SPEECH_FRAMES || self.audio.len() >= MAX_UTTERANCE {
+            return Some(UtteranceUpdate::Final);
+        }
+        if self.since_partial >= PARTIAL_INTERVAL {
+            self.since_partial = 0;
+            return Some(UtteranceUpdate::Partial);
+        }
+        None
+    }
+
+    /// Audio of the utterance being spoken.
+    pub fn audio(&self) -> &[f32] {
+        &self.audio
+    }
+
+    /// Ends the current utterance and returns its audio.
+    pub fn take(&mut self) -> Vec<f32> {
+        self.in_speech = false;
+        self.silent_frames = 0;
+        self.since_partial = 0;
+        std::mem::take(&mut self.audio)
+    }
+}
+
+/// Transcribes the given audio stream live with a local Whisper model, publishing the same
+/// `transcription` events as the Deepgram stream without needing the network.
+pub async fn stream_transcription_whisper(
+    stream: Arc<AudioStream>,
+    languages: Vec<Language>,
+    is_running: Arc<AtomicBool>,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
+    vad_engine: VadEngineEnum,
+) -> Result<()> {
+    start_whisper_stream(
+        stream.subscribe().await,
+        stream.device.clone(),
+        stream.device_config.sample_rate().0,
+        languages,
+        is_running,
+        transcription_engine,
+        vad_engine,
+    )
+    .await
+}
+
+/// Runs the VAD over `stream` and transcribes each utterance with `transcription_engine`:
+/// partial results while it is spoken and a final one when it ends.
+pub async fn start_whisper_stream(
+    mut stream: broadcast::Receiver<Vec<f32>>,
+    device: Arc<AudioDevice>,
+    sample_rate: u32,
+    languages: Vec<Language>,
+    is_running: Arc<AtomicBool>,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
+    vad_engine: VadEngineEnum,
+) -> Result<()> {
+    info!("starting local whisper transcription stream for {}", device);
+
+    // the VAD keeps state between frames, so every stream gets its own
+    let mut vad = create_vad_engine(vad_engine).await?;
+
+    // utterances are transcribed on their own task so that whisper never holds up the audio
+    let (utterance_tx, utterance_rx) = mpsc::channel::<(Vec<f32>, bool)>(8);
+    let transcriber = tokio::spawn(transcribe_utterances(
+        utterance_rx,
+        device.clone(),
+        languages,
+        transcription_engine,
+    ));
+
+    // device audio making up one VAD frame once resampled
+    let device_frame_size = FRAME_SIZE * sample_rate as usize / SAMPLE_RATE as usize;
+    let mut pending = Vec::new();
+    let mut window = UtteranceWindow::new();
+
+    while is_running.load(Ordering::SeqCst) {
+        let chunk = match tokio::time::timeout(Duration::from_millis(100), stream.recv()).await {
+            Ok(Ok(chunk)) => chunk,
+            Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => {
+                warn!(
+                    "whisper stream for {} skipped {} audio chunks",
+                    device, skipped
+                );
+                continue;
+            }
+            Ok(Err(broadcast::error::RecvError::Closed)) => {
+                return Err(anyhow!("audio stream for {} closed", device));
+            }
+            // wake up regularly to notice when the stream is stopped
+            Err(_) => continue,
+        };
+        pending.extend(chunk);
+
+        while pending.len() >= device_frame_size {
+            let frame: Vec<f32> = pending.drain(..device_frame_size).collect();
+            let frame = if sample_rate != SAMPLE_RATE {
+                resample(&frame, sample_rate, SAMPLE_RATE)?
+            } else {
+                frame
+            };
+
+            let is_speech = matches!(vad.audio_type(&frame)?, VadStatus::Speech);
+            match window.push(&frame, is_speech) {
+                // skip partials while another one waits for whisper, it would be stale by then
+                Some(UtteranceUpdate::Partial)
+                    if utterance_tx.capacity() == utterance_tx.max_capacity() =>
+                {
+                    let _ = utterance_tx.try_send((window.audio().to_vec(), false));
+                }
+                Some(UtteranceUpdate::Final) => {
+                    if utterance_tx.send((window.take(), true)).await.is_err() {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    drop(utterance_tx);
+    let _ = transcriber.await;
+    debug!("local whisper transcription stream for {} stopped", device);
+    Ok(())
+}
+
+async fn transcribe_utterances(
+    mut utterances: mpsc::Receiver<(Vec<f32>, bool)>,
+    device: Arc<AudioDevice>,
+    languages: Vec<Language>,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
+) {
+    let device_name = device.to_string();
+    let is_input = device.device_type == DeviceType::Input;
+
+    while let Some((audio, is_final)) = utterances.recv().await {
+        let transcript = match transcription_engine
+            .transcribe(&audio, SAMPLE_RATE, &device_name, &languages)
+            .await
+        {
+            Ok(transcript) => transcript,
+            Err(e) => {
+                error!("live transcription failed for {}: {}", device_name, e);
+                continue;
+            }
+        };
+
+        let text = transcript.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let _ = send_event(
+            "transcription",
+            RealtimeTranscriptionEvent {
+                timestamp: chrono::Utc::now(),
+                device: device_name.clone(),
+                transcription: text.to_string(),
+                is_final,
+                is_input,
+                speaker: None,
+            },
+        );
+    }
+}
@@ -1,8 +1,11 @@
+use screenpipe_core::encryption::open_media;
 use symphonia::core::audio::{AudioBufferRef, Signal};
 use symphonia::core::codecs::CODEC_TYPE_NULL;
 use symphonia::core::conv::FromSample;
 use tracing::debug;
 
+use crate::{transcription::stt::SAMPLE_RATE, utils::ffmpeg::decode_audio_with_ffmpeg};
+
 /// Converts audio samples from any supported format to f32
 fn conv<T>(samples: &mut Vec<f32>, data: std::borrow::Cow<symphonia::core::audio::AudioBuffer<T>>)
 where
@@ -14,6 +17,9 @@ where
 
 /// Decodes an audio file to PCM format (f32 samples)
 ///
+/// Encrypted files are decrypted first. Codecs symphonia can't decode, like opus, are decoded
+/// with ffmpeg at 16kHz.
+///
 /// # Arguments
 /// * `path` - Path to the audio file
 ///
@@ -29,11 +35,38 @@ where
 pub fn pcm_decode<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<(Vec<f32>, u32)> {
     debug!("Starting PCM decoding for {:?}", path.as_ref());
 
-    let src = std::fs::File::open(&path)?;
-    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(src), Default::default());
+    let data = open_media(std::fs::read(&path)?)?;
+    let extension = path
+        .as_ref()
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+
+    match symphonia_decode(data.clone(), extension.as_deref()) {
+        Ok(decoded) => Ok(decoded),
+        Err(e) => {
+            debug!(
+                "symphonia could not decode {:?} ({}), falling back to ffmpeg",
+                path.as_ref(),
+                e
+            );
+            let samples = decode_audio_with_ffmpeg(&data, SAMPLE_RATE)?;
+            Ok((samples, SAMPLE_RATE))
+        }
+    }
+}
+
+fn symphonia_decode(data: Vec<u8>, extension: Option<&str>) -> anyhow::Result<(Vec<f32>, u32)> {
+    let mss = symphonia::core::io::MediaSourceStream::new(
+        Box::new(std::io::Cursor::new(data)),
+        Default::default(),
+    );
 
     // Create a probe hint and use default options
-    let hint = symphonia::core::probe::Hint::new();
+    let mut hint = symphonia::core::probe::Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
     let probed = symphonia::default::get_probe().format(
         &hint,
         mss,
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use chrono::Utc;
 use tracing::debug;
+use screenpipe_core::encryption::{encrypt, media_encryption_key};
 use screenpipe_core::find_ffmpeg_path;
 use std::io::Write;
 use std::path::PathBuf;
@@ -10,37 +11,83 @@ use std::{
 };
 use tracing::error;
 
+use crate::core::encoding::{AudioCodec, AudioEncoding};
+
 fn encode_single_audio(
-    data: &[u8],
+    audio: &[f32],
     sample_rate: u32,
     channels: u16,
     output_path: &Path,
+    encoding: AudioEncoding,
 ) -> anyhow::Result<()> {
+    let encryption_key = media_encryption_key();
+
+    // wav is just a header in front of the samples, no need for ffmpeg
+    if encoding.codec == AudioCodec::Wav {
+        let wav = wav_bytes(audio, sample_rate, channels);
+        let wav = match encryption_key {
+            Some(key) => encrypt(key, &wav)?,
+            None => wav,
+        };
+        std::fs::write(output_path, wav)?;
+        return Ok(());
+    }
+
     debug!("Starting FFmpeg process");
+    let data: &[u8] = bytemuck::cast_slice(audio);
 
     let mut command = Command::new(find_ffmpeg_path().unwrap());
-    command
-        .args([
-            "-f",
-            "f32le",
-            "-ar",
-            &sample_rate.to_string(),
-            "-ac",
-            &channels.to_string(),
-            "-i",
-            "pipe:0",
+    command.args([
+        "-f",
+        "f32le",
+        "-ar",
+        &sample_rate.to_string(),
+        "-ac",
+        &channels.to_string(),
+        "-i",
+        "pipe:0",
+    ]);
+    let bitrate = encoding
+        .bitrate
+        .or(encoding.codec.default_bitrate())
+        .map(|b| format!("{}k", b))
+        .unwrap_or_default();
+    match encoding.codec {
+        AudioCodec::Aac => command.args([
             "-c:a",
             "aac",
             "-b:a",
-            "64k", // Reduced bitrate for higher compression
+            &bitrate,
             "-profile:a",
             "aac_low", // Use AAC-LC profile for better compatibility
-            "-movflags",
-            "+faststart", // Optimize for web streaming
-            "-f",
-            "mp4",
-            output_path.to_str().unwrap(),
-        ])
+        ]),
+        AudioCodec::Opus => command.args([
+            "-c:a",
+            "libopus",
+            "-b:a",
+            &bitrate,
+            "-application",
+            "voip", // Tuned for speech
+        ]),
+        AudioCodec::Mp3 => command.args(["-c:a", "libmp3lame", "-b:a", &bitrate]),
+        AudioCodec::Flac => command.args(["-c:a", "flac", "-compression_level", "8"]),
+        AudioCodec::Wav => unreachable!("wav is written without ffmpeg"),
+    };
+
+    let format = encoding.codec.extension();
+    if encryption_key.is_some() {
+        // We encrypt ffmpeg's output ourselves, and stdout can only take a fragmented mp4
+        if encoding.codec == AudioCodec::Aac {
+            command.args(["-movflags", "frag_keyframe+empty_moov"]);
+        }
+        command.args(["-f", format, "pipe:1"]);
+    } else {
+        if encoding.codec == AudioCodec::Aac {
+            command.args(["-movflags", "+faststart"]); // Optimize for web streaming
+        }
+        command.args(["-f", format, "-y", output_path.to_str().unwrap()]);
+    }
+    command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
@@ -52,18 +99,27 @@ fn encode_single_audio(
     debug!("FFmpeg process spawned");
     let mut stdin = ffmpeg.stdin.take().expect("Failed to open stdin");
 
-    stdin.write_all(data)?;
-
-    debug!("Dropping stdin");
-    drop(stdin);
-    debug!("Waiting for FFmpeg process to exit");
-    let output = ffmpeg.wait_with_output().unwrap();
+    // Feed stdin from another thread so FFmpeg can't stall on a full stdout pipe while we write
+    let (write_result, output) = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || {
+            let result = stdin.write_all(data);
+            debug!("Dropping stdin");
+            drop(stdin);
+            result
+        });
+        debug!("Waiting for FFmpeg process to exit");
+        let output = ffmpeg.wait_with_output();
+        (writer.join().expect("Failed to join stdin writer"), output)
+    });
+    write_result?;
+    let output = output.unwrap();
     let status = output.status;
-    let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
 
     debug!("FFmpeg process exited with status: {}", status);
-    debug!("FFmpeg stdout: {}", stdout);
+    if encryption_key.is_none() {
+        debug!("FFmpeg stdout: {}", String::from_utf8_lossy(&output.stdout));
+    }
     debug!("FFmpeg stderr: {}", stderr);
 
     if !status.success() {
@@ -75,14 +131,23 @@ fn encode_single_audio(
         ));
     }
 
+    if let Some(key) = encryption_key {
+        std::fs::write(output_path, encrypt(key, &output.stdout)?)?;
+    }
+
     Ok(())
 }
 
-pub fn get_new_file_path(device: &str, output_path: &PathBuf) -> String {
+pub fn get_new_file_path(device: &str, output_path: &PathBuf, codec: AudioCodec) -> String {
     let new_file_name = Utc::now().format("%Y-%m-%d_%H-%M-%S").to_string();
     let sanitized_device_name = device.replace(['/', '\\'], "_");
     PathBuf::from(output_path)
-        .join(format!("{}_{}.mp4", sanitized_device_name, new_file_name))
+        .join(format!(
+            "{}_{}.{}",
+            sanitized_device_name,
+            new_file_name,
+            codec.extension()
+        ))
         .to_str()
         .expect("Failed to create valid path")
         .to_string()
@@ -93,15 +158,80 @@ pub fn write_audio_to_file(
     sample_rate: u32,
     path: &PathBuf,
     skip_encoding: bool,
+    encoding: AudioEncoding,
 ) -> Result<()> {
     // Run FFmpeg in a separate task
     if !skip_encoding {
-        encode_single_audio(
-            bytemuck::cast_slice(audio),
-            sample_rate,
-            1,
-            &PathBuf::from(path),
-        )?;
+        encode_single_audio(audio, sample_rate, 1, &PathBuf::from(path), encoding)?;
     }
     Ok(())
 }
+
+/// 16-bit PCM wav file holding `audio`.
+fn wav_bytes(audio: &[f32], sample_rate: u32, channels: u16) -> Vec<u8> {
+    let data_len = (audio.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+    wav.extend_from_slice(&(channels * 2).to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in audio {
+        wav.extend_from_slice(&((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    wav
+}
+
+/// Decodes audio with ffmpeg to mono samples at `sample_rate`, for codecs symphonia can't
+/// read such as opus.
+pub fn decode_audio_with_ffmpeg(data: &[u8], sample_rate: u32) -> Result<Vec<f32>> {
+    let mut command =
+        Command::new(find_ffmpeg_path().ok_or_else(|| anyhow::anyhow!("ffmpeg not found"))?);
+    command
+        .args([
+            "-i",
+            "pipe:0",
+            "-f",
+            "f32le",
+            "-ac",
+            "1",
+            "-ar",
+            &sample_rate.to_string(),
+            "pipe:1",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut ffmpeg = command.spawn()?;
+    let mut stdin = ffmpeg.stdin.take().expect("Failed to open stdin");
+    let (write_result, output) = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(data));
+        let output = ffmpeg.wait_with_output();
+        (writer.join().expect("Failed to join stdin writer"), output)
+    });
+    let output = output?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "ffmpeg failed to decode audio: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    // ffmpeg may stop reading once it has decoded everything
+    if let Err(e) = write_result {
+        debug!("ffmpeg closed stdin early: {}", e);
+    }
+
+    Ok(output
+        .stdout
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
@@ -5,9 +5,11 @@ use screenpipe_audio::speaker::embedding::EmbeddingExtractor;
 use screenpipe_audio::speaker::embedding_manager::EmbeddingManager;
 use screenpipe_audio::speaker::prepare_segments;
 use screenpipe_audio::transcription::stt::SAMPLE_RATE;
+use screenpipe_audio::transcription::whisper::batch::WhisperEngine;
 use screenpipe_audio::transcription::whisper::model::{
     create_whisper_context_parameters, download_whisper_model,
 };
+use screenpipe_audio::transcription::TranscriptionEngine;
 use screenpipe_audio::vad::{silero::SileroVad, VadEngine};
 use screenpipe_audio::{resample, stt, AudioInput};
 use screenpipe_core::Language;
@@ -63,6 +65,8 @@ async fn test_transcription_accuracy() {
         WhisperContext::new_with_params(&quantized_path.to_string_lossy(), context_params)
             .expect("failed to load model"),
     );
+    let transcription_engine: Arc<dyn TranscriptionEngine> =
+        Arc::new(WhisperEngine::new(whisper_context));
 
     let vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>> =
         Arc::new(Mutex::new(Box::new(SileroVad::new().await.unwrap())));
@@ -85,7 +89,7 @@ async fn test_transcription_accuracy() {
     ));
 
     for (audio_file, expected_transcription) in test_cases {
-        let whisper_context = whisper_context.clone();
+        let transcription_engine = transcription_engine.clone();
         let vad_engine = Arc::clone(&vad_engine);
 
         let embedding_extractor = Arc::clone(&embedding_extractor);
@@ -102,6 +106,8 @@ async fn test_transcription_accuracy() {
                 sample_rate: 44100, // hardcoded based on test data sample rate
                 channels: 1,
                 device: Arc::new(default_input_device().unwrap()),
+                processed: false,
+                recorded_at: chrono::Utc::now(),
             };
 
             let audio_data = if audio_input.sample_rate != SAMPLE_RATE {
@@ -126,6 +132,7 @@ async fn test_transcription_accuracy() {
                 embedding_manager,
                 embedding_extractor,
                 &audio_input.device.name,
+                false,
             )
             .await
             .unwrap();
@@ -136,15 +143,13 @@ async fn test_transcription_accuracy() {
                     &segment.samples,
                     audio_input.sample_rate,
                     &audio_input.device.to_string(),
-                    Arc::new(AudioTranscriptionEngine::WhisperLargeV3Turbo),
-                    None,
+                    transcription_engine.clone(),
                     vec![Language::English],
-                    whisper_context.clone(),
                 )
                 .await
                 .unwrap();
 
-                transcription.push_str(&transcript);
+                transcription.push_str(&transcript.text);
             }
 
             let distance = levenshtein(expected_transcription, &transcription.to_lowercase());
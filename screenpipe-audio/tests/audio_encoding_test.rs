@@ -0,0 +1,136 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_audio::core::encoding::{recompress_audio_file, AudioCodec, AudioEncoding};
+    use screenpipe_audio::{pcm_decode, write_audio_to_file};
+    use std::f32::consts::PI;
+
+    fn sine(sample_rate: u32, seconds: u32) -> Vec<f32> {
+        (0..seconds * sample_rate)
+            .map(|i| 0.5 * (2.0 * PI * 440.0 * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_audio_codec_parsing() {
+        for codec in AudioCodec::ALL {
+            assert_eq!(codec.to_string().parse::<AudioCodec>().unwrap(), codec);
+        }
+        assert_eq!("OPUS".parse::<AudioCodec>().unwrap(), AudioCodec::Opus);
+        assert!("vorbis".parse::<AudioCodec>().is_err());
+
+        assert_eq!(
+            AudioCodec::from_path("/data/MacBook Pro Microphone (input)_2024.mp4"),
+            Some(AudioCodec::Aac)
+        );
+        assert_eq!(AudioCodec::from_path("chunk.OGG"), Some(AudioCodec::Opus));
+        assert_eq!(AudioCodec::from_path("chunk.m4a"), None);
+        assert_eq!(AudioCodec::from_path("chunk"), None);
+    }
+
+    #[test]
+    fn test_audio_encoding_bitrate_defaults() {
+        assert_eq!(AudioEncoding::default().codec, AudioCodec::Aac);
+        assert_eq!(AudioEncoding::default().bitrate, Some(64));
+        assert_eq!(AudioEncoding::new(AudioCodec::Opus, None).bitrate, Some(24));
+        assert_eq!(
+            AudioEncoding::new(AudioCodec::Opus, Some(16)).to_string(),
+            "opus 16k"
+        );
+        // lossless codecs ignore the bitrate
+        assert_eq!(
+            AudioEncoding::new(AudioCodec::Flac, Some(128)).bitrate,
+            None
+        );
+        assert_eq!(AudioEncoding::new(AudioCodec::Wav, None).to_string(), "wav");
+    }
+
+    #[test]
+    fn test_recompress_audio_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let sample_rate = 16000;
+        let audio = sine(sample_rate, 2);
+        let original = dir.path().join("chunk.wav");
+        write_audio_to_file(
+            &audio,
+            sample_rate,
+            &original,
+            false,
+            AudioEncoding::new(AudioCodec::Wav, None),
+        )
+        .unwrap();
+
+        assert!(
+            recompress_audio_file(&original, AudioEncoding::new(AudioCodec::Wav, None)).is_err()
+        );
+
+        let flac =
+            recompress_audio_file(&original, AudioEncoding::new(AudioCodec::Flac, None)).unwrap();
+        assert_eq!(flac, dir.path().join("chunk.flac"));
+        assert!(original.exists());
+
+        let (decoded, decoded_rate) = pcm_decode(&flac).unwrap();
+        assert_eq!(decoded_rate, sample_rate);
+        assert!((decoded.len() as i64 - audio.len() as i64).abs() < sample_rate as i64 / 10);
+        let error = decoded
+            .iter()
+            .zip(&audio)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0f32, f32::max);
+        assert!(error < 0.01, "lossless round trip differs by {}", error);
+    }
+
+    #[test]
+    fn test_recompress_audio_file_to_another_bitrate() {
+        let dir = tempfile::tempdir().unwrap();
+        let sample_rate = 16000;
+        let original = dir.path().join("chunk.mp3");
+        write_audio_to_file(
+            &sine(sample_rate, 2),
+            sample_rate,
+            &original,
+            false,
+            AudioEncoding::new(AudioCodec::Mp3, Some(128)),
+        )
+        .unwrap();
+        let size_before = std::fs::metadata(&original).unwrap().len();
+
+        // the same codec at another bitrate replaces the file
+        let recompressed =
+            recompress_audio_file(&original, AudioEncoding::new(AudioCodec::Mp3, Some(32)))
+                .unwrap();
+        assert_eq!(recompressed, original);
+        assert!(std::fs::metadata(&original).unwrap().len() < size_before);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(pcm_decode(&original).is_ok());
+    }
+
+    #[test]
+    fn test_every_codec_decodes() {
+        let dir = tempfile::tempdir().unwrap();
+        let sample_rate = 16000;
+        let audio = sine(sample_rate, 2);
+
+        for codec in AudioCodec::ALL {
+            let path = dir.path().join(format!("chunk.{}", codec.extension()));
+            write_audio_to_file(
+                &audio,
+                sample_rate,
+                &path,
+                false,
+                AudioEncoding::new(codec, None),
+            )
+            .unwrap();
+
+            let (decoded, decoded_rate) = pcm_decode(&path)
+                .unwrap_or_else(|e| panic!("failed to decode {} chunk: {}", codec, e));
+            // lossy encoders pad the stream a bit
+            let seconds = decoded.len() as f64 / decoded_rate as f64;
+            assert!(
+                (seconds - 2.0).abs() < 0.25,
+                "{} chunk decoded to {}s",
+                codec,
+                seconds
+            );
+        }
+    }
+}
@@ -0,0 +1,165 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_audio::core::device::{AudioDevice, DeviceType};
+    use screenpipe_audio::processing::{
+        echo_cancellation::EchoReference, gain_control::GainControl,
+        noise_suppression::NoiseSuppressor, AudioProcessing, AudioProcessingConfig, AudioProcessor,
+    };
+    use std::f32::consts::PI;
+    use tokio::sync::broadcast;
+
+    const SAMPLE_RATE: u32 = 16000;
+    const CHUNK: usize = 160;
+
+    /// Deterministic white noise in [-amplitude, amplitude]
+    fn noise(len: usize, amplitude: f32, seed: u64) -> Vec<f32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                ((state >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0) * amplitude
+            })
+            .collect()
+    }
+
+    fn sine(len: usize, frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..len)
+            .map(|i| amplitude * (2.0 * PI * frequency * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    fn rms(audio: &[f32]) -> f32 {
+        (audio.iter().map(|s| s * s).sum::<f32>() / audio.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_gain_control_levels_quiet_and_loud_audio() {
+        for (amplitude, expected) in [(0.01, 0.0707), (0.9, 0.1)] {
+            let mut gain_control = GainControl::new(SAMPLE_RATE);
+            let mut audio = sine(10 * SAMPLE_RATE as usize, 440.0, amplitude);
+            for chunk in audio.chunks_mut(CHUNK) {
+                gain_control.process(chunk);
+            }
+
+            let level = rms(&audio[9 * SAMPLE_RATE as usize..]);
+            assert!(
+                (level - expected).abs() < 0.01,
+                "amplitude {} ended at rms {}",
+                amplitude,
+                level
+            );
+        }
+    }
+
+    #[test]
+    fn test_noise_suppression_removes_noise_and_keeps_speech() {
+        let second = SAMPLE_RATE as usize;
+        // two seconds of background noise, then a tone over it
+        let mut tone = vec![0.0; 2 * second];
+        tone.extend(sine(second, 440.0, 0.3));
+        let input: Vec<f32> = noise(3 * second, 0.05, 7)
+            .iter()
+            .zip(&tone)
+            .map(|(n, t)| n + t)
+            .collect();
+
+        let mut suppressor = NoiseSuppressor::new(SAMPLE_RATE);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(CHUNK) {
+            suppressor.process(chunk).unwrap();
+        }
+
+        let noise_ratio = rms(&output[second..2 * second]) / rms(&input[second..2 * second]);
+        assert!(noise_ratio < 0.3, "noise only reduced to {}", noise_ratio);
+
+        let tone_start = 2 * second + second / 5;
+        let tone_ratio = rms(&output[tone_start..]) / rms(&input[tone_start..]);
+        assert!(tone_ratio > 0.9, "tone reduced to {}", tone_ratio);
+    }
+
+    /// How much of the echo of noise played on the speakers is left after cancelling it, when
+    /// the speakers reach the mic `echo_delay` samples later, and the output audio reaches the
+    /// canceller `lead` chunks before the mic audio.
+    fn remaining_echo(echo_delay: usize, lead: usize) -> f32 {
+        let len = 8 * SAMPLE_RATE as usize;
+        let far = noise(len + lead * CHUNK, 0.5, 42);
+        // quieter and slightly smeared
+        let near: Vec<f32> = (0..len)
+            .map(|i| {
+                let delayed = |d: usize| if i >= d { far[i - d] } else { 0.0 };
+                0.3 * delayed(echo_delay) + 0.1 * delayed(echo_delay + 1)
+            })
+            .collect();
+
+        let (tx, rx) = broadcast::channel(1000);
+        let mut reference = EchoReference::new();
+        reference.add_source(rx, SAMPLE_RATE);
+        let processing: AudioProcessing = "aec".parse().unwrap();
+        let mut processor =
+            AudioProcessor::new(processing, &DeviceType::Input, SAMPLE_RATE, Some(reference));
+
+        let mut played = far.chunks(CHUNK);
+        for chunk in played.by_ref().take(lead) {
+            tx.send(chunk.to_vec()).unwrap();
+        }
+        let mut output = near.clone();
+        for (chunk, played) in output.chunks_mut(CHUNK).zip(played) {
+            tx.send(played.to_vec()).unwrap();
+            processor.process(chunk).unwrap();
+        }
+
+        let tail = len - 2 * SAMPLE_RATE as usize;
+        rms(&output[tail..]) / rms(&near[tail..])
+    }
+
+    #[tokio::test]
+    async fn test_echo_cancellation_removes_played_audio() {
+        // the speakers reach the mic 50ms later
+        let ratio = remaining_echo(800, 0);
+        assert!(ratio < 0.1, "echo only reduced to {}", ratio);
+    }
+
+    #[tokio::test]
+    async fn test_echo_cancellation_with_delayed_echo() {
+        // the echo arrives 400ms after its reference, longer than the filter covers
+        let ratio = remaining_echo(6400, 0);
+        assert!(ratio < 0.1, "echo only reduced to {}", ratio);
+    }
+
+    #[tokio::test]
+    async fn test_echo_cancellation_with_delayed_mic_audio() {
+        // output audio buffered ahead of the mic, within the lookahead and far beyond it
+        for lead in [2, 50] {
+            let ratio = remaining_echo(800, lead);
+            assert!(
+                ratio < 0.1,
+                "echo only reduced to {} with the output {} chunks ahead",
+                ratio,
+                lead
+            );
+        }
+    }
+
+    #[test]
+    fn test_audio_processing_parsing() {
+        let processing: AudioProcessing = "agc, denoise".parse().unwrap();
+        assert!(processing.gain_control && processing.noise_suppression);
+        assert!(!processing.echo_cancellation);
+        assert_eq!(processing.to_string(), "agc,denoise");
+        assert_eq!(
+            "none".parse::<AudioProcessing>().unwrap().to_string(),
+            "none"
+        );
+        assert!("reverb".parse::<AudioProcessing>().is_err());
+
+        let config =
+            AudioProcessingConfig::parse(&["agc", "Studio Mic (input)=agc,denoise,aec"]).unwrap();
+        let studio = AudioDevice::new("Studio Mic".to_string(), DeviceType::Input);
+        let other = AudioDevice::new("Speakers".to_string(), DeviceType::Output);
+        assert!(config.for_device(&studio).echo_cancellation);
+        assert_eq!(config.for_device(&other), config.default);
+        assert!(config.default.gain_control);
+    }
+}
@@ -9,12 +9,15 @@ mod tests {
     use screenpipe_audio::core::engine::AudioTranscriptionEngine;
     use screenpipe_audio::core::record_and_transcribe;
     use screenpipe_audio::core::stream::AudioStream;
+    use screenpipe_audio::processing::AudioProcessing;
     use screenpipe_audio::speaker::embedding::EmbeddingExtractor;
     use screenpipe_audio::speaker::embedding_manager::EmbeddingManager;
     use screenpipe_audio::speaker::prepare_segments;
+    use screenpipe_audio::transcription::whisper::batch::WhisperEngine;
     use screenpipe_audio::transcription::whisper::model::{
         create_whisper_context_parameters, download_whisper_model,
     };
+    use screenpipe_audio::transcription::TranscriptionEngine;
     use screenpipe_audio::vad::{silero::SileroVad, VadEngine};
     use screenpipe_audio::{pcm_decode, stt, AudioInput};
     use screenpipe_core::Language;
@@ -77,6 +80,8 @@ mod tests {
             duration,
             Arc::new(sender),
             is_running,
+            AudioProcessing::default(),
+            None,
         )
         .await;
         println!("record_and_transcribe completed");
@@ -143,6 +148,8 @@ mod tests {
             duration,
             Arc::new(sender),
             is_running,
+            AudioProcessing::default(),
+            None,
         )
         .await
         .unwrap();
@@ -222,6 +229,8 @@ mod tests {
             WhisperContext::new_with_params(&quantized_path.to_string_lossy(), context_params)
                 .expect("failed to load model"),
         );
+        let transcription_engine: Arc<dyn TranscriptionEngine> =
+            Arc::new(WhisperEngine::new(whisper_context));
 
         let vad_engine: Arc<tokio::sync::Mutex<Box<dyn VadEngine + Send>>> = Arc::new(
             tokio::sync::Mutex::new(Box::new(SileroVad::new().await.unwrap())),
@@ -234,6 +243,8 @@ mod tests {
             sample_rate: 44100, // hardcoded based on test data sample rate
             channels: 1,
             device: Arc::new(default_input_device().unwrap()),
+            processed: false,
+            recorded_at: chrono::Utc::now(),
         };
 
         // Create the missing parameters
@@ -265,6 +276,7 @@ mod tests {
             embedding_manager,
             embedding_extractor,
             &audio_input.device.to_string(),
+            false,
         )
         .await
         .unwrap();
@@ -275,15 +287,13 @@ mod tests {
                 &segment.samples,
                 audio_input.sample_rate,
                 &audio_input.device.to_string(),
-                Arc::new(AudioTranscriptionEngine::WhisperLargeV3Turbo),
-                None,
+                transcription_engine.clone(),
                 vec![Language::Arabic],
-                whisper_context.clone(),
             )
             .await
             .unwrap();
 
-            transcription_result.push_str(&transcript);
+            transcription_result.push_str(&transcript.text);
             transcription_result.push('\n');
         }
 
@@ -317,6 +327,8 @@ mod tests {
             sample_rate: 16000, // Adjust this based on your test audio
             channels: 1,
             device: Arc::new(default_output_device().await.unwrap()),
+            processed: false,
+            recorded_at: chrono::Utc::now(),
         };
 
         let project_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -351,6 +363,8 @@ mod tests {
             WhisperContext::new_with_params(&quantized_path.to_string_lossy(), context_params)
                 .expect("failed to load model"),
         );
+        let transcription_engine: Arc<dyn TranscriptionEngine> =
+            Arc::new(WhisperEngine::new(whisper_context));
 
         // Initialize VAD engine
         let vad_engine: Box<dyn VadEngine + Send> = Box::new(SileroVad::new().await.unwrap());
@@ -366,6 +380,7 @@ mod tests {
             embedding_manager,
             embedding_extractor,
             &audio_input.device.to_string(),
+            false,
         )
         .await
         .unwrap();
@@ -376,15 +391,13 @@ mod tests {
                 &segment.samples,
                 audio_input.sample_rate,
                 &audio_input.device.to_string(),
-                Arc::new(AudioTranscriptionEngine::WhisperLargeV3Turbo),
-                None,
+                transcription_engine.clone(),
                 vec![Language::English],
-                whisper_context.clone(),
             )
             .await
             .unwrap();
 
-            transcription.push_str(&transcript);
+            transcription.push_str(&transcript.text);
         }
 
         let elapsed_time = start_time.elapsed();
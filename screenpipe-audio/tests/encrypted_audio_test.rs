@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_audio::core::encoding::{recompress_audio_file, AudioCodec, AudioEncoding};
+    use screenpipe_audio::{pcm_decode, write_audio_to_file};
+    use screenpipe_core::encryption::{
+        is_encrypted, open_media, set_media_encryption_key, EncryptionKey,
+    };
+    use std::f32::consts::PI;
+
+    // the key is process wide, which is why these tests live in their own binary
+    fn enable_encryption() {
+        let _ = set_media_encryption_key(EncryptionKey::from_hex(&"cd".repeat(32)).unwrap());
+    }
+
+    fn sine(sample_rate: u32, seconds: u32) -> Vec<f32> {
+        (0..seconds * sample_rate)
+            .map(|i| 0.5 * (2.0 * PI * 440.0 * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_every_codec_decodes_encrypted() {
+        enable_encryption();
+        let dir = tempfile::tempdir().unwrap();
+        let sample_rate = 16000;
+        let audio = sine(sample_rate, 2);
+
+        for codec in AudioCodec::ALL {
+            let path = dir.path().join(format!("chunk.{}", codec.extension()));
+            write_audio_to_file(
+                &audio,
+                sample_rate,
+                &path,
+                false,
+                AudioEncoding::new(codec, None),
+            )
+            .unwrap();
+
+            let data = std::fs::read(&path).unwrap();
+            assert!(is_encrypted(&data), "{} chunk is not encrypted", codec);
+            if codec == AudioCodec::Aac {
+                // only a fragmented mp4 can be piped back to ffmpeg once decrypted
+                let plaintext = open_media(data).unwrap();
+                assert!(plaintext.windows(4).any(|w| w == b"moof"));
+            }
+
+            let (decoded, decoded_rate) = pcm_decode(&path)
+                .unwrap_or_else(|e| panic!("failed to decode encrypted {} chunk: {}", codec, e));
+            let seconds = decoded.len() as f64 / decoded_rate as f64;
+            assert!(
+                (seconds - 2.0).abs() < 0.25,
+                "encrypted {} chunk decoded to {}s",
+                codec,
+                seconds
+            );
+        }
+    }
+
+    #[test]
+    fn test_recompress_encrypted_audio_file() {
+        enable_encryption();
+        let dir = tempfile::tempdir().unwrap();
+        let sample_rate = 16000;
+        let original = dir.path().join("chunk.mp4");
+        write_audio_to_file(
+            &sine(sample_rate, 2),
+            sample_rate,
+            &original,
+            false,
+            AudioEncoding::default(),
+        )
+        .unwrap();
+
+        let opus =
+            recompress_audio_file(&original, AudioEncoding::new(AudioCodec::Opus, None)).unwrap();
+        assert!(is_encrypted(&std::fs::read(&opus).unwrap()));
+        let (decoded, decoded_rate) = pcm_decode(&opus).unwrap();
+        assert!((decoded.len() as f64 / decoded_rate as f64 - 2.0).abs() < 0.25);
+    }
+}
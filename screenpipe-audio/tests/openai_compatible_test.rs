@@ -0,0 +1,129 @@
+use screenpipe_audio::transcription::openai_compatible::OpenAiCompatibleEngine;
+use screenpipe_audio::transcription::TranscriptionEngine;
+use screenpipe_core::Language;
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Answers a single request with `status` and `body`, returns the raw request it received.
+async fn serve_once(status: &'static str, body: String) -> (String, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+            let Some(header_end) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+                continue;
+            };
+            let headers = String::from_utf8_lossy(&request[..header_end]).to_lowercase();
+            if headers.contains("transfer-encoding: chunked") {
+                if request.ends_with(b"0\r\n\r\n") {
+                    break;
+                }
+                continue;
+            }
+            let content_length = headers
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            if request.len() >= header_end + 4 + content_length {
+                break;
+            }
+        }
+
+        let response = format!(
+            "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8_lossy(&request).to_string()
+    });
+
+    (url, handle)
+}
+
+#[tokio::test]
+async fn test_openai_compatible_engine_parses_verbose_json() {
+    let body = json!({
+        "text": " Hello there. General Kenobi.",
+        "language": "english",
+        "segments": [
+            { "id": 0, "start": 0.0, "end": 1.2, "text": " Hello there." },
+            { "id": 1, "start": 1.5, "end": 2.8, "text": " General Kenobi." }
+        ],
+        "words": [
+            { "word": "Hello", "start": 0.0, "end": 0.5, "probability": 0.9 },
+            { "word": "there.", "start": 0.6, "end": 1.2 },
+            { "word": "General", "start": 1.5, "end": 2.0 },
+            { "word": "Kenobi.", "start": 2.1, "end": 2.8 }
+        ]
+    })
+    .to_string();
+    let (url, server) = serve_once("200 OK", body).await;
+
+    let engine = OpenAiCompatibleEngine::new(
+        &format!("{}/v1/", url),
+        Some("base.en".to_string()),
+        Some("secret".to_string()),
+    )
+    .unwrap();
+    let audio = vec![0.0f32; 1600];
+    let transcript = engine
+        .transcribe(&audio, 16000, "mic", &[Language::English])
+        .await
+        .unwrap();
+
+    assert_eq!(transcript.text, "Hello there. General Kenobi.");
+    assert_eq!(transcript.segments.len(), 2);
+    assert_eq!(transcript.segments[1].text, "General Kenobi.");
+    assert_eq!(transcript.segments[1].start_time, 1.5);
+    assert_eq!(transcript.segments[0].words.len(), 2);
+    assert_eq!(transcript.segments[0].words[0].text, "Hello");
+    assert_eq!(transcript.segments[0].words[0].confidence, Some(0.9));
+    assert_eq!(transcript.segments[1].words[1].text, "Kenobi.");
+
+    let request = server.await.unwrap();
+    assert!(request.starts_with("POST /v1/audio/transcriptions HTTP/1.1"));
+    assert!(request
+        .to_lowercase()
+        .contains("authorization: bearer secret"));
+    assert!(request.contains("name=\"model\"\r\n\r\nbase.en"));
+    assert!(request.contains("name=\"response_format\"\r\n\r\nverbose_json"));
+    assert!(request.contains("name=\"language\"\r\n\r\nen"));
+    assert!(request.contains("filename=\"audio.wav\""));
+    assert!(request.contains("RIFF"));
+}
+
+#[tokio::test]
+async fn test_openai_compatible_engine_reports_server_errors() {
+    let (url, server) = serve_once(
+        "500 Internal Server Error",
+        json!({ "error": "model not loaded" }).to_string(),
+    )
+    .await;
+
+    let engine = OpenAiCompatibleEngine::new(&url, None, None).unwrap();
+    let audio = vec![0.0f32; 1600];
+    let error = engine
+        .transcribe(&audio, 16000, "mic", &[])
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("model not loaded"));
+
+    let request = server.await.unwrap();
+    assert!(request.contains("name=\"model\"\r\n\r\nwhisper-1"));
+    assert!(!request.contains("name=\"language\""));
+    assert!(!request.to_lowercase().contains("authorization"));
+}
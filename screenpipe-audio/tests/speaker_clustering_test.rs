@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_audio::speaker::clustering::{
+        cluster_speakers, cluster_speakers_in_blocks, SpeakerMergeSet,
+    };
+    use screenpipe_db::{Speaker, SpeakerCentroid};
+
+    fn speaker(id: i64, name: &str, enrolled: bool, axis: usize, mix: f32) -> SpeakerCentroid {
+        let mut centroid = vec![0.0; 8];
+        centroid[axis] = 1.0;
+        centroid[(axis + 1) % 8] = mix;
+        SpeakerCentroid {
+            speaker: Speaker {
+                id,
+                name: name.to_string(),
+                metadata: String::new(),
+            },
+            enrolled,
+            centroid,
+        }
+    }
+
+    #[test]
+    fn test_cluster_speakers_groups_close_voices() {
+        let speakers = vec![
+            speaker(1, "", false, 0, 0.0),
+            speaker(2, "", false, 0, 0.1),
+            speaker(3, "", false, 3, 0.0),
+            speaker(4, "alice", false, 0, 0.2),
+            speaker(5, "", false, 3, 0.15),
+            speaker(6, "", false, 6, 0.0),
+        ];
+
+        let merge_sets = cluster_speakers(&speakers, 0.1);
+        assert_eq!(
+            merge_sets,
+            vec![
+                // the named speaker is kept
+                SpeakerMergeSet {
+                    speaker_to_keep_id: 4,
+                    speaker_ids_to_merge: vec![1, 2],
+                },
+                SpeakerMergeSet {
+                    speaker_to_keep_id: 3,
+                    speaker_ids_to_merge: vec![5],
+                },
+            ]
+        );
+
+        // nothing is close enough with a strict threshold
+        assert!(cluster_speakers(&speakers, 0.001).is_empty());
+    }
+
+    #[test]
+    fn test_cluster_speakers_keeps_differently_named_speakers_apart() {
+        let speakers = vec![
+            speaker(1, "alice", false, 0, 0.0),
+            speaker(2, "bob", false, 0, 0.05),
+            speaker(3, "", true, 0, 0.1),
+            speaker(4, "", false, 0, 0.02),
+        ];
+
+        // each unnamed voice joins the closest named speaker it can be grouped with
+        let merge_sets = cluster_speakers(&speakers, 0.5);
+        assert_eq!(
+            merge_sets,
+            vec![
+                SpeakerMergeSet {
+                    speaker_to_keep_id: 1,
+                    speaker_ids_to_merge: vec![4],
+                },
+                SpeakerMergeSet {
+                    speaker_to_keep_id: 2,
+                    speaker_ids_to_merge: vec![3],
+                },
+            ]
+        );
+
+        // enrolled speakers are preferred over unnamed ones
+        let merge_sets = cluster_speakers(&speakers[2..], 0.5);
+        assert_eq!(
+            merge_sets,
+            vec![SpeakerMergeSet {
+                speaker_to_keep_id: 3,
+                speaker_ids_to_merge: vec![4],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cluster_speakers_in_blocks() {
+        // the same voice split across blocks of two is merged in a later pass
+        let speakers = vec![
+            speaker(1, "", false, 0, 0.0),
+            speaker(2, "", false, 3, 0.0),
+            speaker(3, "", false, 0, 0.0),
+            speaker(4, "", false, 3, 0.0),
+            speaker(5, "", false, 0, 0.0),
+        ];
+
+        let merge_sets = cluster_speakers_in_blocks(&speakers, 0.1, 2);
+        assert_eq!(
+            merge_sets,
+            vec![
+                SpeakerMergeSet {
+                    speaker_to_keep_id: 1,
+                    speaker_ids_to_merge: vec![3, 5],
+                },
+                SpeakerMergeSet {
+                    speaker_to_keep_id: 2,
+                    speaker_ids_to_merge: vec![4],
+                },
+            ]
+        );
+        assert_eq!(merge_sets, cluster_speakers(&speakers, 0.1));
+    }
+}
@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_audio::transcription::retain_segment_words;
+    use screenpipe_db::{TranscriptionSegment, TranscriptionWord};
+
+    fn word(start_time: f64, end_time: f64, text: &str) -> TranscriptionWord {
+        TranscriptionWord {
+            start_time,
+            end_time,
+            text: text.to_string(),
+            confidence: None,
+        }
+    }
+
+    fn segments() -> Vec<TranscriptionSegment> {
+        vec![
+            TranscriptionSegment {
+                start_time: 0.0,
+                end_time: 1.0,
+                text: "good morning".to_string(),
+                words: vec![word(0.0, 0.4, "good"), word(0.4, 1.0, "morning")],
+            },
+            TranscriptionSegment {
+                start_time: 1.0,
+                end_time: 2.5,
+                text: "the quick brown fox".to_string(),
+                words: vec![
+                    word(1.0, 1.3, "the"),
+                    word(1.3, 1.8, "quick"),
+                    word(1.8, 2.2, "brown"),
+                    word(2.2, 2.5, "fox"),
+                ],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_retain_segment_words_trims_the_start() {
+        let retained = retain_segment_words(&segments(), 3..usize::MAX);
+        assert_eq!(retained.len(), 1);
+        assert_eq!(retained[0].text, "quick brown fox");
+        assert_eq!(retained[0].start_time, 1.3);
+        assert_eq!(retained[0].end_time, 2.5);
+        assert_eq!(retained[0].words.len(), 3);
+    }
+
+    #[test]
+    fn test_retain_segment_words_trims_the_end() {
+        let retained = retain_segment_words(&segments(), 0..2);
+        assert_eq!(retained, segments()[..1].to_vec());
+
+        let retained = retain_segment_words(&segments(), 0..4);
+        assert_eq!(retained.len(), 2);
+        assert_eq!(retained[1].text, "the quick");
+        assert_eq!(retained[1].end_time, 1.8);
+    }
+
+    #[test]
+    fn test_retain_segment_words_without_matching_word_timings() {
+        let mut segments = segments();
+        segments[1].words.pop();
+        let retained = retain_segment_words(&segments, 3..usize::MAX);
+        assert_eq!(retained[0].text, "quick brown fox");
+        // the segment's timing is kept when the words can't be lined up with the text
+        assert_eq!(retained[0].start_time, 1.0);
+        assert!(retained[0].words.is_empty());
+    }
+}
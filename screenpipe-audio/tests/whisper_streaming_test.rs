@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_audio::transcription::whisper::streaming::{UtteranceUpdate, UtteranceWindow};
+
+    const FRAME: usize = 1600;
+
+    fn push_frames(
+        window: &mut UtteranceWindow,
+        frames: usize,
+        is_speech: bool,
+    ) -> Vec<UtteranceUpdate> {
+        (0..frames)
+            .filter_map(|_| window.push(&[0.1; FRAME], is_speech))
+            .collect()
+    }
+
+    #[test]
+    fn test_utterance_window_ignores_silence() {
+        let mut window = UtteranceWindow::new();
+        assert!(push_frames(&mut window, 50, false).is_empty());
+        assert!(window.audio().is_empty());
+    }
+
+    #[test]
+    fn test_utterance_window_partial_and_final_results() {
+        let mut window = UtteranceWindow::new();
+        push_frames(&mut window, 5, false);
+
+        // a partial result every second of speech
+        let updates = push_frames(&mut window, 25, true);
+        assert_eq!(updates, vec![UtteranceUpdate::Partial; 2]);
+        // frames from just before speech started are kept
+        assert_eq!(window.audio().len(), (3 + 25) * FRAME);
+
+        // a short pause doesn't end the utterance
+        assert!(push_frames(&mut window, 4, false).is_empty());
+        push_frames(&mut window, 2, true);
+
+        let updates = push_frames(&mut window, 8, false);
+        assert_eq!(updates.last(), Some(&UtteranceUpdate::Final));
+
+        let audio = window.take();
+        assert_eq!(audio.len(), (3 + 25 + 4 + 2 + 8) * FRAME);
+        assert!(window.audio().is_empty());
+        assert!(push_frames(&mut window, 10, false).is_empty());
+    }
+
+    #[test]
+    fn test_utterance_window_cuts_long_utterances() {
+        let mut window = UtteranceWindow::new();
+        let updates = push_frames(&mut window, 150, true);
+        assert_eq!(updates.last(), Some(&UtteranceUpdate::Final));
+        assert_eq!(window.take().len(), 150 * FRAME);
+    }
+}
@@ -0,0 +1,297 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::OnceCell;
+use rand::RngCore;
+use std::fmt;
+use std::path::Path;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tracing::warn;
+
+/// Environment variable holding the hex encoded 32 byte encryption key.
+pub const ENCRYPTION_KEY_ENV: &str = "SCREENPIPE_ENCRYPTION_KEY";
+
+// Encrypted files start with MAGIC followed by a random nonce prefix, then a sequence of
+// segments, each stored as a little endian u32 length and an AES-256-GCM ciphertext. The nonce
+// of a segment is the file's prefix followed by the segment's index, so segments cannot be
+// reordered or moved between files.
+const MAGIC: &[u8; 8] = b"SPENC001";
+const NONCE_PREFIX_LEN: usize = 8;
+const HEADER_LEN: usize = MAGIC.len() + NONCE_PREFIX_LEN;
+const SEGMENT_SIZE: usize = 64 * 1024;
+
+static MEDIA_KEY: OnceCell<EncryptionKey> = OnceCell::new();
+
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn from_hex(hex_key: &str) -> Result<Self> {
+        let bytes = hex::decode(hex_key.trim()).context("encryption key is not valid hex")?;
+        let key: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| anyhow!("encryption key must be 32 bytes (64 hex characters)"))?;
+        Ok(Self(key))
+    }
+
+    /// Loads the key from `key_file` if given, otherwise from `SCREENPIPE_ENCRYPTION_KEY`.
+    /// Returns `None` when neither is set, i.e. encryption is disabled.
+    pub fn load(key_file: Option<&Path>) -> Result<Option<Self>> {
+        if let Some(key_file) = key_file {
+            let contents = std::fs::read_to_string(key_file).with_context(|| {
+                format!("failed to read encryption key file {}", key_file.display())
+            })?;
+            return Self::from_hex(&contents).map(Some);
+        }
+
+        match std::env::var(ENCRYPTION_KEY_ENV) {
+            Ok(value) if !value.trim().is_empty() => Self::from_hex(&value).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// Sets the key used to encrypt recorded media for the rest of the process.
+pub fn set_media_encryption_key(key: EncryptionKey) -> Result<()> {
+    MEDIA_KEY
+        .set(key)
+        .map_err(|_| anyhow!("media encryption key is already set"))
+}
+
+/// The key recorded media is encrypted with, if encryption is enabled.
+pub fn media_encryption_key() -> Option<&'static EncryptionKey> {
+    MEDIA_KEY.get()
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+struct SegmentCipher {
+    cipher: Aes256Gcm,
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    index: u32,
+}
+
+impl SegmentCipher {
+    fn new(key: &EncryptionKey, nonce_prefix: [u8; NONCE_PREFIX_LEN]) -> Self {
+        Self {
+            cipher: key.cipher(),
+            nonce_prefix,
+            index: 0,
+        }
+    }
+
+    fn next_nonce(&mut self) -> Result<[u8; 12]> {
+        let mut nonce = [0u8; 12];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(&self.nonce_prefix);
+        nonce[NONCE_PREFIX_LEN..].copy_from_slice(&self.index.to_be_bytes());
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("file is too large to encrypt"))?;
+        Ok(nonce)
+    }
+
+    fn seal(&mut self, plaintext: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        let nonce = self.next_nonce()?;
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| anyhow!("failed to encrypt segment"))?;
+        out.extend_from_slice(&(ciphertext.len() as u32).to_le_bytes());
+        out.extend_from_slice(&ciphertext);
+        Ok(())
+    }
+
+    fn open(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = self.next_nonce()?;
+        self.cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext)
+            .map_err(|_| anyhow!("failed to decrypt segment, wrong key or corrupted file"))
+    }
+}
+
+fn new_header() -> (Vec<u8>, [u8; NONCE_PREFIX_LEN]) {
+    let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut nonce_prefix);
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&nonce_prefix);
+    (header, nonce_prefix)
+}
+
+pub fn encrypt(key: &EncryptionKey, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let (mut out, nonce_prefix) = new_header();
+    let mut segments = SegmentCipher::new(key, nonce_prefix);
+    for chunk in plaintext.chunks(SEGMENT_SIZE) {
+        segments.seal(chunk, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Decrypts data produced by [`encrypt`] or [`encrypt_to_file`]. A segment cut off at the end,
+/// e.g. because recording was interrupted, is dropped and the data before it is returned.
+pub fn decrypt(key: &EncryptionKey, data: &[u8]) -> Result<Vec<u8>> {
+    if !is_encrypted(data) || data.len() < HEADER_LEN {
+        return Err(anyhow!("data is not encrypted"));
+    }
+
+    let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+    nonce_prefix.copy_from_slice(&data[MAGIC.len()..HEADER_LEN]);
+    let mut segments = SegmentCipher::new(key, nonce_prefix);
+
+    let mut plaintext = Vec::with_capacity(data.len());
+    let mut rest = &data[HEADER_LEN..];
+    while !rest.is_empty() {
+        if rest.len() < 4 {
+            warn!("ignoring truncated segment at the end of encrypted data");
+            break;
+        }
+        let len = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if rest.len() < 4 + len {
+            warn!("ignoring truncated segment at the end of encrypted data");
+            break;
+        }
+        plaintext.extend_from_slice(&segments.open(&rest[4..4 + len])?);
+        rest = &rest[4 + len..];
+    }
+
+    Ok(plaintext)
+}
+
+/// Encrypts everything read from `reader` into `path` as it arrives, so the plaintext is never
+/// written to disk.
+pub async fn encrypt_to_file<R: AsyncRead + Unpin>(
+    key: &EncryptionKey,
+    mut reader: R,
+    path: &str,
+) -> Result<()> {
+    let mut file = tokio::fs::File::create(path).await?;
+    let (header, nonce_prefix) = new_header();
+    file.write_all(&header).await?;
+
+    let mut segments = SegmentCipher::new(key, nonce_prefix);
+    let mut buffer = vec![0u8; SEGMENT_SIZE];
+    let mut sealed = Vec::with_capacity(SEGMENT_SIZE + 32);
+    loop {
+        // fill a whole segment unless the stream ends first
+        let mut filled = 0;
+        while filled < SEGMENT_SIZE {
+            let read = reader.read(&mut buffer[filled..]).await?;
+            if read == 0 {
+                break;
+            }
+            filled += read;
+        }
+        if filled == 0 {
+            break;
+        }
+
+        sealed.clear();
+        segments.seal(&buffer[..filled], &mut sealed)?;
+        file.write_all(&sealed).await?;
+
+        if filled < SEGMENT_SIZE {
+            break;
+        }
+    }
+
+    file.flush().await?;
+    Ok(())
+}
+
+/// Encrypts `data` with the media key if encryption is enabled, otherwise returns it unchanged.
+pub fn seal_media(data: Vec<u8>) -> Result<Vec<u8>> {
+    match media_encryption_key() {
+        Some(key) => encrypt(key, &data),
+        None => Ok(data),
+    }
+}
+
+/// Decrypts `data` with the media key if it is encrypted, otherwise returns it unchanged.
+pub fn open_media(data: Vec<u8>) -> Result<Vec<u8>> {
+    if !is_encrypted(&data) {
+        return Ok(data);
+    }
+    let key = media_encryption_key()
+        .ok_or_else(|| anyhow!("media is encrypted but no encryption key was provided"))?;
+    decrypt(key, &data)
+}
+
+/// Reads a media file, decrypting it if needed.
+pub async fn read_media_file(path: &str) -> Result<Vec<u8>> {
+    open_media(tokio::fs::read(path).await?)
+}
+
+/// Whether the file at `path` was written encrypted.
+pub async fn is_encrypted_file(path: &str) -> Result<bool> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut magic = [0u8; MAGIC.len()];
+    match file.read_exact(&mut magic).await {
+        Ok(_) => Ok(&magic == MAGIC),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key() -> EncryptionKey {
+        EncryptionKey::from_hex(&"ab".repeat(32)).unwrap()
+    }
+
+    #[test]
+    fn test_encrypt_roundtrip() {
+        let key = test_key();
+        let plaintext: Vec<u8> = (0..SEGMENT_SIZE * 2 + 123).map(|i| i as u8).collect();
+
+        let encrypted = encrypt(&key, &plaintext).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(decrypt(&key, &encrypted).unwrap(), plaintext);
+
+        let wrong_key = EncryptionKey::from_hex(&"cd".repeat(32)).unwrap();
+        assert!(decrypt(&wrong_key, &encrypted).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_to_file() {
+        let key = test_key();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chunk.mp4");
+        let plaintext: Vec<u8> = (0..SEGMENT_SIZE + 7).map(|i| (i % 251) as u8).collect();
+
+        encrypt_to_file(&key, plaintext.as_slice(), path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        let data = tokio::fs::read(&path).await.unwrap();
+        assert!(is_encrypted_file(path.to_str().unwrap()).await.unwrap());
+        assert_eq!(decrypt(&key, &data).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(EncryptionKey::from_hex("not hex").is_err());
+        assert!(EncryptionKey::from_hex("abcd").is_err());
+    }
+}
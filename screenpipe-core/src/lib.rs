@@ -1,5 +1,6 @@
 pub mod ffmpeg;
 pub use ffmpeg::find_ffmpeg_path;
+pub mod encryption;
 #[cfg(feature = "llm")]
 pub mod llm;
 #[cfg(feature = "llm")]
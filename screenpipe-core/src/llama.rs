@@ -1,7 +1,7 @@
 #[cfg(feature = "llm")]
 mod llm_module {
     use anyhow::{Error as E, Result};
-    use std::path::PathBuf;
+    use std::sync::Mutex;
 
     use candle::{DType, Device, Tensor};
     use candle_nn::VarBuilder;
@@ -94,23 +94,37 @@ mod llm_module {
     }
 
     pub struct Llama {
+        model_id: String,
         llama_config: candle_transformers::models::llama::Config,
-        // device: Device,
-        filenames: Vec<PathBuf>,
+        device: Device,
+        dtype: DType,
         tokenizer: Tokenizer,
-        // llama: model::Llama,
+        /// Loaded once, locked for a whole generation so that requests run one at a time
+        llama: Mutex<model::Llama>,
         eos_token_id: Option<model::LlamaEosToks>,
         config: LlamaInitConfig,
     }
 
     impl Llama {
         pub fn new() -> Result<Self> {
-            // let device = Device::new_metal(0).unwrap_or(Device::new_cuda(0).unwrap_or(Device::Cpu));
+            Self::from_hub(&LlamaInitConfig::default().which.model_id())
+        }
+
+        /// Loads a Llama architecture model from the Hugging Face hub. The repo must contain
+        /// `config.json`, `tokenizer.json` and a single `model.safetensors`.
+        pub fn from_hub(model_id: &str) -> Result<Self> {
+            let device = Device::new_metal(0).unwrap_or(Device::new_cuda(0).unwrap_or(Device::Cpu));
             let init_config = LlamaInitConfig::default();
+            // half precision is slow or unsupported for many ops on cpu
+            let dtype = if device.is_cpu() {
+                DType::F32
+            } else {
+                init_config.dtype
+            };
             let api = hf_hub::api::sync::Api::new()?;
 
             let hf_api = api.repo(Repo::with_revision(
-                init_config.which.model_id(),
+                model_id.to_string(),
                 RepoType::Model,
                 "main".to_string(),
             ));
@@ -121,47 +135,46 @@ mod llm_module {
             let llama_config = config.into_config(init_config.use_flash_attn);
 
             let filenames = vec![hf_api.get("model.safetensors")?];
-            // let vb = unsafe {
-            //     VarBuilder::from_mmaped_safetensors(&filenames, init_config.dtype, &device)?
-            // };
+            let vb = unsafe { VarBuilder::from_mmaped_safetensors(&filenames, dtype, &device)? };
 
             let tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
 
-            // let llama = model::Llama::load(vb, &llama_config)?;
+            let llama = model::Llama::load(vb, &llama_config)?;
 
-            let eos_token_id = tokenizer
-                .token_to_id(EOS_TOKEN)
-                .map(model::LlamaEosToks::Single);
+            // llama 3 models end turns with tokens listed in their config rather than </s>
+            let eos_token_id = llama_config.eos_token_id.clone().or_else(|| {
+                tokenizer
+                    .token_to_id(EOS_TOKEN)
+                    .map(model::LlamaEosToks::Single)
+            });
             Ok(Self {
+                model_id: model_id.to_string(),
                 llama_config,
-                // device,
-                filenames,
+                device,
+                dtype,
                 eos_token_id,
                 tokenizer,
-                // llama,
+                llama: Mutex::new(llama),
                 config: init_config,
             })
         }
 
-        // TODO Implement
-        pub fn llama_stream_text(&self) -> Result<()> {
-            Ok(())
+        pub fn model_id(&self) -> &str {
+            &self.model_id
         }
     }
 
     impl Model for Llama {
-        fn chat(&self, request: ChatRequest) -> Result<ChatResponse> {
-            let device = Device::new_metal(0).unwrap_or(Device::new_cuda(0).unwrap_or(Device::Cpu));
-
-            let vb = unsafe {
-                VarBuilder::from_mmaped_safetensors(
-                    &self.filenames.clone(),
-                    self.config.dtype,
-                    &device,
-                )?
-            };
-
-            let llama = model::Llama::load(vb, &self.llama_config)?;
+        fn chat_stream(
+            &self,
+            request: ChatRequest,
+            on_token: &mut dyn FnMut(&str) -> Result<()>,
+        ) -> Result<ChatResponse> {
+            let (device, dtype) = (&self.device, self.dtype);
+            let llama = self
+                .llama
+                .lock()
+                .map_err(|_| E::msg("llama model lock poisoned"))?;
 
             let sample_len = request
                 .max_completion_tokens
@@ -172,8 +185,7 @@ mod llm_module {
                 .map(|m| m.content.as_str())
                 .collect::<Vec<&str>>()
                 .join("\n\n");
-            let mut cache =
-                model::Cache::new(true, self.config.dtype, &self.llama_config, &device)?;
+            let mut cache = model::Cache::new(true, dtype, &self.llama_config, device)?;
 
             let temperature = request.temperature.unwrap_or(self.config.temperature);
             let top_k = request.top_k.or(self.config.top_k);
@@ -219,7 +231,7 @@ mod llm_module {
                     start_gen = std::time::Instant::now()
                 }
                 let ctxt = &tokens[tokens.len().saturating_sub(context_size)..];
-                let input = Tensor::new(ctxt, &device)?.unsqueeze(0)?;
+                let input = Tensor::new(ctxt, device)?.unsqueeze(0)?;
                 let logits = llama.forward(&input, context_index, &mut cache)?;
                 let logits = logits.squeeze(0)?;
                 let logits = if self.config.repeat_penalty == 1. {
@@ -251,16 +263,18 @@ mod llm_module {
                     _ => (),
                 }
                 if let Some(t) = tokenizer.next_token(next_token)? {
+                    on_token(&t)?;
                     output.push_str(&t);
                 }
             }
             if let Some(rest) = tokenizer.decode_rest().map_err(E::msg)? {
+                on_token(&rest)?;
                 output.push_str(&rest);
             }
             let dt = start_gen.elapsed();
             tokenizer.clear();
 
-            let tokens_per_second = (token_generated - 1) as f64 / dt.as_secs_f64();
+            let tokens_per_second = (token_generated - 1).max(0) as f64 / dt.as_secs_f64();
 
             debug!(
                 "Llama: {} tokens generated ({} token/s)",
@@ -268,10 +282,10 @@ mod llm_module {
             );
 
             Ok(ChatResponse {
-                id: "123".to_string(),
+                id: format!("chatcmpl-{:016x}", rand::random::<u64>()),
                 object: "chat.completion".to_string(),
-                created: 1,
-                model: "llama3.2-1B-Instruct".to_string(),
+                created: chrono::Utc::now().timestamp(),
+                model: self.model_id.clone(),
                 system_fingerprint: "".to_string(),
                 choices: vec![ChatResponseChoice {
                     index: 0,
@@ -285,12 +299,16 @@ mod llm_module {
                 usage: ChatResponseUsage {
                     prompt_tokens: prompt_tokens as i64,
                     completion_tokens: token_generated,
-                    total_tokens: token_generated,
+                    total_tokens: prompt_tokens as i64 + token_generated,
                     completion_tokens_details: serde_json::Value::Null,
                     tokens_per_second,
                 },
             })
         }
+
+        fn model_id(&self) -> &str {
+            &self.model_id
+        }
     }
 }
 // Optionally, you can re-export the module contents if needed
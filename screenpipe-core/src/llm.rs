@@ -38,6 +38,34 @@ mod llm_module {
         pub usage: ChatResponseUsage,
     }
 
+    /// The part of a message generated since the previous chunk.
+    #[derive(Deserialize, Serialize, Clone, Debug, Default)]
+    pub struct ChatMessageDelta {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub role: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub content: Option<String>,
+    }
+
+    #[derive(Deserialize, Serialize)]
+    pub struct ChatResponseChunkChoice {
+        pub index: i64,
+        pub delta: ChatMessageDelta,
+        pub logprobs: Option<serde_json::Value>,
+        pub finish_reason: Option<String>,
+    }
+
+    /// A streamed piece of a [`ChatResponse`], sent as `chat.completion.chunk` events.
+    #[derive(Serialize, Deserialize)]
+    pub struct ChatResponseChunk {
+        pub id: String,
+        pub object: String,
+        pub created: i64,
+        pub model: String,
+        pub system_fingerprint: String,
+        pub choices: Vec<ChatResponseChunkChoice>,
+    }
+
     #[derive(Deserialize, Clone, Debug)]
     pub struct ChatRequest {
         pub messages: Vec<ChatMessage>,
@@ -51,29 +79,64 @@ mod llm_module {
     }
 
     pub trait Model {
-        fn chat(&self, request: ChatRequest) -> anyhow::Result<ChatResponse>;
+        /// Generates a reply, passing each piece of text to `on_token` as soon as it is decoded.
+        /// Generation stops early if `on_token` returns an error.
+        fn chat_stream(
+            &self,
+            request: ChatRequest,
+            on_token: &mut dyn FnMut(&str) -> anyhow::Result<()>,
+        ) -> anyhow::Result<ChatResponse>;
+
+        fn chat(&self, request: ChatRequest) -> anyhow::Result<ChatResponse> {
+            self.chat_stream(request, &mut |_| Ok(()))
+        }
+
+        /// Name the model is reported under in responses.
+        fn model_id(&self) -> &str;
     }
 
     pub struct LLM {
-        model: Llama,
+        model: Box<dyn Model + Send + Sync>,
     }
 
     pub enum ModelName {
         Llama,
+        /// Any Llama architecture model on the Hugging Face hub, e.g. a tiny one to test on CPU.
+        LlamaFromHub(String),
     }
 
     impl LLM {
         pub fn new(model_name: ModelName) -> anyhow::Result<Self> {
             let model = match model_name {
                 ModelName::Llama => Llama::new()?,
+                ModelName::LlamaFromHub(model_id) => Llama::from_hub(&model_id)?,
             };
 
-            Ok(Self { model })
+            Ok(Self::from_model(model))
+        }
+
+        /// Wraps any other model, e.g. a stub that serves canned replies in tests.
+        pub fn from_model(model: impl Model + Send + Sync + 'static) -> Self {
+            Self {
+                model: Box::new(model),
+            }
         }
 
         pub fn chat(&self, request: ChatRequest) -> anyhow::Result<ChatResponse> {
             self.model.chat(request)
         }
+
+        pub fn chat_stream(
+            &self,
+            request: ChatRequest,
+            on_token: &mut dyn FnMut(&str) -> anyhow::Result<()>,
+        ) -> anyhow::Result<ChatResponse> {
+            self.model.chat_stream(request, on_token)
+        }
+
+        pub fn model_id(&self) -> &str {
+            self.model.model_id()
+        }
     }
     /// This is a wrapper around a tokenizer to ensure that tokens can be returned to the user in a
     /// streaming way rather than having to wait for the full decoding.
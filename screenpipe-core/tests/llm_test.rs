@@ -0,0 +1,60 @@
+#[cfg(all(test, feature = "llm"))]
+mod tests {
+    use screenpipe_core::{ChatMessage, ChatRequest, ModelName, LLM};
+
+    // randomly initialized llama with a few layers, small enough to run on cpu
+    const TINY_MODEL: &str = "hf-internal-testing/tiny-random-LlamaForCausalLM";
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "summarize my day".to_string(),
+            }],
+            stream: true,
+            max_completion_tokens: Some(16),
+            temperature: Some(0.0),
+            top_p: None,
+            top_k: None,
+            seed: Some(42),
+        }
+    }
+
+    #[test]
+    #[ignore] // downloads a model from the hugging face hub
+    fn test_chat_stream_matches_response() {
+        let llm = LLM::new(ModelName::LlamaFromHub(TINY_MODEL.to_string())).unwrap();
+
+        let mut streamed = String::new();
+        let response = llm
+            .chat_stream(request(), &mut |token| {
+                streamed.push_str(token);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(response.model, TINY_MODEL);
+        assert_eq!(response.choices[0].message.content, streamed);
+        assert!(response.usage.completion_tokens > 0);
+        assert!(response.usage.completion_tokens <= 16);
+
+        // greedy sampling makes the non streaming reply identical
+        let response = llm.chat(request()).unwrap();
+        assert_eq!(response.choices[0].message.content, streamed);
+    }
+
+    #[test]
+    #[ignore] // downloads a model from the hugging face hub
+    fn test_chat_stream_stops_when_callback_fails() {
+        let llm = LLM::new(ModelName::LlamaFromHub(TINY_MODEL.to_string())).unwrap();
+
+        let mut calls = 0;
+        let result = llm.chat_stream(request(), &mut |_| {
+            calls += 1;
+            anyhow::bail!("client disconnected")
+        });
+
+        assert!(result.is_err());
+        assert!(calls <= 1);
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::{Sqlite, SqlitePool, Transaction};
+
+use crate::{
+    ExportedAudioChunk, ExportedAudioTranscription, ExportedFrame, ExportedOcrText,
+    ExportedSpeaker, ExportedTag, ExportedTranscriptionSegment, ExportedUiMonitoring,
+    ExportedVideoChunk, ImportedContent,
+};
+
+/// Rows added in one transaction, so that a large import doesn't hold the write lock the
+/// recorder needs for long
+const BATCH_SIZE: usize = 1000;
+
+/// Adds rows exported from another database, remapping their ids.
+///
+/// Rows must be added parents first (speakers, video chunks, frames, OCR text, audio chunks,
+/// audio transcriptions, transcription segments, tags, UI monitoring), so that references can be resolved. Chunks are
+/// matched by file name and rows that are already present are skipped, so importing the same
+/// archive twice adds nothing. Rows are committed in batches, the last one by
+/// [`DataImport::commit`]. An import that fails keeps the batches committed before, importing
+/// again adds the rest.
+pub struct DataImport<'a> {
+    pool: &'a SqlitePool,
+    tx: Transaction<'static, Sqlite>,
+    batch_rows: usize,
+    committed_batches: usize,
+    speaker_ids: HashMap<i64, i64>,
+    video_chunk_ids: HashMap<i64, i64>,
+    frame_ids: HashMap<i64, i64>,
+    new_frames: HashSet<i64>,
+    audio_chunk_ids: HashMap<i64, i64>,
+    audio_transcription_ids: HashMap<i64, i64>,
+    new_audio_transcriptions: HashSet<i64>,
+    imported: ImportedContent,
+}
+
+impl<'a> DataImport<'a> {
+    pub(crate) async fn new(pool: &'a SqlitePool) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            pool,
+            tx: pool.begin().await?,
+            batch_rows: 0,
+            committed_batches: 0,
+            speaker_ids: HashMap::new(),
+            video_chunk_ids: HashMap::new(),
+            frame_ids: HashMap::new(),
+            new_frames: HashSet::new(),
+            audio_chunk_ids: HashMap::new(),
+            audio_transcription_ids: HashMap::new(),
+            new_audio_transcriptions: HashSet::new(),
+            imported: ImportedContent::default(),
+        })
+    }
+
+    /// Batches committed so far. Rows added since are rolled back if the import fails.
+    pub fn committed_batches(&self) -> usize {
+        self.committed_batches
+    }
+
+    /// Named speakers are merged with an existing speaker of the same name.
+    pub async fn add_speaker(&mut self, speaker: &ExportedSpeaker) -> Result<(), sqlx::Error> {
+        self.next_row().await?;
+        if let Some(name) = speaker.name.as_deref().filter(|name| !name.is_empty()) {
+            let existing: Option<i64> =
+                sqlx::query_scalar("SELECT id FROM speakers WHERE name = ?1 ORDER BY id LIMIT 1")
+                    .bind(name)
+                    .fetch_optional(&mut *self.tx)
+                    .await?;
+            if let Some(id) = existing {
+                self.speaker_ids.insert(speaker.id, id);
+                self.imported.skipped += 1;
+                return Ok(());
+            }
+        }
+
+        let id =
+            sqlx::query("INSERT INTO speakers (name, metadata, hallucination) VALUES (?1, ?2, ?3)")
+                .bind(&speaker.name)
+                .bind(&speaker.metadata)
+                .bind(speaker.hallucination.unwrap_or(false))
+                .execute(&mut *self.tx)
+                .await?
+                .last_insert_rowid();
+        self.speaker_ids.insert(speaker.id, id);
+        self.imported.speakers += 1;
+        Ok(())
+    }
+
+    /// Returns whether the chunk was inserted, i.e. whether its media still has to be placed
+    /// at `file_path`.
+    pub async fn add_video_chunk(
+        &mut self,
+        chunk: &ExportedVideoChunk,
+        file_path: &str,
+    ) -> Result<bool, sqlx::Error> {
+        self.next_row().await?;
+        if let Some(id) = self.find_chunk("video_chunks", file_path).await? {
+            self.video_chunk_ids.insert(chunk.id, id);
+            self.imported.skipped += 1;
+            return Ok(false);
+        }
+
+        let id = sqlx::query("INSERT INTO video_chunks (file_path, device_name) VALUES (?1, ?2)")
+            .bind(file_path)
+            .bind(&chunk.device_name)
+            .execute(&mut *self.tx)
+            .await?
+            .last_insert_rowid();
+        self.video_chunk_ids.insert(chunk.id, id);
+        self.imported.video_chunks += 1;
+        Ok(true)
+    }
+
+    pub async fn add_frame(&mut self, frame: &ExportedFrame) -> Result<(), sqlx::Error> {
+        self.next_row().await?;
+        let Some(&video_chunk_id) = self.video_chunk_ids.get(&frame.video_chunk_id) else {
+            self.imported.skipped += 1;
+            return Ok(());
+        };
+
+        let existing: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM frames WHERE video_chunk_id = ?1 AND offset_index = ?2 LIMIT 1",
+        )
+        .bind(video_chunk_id)
+        .bind(frame.offset_index)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        if let Some(id) = existing {
+            self.frame_ids.insert(frame.id, id);
+            self.imported.skipped += 1;
+            return Ok(());
+        }
+
+        let id = sqlx::query(
+            "INSERT INTO frames (video_chunk_id, offset_index, timestamp, name, browser_url, app_name, window_name, focused) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )
+        .bind(video_chunk_id)
+        .bind(frame.offset_index)
+        .bind(frame.timestamp)
+        .bind(&frame.name)
+        .bind(&frame.browser_url)
+        .bind(&frame.app_name)
+        .bind(&frame.window_name)
+        .bind(frame.focused)
+        .execute(&mut *self.tx)
+        .await?
+        .last_insert_rowid();
+        self.frame_ids.insert(frame.id, id);
+        self.new_frames.insert(frame.id);
+        self.imported.frames += 1;
+        Ok(())
+    }
+
+    /// Only frames inserted by this import or left without OCR text, e.g. by an import that
+    /// failed, get OCR text. Other existing frames keep theirs.
+    pub async fn add_ocr_text(&mut self, ocr: &ExportedOcrText) -> Result<(), sqlx::Error> {
+        self.next_row().await?;
+        let Some(&frame_id) = self.frame_ids.get(&ocr.frame_id) else {
+            self.imported.skipped += 1;
+            return Ok(());
+        };
+        if !self.new_frames.contains(&ocr.frame_id) {
+            let has_text: Option<i64> =
+                sqlx::query_scalar("SELECT 1 FROM ocr_text WHERE frame_id = ?1 LIMIT 1")
+                    .bind(frame_id)
+                    .fetch_optional(&mut *self.tx)
+                    .await?;
+            if has_text.is_some() {
+                self.imported.skipped += 1;
+                return Ok(());
+            }
+            self.new_frames.insert(ocr.frame_id);
+        }
+
+        sqlx::query(
+            "INSERT INTO ocr_text (frame_id, text, text_json, app_name, ocr_engine, window_name, focused, text_length) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )
+        .bind(frame_id)
+        .bind(&ocr.text)
+        .bind(&ocr.text_json)
+        .bind(&ocr.app_name)
+        .bind(&ocr.ocr_engine)
+        .bind(&ocr.window_name)
+        .bind(ocr.focused)
+        .bind(ocr.text.len() as i64)
+        .execute(&mut *self.tx)
+        .await?;
+        self.imported.ocr_text += 1;
+        Ok(())
+    }
+
+    /// Returns whether the chunk was inserted, i.e. whether its media still has to be placed
+    /// at `file_path`.
+    pub async fn add_audio_chunk(
+        &mut self,
+        chunk: &ExportedAudioChunk,
+        file_path: &str,
+    ) -> Result<bool, sqlx::Error> {
+        self.next_row().await?;
+        if let Some(id) = self.find_chunk("audio_chunks", file_path).await? {
+            self.audio_chunk_ids.insert(chunk.id, id);
+            self.imported.skipped += 1;
+            return Ok(false);
+        }
+
+        let id = sqlx::query("INSERT INTO audio_chunks (file_path, timestamp) VALUES (?1, ?2)")
+            .bind(file_path)
+            .bind(chunk.timestamp)
+            .execute(&mut *self.tx)
+            .await?
+            .last_insert_rowid();
+        self.audio_chunk_ids.insert(chunk.id, id);
+        self.imported.audio_chunks += 1;
+        Ok(true)
+    }
+
+    pub async fn add_audio_transcription(
+        &mut self,
+        transcription: &ExportedAudioTranscription,
+    ) -> Result<(), sqlx::Error> {
+        self.next_row().await?;
+        let Some(&audio_chunk_id) = self.audio_chunk_ids.get(&transcription.audio_chunk_id) else {
+            self.imported.skipped += 1;
+            return Ok(());
+        };
+
+        let existing: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM audio_transcriptions WHERE audio_chunk_id = ?1 AND offset_index = ?2 AND transcription = ?3 LIMIT 1",
+        )
+        .bind(audio_chunk_id)
+        .bind(transcription.offset_index)
+        .bind(&transcription.transcription)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        if let Some(id) = existing {
+            self.audio_transcription_ids.insert(transcription.id, id);
+            self.imported.skipped += 1;
+            return Ok(());
+        }
+
+        let speaker_id = transcription
+            .speaker_id
+            .and_then(|id| self.speaker_ids.get(&id).copied());
+        let id = sqlx::query(
+            "INSERT INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine, device, is_input_device, speaker_id, start_time, end_time, text_length) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )
+        .bind(audio_chunk_id)
+        .bind(&transcription.transcription)
+        .bind(transcription.offset_index)
+        .bind(transcription.timestamp)
+        .bind(&transcription.transcription_engine)
+        .bind(&transcription.device)
+        .bind(transcription.is_input_device)
+        .bind(speaker_id)
+        .bind(transcription.start_time)
+        .bind(transcription.end_time)
+        .bind(transcription.transcription.len() as i64)
+        .execute(&mut *self.tx)
+        .await?
+        .last_insert_rowid();
+        self.audio_transcription_ids.insert(transcription.id, id);
+        self.new_audio_transcriptions.insert(transcription.id);
+        self.imported.audio_transcriptions += 1;
+        Ok(())
+    }
+
+    /// Only transcriptions inserted by this import or left without segments get segments,
+    /// other existing ones keep theirs.
+    pub async fn add_transcription_segment(
+        &mut self,
+        segment: &ExportedTranscriptionSegment,
+    ) -> Result<(), sqlx::Error> {
+        self.next_row().await?;
+        let Some(&audio_transcription_id) = self
+            .audio_transcription_ids
+            .get(&segment.audio_transcription_id)
+        else {
+            self.imported.skipped += 1;
+            return Ok(());
+        };
+        if !self
+            .new_audio_transcriptions
+            .contains(&segment.audio_transcription_id)
+        {
+            let has_segments: Option<i64> = sqlx::query_scalar(
+                "SELECT 1 FROM transcription_segments WHERE audio_transcription_id = ?1 LIMIT 1",
+            )
+            .bind(audio_transcription_id)
+            .fetch_optional(&mut *self.tx)
+            .await?;
+            if has_segments.is_some() {
+                self.imported.skipped += 1;
+                return Ok(());
+            }
+            self.new_audio_transcriptions
+                .insert(segment.audio_transcription_id);
+        }
+
+        sqlx::query(
+            "INSERT INTO transcription_segments (audio_transcription_id, segment_index, start_time, end_time, text, words) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )
+        .bind(audio_transcription_id)
+        .bind(segment.segment_index)
+        .bind(segment.start_time)
+        .bind(segment.end_time)
+        .bind(&segment.text)
+        .bind(&segment.words)
+        .execute(&mut *self.tx)
+        .await?;
+        self.imported.transcription_segments += 1;
+        Ok(())
+    }
+
+    pub async fn add_tag(&mut self, tag: &ExportedTag) -> Result<(), sqlx::Error> {
+        self.next_row().await?;
+        let (sql, target_id) = match tag.content_type.as_str() {
+            "vision" => (
+                "INSERT INTO vision_tags (vision_id, tag_id) VALUES (?, ?) ON CONFLICT DO NOTHING",
+                self.frame_ids.get(&tag.id),
+            ),
+            "audio" => (
+                "INSERT INTO audio_tags (audio_chunk_id, tag_id) VALUES (?, ?) ON CONFLICT DO NOTHING",
+                self.audio_chunk_ids.get(&tag.id),
+            ),
+            _ => ("", None),
+        };
+        let Some(&target_id) = target_id else {
+            self.imported.skipped += 1;
+            return Ok(());
+        };
+
+        let tag_id: i64 = sqlx::query_scalar(
+            "INSERT INTO tags (name) VALUES (?) ON CONFLICT(name) DO UPDATE SET name=name RETURNING id",
+        )
+        .bind(&tag.name)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        let inserted = sqlx::query(sql)
+            .bind(target_id)
+            .bind(tag_id)
+            .execute(&mut *self.tx)
+            .await?
+            .rows_affected();
+        if inserted > 0 {
+            self.imported.tags += 1;
+        } else {
+            self.imported.skipped += 1;
+        }
+        Ok(())
+    }
+
+    pub async fn add_ui_monitoring(
+        &mut self,
+        ui: &ExportedUiMonitoring,
+    ) -> Result<(), sqlx::Error> {
+        self.next_row().await?;
+        let exists: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM ui_monitoring WHERE timestamp = ?1 AND app = ?2 AND window = ?3 LIMIT 1",
+        )
+        .bind(ui.timestamp)
+        .bind(&ui.app)
+        .bind(&ui.window)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        if exists.is_some() {
+            self.imported.skipped += 1;
+            return Ok(());
+        }
+
+        sqlx::query(
+            "INSERT INTO ui_monitoring (text_output, timestamp, app, window, initial_traversal_at, text_length) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )
+        .bind(&ui.text_output)
+        .bind(ui.timestamp)
+        .bind(&ui.app)
+        .bind(&ui.window)
+        .bind(ui.initial_traversal_at)
+        .bind(ui.text_output.len() as i64)
+        .execute(&mut *self.tx)
+        .await?;
+        self.imported.ui_monitoring += 1;
+        Ok(())
+    }
+
+    pub async fn commit(self) -> Result<ImportedContent, sqlx::Error> {
+        self.tx.commit().await?;
+        Ok(self.imported)
+    }
+
+    /// Commits the batch once it is full and starts the next one.
+    async fn next_row(&mut self) -> Result<(), sqlx::Error> {
+        if self.batch_rows == BATCH_SIZE {
+            let tx = std::mem::replace(&mut self.tx, self.pool.begin().await?);
+            tx.commit().await?;
+            self.batch_rows = 0;
+            self.committed_batches += 1;
+        }
+        self.batch_rows += 1;
+        Ok(())
+    }
+
+    /// Finds a chunk stored at `file_path` or under the same file name in another directory.
+    async fn find_chunk(
+        &mut self,
+        table: &str,
+        file_path: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let file_name = file_path.rsplit(['/', '\\']).next().unwrap_or(file_path);
+        sqlx::query_scalar(&format!(
+            r#"
+            SELECT id FROM {}
+            WHERE file_path = ?1
+                OR substr(file_path, -length(?2) - 1) IN ('/' || ?2, '\' || ?2)
+            ORDER BY id
+            LIMIT 1
+            "#,
+            table
+        ))
+        .bind(file_path)
+        .bind(file_name)
+        .fetch_optional(&mut *self.tx)
+        .await
+    }
+}
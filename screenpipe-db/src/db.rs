@@ -1,61 +1,117 @@
 use chrono::{DateTime, Utc};
 use image::DynamicImage;
-use libsqlite3_sys::sqlite3_auto_extension;
+use libsqlite3_sys::{sqlite3_auto_extension, sqlite3_interrupt};
 use sqlite_vec::sqlite3_vec_init;
 use sqlx::migrate::MigrateDatabase;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
 use sqlx::Column;
+use sqlx::ConnectOptions;
+use sqlx::Connection;
 use sqlx::Error as SqlxError;
 use sqlx::Row;
 use sqlx::TypeInfo;
 use sqlx::ValueRef;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::io::AsyncReadExt;
 use tracing::{debug, error, warn};
 
-use std::collections::BTreeMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use zerocopy::AsBytes;
 
 use futures::future::try_join_all;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 
+use crate::data_import::DataImport;
 use crate::{
-    AudioChunksResponse, AudioDevice, AudioEntry, AudioResult, AudioResultRaw, ContentType,
-    DeviceType, FrameData, FrameRow, OCREntry, OCRResult, OCRResultRaw, OcrEngine, OcrTextBlock,
-    Order, SearchMatch, SearchResult, Speaker, TagContentType, TextBounds, TextPosition,
-    TimeSeriesChunk, UiContent, VideoMetadata,
+    AudioChunk, AudioChunkToRetranscribe, AudioChunksResponse, AudioDevice, AudioEntry,
+    AudioResult, AudioResultRaw, ContentType, DeletedContent, DeviceType, ExportedAudioChunk,
+    ExportedAudioTranscription, ExportedFrame, ExportedOcrText, ExportedSpeaker, ExportedTag,
+    ExportedTranscriptionSegment, ExportedUiMonitoring, ExportedVideoChunk, FrameData, FrameRow,
+    OCREntry, OCRResult, OCRResultRaw, OcrEngine, OcrTextBlock, Order, PartialVideoFile,
+    RawSqlColumn, RawSqlOptions, RawSqlResult, RetranscribedAudio, SearchMatch, SearchResult,
+    Speaker, SpeakerAudioClip, SpeakerCentroid, SpeakerMatch, SpeakerSpan, TagContentType,
+    TextBounds, TextPosition, TimeSeriesChunk, TranscriptEntry, TranscriptionSegment,
+    TranscriptionSpan, UiContent, VideoMetadata, DEFAULT_SPEAKER_MATCH_THRESHOLD,
 };
 
+/// Number of rows deleted per transaction when pruning data, so that recording
+/// is not blocked for long while a large backlog is removed.
+const DELETE_BATCH_SIZE: i64 = 1000;
+/// Cosine distance by which an enrolled speaker may be further from a voice than a speaker
+/// only recognised in recordings and still be matched.
+const ENROLLED_SPEAKER_MARGIN: f64 = 0.02;
+
 pub struct DatabaseManager {
     pub pool: SqlitePool,
+    /// Connections that can't modify the database, for statements written by callers.
+    read_only_pool: SqlitePool,
 }
 
 impl DatabaseManager {
     pub async fn new(database_path: &str) -> Result<Self, sqlx::Error> {
+        Self::new_with_key(database_path, None).await
+    }
+
+    /// Opens the database, encrypted with SQLCipher if `key` (32 bytes, hex encoded) is given.
+    /// An existing unencrypted database is encrypted the first time it is opened with a key.
+    pub async fn new_with_key(database_path: &str, key: Option<&str>) -> Result<Self, sqlx::Error> {
         debug!(
             "Initializing DatabaseManager with database path: {}",
             database_path
         );
         let connection_string = format!("sqlite:{}", database_path);
 
-        unsafe {
-            sqlite3_auto_extension(Some(
-                std::mem::transmute::<*const (), unsafe extern "C" fn()>(
-                    sqlite3_vec_init as *const (),
-                ),
-            ));
+        if let Some(key) = key {
+            if !cfg!(feature = "encryption") {
+                return Err(SqlxError::Configuration(
+                    "database encryption requires screenpipe to be built with the `encryption` feature".into(),
+                ));
+            }
+            if key.len() != 64 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(SqlxError::Configuration(
+                    "database encryption key must be 64 hex characters".into(),
+                ));
+            }
         }
 
+        register_sqlite_vec();
+
         // Create the database if it doesn't exist
         if !sqlx::Sqlite::database_exists(&connection_string).await? {
             sqlx::Sqlite::create_database(&connection_string).await?;
         }
 
+        let mut connect_options = SqliteConnectOptions::from_str(&connection_string)?;
+        if let Some(key) = key {
+            if is_plaintext_database(database_path).await? {
+                encrypt_plaintext_database(database_path, key).await?;
+            }
+            // sqlx always sends the key pragma first, as SQLCipher requires
+            connect_options = connect_options.pragma("key", format!("\"x'{}'\"", key));
+        }
+
+        // opened on first use, so that tools which close `pool` aren't left with open connections
+        let read_only_pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .idle_timeout(Duration::from_secs(60))
+            .acquire_timeout(Duration::from_secs(10))
+            .connect_lazy_with(
+                connect_options
+                    .clone()
+                    .read_only(true)
+                    .pragma("query_only", "ON"),
+            );
+
         let pool = SqlitePoolOptions::new()
             .max_connections(50)
             .min_connections(3) // Minimum number of idle connections
             .acquire_timeout(Duration::from_secs(10))
-            .connect(&connection_string)
+            .connect_with(connect_options)
             .await?;
 
         // Enable WAL mode
@@ -73,7 +129,10 @@ impl DatabaseManager {
             .execute(&pool)
             .await?;
 
-        let db_manager = DatabaseManager { pool };
+        let db_manager = DatabaseManager {
+            pool,
+            read_only_pool,
+        };
 
         // Run migrations after establishing the connection
         Self::run_migrations(&db_manager.pool).await?;
@@ -91,13 +150,28 @@ impl DatabaseManager {
     }
 
     pub async fn insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
+        self.insert_audio_chunk_at(file_path, Utc::now(), None)
+            .await
+    }
+
+    /// Inserts an audio chunk whose recording started at `recorded_at`, stored with
+    /// `encoding` (codec and bitrate) when known.
+    pub async fn insert_audio_chunk_at(
+        &self,
+        file_path: &str,
+        recorded_at: DateTime<Utc>,
+        encoding: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
-        let id = sqlx::query("INSERT INTO audio_chunks (file_path, timestamp) VALUES (?1, ?2)")
-            .bind(file_path)
-            .bind(Utc::now())
-            .execute(&mut *tx)
-            .await?
-            .last_insert_rowid();
+        let id = sqlx::query(
+            "INSERT INTO audio_chunks (file_path, timestamp, encoding) VALUES (?1, ?2, ?3)",
+        )
+        .bind(file_path)
+        .bind(recorded_at)
+        .bind(encoding)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
         tx.commit().await?;
         Ok(id)
     }
@@ -110,14 +184,71 @@ impl DatabaseManager {
         Ok(id.unwrap_or(0))
     }
 
-    pub async fn get_or_insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
+    pub async fn get_or_insert_audio_chunk(
+        &self,
+        file_path: &str,
+        recorded_at: DateTime<Utc>,
+        encoding: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
         let mut id = self.get_audio_chunk_id(file_path).await?;
         if id == 0 {
-            id = self.insert_audio_chunk(file_path).await?;
+            id = self
+                .insert_audio_chunk_at(file_path, recorded_at, encoding)
+                .await?;
         }
         Ok(id)
     }
 
+    /// Audio chunks after `after_id`, recorded before `before`, stored with another encoding
+    /// than `encoding`, oldest first. Chunks whose encoding wasn't recorded are picked when
+    /// their file doesn't have the given extension, their bitrate is unknown.
+    pub async fn get_audio_chunks_to_recompress(
+        &self,
+        encoding: &str,
+        extension: &str,
+        before: DateTime<Utc>,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<AudioChunk>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, file_path, timestamp
+            FROM audio_chunks
+            WHERE id > ?1 AND timestamp < ?2
+                AND CASE
+                    WHEN encoding IS NULL THEN LOWER(file_path) NOT LIKE '%.' || ?4
+                    ELSE encoding != ?3
+                END
+            ORDER BY id
+            LIMIT ?5
+            "#,
+        )
+        .bind(after_id)
+        .bind(before)
+        .bind(encoding)
+        .bind(extension.to_lowercase())
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Points an audio chunk at another file stored with `encoding`, e.g. once it was
+    /// re-encoded.
+    pub async fn update_audio_chunk_file(
+        &self,
+        id: i64,
+        file_path: &str,
+        encoding: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE audio_chunks SET file_path = ?1, encoding = ?2 WHERE id = ?3")
+            .bind(file_path)
+            .bind(encoding)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     pub async fn count_audio_transcriptions(
         &self,
         audio_chunk_id: i64,
@@ -171,6 +302,123 @@ impl DatabaseManager {
         Ok(id)
     }
 
+    /// Stores the segment and word timings of a transcription, replacing any stored before.
+    pub async fn insert_transcription_segments(
+        &self,
+        audio_transcription_id: i64,
+        segments: &[TranscriptionSegment],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM transcription_segments WHERE audio_transcription_id = ?1")
+            .bind(audio_transcription_id)
+            .execute(&mut *tx)
+            .await?;
+        for (index, segment) in segments.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO transcription_segments (audio_transcription_id, segment_index, start_time, end_time, text, words) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .bind(audio_transcription_id)
+            .bind(index as i64)
+            .bind(segment.start_time)
+            .bind(segment.end_time)
+            .bind(&segment.text)
+            .bind(serde_json::to_string(&segment.words).unwrap_or_else(|_| "[]".to_string()))
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Replaces the segments of the transcriptions of `audio_chunk_id`, after their text was
+    /// rewritten by [`Self::update_audio_transcription`].
+    pub async fn update_transcription_segments(
+        &self,
+        audio_chunk_id: i64,
+        segments: &[TranscriptionSegment],
+    ) -> Result<(), sqlx::Error> {
+        let audio_transcription_ids: Vec<i64> =
+            sqlx::query_scalar("SELECT id FROM audio_transcriptions WHERE audio_chunk_id = ?1")
+                .bind(audio_chunk_id)
+                .fetch_all(&self.pool)
+                .await?;
+        for audio_transcription_id in audio_transcription_ids {
+            self.insert_transcription_segments(audio_transcription_id, segments)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Segments of the given transcriptions in order, by transcription id. Transcriptions
+    /// without timings are left out.
+    pub async fn get_transcription_segments(
+        &self,
+        audio_transcription_ids: &[i64],
+    ) -> Result<HashMap<i64, Vec<TranscriptionSegment>>, sqlx::Error> {
+        if audio_transcription_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let rows: Vec<(i64, f64, f64, String, String)> = sqlx::query_as(
+            r#"
+            SELECT audio_transcription_id, start_time, end_time, text, words
+            FROM transcription_segments
+            WHERE audio_transcription_id IN (SELECT value FROM json_each(?1))
+            ORDER BY audio_transcription_id, segment_index
+            "#,
+        )
+        .bind(serde_json::to_string(audio_transcription_ids).unwrap_or_default())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut segments: HashMap<i64, Vec<TranscriptionSegment>> = HashMap::new();
+        for (id, start_time, end_time, text, words) in rows {
+            segments.entry(id).or_default().push(TranscriptionSegment {
+                start_time,
+                end_time,
+                text,
+                words: serde_json::from_str(&words).unwrap_or_default(),
+            });
+        }
+        Ok(segments)
+    }
+
+    /// Lists the transcriptions of all devices in a time range in the order they were
+    /// recorded, leaving out speakers marked as hallucinations.
+    pub async fn get_transcript_entries(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<TranscriptEntry>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                audio_transcriptions.id AS audio_transcription_id,
+                audio_transcriptions.audio_chunk_id,
+                audio_chunks.timestamp AS chunk_timestamp,
+                audio_transcriptions.timestamp,
+                audio_transcriptions.start_time,
+                audio_transcriptions.end_time,
+                audio_transcriptions.transcription,
+                audio_transcriptions.device AS device_name,
+                audio_transcriptions.is_input_device,
+                audio_transcriptions.speaker_id,
+                speakers.name AS speaker_name
+            FROM audio_transcriptions
+            JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            LEFT JOIN speakers ON audio_transcriptions.speaker_id = speakers.id
+            WHERE audio_transcriptions.timestamp >= ?1
+                AND audio_transcriptions.timestamp <= ?2
+                AND (speakers.id IS NULL OR speakers.hallucination = 0)
+            ORDER BY audio_chunks.timestamp, audio_transcriptions.start_time, audio_transcriptions.id
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await
+    }
+
     pub async fn update_audio_transcription(
         &self,
         audio_chunk_id: i64,
@@ -195,6 +443,225 @@ impl DatabaseManager {
         Ok(affected as i64)
     }
 
+    /// Number of audio chunks after `after_id` with transcriptions made by another engine than
+    /// `transcription_engine`, leaving out chunks already re-transcribed with that engine.
+    pub async fn count_audio_chunks_to_retranscribe(
+        &self,
+        transcription_engine: &str,
+        after_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(DISTINCT audio_transcriptions.audio_chunk_id)
+            FROM audio_transcriptions
+            JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            WHERE audio_transcriptions.audio_chunk_id > ?1
+                AND audio_transcriptions.transcription_engine != ?2
+                AND audio_chunks.retranscribed_with IS NOT ?2
+            "#,
+        )
+        .bind(after_id)
+        .bind(transcription_engine)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Audio chunks after `after_id` with transcriptions made by another engine than
+    /// `transcription_engine`, oldest first. Chunks already re-transcribed with that engine are
+    /// left out, including those its fallback transcribed or in which it found no speech.
+    pub async fn get_audio_chunks_to_retranscribe(
+        &self,
+        transcription_engine: &str,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<AudioChunkToRetranscribe>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                audio_chunks.id,
+                audio_chunks.file_path,
+                MIN(audio_transcriptions.device) AS device_name,
+                MAX(audio_transcriptions.is_input_device) AS is_input_device,
+                MIN(audio_transcriptions.timestamp) AS timestamp
+            FROM audio_chunks
+            JOIN audio_transcriptions ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            WHERE audio_chunks.id > ?1 AND audio_chunks.retranscribed_with IS NOT ?2
+            GROUP BY audio_chunks.id
+            HAVING SUM(audio_transcriptions.transcription_engine != ?2) > 0
+            ORDER BY audio_chunks.id
+            LIMIT ?3
+            "#,
+        )
+        .bind(after_id)
+        .bind(transcription_engine)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Replaces the transcriptions of an audio chunk, and their segments, with ones made by
+    /// `transcription_engine`, and marks the chunk as re-transcribed with it. The search index
+    /// follows through the table triggers.
+    pub async fn replace_audio_transcriptions(
+        &self,
+        chunk: &AudioChunkToRetranscribe,
+        transcription_engine: &str,
+        transcriptions: &[RetranscribedAudio],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM audio_transcriptions WHERE audio_chunk_id = ?1")
+            .bind(chunk.id)
+            .execute(&mut *tx)
+            .await?;
+
+        for transcription in transcriptions {
+            Self::insert_transcription_with_segments(
+                &mut tx,
+                chunk.id,
+                chunk.timestamp,
+                transcription_engine,
+                &chunk.device_name,
+                chunk.is_input_device,
+                transcription,
+            )
+            .await?;
+        }
+
+        // rows made by a fallback carry its name, this keeps the chunk from being picked up
+        // again for the engine that was asked for
+        sqlx::query("UPDATE audio_chunks SET retranscribed_with = ?1 WHERE id = ?2")
+            .bind(transcription_engine)
+            .bind(chunk.id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Records that `transcription_engine` found no speech in an audio chunk, so it keeps its
+    /// transcriptions and isn't re-transcribed with that engine again.
+    pub async fn mark_audio_chunk_retranscribed(
+        &self,
+        audio_chunk_id: i64,
+        transcription_engine: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE audio_chunks SET retranscribed_with = ?1 WHERE id = ?2")
+            .bind(transcription_engine)
+            .bind(audio_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Speakers of the transcriptions stored for an audio chunk, in the order they spoke.
+    /// Transcriptions without a speaker or timings are left out.
+    pub async fn get_audio_chunk_speakers(
+        &self,
+        audio_chunk_id: i64,
+    ) -> Result<Vec<SpeakerSpan>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT speaker_id, start_time, end_time
+            FROM audio_transcriptions
+            WHERE audio_chunk_id = ?1
+                AND speaker_id IS NOT NULL
+                AND start_time IS NOT NULL
+                AND end_time IS NOT NULL
+            ORDER BY start_time
+            "#,
+        )
+        .bind(audio_chunk_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Stores an audio file recorded elsewhere, e.g. a call or a voice memo, with its
+    /// transcriptions. Each transcription is timestamped `recorded_at` plus its start in the
+    /// file. Returns the id of the new audio chunk.
+    pub async fn insert_imported_audio(
+        &self,
+        file_path: &str,
+        recorded_at: DateTime<Utc>,
+        device: &AudioDevice,
+        transcription_engine: &str,
+        transcriptions: &[RetranscribedAudio],
+    ) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let audio_chunk_id =
+            sqlx::query("INSERT INTO audio_chunks (file_path, timestamp) VALUES (?1, ?2)")
+                .bind(file_path)
+                .bind(recorded_at)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid();
+
+        for transcription in transcriptions {
+            let timestamp = recorded_at
+                + chrono::Duration::milliseconds((transcription.start_time * 1000.0) as i64);
+            Self::insert_transcription_with_segments(
+                &mut tx,
+                audio_chunk_id,
+                timestamp,
+                transcription_engine,
+                &device.name,
+                device.device_type == DeviceType::Input,
+                transcription,
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(audio_chunk_id)
+    }
+
+    async fn insert_transcription_with_segments(
+        conn: &mut sqlx::SqliteConnection,
+        audio_chunk_id: i64,
+        timestamp: DateTime<Utc>,
+        transcription_engine: &str,
+        device_name: &str,
+        is_input_device: bool,
+        transcription: &RetranscribedAudio,
+    ) -> Result<(), sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine, device, is_input_device, speaker_id, start_time, end_time, text_length) VALUES (?1, ?2, 0, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        )
+        .bind(audio_chunk_id)
+        .bind(&transcription.transcription)
+        .bind(timestamp)
+        .bind(
+            transcription
+                .fallback_engine
+                .as_deref()
+                .unwrap_or(transcription_engine),
+        )
+        .bind(device_name)
+        .bind(is_input_device)
+        .bind(transcription.speaker_id)
+        .bind(transcription.start_time)
+        .bind(transcription.end_time)
+        .bind(transcription.transcription.len() as i64)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+
+        for (index, segment) in transcription.segments.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO transcription_segments (audio_transcription_id, segment_index, start_time, end_time, text, words) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .bind(id)
+            .bind(index as i64)
+            .bind(segment.start_time)
+            .bind(segment.end_time)
+            .bind(&segment.text)
+            .bind(serde_json::to_string(&segment.words).unwrap_or_else(|_| "[]".to_string()))
+            .execute(&mut *conn)
+            .await?;
+        }
+        Ok(())
+    }
+
     pub async fn insert_speaker(&self, embedding: &[f32]) -> Result<Speaker, SqlxError> {
         let mut tx = self.pool.begin().await?;
 
@@ -247,48 +714,225 @@ impl DatabaseManager {
         &self,
         embedding: &[f32],
     ) -> Result<Option<Speaker>, SqlxError> {
-        let speaker_threshold = 0.5;
+        Ok(self
+            .match_speaker(embedding, DEFAULT_SPEAKER_MATCH_THRESHOLD)
+            .await?
+            .map(|speaker_match| speaker_match.speaker))
+    }
+
+    /// Finds the speaker closest to `embedding`, comparing it with every stored embedding of a
+    /// speaker and with their centroid. Speakers enrolled by the user win over ones that were
+    /// only recognised in recordings, which are often the same voice split up, as long as they
+    /// are no more than a small margin further away. `None` if no speaker is within
+    /// `threshold` cosine distance, meaning the voice is unknown.
+    pub async fn match_speaker(
+        &self,
+        embedding: &[f32],
+        threshold: f64,
+    ) -> Result<Option<SpeakerMatch>, SqlxError> {
         let bytes: &[u8] = embedding.as_bytes();
 
-        // Using subquery with LIMIT 1 instead of JOIN
-        let speaker = sqlx::query_as(
-            "SELECT id, name, metadata
-             FROM speakers
-             WHERE id = (
-                 SELECT speaker_id
-                 FROM speaker_embeddings
-                 WHERE vec_distance_cosine(embedding, vec_f32(?1)) < ?2
-                 ORDER BY vec_distance_cosine(embedding, vec_f32(?1))
-                 LIMIT 1
-             )",
+        let row: Option<(i64, String, String, f64)> = sqlx::query_as(
+            r#"
+            WITH distances AS (
+                SELECT speaker_id, vec_distance_cosine(embedding, vec_f32(?1)) AS distance
+                FROM speaker_embeddings
+                UNION ALL
+                SELECT id, vec_distance_cosine(centroid, vec_f32(?1))
+                FROM speakers
+                WHERE centroid IS NOT NULL
+            )
+            SELECT
+                speakers.id,
+                COALESCE(speakers.name, ''),
+                COALESCE(speakers.metadata, ''),
+                MIN(distances.distance) AS distance
+            FROM distances
+            JOIN speakers ON speakers.id = distances.speaker_id
+            GROUP BY speakers.id
+            HAVING MIN(distances.distance) < ?2
+            ORDER BY distance - CASE WHEN speakers.enrolled THEN ?3 ELSE 0 END, speakers.id
+            LIMIT 1
+            "#,
         )
         .bind(bytes)
-        .bind(speaker_threshold)
+        .bind(threshold)
+        .bind(ENROLLED_SPEAKER_MARGIN)
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(speaker)
+        Ok(row.map(|(id, name, metadata, distance)| SpeakerMatch {
+            speaker: Speaker { id, name, metadata },
+            distance,
+        }))
     }
 
-    pub async fn update_speaker_name(&self, speaker_id: i64, name: &str) -> Result<i64, SqlxError> {
+    /// Enrolls a speaker from embeddings of clips of their voice. The embeddings are added to
+    /// `speaker_id`, or to a new speaker if it's `None`, and the speaker's centroid is
+    /// recomputed so that later recordings of them are recognised. Fails with
+    /// `RowNotFound`, storing nothing, if `speaker_id` doesn't exist.
+    pub async fn enroll_speaker(
+        &self,
+        speaker_id: Option<i64>,
+        name: Option<&str>,
+        embeddings: &[Vec<f32>],
+    ) -> Result<Speaker, SqlxError> {
         let mut tx = self.pool.begin().await?;
-        sqlx::query("UPDATE speakers SET name = ?1 WHERE id = ?2")
-            .bind(name)
+
+        let speaker_id = match speaker_id {
+            Some(id) => {
+                let updated = sqlx::query("UPDATE speakers SET enrolled = TRUE WHERE id = ?1")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+                if updated == 0 {
+                    return Err(SqlxError::RowNotFound);
+                }
+                id
+            }
+            None => sqlx::query("INSERT INTO speakers (name, enrolled) VALUES (NULL, TRUE)")
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid(),
+        };
+        if let Some(name) = name {
+            sqlx::query("UPDATE speakers SET name = ?1 WHERE id = ?2")
+                .bind(name)
+                .bind(speaker_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        for embedding in embeddings {
+            let bytes: &[u8] = embedding.as_bytes();
+            sqlx::query(
+                "INSERT INTO speaker_embeddings (embedding, speaker_id) VALUES (vec_f32(?1), ?2)",
+            )
+            .bind(bytes)
             .bind(speaker_id)
             .execute(&mut *tx)
             .await?;
+        }
+        Self::update_speaker_centroid(&mut *tx, speaker_id).await?;
         tx.commit().await?;
-        Ok(speaker_id)
+
+        self.get_speaker_by_id(speaker_id).await
     }
 
-    pub async fn insert_video_chunk(
-        &self,
-        file_path: &str,
-        device_name: &str,
-    ) -> Result<i64, sqlx::Error> {
-        let mut tx = self.pool.begin().await?;
-        let id = sqlx::query("INSERT INTO video_chunks (file_path, device_name) VALUES (?1, ?2)")
-            .bind(file_path)
+    /// Sets the centroid of a speaker to the normalised mean of their embeddings.
+    async fn update_speaker_centroid(
+        conn: &mut sqlx::SqliteConnection,
+        speaker_id: i64,
+    ) -> Result<(), SqlxError> {
+        let embeddings: Vec<Vec<u8>> =
+            sqlx::query_scalar("SELECT embedding FROM speaker_embeddings WHERE speaker_id = ?1")
+                .bind(speaker_id)
+                .fetch_all(&mut *conn)
+                .await?;
+
+        let centroid = speaker_centroid(embeddings.iter().map(|bytes| embedding_from_bytes(bytes)));
+        match centroid {
+            Some(centroid) => {
+                let bytes: &[u8] = centroid.as_bytes();
+                sqlx::query("UPDATE speakers SET centroid = vec_f32(?1) WHERE id = ?2")
+                    .bind(bytes)
+                    .bind(speaker_id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            None => {
+                sqlx::query("UPDATE speakers SET centroid = NULL WHERE id = ?1")
+                    .bind(speaker_id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Centroids of all speakers with embeddings, leaving out those marked as hallucinations.
+    pub async fn get_speaker_centroids(&self) -> Result<Vec<SpeakerCentroid>, SqlxError> {
+        let rows: Vec<(i64, String, String, bool, Vec<u8>)> = sqlx::query_as(
+            r#"
+            SELECT
+                speakers.id,
+                COALESCE(speakers.name, ''),
+                COALESCE(speakers.metadata, ''),
+                speakers.enrolled,
+                speaker_embeddings.embedding
+            FROM speakers
+            JOIN speaker_embeddings ON speaker_embeddings.speaker_id = speakers.id
+            WHERE COALESCE(speakers.hallucination, FALSE) = FALSE
+            ORDER BY speakers.id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut speakers: Vec<(Speaker, bool, Vec<Vec<f32>>)> = Vec::new();
+        for (id, name, metadata, enrolled, embedding) in rows {
+            let embedding = embedding_from_bytes(&embedding);
+            match speakers.last_mut() {
+                Some((speaker, _, embeddings)) if speaker.id == id => embeddings.push(embedding),
+                _ => speakers.push((Speaker { id, name, metadata }, enrolled, vec![embedding])),
+            }
+        }
+
+        Ok(speakers
+            .into_iter()
+            .filter_map(|(speaker, enrolled, embeddings)| {
+                Some(SpeakerCentroid {
+                    speaker,
+                    enrolled,
+                    centroid: speaker_centroid(embeddings)?,
+                })
+            })
+            .collect())
+    }
+
+    /// Recorded audio of the given transcriptions, to enroll the speaker heard in them.
+    pub async fn get_speaker_audio_clips(
+        &self,
+        audio_transcription_ids: &[i64],
+    ) -> Result<Vec<SpeakerAudioClip>, SqlxError> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                audio_transcriptions.id AS audio_transcription_id,
+                audio_chunks.file_path,
+                audio_transcriptions.start_time,
+                audio_transcriptions.end_time
+            FROM audio_transcriptions
+            JOIN audio_chunks ON audio_chunks.id = audio_transcriptions.audio_chunk_id
+            WHERE audio_transcriptions.id IN (SELECT value FROM json_each(?1))
+            ORDER BY audio_transcriptions.id
+            "#,
+        )
+        .bind(serde_json::to_string(audio_transcription_ids).unwrap_or_default())
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn update_speaker_name(&self, speaker_id: i64, name: &str) -> Result<i64, SqlxError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE speakers SET name = ?1 WHERE id = ?2")
+            .bind(name)
+            .bind(speaker_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(speaker_id)
+    }
+
+    pub async fn insert_video_chunk(
+        &self,
+        file_path: &str,
+        device_name: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let id = sqlx::query("INSERT INTO video_chunks (file_path, device_name) VALUES (?1, ?2)")
+            .bind(file_path)
             .bind(device_name)
             .execute(&mut *tx)
             .await?
@@ -429,6 +1073,7 @@ impl DatabaseManager {
                                 frame_name,
                                 browser_url,
                                 focused,
+                                false
                             ),
                             self.search_audio(
                                 query,
@@ -438,7 +1083,8 @@ impl DatabaseManager {
                                 end_time,
                                 min_length,
                                 max_length,
-                                speaker_ids
+                                speaker_ids,
+                                false
                             ),
                             self.search_ui_monitoring(
                                 query,
@@ -448,6 +1094,7 @@ impl DatabaseManager {
                                 end_time,
                                 limit,
                                 offset,
+                                false
                             )
                         )?;
                         (ocr, Some(audio), ui)
@@ -467,6 +1114,7 @@ impl DatabaseManager {
                                 frame_name,
                                 browser_url,
                                 focused,
+                                false
                             ),
                             self.search_ui_monitoring(
                                 query,
@@ -476,6 +1124,7 @@ impl DatabaseManager {
                                 end_time,
                                 limit,
                                 offset,
+                                false
                             )
                         )?;
                         (ocr, None, ui)
@@ -502,6 +1151,7 @@ impl DatabaseManager {
                         frame_name,
                         browser_url,
                         focused,
+                        false,
                     )
                     .await?;
                 results.extend(ocr_results.into_iter().map(SearchResult::OCR));
@@ -518,6 +1168,7 @@ impl DatabaseManager {
                             min_length,
                             max_length,
                             speaker_ids,
+                            false,
                         )
                         .await?;
                     results.extend(audio_results.into_iter().map(SearchResult::Audio));
@@ -533,6 +1184,7 @@ impl DatabaseManager {
                         end_time,
                         limit,
                         offset,
+                        false,
                     )
                     .await?;
                 results.extend(ui_results.into_iter().map(SearchResult::UI));
@@ -548,6 +1200,7 @@ impl DatabaseManager {
                         min_length,
                         max_length,
                         speaker_ids,
+                        false,
                     )
                     .await?;
                 let ui_results = self
@@ -559,6 +1212,7 @@ impl DatabaseManager {
                         end_time,
                         limit / 2,
                         offset,
+                        false,
                     )
                     .await?;
 
@@ -580,6 +1234,7 @@ impl DatabaseManager {
                         frame_name,
                         browser_url,
                         focused,
+                        false,
                     )
                     .await?;
                 let ui_results = self
@@ -591,6 +1246,7 @@ impl DatabaseManager {
                         end_time,
                         limit / 2,
                         offset,
+                        false,
                     )
                     .await?;
 
@@ -608,6 +1264,7 @@ impl DatabaseManager {
                         min_length,
                         max_length,
                         speaker_ids,
+                        false,
                     )
                     .await?;
                 let ocr_results = self
@@ -624,6 +1281,7 @@ impl DatabaseManager {
                         frame_name,
                         browser_url,
                         focused,
+                        false,
                     )
                     .await?;
 
@@ -672,34 +1330,9 @@ impl DatabaseManager {
         frame_name: Option<&str>,
         browser_url: Option<&str>,
         focused: Option<bool>,
+        order_by_relevance: bool,
     ) -> Result<Vec<OCRResult>, sqlx::Error> {
-        let mut frame_fts_parts = Vec::new();
-
-        if let Some(app) = app_name {
-            if !app.is_empty() {
-                frame_fts_parts.push(format!("app_name:{}", app));
-            }
-        }
-        if let Some(window) = window_name {
-            if !window.is_empty() {
-                frame_fts_parts.push(format!("window_name:{}", window));
-            }
-        }
-        if let Some(browser) = browser_url {
-            if !browser.is_empty() {
-                frame_fts_parts.push(format!("browser_url:{}", browser));
-            }
-        }
-        if let Some(is_focused) = focused {
-            frame_fts_parts.push(format!("focused:{}", if is_focused { "1" } else { "0" }));
-        }
-        if let Some(frame_name) = frame_name {
-            if !frame_name.is_empty() {
-                frame_fts_parts.push(format!("name:{}", frame_name));
-            }
-        }
-
-        let frame_query = frame_fts_parts.join(" ");
+        let frame_query = frame_fts_query(app_name, window_name, browser_url, focused, frame_name);
 
         let sql = format!(
             r#"
@@ -732,9 +1365,14 @@ impl DatabaseManager {
             AND (?4 IS NULL OR COALESCE(ocr_text.text_length, LENGTH(ocr_text.text)) >= ?4)
             AND (?5 IS NULL OR COALESCE(ocr_text.text_length, LENGTH(ocr_text.text)) <= ?5)
         GROUP BY frames.id
-        ORDER BY frames.timestamp DESC
+        ORDER BY {order_by}
         LIMIT ?7 OFFSET ?8
         "#,
+            order_by = if order_by_relevance && !query.trim().is_empty() {
+                "ocr_text_fts.rank, frames.timestamp DESC"
+            } else {
+                "frames.timestamp DESC"
+            },
             frame_fts_join = if frame_query.trim().is_empty() {
                 ""
             } else {
@@ -779,27 +1417,7 @@ impl DatabaseManager {
             .fetch_all(&self.pool)
             .await?;
 
-        Ok(raw_results
-            .into_iter()
-            .map(|raw| OCRResult {
-                frame_id: raw.frame_id,
-                ocr_text: raw.ocr_text,
-                text_json: raw.text_json,
-                timestamp: raw.timestamp,
-                frame_name: raw.frame_name,
-                file_path: raw.file_path,
-                offset_index: raw.offset_index,
-                app_name: raw.app_name,
-                ocr_engine: raw.ocr_engine,
-                window_name: raw.window_name,
-                tags: raw
-                    .tags
-                    .map(|t| t.split(',').map(String::from).collect())
-                    .unwrap_or_default(),
-                browser_url: raw.browser_url,
-                focused: raw.focused,
-            })
-            .collect())
+        Ok(raw_results.into_iter().map(ocr_result_from_raw).collect())
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -813,10 +1431,12 @@ impl DatabaseManager {
         min_length: Option<usize>,
         max_length: Option<usize>,
         speaker_ids: Option<Vec<i64>>,
+        order_by_relevance: bool,
     ) -> Result<Vec<AudioResult>, sqlx::Error> {
         // base query for audio search
         let mut base_sql = String::from(
             "SELECT
+                audio_transcriptions.id AS audio_transcription_id,
                 audio_transcriptions.audio_chunk_id,
                 audio_transcriptions.transcription,
                 audio_transcriptions.timestamp,
@@ -870,8 +1490,14 @@ impl DatabaseManager {
 
         // complete sql with group, order, limit and offset
         let sql = format!(
-            "{} {} GROUP BY audio_transcriptions.audio_chunk_id, audio_transcriptions.offset_index ORDER BY audio_transcriptions.timestamp DESC LIMIT ? OFFSET ?",
-            base_sql, where_clause
+            "{} {} GROUP BY audio_transcriptions.audio_chunk_id, audio_transcriptions.offset_index ORDER BY {} LIMIT ? OFFSET ?",
+            base_sql,
+            where_clause,
+            if order_by_relevance && !query.is_empty() {
+                "audio_transcriptions_fts.rank, audio_transcriptions.timestamp DESC"
+            } else {
+                "audio_transcriptions.timestamp DESC"
+            }
         );
 
         // prepare binding for speaker_ids (if any)
@@ -907,6 +1533,24 @@ impl DatabaseManager {
 
         let results_raw: Vec<AudioResultRaw> = query_builder.fetch_all(&self.pool).await?;
 
+        let mut results = self.audio_results_from_raw(results_raw).await?;
+        if !query.is_empty() {
+            let ids: Vec<i64> = results.iter().map(|r| r.audio_transcription_id).collect();
+            let mut segments = self.get_transcription_segments(&ids).await?;
+            for result in &mut results {
+                if let Some(segments) = segments.remove(&result.audio_transcription_id) {
+                    result.matched_segment = find_matching_span(query, &segments);
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Resolves speakers of raw audio rows, keeping their order.
+    async fn audio_results_from_raw(
+        &self,
+        results_raw: Vec<AudioResultRaw>,
+    ) -> Result<Vec<AudioResult>, sqlx::Error> {
         // map raw results into audio result type
         let futures: Vec<_> = results_raw
             .into_iter()
@@ -920,6 +1564,7 @@ impl DatabaseManager {
                 };
 
                 Ok::<AudioResult, sqlx::Error>(AudioResult {
+                    audio_transcription_id: raw.audio_transcription_id,
                     audio_chunk_id: raw.audio_chunk_id,
                     transcription: raw.transcription,
                     timestamp: raw.timestamp,
@@ -939,6 +1584,7 @@ impl DatabaseManager {
                     speaker,
                     start_time: raw.start_time,
                     end_time: raw.end_time,
+                    matched_segment: None,
                 })
             })
             .collect();
@@ -1423,39 +2069,116 @@ impl DatabaseManager {
     pub async fn execute_raw_sql(&self, query: &str) -> Result<serde_json::Value, sqlx::Error> {
         let rows = sqlx::query(query).fetch_all(&self.pool).await?;
 
-        let result: Vec<serde_json::Map<String, serde_json::Value>> = rows
-            .iter()
-            .map(|row| {
-                let mut map = serde_json::Map::new();
-                for (i, column) in row.columns().iter().enumerate() {
-                    if let Ok(value) = row.try_get_raw(i) {
-                        let json_value = match value.type_info().name() {
-                            "TEXT" => {
-                                let s: String = row.try_get(i).unwrap_or_default();
-                                serde_json::Value::String(s)
-                            }
-                            "INTEGER" => {
-                                let i: i64 = row.try_get(i).unwrap_or_default();
-                                serde_json::Value::Number(i.into())
-                            }
-                            "REAL" => {
-                                let f: f64 = row.try_get(i).unwrap_or_default();
-                                serde_json::Value::Number(
-                                    serde_json::Number::from_f64(f).unwrap_or(0.into()),
-                                )
-                            }
-                            _ => serde_json::Value::Null,
-                        };
-                        map.insert(column.name().to_string(), json_value);
+        Ok(serde_json::Value::Array(
+            rows.iter()
+                .map(|row| serde_json::Value::Object(raw_sql_row(row)))
+                .collect(),
+        ))
+    }
+
+    /// Runs a statement written by the caller, e.g. a pipe, with `params` bound to its `?`
+    /// placeholders. Unless `options.allow_writes` is set it runs on a read-only connection, so
+    /// statements that modify the database fail with `SQLITE_READONLY`. Statements running
+    /// longer than `options.timeout` are interrupted and fail with `SQLITE_INTERRUPT`.
+    pub async fn query_raw_sql(
+        &self,
+        query: &str,
+        params: &[serde_json::Value],
+        options: &RawSqlOptions,
+    ) -> Result<RawSqlResult, sqlx::Error> {
+        let pool = if options.allow_writes {
+            &self.pool
+        } else {
+            &self.read_only_pool
+        };
+        let mut conn = pool.acquire().await?;
+        let handle = conn.lock_handle().await?.as_raw_handle();
+
+        let mut statement = sqlx::query(query);
+        for param in params {
+            statement = match param {
+                serde_json::Value::Null => statement.bind(None::<String>),
+                serde_json::Value::Bool(b) => statement.bind(*b),
+                serde_json::Value::Number(n) => match n.as_i64() {
+                    Some(i) => statement.bind(i),
+                    None => statement.bind(n.as_f64()),
+                },
+                serde_json::Value::String(s) => statement.bind(s.clone()),
+                other => statement.bind(other.to_string()),
+            };
+        }
+
+        let mut rows = Vec::new();
+        let mut truncated = false;
+        {
+            let fetch = async {
+                let mut stream = statement.fetch(&mut *conn);
+                while let Some(row) = stream.try_next().await? {
+                    if rows.len() == options.max_rows {
+                        truncated = true;
+                        break;
                     }
+                    rows.push(row);
                 }
-                map
-            })
-            .collect();
+                Ok::<_, sqlx::Error>(())
+            };
+            tokio::pin!(fetch);
+            tokio::select! {
+                result = &mut fetch => result?,
+                _ = tokio::time::sleep(options.timeout) => {
+                    // the connection is still borrowed by `fetch`, so the handle is valid
+                    unsafe { sqlite3_interrupt(handle.as_ptr()) };
+                    fetch.await?
+                }
+            }
+        }
 
-        Ok(serde_json::Value::Array(
-            result.into_iter().map(serde_json::Value::Object).collect(),
-        ))
+        let mut columns: Vec<RawSqlColumn> = match rows.first() {
+            Some(row) => row
+                .columns()
+                .iter()
+                .map(|column| RawSqlColumn {
+                    name: column.name().to_string(),
+                    type_name: column.type_info().name().to_string(),
+                })
+                .collect(),
+            None => sqlx::Executor::describe(&mut *conn, query)
+                .await
+                .map(|describe| {
+                    describe
+                        .columns()
+                        .iter()
+                        .map(|column| RawSqlColumn {
+                            name: column.name().to_string(),
+                            type_name: column.type_info().name().to_string(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+        // expressions have no declared type, use the type of their first non-null value
+        for (i, column) in columns.iter_mut().enumerate() {
+            if column.type_name != "NULL" {
+                continue;
+            }
+            if let Some(type_name) = rows.iter().find_map(|row| {
+                row.try_get_raw(i)
+                    .ok()
+                    .filter(|value| !value.is_null())
+                    .map(|value| value.type_info().name().to_string())
+            }) {
+                column.type_name = type_name;
+            }
+        }
+
+        Ok(RawSqlResult {
+            columns,
+            rows: rows
+                .iter()
+                .map(|row| serde_json::Value::Object(raw_sql_row(row)))
+                .collect(),
+            truncated,
+        })
     }
 
     pub async fn find_video_chunks(
@@ -1579,6 +2302,7 @@ impl DatabaseManager {
         end_time: Option<DateTime<Utc>>,
         limit: u32,
         offset: u32,
+        order_by_relevance: bool,
     ) -> Result<Vec<UiContent>, sqlx::Error> {
         // combine search aspects into single fts query
         let mut fts_parts = Vec::new();
@@ -1628,10 +2352,16 @@ impl DatabaseManager {
                 AND (?2 IS NULL OR ui_monitoring.timestamp >= ?2)
                 AND (?3 IS NULL OR ui_monitoring.timestamp <= ?3)
             GROUP BY ui_monitoring.id
-            ORDER BY ui_monitoring.timestamp DESC
+            ORDER BY {}
             LIMIT ?4 OFFSET ?5
             "#,
-            base_sql, where_clause
+            base_sql,
+            where_clause,
+            if order_by_relevance && !combined_query.is_empty() {
+                "ui_monitoring_fts.rank, ui_monitoring.timestamp DESC"
+            } else {
+                "ui_monitoring.timestamp DESC"
+            }
         );
 
         sqlx::query_as(&sql)
@@ -1804,30 +2534,64 @@ impl DatabaseManager {
         speaker_to_merge_id: i64,
     ) -> Result<Speaker, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
+        Self::merge_speaker_into(&mut *tx, speaker_to_keep_id, speaker_to_merge_id).await?;
+        tx.commit().await?;
+
+        self.get_speaker_by_id(speaker_to_keep_id).await
+    }
+
+    /// Merges each `(speaker_to_keep_id, speaker_ids_to_merge)` set in one transaction, so a
+    /// failure leaves no speaker partly merged.
+    pub async fn merge_speaker_sets(
+        &self,
+        merge_sets: &[(i64, Vec<i64>)],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (speaker_to_keep_id, speaker_ids_to_merge) in merge_sets {
+            for &speaker_to_merge_id in speaker_ids_to_merge {
+                Self::merge_speaker_into(&mut *tx, *speaker_to_keep_id, speaker_to_merge_id)
+                    .await?;
+            }
+        }
+        tx.commit().await
+    }
 
+    async fn merge_speaker_into(
+        conn: &mut sqlx::SqliteConnection,
+        speaker_to_keep_id: i64,
+        speaker_to_merge_id: i64,
+    ) -> Result<(), sqlx::Error> {
         // for each audio transcription of the speaker to merge, update the speaker_id to the speaker to keep
         sqlx::query("UPDATE audio_transcriptions SET speaker_id = ? WHERE speaker_id = ?")
             .bind(speaker_to_keep_id)
             .bind(speaker_to_merge_id)
-            .execute(&mut *tx)
+            .execute(&mut *conn)
             .await?;
 
         // update speaker_embeddings
         sqlx::query("UPDATE speaker_embeddings SET speaker_id = ? WHERE speaker_id = ?")
             .bind(speaker_to_keep_id)
             .bind(speaker_to_merge_id)
-            .execute(&mut *tx)
+            .execute(&mut *conn)
             .await?;
 
+        // the kept speaker is recognised by the voice of both from now on
+        sqlx::query(
+            "UPDATE speakers SET enrolled = enrolled OR (SELECT enrolled FROM speakers WHERE id = ?2) WHERE id = ?1",
+        )
+        .bind(speaker_to_keep_id)
+        .bind(speaker_to_merge_id)
+        .execute(&mut *conn)
+        .await?;
+        Self::update_speaker_centroid(&mut *conn, speaker_to_keep_id).await?;
+
         // delete the speaker to merge
         sqlx::query("DELETE FROM speakers WHERE id = ?")
             .bind(speaker_to_merge_id)
-            .execute(&mut *tx)
+            .execute(&mut *conn)
             .await?;
 
-        tx.commit().await?;
-
-        self.get_speaker_by_id(speaker_to_keep_id).await
+        Ok(())
     }
 
     pub async fn search_speakers(&self, name_prefix: &str) -> Result<Vec<Speaker>, sqlx::Error> {
@@ -2001,129 +2765,1184 @@ impl DatabaseManager {
             .bind(metadata.name.as_deref().unwrap_or(file_path))  // Use reference instead of clone
             .execute(&mut *tx)
             .await?
-            .last_insert_rowid();
+            .last_insert_rowid();
+
+            frame_ids.push(frame_id);
+        }
+
+        tx.commit().await?;
+        debug!(
+            "created {} frames for video chunk {}",
+            frames.len(),
+            video_chunk_id
+        );
+
+        Ok(frame_ids)
+    }
+
+    /// OCR text of frames that have no embedding made by `model` yet, newest first. Frames in
+    /// `skip` (e.g. ones that failed to embed) are left out.
+    pub async fn get_ocr_text_without_embeddings(
+        &self,
+        model: &str,
+        limit: i64,
+        skip: &[i64],
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        let skip_json = serde_json::to_string(skip).unwrap_or_else(|_| "[]".to_string());
+        sqlx::query_as(
+            r#"
+            SELECT ocr_text.frame_id, ocr_text.text
+            FROM ocr_text
+            WHERE length(trim(ocr_text.text)) > 0
+            AND NOT EXISTS (
+                SELECT 1 FROM ocr_text_embeddings
+                WHERE ocr_text_embeddings.frame_id = ocr_text.frame_id
+                    AND ocr_text_embeddings.model = ?3
+            )
+            AND ocr_text.frame_id NOT IN (SELECT value FROM json_each(?1))
+            GROUP BY ocr_text.frame_id
+            ORDER BY ocr_text.frame_id DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(skip_json)
+        .bind(limit)
+        .bind(model)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Audio transcriptions that have no embedding made by `model` yet, newest first.
+    /// Transcriptions in `skip` are left out.
+    pub async fn get_audio_transcriptions_without_embeddings(
+        &self,
+        model: &str,
+        limit: i64,
+        skip: &[i64],
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        let skip_json = serde_json::to_string(skip).unwrap_or_else(|_| "[]".to_string());
+        sqlx::query_as(
+            r#"
+            SELECT audio_transcriptions.id, audio_transcriptions.transcription
+            FROM audio_transcriptions
+            WHERE length(trim(audio_transcriptions.transcription)) > 0
+            AND NOT EXISTS (
+                SELECT 1 FROM audio_transcription_embeddings
+                WHERE audio_transcription_embeddings.audio_transcription_id = audio_transcriptions.id
+                    AND audio_transcription_embeddings.model = ?3
+            )
+            AND audio_transcriptions.id NOT IN (SELECT value FROM json_each(?1))
+            ORDER BY audio_transcriptions.id DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(skip_json)
+        .bind(limit)
+        .bind(model)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Stores embeddings of OCR text made by `model` as `(frame_id, embedding)` pairs,
+    /// replacing the frames' embeddings made by other models.
+    pub async fn insert_ocr_text_embeddings(
+        &self,
+        model: &str,
+        embeddings: &[(i64, Vec<f32>)],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (frame_id, embedding) in embeddings {
+            sqlx::query("DELETE FROM ocr_text_embeddings WHERE frame_id = ?1")
+                .bind(frame_id)
+                .execute(&mut *tx)
+                .await?;
+            let bytes: &[u8] = embedding.as_bytes();
+            sqlx::query(
+                "INSERT INTO ocr_text_embeddings (frame_id, embedding, model) VALUES (?1, vec_f32(?2), ?3)",
+            )
+            .bind(frame_id)
+            .bind(bytes)
+            .bind(model)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stores embeddings of audio transcriptions made by `model` as
+    /// `(audio_transcription_id, embedding)` pairs, replacing the transcriptions' embeddings made
+    /// by other models.
+    pub async fn insert_audio_transcription_embeddings(
+        &self,
+        model: &str,
+        embeddings: &[(i64, Vec<f32>)],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (audio_transcription_id, embedding) in embeddings {
+            sqlx::query(
+                "DELETE FROM audio_transcription_embeddings WHERE audio_transcription_id = ?1",
+            )
+            .bind(audio_transcription_id)
+            .execute(&mut *tx)
+            .await?;
+            let bytes: &[u8] = embedding.as_bytes();
+            sqlx::query(
+                "INSERT INTO audio_transcription_embeddings (audio_transcription_id, embedding, model) VALUES (?1, vec_f32(?2), ?3)",
+            )
+            .bind(audio_transcription_id)
+            .bind(bytes)
+            .bind(model)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// OCR text whose embedding made by `model` is within `threshold` (cosine) of `embedding`.
+    pub async fn search_similar_embeddings(
+        &self,
+        model: &str,
+        embedding: Vec<f32>,
+        limit: u32,
+        threshold: f32,
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        debug!("searching similar embeddings with threshold {}", threshold);
+
+        let sql = r#"
+            WITH embedding_matches AS (
+                SELECT
+                    frame_id,
+                    vec_distance_cosine(embedding, vec_f32(?1)) as similarity
+                FROM ocr_text_embeddings
+                WHERE model = ?4 AND vec_distance_cosine(embedding, vec_f32(?1)) < ?2
+                ORDER BY similarity ASC
+                LIMIT ?3
+            )
+            SELECT
+                ocr_text.frame_id,
+                ocr_text.text as ocr_text,
+                ocr_text.text_json,
+                frames.timestamp,
+                video_chunks.file_path,
+                frames.offset_index,
+                frames.name as frame_name,
+                frames.app_name,
+                ocr_text.ocr_engine,
+                frames.window_name,
+                GROUP_CONCAT(tags.name, ',') as tags,
+                frames.browser_url,
+                frames.focused
+            FROM embedding_matches
+            JOIN ocr_text ON embedding_matches.frame_id = ocr_text.frame_id
+            JOIN frames ON ocr_text.frame_id = frames.id
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
+            LEFT JOIN tags ON vision_tags.tag_id = tags.id
+            GROUP BY ocr_text.frame_id
+            ORDER BY embedding_matches.similarity ASC
+        "#;
+
+        let bytes = embedding.as_bytes();
+
+        let raw_results: Vec<OCRResultRaw> = sqlx::query_as(sql)
+            .bind(bytes)
+            .bind(threshold)
+            .bind(limit)
+            .bind(model)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(raw_results.into_iter().map(ocr_result_from_raw).collect())
+    }
+
+    /// OCR text whose embedding made by `model` is within `max_distance` (cosine) of
+    /// `embedding`, closest first. Takes the same filters as keyword search.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_ocr_by_embedding(
+        &self,
+        model: &str,
+        embedding: &[f32],
+        max_distance: f32,
+        limit: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        frame_name: Option<&str>,
+        browser_url: Option<&str>,
+        focused: Option<bool>,
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        let frame_query = frame_fts_query(app_name, window_name, browser_url, focused, frame_name);
+
+        let sql = format!(
+            r#"
+            WITH embedding_matches AS (
+                SELECT
+                    frame_id,
+                    MIN(vec_distance_cosine(embedding, vec_f32(?1))) as distance
+                FROM ocr_text_embeddings
+                WHERE model = ?9
+                GROUP BY frame_id
+            )
+            SELECT
+                ocr_text.frame_id,
+                ocr_text.text as ocr_text,
+                ocr_text.text_json,
+                frames.timestamp,
+                frames.name as frame_name,
+                video_chunks.file_path,
+                frames.offset_index,
+                frames.app_name,
+                ocr_text.ocr_engine,
+                frames.window_name,
+                GROUP_CONCAT(tags.name, ',') as tags,
+                frames.browser_url,
+                frames.focused
+            FROM embedding_matches
+            JOIN frames ON embedding_matches.frame_id = frames.id
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            JOIN ocr_text ON frames.id = ocr_text.frame_id
+            LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
+            LEFT JOIN tags ON vision_tags.tag_id = tags.id
+            {frame_fts_join}
+            WHERE embedding_matches.distance < ?2
+                {frame_fts_condition}
+                AND (?4 IS NULL OR frames.timestamp >= ?4)
+                AND (?5 IS NULL OR frames.timestamp <= ?5)
+                AND (?6 IS NULL OR COALESCE(ocr_text.text_length, LENGTH(ocr_text.text)) >= ?6)
+                AND (?7 IS NULL OR COALESCE(ocr_text.text_length, LENGTH(ocr_text.text)) <= ?7)
+            GROUP BY frames.id
+            ORDER BY embedding_matches.distance ASC
+            LIMIT ?8
+            "#,
+            frame_fts_join = if frame_query.is_empty() {
+                ""
+            } else {
+                "JOIN frames_fts ON frames.id = frames_fts.id"
+            },
+            frame_fts_condition = if frame_query.is_empty() {
+                ""
+            } else {
+                "AND frames_fts MATCH ?3"
+            },
+        );
+
+        let raw_results: Vec<OCRResultRaw> = sqlx::query_as(&sql)
+            .bind(embedding.as_bytes())
+            .bind(max_distance)
+            .bind(if frame_query.is_empty() {
+                None
+            } else {
+                Some(&frame_query)
+            })
+            .bind(start_time)
+            .bind(end_time)
+            .bind(min_length.map(|l| l as i64))
+            .bind(max_length.map(|l| l as i64))
+            .bind(limit)
+            .bind(model)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(raw_results.into_iter().map(ocr_result_from_raw).collect())
+    }
+
+    /// Audio transcriptions whose embedding made by `model` is within `max_distance` (cosine)
+    /// of `embedding`, closest first. Takes the same filters as keyword search.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_audio_by_embedding(
+        &self,
+        model: &str,
+        embedding: &[f32],
+        max_distance: f32,
+        limit: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+    ) -> Result<Vec<AudioResult>, sqlx::Error> {
+        let sql = r#"
+            WITH embedding_matches AS (
+                SELECT
+                    audio_transcription_id,
+                    MIN(vec_distance_cosine(embedding, vec_f32(?1))) as distance
+                FROM audio_transcription_embeddings
+                WHERE model = ?9
+                GROUP BY audio_transcription_id
+            )
+            SELECT
+                audio_transcriptions.id AS audio_transcription_id,
+                audio_transcriptions.audio_chunk_id,
+                audio_transcriptions.transcription,
+                audio_transcriptions.timestamp,
+                audio_chunks.file_path,
+                audio_transcriptions.offset_index,
+                audio_transcriptions.transcription_engine,
+                GROUP_CONCAT(tags.name, ',') as tags,
+                audio_transcriptions.device as device_name,
+                audio_transcriptions.is_input_device,
+                audio_transcriptions.speaker_id,
+                audio_transcriptions.start_time,
+                audio_transcriptions.end_time
+            FROM embedding_matches
+            JOIN audio_transcriptions ON embedding_matches.audio_transcription_id = audio_transcriptions.id
+            JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            LEFT JOIN speakers ON audio_transcriptions.speaker_id = speakers.id
+            LEFT JOIN audio_tags ON audio_chunks.id = audio_tags.audio_chunk_id
+            LEFT JOIN tags ON audio_tags.tag_id = tags.id
+            WHERE embedding_matches.distance < ?2
+                AND (?3 IS NULL OR audio_transcriptions.timestamp >= ?3)
+                AND (?4 IS NULL OR audio_transcriptions.timestamp <= ?4)
+                AND (?5 IS NULL OR COALESCE(audio_transcriptions.text_length, LENGTH(audio_transcriptions.transcription)) >= ?5)
+                AND (?6 IS NULL OR COALESCE(audio_transcriptions.text_length, LENGTH(audio_transcriptions.transcription)) <= ?6)
+                AND (speakers.id IS NULL OR speakers.hallucination = 0)
+                AND (?7 IS NULL OR json_array_length(?7) = 0 OR audio_transcriptions.speaker_id IN (SELECT value FROM json_each(?7)))
+            GROUP BY audio_transcriptions.id
+            ORDER BY embedding_matches.distance ASC
+            LIMIT ?8
+        "#;
+
+        let speaker_ids_json = speaker_ids
+            .as_ref()
+            .map(|ids| serde_json::to_string(ids).unwrap_or_else(|_| "[]".to_string()));
+
+        let results_raw: Vec<AudioResultRaw> = sqlx::query_as(sql)
+            .bind(embedding.as_bytes())
+            .bind(max_distance)
+            .bind(start_time)
+            .bind(end_time)
+            .bind(min_length.map(|l| l as i64))
+            .bind(max_length.map(|l| l as i64))
+            .bind(speaker_ids_json)
+            .bind(limit)
+            .bind(model)
+            .fetch_all(&self.pool)
+            .await?;
+
+        self.audio_results_from_raw(results_raw).await
+    }
+
+    /// Runs keyword (FTS5) and vector search with the same filters and merges them with
+    /// reciprocal-rank fusion. Returns the requested page of results with their fused score,
+    /// highest first, and the number of fused results the page was taken from.
+    ///
+    /// Audio is left out when filtering on app, window or frame name, and only OCR is searched
+    /// when filtering on `browser_url` or `focused`, like [`DatabaseManager::search`]. UI
+    /// monitoring content has no embeddings and is only ranked by keyword. Only embeddings made
+    /// by `model`, the one `embedding` comes from, are compared.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_hybrid(
+        &self,
+        query: &str,
+        model: &str,
+        embedding: &[f32],
+        max_distance: f32,
+        mut content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+        frame_name: Option<&str>,
+        browser_url: Option<&str>,
+        focused: Option<bool>,
+    ) -> Result<(Vec<(SearchResult, f64)>, usize), sqlx::Error> {
+        if focused.is_some() || browser_url.is_some() {
+            content_type = ContentType::OCR;
+        }
+
+        let (search_ocr, search_audio, search_ui) = match content_type {
+            ContentType::All => (true, true, true),
+            ContentType::OCR => (true, false, false),
+            ContentType::Audio => (false, true, false),
+            ContentType::UI => (false, false, true),
+            ContentType::AudioAndUi => (false, true, true),
+            ContentType::OcrAndUi => (true, false, true),
+            ContentType::AudioAndOcr => (true, true, false),
+        };
+        let search_audio =
+            search_audio && app_name.is_none() && window_name.is_none() && frame_name.is_none();
+
+        // every list has to cover the requested page on its own
+        let candidates = offset + limit;
+
+        let (ocr_keyword, ocr_vector, audio_keyword, audio_vector, ui_keyword) = tokio::try_join!(
+            async {
+                if !search_ocr {
+                    return Ok(Vec::new());
+                }
+                self.search_ocr(
+                    query,
+                    candidates,
+                    0,
+                    start_time,
+                    end_time,
+                    app_name,
+                    window_name,
+                    min_length,
+                    max_length,
+                    frame_name,
+                    browser_url,
+                    focused,
+                    true,
+                )
+                .await
+            },
+            async {
+                if !search_ocr {
+                    return Ok(Vec::new());
+                }
+                self.search_ocr_by_embedding(
+                    model,
+                    embedding,
+                    max_distance,
+                    candidates,
+                    start_time,
+                    end_time,
+                    app_name,
+                    window_name,
+                    min_length,
+                    max_length,
+                    frame_name,
+                    browser_url,
+                    focused,
+                )
+                .await
+            },
+            async {
+                if !search_audio {
+                    return Ok(Vec::new());
+                }
+                self.search_audio(
+                    query,
+                    candidates,
+                    0,
+                    start_time,
+                    end_time,
+                    min_length,
+                    max_length,
+                    speaker_ids.clone(),
+                    true,
+                )
+                .await
+            },
+            async {
+                if !search_audio {
+                    return Ok(Vec::new());
+                }
+                self.search_audio_by_embedding(
+                    model,
+                    embedding,
+                    max_distance,
+                    candidates,
+                    start_time,
+                    end_time,
+                    min_length,
+                    max_length,
+                    speaker_ids.clone(),
+                )
+                .await
+            },
+            async {
+                if !search_ui {
+                    return Ok(Vec::new());
+                }
+                self.search_ui_monitoring(
+                    query,
+                    app_name,
+                    window_name,
+                    start_time,
+                    end_time,
+                    candidates,
+                    0,
+                    true,
+                )
+                .await
+            },
+        )?;
+
+        let mut fused: Vec<(SearchResult, f64)> = Vec::new();
+        let mut positions: HashMap<FusionKey, usize> = HashMap::new();
+        let ranked_lists: [Vec<SearchResult>; 5] = [
+            ocr_keyword.into_iter().map(SearchResult::OCR).collect(),
+            ocr_vector.into_iter().map(SearchResult::OCR).collect(),
+            audio_keyword.into_iter().map(SearchResult::Audio).collect(),
+            audio_vector.into_iter().map(SearchResult::Audio).collect(),
+            ui_keyword.into_iter().map(SearchResult::UI).collect(),
+        ];
+        for list in ranked_lists {
+            for (rank, result) in list.into_iter().enumerate() {
+                let score = 1.0 / (RRF_K + rank as f64 + 1.0);
+                match positions.entry(FusionKey::of(&result)) {
+                    Entry::Occupied(entry) => fused[*entry.get()].1 += score,
+                    Entry::Vacant(entry) => {
+                        entry.insert(fused.len());
+                        fused.push((result, score));
+                    }
+                }
+            }
+        }
+
+        fused.sort_by(|(a, score_a), (b, score_b)| {
+            score_b
+                .total_cmp(score_a)
+                .then_with(|| result_timestamp(b).cmp(&result_timestamp(a)))
+        });
+
+        let total = fused.len();
+        let mut page: Vec<(SearchResult, f64)> = fused
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+
+        // transcriptions only found by meaning didn't go through keyword search, which finds
+        // where the query was said
+        let unmatched: Vec<i64> = page
+            .iter()
+            .filter_map(|(result, _)| match result {
+                SearchResult::Audio(audio) if audio.matched_segment.is_none() => {
+                    Some(audio.audio_transcription_id)
+                }
+                _ => None,
+            })
+            .collect();
+        if !unmatched.is_empty() {
+            let mut segments = self.get_transcription_segments(&unmatched).await?;
+            for (result, _) in &mut page {
+                if let SearchResult::Audio(audio) = result {
+                    if let Some(segments) = segments.remove(&audio.audio_transcription_id) {
+                        audio.matched_segment = find_matching_span(query, &segments);
+                    }
+                }
+            }
+        }
+
+        Ok((page, total))
+    }
+
+    // Add method to update frame names
+    pub async fn update_frame_name(&self, frame_id: i64, name: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET name = ?1 WHERE id = ?2")
+            .bind(name)
+            .bind(frame_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // Add method to update all frames in a video chunk
+    pub async fn update_video_chunk_frames_names(
+        &self,
+        video_chunk_id: i64,
+        name: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET name = ?1 WHERE video_chunk_id = ?2")
+            .bind(name)
+            .bind(video_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes frames captured before `before`, together with their OCR text, embeddings and tags.
+    /// Video chunks that no longer have any frames are removed as well and their file paths are
+    /// returned so the caller can delete them from disk.
+    pub async fn delete_frames_before(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<DeletedContent, sqlx::Error> {
+        self.delete_frames_in_range(None, before).await
+    }
+
+    /// Deletes audio chunks recorded before `before`, together with their transcriptions and
+    /// tags. The file paths of the deleted chunks are returned so the caller can delete them
+    /// from disk.
+    pub async fn delete_audio_before(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<DeletedContent, sqlx::Error> {
+        self.delete_audio_in_range(None, before).await
+    }
+
+    /// Deletes UI monitoring entries captured before `before`, together with their tags.
+    pub async fn delete_ui_monitoring_before(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<DeletedContent, sqlx::Error> {
+        self.delete_ui_monitoring_in_range(None, before).await
+    }
+
+    /// Deletes everything captured between `start` (inclusive) and `end` (exclusive): frames,
+    /// OCR text, embeddings, UI monitoring entries, audio transcriptions and their tags. FTS
+    /// entries are removed by the delete triggers.
+    ///
+    /// Audio chunks that overlap the range are deleted as a whole. Video chunks that only
+    /// partially overlap it are returned in `partial_video_files` so the caller can blank out
+    /// the deleted frames in the file.
+    pub async fn delete_time_range(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<DeletedContent, sqlx::Error> {
+        let mut deleted = self.delete_frames_in_range(Some(start), end).await?;
+        deleted.merge(self.delete_audio_in_range(Some(start), end).await?);
+        deleted.merge(self.delete_ui_monitoring_in_range(Some(start), end).await?);
+        Ok(deleted)
+    }
+
+    async fn delete_frames_in_range(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: DateTime<Utc>,
+    ) -> Result<DeletedContent, sqlx::Error> {
+        let mut deleted = DeletedContent::default();
+        // a chunk can lose frames in several batches and its last one in a later batch, so
+        // partial chunks are only known once every batch is done
+        let mut partial_chunks: BTreeMap<i64, PartialVideoFile> = BTreeMap::new();
+
+        loop {
+            let mut tx = self.pool.begin().await?;
+
+            let frames: Vec<(i64, i64, i64, String)> = sqlx::query_as(
+                r#"
+                SELECT frames.id, frames.video_chunk_id, frames.offset_index, video_chunks.file_path
+                FROM frames
+                JOIN video_chunks ON video_chunks.id = frames.video_chunk_id
+                WHERE (?1 IS NULL OR frames.timestamp >= ?1) AND frames.timestamp < ?2
+                ORDER BY frames.id
+                LIMIT ?3
+                "#,
+            )
+            .bind(start)
+            .bind(end)
+            .bind(DELETE_BATCH_SIZE)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            if frames.is_empty() {
+                tx.rollback().await?;
+                break;
+            }
+
+            let mut batch = DeletedContent::default();
+            let frame_ids: Vec<i64> = frames.iter().map(|(id, ..)| *id).collect();
+            let ids_json = serde_json::to_string(&frame_ids).unwrap_or_else(|_| "[]".to_string());
+
+            let mut chunk_ids = BTreeSet::new();
+            for (_, chunk_id, offset_index, file_path) in frames {
+                chunk_ids.insert(chunk_id);
+                partial_chunks
+                    .entry(chunk_id)
+                    .or_insert_with(|| PartialVideoFile {
+                        file_path,
+                        offset_indexes: Vec::new(),
+                    })
+                    .offset_indexes
+                    .push(offset_index);
+            }
+
+            batch.ocr_text = sqlx::query(
+                "DELETE FROM ocr_text WHERE frame_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids_json)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            sqlx::query(
+                "DELETE FROM ocr_text_embeddings WHERE frame_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids_json)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                "DELETE FROM vision_tags WHERE vision_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids_json)
+            .execute(&mut *tx)
+            .await?;
+
+            batch.frames =
+                sqlx::query("DELETE FROM frames WHERE id IN (SELECT value FROM json_each(?1))")
+                    .bind(&ids_json)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+
+            // video chunks touched by this batch that have no frames left
+            let chunk_ids: Vec<i64> = chunk_ids.into_iter().collect();
+            let chunks_json =
+                serde_json::to_string(&chunk_ids).unwrap_or_else(|_| "[]".to_string());
+            let orphaned_chunks: Vec<i64> = sqlx::query_scalar(
+                r#"
+                SELECT id FROM video_chunks
+                WHERE id IN (SELECT value FROM json_each(?1))
+                AND NOT EXISTS (SELECT 1 FROM frames WHERE frames.video_chunk_id = video_chunks.id)
+                "#,
+            )
+            .bind(&chunks_json)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for chunk_id in orphaned_chunks {
+                sqlx::query("DELETE FROM video_chunks WHERE id = ?1")
+                    .bind(chunk_id)
+                    .execute(&mut *tx)
+                    .await?;
+                if let Some(chunk) = partial_chunks.remove(&chunk_id) {
+                    batch.video_files.push(chunk.file_path);
+                }
+            }
+
+            tx.commit().await?;
+            debug!(
+                "deleted batch of {} frames captured between {:?} and {}",
+                frame_ids.len(),
+                start,
+                end
+            );
+
+            deleted.merge(batch);
+        }
+
+        deleted.partial_video_files = partial_chunks.into_values().collect();
+        Ok(deleted)
+    }
+
+    async fn delete_audio_in_range(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: DateTime<Utc>,
+    ) -> Result<DeletedContent, sqlx::Error> {
+        let mut deleted = DeletedContent::default();
+
+        loop {
+            let mut tx = self.pool.begin().await?;
+
+            // a chunk is deleted as a whole as soon as any of its audio falls in the range
+            let chunks: Vec<(i64, String)> = sqlx::query_as(
+                r#"
+                SELECT id, file_path FROM audio_chunks
+                WHERE ((?1 IS NULL OR timestamp >= ?1) AND timestamp < ?2)
+                OR EXISTS (
+                    SELECT 1 FROM audio_transcriptions
+                    WHERE audio_transcriptions.audio_chunk_id = audio_chunks.id
+                    AND (?1 IS NULL OR audio_transcriptions.timestamp >= ?1)
+                    AND audio_transcriptions.timestamp < ?2
+                )
+                ORDER BY id
+                LIMIT ?3
+                "#,
+            )
+            .bind(start)
+            .bind(end)
+            .bind(DELETE_BATCH_SIZE)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            if chunks.is_empty() {
+                tx.rollback().await?;
+                break;
+            }
+
+            let chunk_ids: Vec<i64> = chunks.iter().map(|(id, _)| *id).collect();
+            let ids_json = serde_json::to_string(&chunk_ids).unwrap_or_else(|_| "[]".to_string());
+
+            deleted.audio_transcriptions += sqlx::query(
+                "DELETE FROM audio_transcriptions WHERE audio_chunk_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids_json)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            sqlx::query(
+                "DELETE FROM audio_tags WHERE audio_chunk_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids_json)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query("DELETE FROM audio_chunks WHERE id IN (SELECT value FROM json_each(?1))")
+                .bind(&ids_json)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+            debug!(
+                "deleted batch of {} audio chunks recorded between {:?} and {}",
+                chunks.len(),
+                start,
+                end
+            );
+
+            deleted
+                .audio_files
+                .extend(chunks.into_iter().map(|(_, file_path)| file_path));
+        }
+
+        Ok(deleted)
+    }
+
+    async fn delete_ui_monitoring_in_range(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: DateTime<Utc>,
+    ) -> Result<DeletedContent, sqlx::Error> {
+        let mut deleted = DeletedContent::default();
+
+        loop {
+            let mut tx = self.pool.begin().await?;
+
+            let ui_ids: Vec<i64> = sqlx::query_scalar(
+                r#"
+                SELECT id FROM ui_monitoring
+                WHERE (?1 IS NULL OR timestamp >= ?1) AND timestamp < ?2
+                ORDER BY id
+                LIMIT ?3
+                "#,
+            )
+            .bind(start)
+            .bind(end)
+            .bind(DELETE_BATCH_SIZE)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            if ui_ids.is_empty() {
+                tx.rollback().await?;
+                break;
+            }
+
+            let ids_json = serde_json::to_string(&ui_ids).unwrap_or_else(|_| "[]".to_string());
+
+            sqlx::query(
+                "DELETE FROM ui_monitoring_tags WHERE ui_monitoring_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids_json)
+            .execute(&mut *tx)
+            .await?;
+
+            deleted.ui_monitoring += sqlx::query(
+                "DELETE FROM ui_monitoring WHERE id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids_json)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
 
-            frame_ids.push(frame_id);
+            tx.commit().await?;
         }
 
-        tx.commit().await?;
-        debug!(
-            "created {} frames for video chunk {}",
-            frames.len(),
-            video_chunk_id
-        );
+        Ok(deleted)
+    }
 
-        Ok(frame_ids)
+    /// Frames captured in `[start, end)`, for exporting.
+    pub fn export_frames(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxStream<'_, Result<ExportedFrame, sqlx::Error>> {
+        sqlx::query_as(
+            r#"
+            SELECT id, video_chunk_id, offset_index, timestamp, name, browser_url, app_name,
+                window_name, focused
+            FROM frames
+            WHERE timestamp >= ?1 AND timestamp < ?2
+            ORDER BY id
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch(&self.pool)
     }
 
-    pub async fn insert_embeddings(
+    /// Video chunks holding frames captured in `[start, end)`, for exporting.
+    pub fn export_video_chunks(
         &self,
-        frame_id: i64,
-        embedding: String,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query("INSERT INTO ocr_text_embeddings (frame_id, embedding) VALUES (?1, ?2)")
-            .bind(frame_id)
-            .bind(embedding)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxStream<'_, Result<ExportedVideoChunk, sqlx::Error>> {
+        sqlx::query_as(
+            r#"
+            SELECT id, file_path, device_name
+            FROM video_chunks
+            WHERE id IN (
+                SELECT video_chunk_id FROM frames WHERE timestamp >= ?1 AND timestamp < ?2
+            )
+            ORDER BY id
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch(&self.pool)
     }
 
-    pub async fn search_similar_embeddings(
+    /// OCR text of frames captured in `[start, end)`, for exporting.
+    pub fn export_ocr_text(
         &self,
-        embedding: Vec<f32>,
-        limit: u32,
-        threshold: f32,
-    ) -> Result<Vec<OCRResult>, sqlx::Error> {
-        debug!("searching similar embeddings with threshold {}", threshold);
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxStream<'_, Result<ExportedOcrText, sqlx::Error>> {
+        sqlx::query_as(
+            r#"
+            SELECT ocr_text.frame_id, ocr_text.text, ocr_text.text_json, ocr_text.app_name,
+                ocr_text.ocr_engine, ocr_text.window_name, ocr_text.focused
+            FROM ocr_text
+            JOIN frames ON ocr_text.frame_id = frames.id
+            WHERE frames.timestamp >= ?1 AND frames.timestamp < ?2
+            ORDER BY ocr_text.frame_id
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch(&self.pool)
+    }
 
-        let sql = r#"
-            WITH embedding_matches AS (
-                SELECT
-                    frame_id,
-                    vec_distance_cosine(embedding, vec_f32(?1)) as similarity
-                FROM ocr_text_embeddings
-                WHERE vec_distance_cosine(embedding, vec_f32(?1)) < ?2
-                ORDER BY similarity ASC
-                LIMIT ?3
+    /// Audio transcriptions from `[start, end)`, for exporting.
+    pub fn export_audio_transcriptions(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxStream<'_, Result<ExportedAudioTranscription, sqlx::Error>> {
+        sqlx::query_as(
+            r#"
+            SELECT id, audio_chunk_id, offset_index, timestamp, transcription, device,
+                is_input_device, speaker_id, transcription_engine, start_time, end_time
+            FROM audio_transcriptions
+            WHERE timestamp >= ?1 AND timestamp < ?2
+            ORDER BY id
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch(&self.pool)
+    }
+
+    /// Segments of audio transcriptions from `[start, end)`, for exporting.
+    pub fn export_transcription_segments(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxStream<'_, Result<ExportedTranscriptionSegment, sqlx::Error>> {
+        sqlx::query_as(
+            r#"
+            SELECT transcription_segments.audio_transcription_id,
+                transcription_segments.segment_index, transcription_segments.start_time,
+                transcription_segments.end_time, transcription_segments.text,
+                transcription_segments.words
+            FROM transcription_segments
+            JOIN audio_transcriptions
+                ON transcription_segments.audio_transcription_id = audio_transcriptions.id
+            WHERE audio_transcriptions.timestamp >= ?1 AND audio_transcriptions.timestamp < ?2
+            ORDER BY transcription_segments.audio_transcription_id,
+                transcription_segments.segment_index
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch(&self.pool)
+    }
+
+    /// Audio chunks holding transcriptions from `[start, end)`, for exporting.
+    pub fn export_audio_chunks(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxStream<'_, Result<ExportedAudioChunk, sqlx::Error>> {
+        sqlx::query_as(
+            r#"
+            SELECT id, file_path, timestamp
+            FROM audio_chunks
+            WHERE id IN (
+                SELECT audio_chunk_id FROM audio_transcriptions
+                WHERE timestamp >= ?1 AND timestamp < ?2
             )
-            SELECT
-                ocr_text.frame_id,
-                ocr_text.text as ocr_text,
-                ocr_text.text_json,
-                frames.timestamp,
-                video_chunks.file_path,
-                frames.offset_index,
-                frames.name as frame_name,
-                frames.app_name,
-                ocr_text.ocr_engine,
-                frames.window_name,
-                GROUP_CONCAT(tags.name, ',') as tags,
-                frames.browser_url
-            FROM embedding_matches
-            JOIN ocr_text ON embedding_matches.frame_id = ocr_text.frame_id
-            JOIN frames ON ocr_text.frame_id = frames.id
-            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
-            LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
-            LEFT JOIN tags ON vision_tags.tag_id = tags.id
-            GROUP BY ocr_text.frame_id
-            ORDER BY embedding_matches.similarity ASC
-        "#;
+            ORDER BY id
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch(&self.pool)
+    }
 
-        let bytes = embedding.as_bytes();
+    /// Speakers of audio transcriptions from `[start, end)`, for exporting.
+    pub fn export_speakers(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxStream<'_, Result<ExportedSpeaker, sqlx::Error>> {
+        sqlx::query_as(
+            r#"
+            SELECT id, name, metadata, hallucination
+            FROM speakers
+            WHERE id IN (
+                SELECT speaker_id FROM audio_transcriptions
+                WHERE timestamp >= ?1 AND timestamp < ?2
+            )
+            ORDER BY id
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch(&self.pool)
+    }
 
-        let raw_results: Vec<OCRResultRaw> = sqlx::query_as(sql)
-            .bind(bytes)
-            .bind(threshold)
-            .bind(limit)
-            .fetch_all(&self.pool)
-            .await?;
+    /// Tags on frames captured in `[start, end)` and on audio chunks holding transcriptions
+    /// from it, for exporting.
+    pub fn export_tags(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxStream<'_, Result<ExportedTag, sqlx::Error>> {
+        sqlx::query_as(
+            r#"
+            SELECT 'vision' AS content_type, vision_tags.vision_id AS id, tags.name
+            FROM vision_tags
+            JOIN tags ON vision_tags.tag_id = tags.id
+            JOIN frames ON vision_tags.vision_id = frames.id
+            WHERE frames.timestamp >= ?1 AND frames.timestamp < ?2
+            UNION ALL
+            SELECT 'audio' AS content_type, audio_tags.audio_chunk_id AS id, tags.name
+            FROM audio_tags
+            JOIN tags ON audio_tags.tag_id = tags.id
+            WHERE audio_tags.audio_chunk_id IN (
+                SELECT audio_chunk_id FROM audio_transcriptions
+                WHERE timestamp >= ?1 AND timestamp < ?2
+            )
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch(&self.pool)
+    }
 
-        Ok(raw_results
-            .into_iter()
-            .map(|raw| OCRResult {
-                frame_id: raw.frame_id,
-                ocr_text: raw.ocr_text,
-                text_json: raw.text_json,
-                timestamp: raw.timestamp,
-                file_path: raw.file_path,
-                offset_index: raw.offset_index,
-                app_name: raw.app_name,
-                ocr_engine: raw.ocr_engine,
-                window_name: raw.window_name,
-                frame_name: raw.frame_name,
-                tags: raw
-                    .tags
-                    .map(|t| t.split(',').map(String::from).collect())
-                    .unwrap_or_default(),
-                browser_url: raw.browser_url,
-                focused: raw.focused,
-            })
-            .collect())
+    /// UI monitoring entries from `[start, end)`, for exporting.
+    pub fn export_ui_monitoring(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxStream<'_, Result<ExportedUiMonitoring, sqlx::Error>> {
+        sqlx::query_as(
+            r#"
+            SELECT id, text_output, timestamp, app, window, initial_traversal_at
+            FROM ui_monitoring
+            WHERE timestamp >= ?1 AND timestamp < ?2
+            ORDER BY id
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch(&self.pool)
     }
 
-    // Add method to update frame names
-    pub async fn update_frame_name(&self, frame_id: i64, name: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE frames SET name = ?1 WHERE id = ?2")
-            .bind(name)
-            .bind(frame_id)
+    /// Earliest and latest time recorded in the chunks exported for `[start, end)`. Chunks are
+    /// exported whole, so they can reach past both ends of the range.
+    pub async fn export_media_range(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>, sqlx::Error> {
+        let (first, last): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) = sqlx::query_as(
+            r#"
+            SELECT MIN(timestamp), MAX(timestamp)
+            FROM (
+                SELECT timestamp FROM frames
+                WHERE video_chunk_id IN (
+                    SELECT video_chunk_id FROM frames WHERE timestamp >= ?1 AND timestamp < ?2
+                )
+                UNION ALL
+                SELECT timestamp FROM audio_transcriptions
+                WHERE audio_chunk_id IN (
+                    SELECT audio_chunk_id FROM audio_transcriptions
+                    WHERE timestamp >= ?1 AND timestamp < ?2
+                )
+                UNION ALL
+                SELECT timestamp FROM audio_chunks
+                WHERE id IN (
+                    SELECT audio_chunk_id FROM audio_transcriptions
+                    WHERE timestamp >= ?1 AND timestamp < ?2
+                )
+            )
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(first.zip(last))
+    }
+
+    /// Starts importing exported rows in batches of transactions, see [`DataImport`].
+    pub async fn begin_import(&self) -> Result<DataImport<'_>, sqlx::Error> {
+        DataImport::new(&self.pool).await
+    }
+
+    /// Writes a consistent copy of the database to `path` while it stays in use. The copy is
+    /// compacted and, with SQLCipher, encrypted with the same key. `path` must not exist.
+    pub async fn backup_into(&self, path: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("VACUUM INTO ?1")
+            .bind(path)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
-    // Add method to update all frames in a video chunk
-    pub async fn update_video_chunk_frames_names(
-        &self,
-        video_chunk_id: i64,
-        name: &str,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE frames SET name = ?1 WHERE video_chunk_id = ?2")
-            .bind(name)
-            .bind(video_chunk_id)
-            .execute(&self.pool)
+    /// Paths of all video and audio chunks referenced by the database.
+    pub async fn list_media_files(&self) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT file_path FROM video_chunks UNION SELECT file_path FROM audio_chunks ORDER BY 1",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Runs `PRAGMA integrity_check`, returning the problems it found. Empty if the database is
+    /// intact.
+    pub async fn integrity_check(&self) -> Result<Vec<String>, sqlx::Error> {
+        let results: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+            .fetch_all(&self.pool)
             .await?;
-        Ok(())
+        Ok(results
+            .into_iter()
+            .filter(|result| result != "ok")
+            .collect())
+    }
+
+    /// Checks the database at `database_path` over a single read-only connection, without
+    /// migrating it or changing its journal mode, so that a file can be verified before anything
+    /// uses it. Returns the problems found, empty if the database is intact.
+    pub async fn check_database_file(
+        database_path: &str,
+        key: Option<&str>,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        register_sqlite_vec();
+
+        let mut connect_options =
+            SqliteConnectOptions::from_str(&format!("sqlite:{}", database_path))?.read_only(true);
+        if let Some(key) = key {
+            connect_options = connect_options.pragma("key", format!("\"x'{}'\"", key));
+        }
+        let mut conn = connect_options.connect().await?;
+        let results: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+            .fetch_all(&mut conn)
+            .await?;
+        conn.close().await?;
+
+        Ok(results
+            .into_iter()
+            .filter(|result| result != "ok")
+            .collect())
     }
 
     pub async fn repair_database(&self) -> Result<(), anyhow::Error> {
@@ -2384,3 +4203,276 @@ fn calculate_confidence(positions: &[TextPosition]) -> f32 {
 
     positions.iter().map(|pos| pos.confidence).sum::<f32>() / positions.len() as f32
 }
+
+/// Loads sqlite-vec into every connection opened from now on.
+fn register_sqlite_vec() {
+    unsafe {
+        sqlite3_auto_extension(Some(
+            std::mem::transmute::<*const (), unsafe extern "C" fn()>(sqlite3_vec_init as *const ()),
+        ));
+    }
+}
+
+/// Whether the file at `database_path` is an existing, unencrypted SQLite database.
+async fn is_plaintext_database(database_path: &str) -> Result<bool, sqlx::Error> {
+    let mut file = match tokio::fs::File::open(database_path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut header = [0u8; 16];
+    match file.read_exact(&mut header).await {
+        Ok(_) => Ok(&header == b"SQLite format 3\0"),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Rewrites an unencrypted database as a SQLCipher database encrypted with `key`.
+async fn encrypt_plaintext_database(database_path: &str, key: &str) -> Result<(), sqlx::Error> {
+    warn!("encrypting existing database at {}", database_path);
+
+    let encrypted_path = format!("{}.encrypting", database_path);
+    let _ = tokio::fs::remove_file(&encrypted_path).await;
+
+    let mut conn = SqliteConnectOptions::from_str(&format!("sqlite:{}", database_path))?
+        .connect()
+        .await?;
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE);")
+        .execute(&mut conn)
+        .await?;
+    sqlx::query(&format!(
+        "ATTACH DATABASE '{}' AS encrypted KEY \"x'{}'\";",
+        encrypted_path.replace('\'', "''"),
+        key
+    ))
+    .execute(&mut conn)
+    .await?;
+    sqlx::query("SELECT sqlcipher_export('encrypted');")
+        .execute(&mut conn)
+        .await?;
+    sqlx::query("DETACH DATABASE encrypted;")
+        .execute(&mut conn)
+        .await?;
+    conn.close().await?;
+
+    tokio::fs::rename(&encrypted_path, database_path).await?;
+    for suffix in ["-wal", "-shm"] {
+        let _ = tokio::fs::remove_file(format!("{}{}", database_path, suffix)).await;
+    }
+
+    Ok(())
+}
+
+/// Finds where `query` was spoken: the first run of words matching its terms in order, else the
+/// first segment containing all of them.
+fn find_matching_span(query: &str, segments: &[TranscriptionSegment]) -> Option<TranscriptionSpan> {
+    // FTS5 operators and syntax aren't part of what was said
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .filter(|term| !matches!(*term, "AND" | "OR" | "NOT" | "NEAR"))
+        .map(normalize_word)
+        .filter(|term| !term.is_empty())
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+
+    for segment in segments {
+        let words: Vec<String> = segment
+            .words
+            .iter()
+            .map(|word| normalize_word(&word.text))
+            .collect();
+        if let Some(start) = words
+            .windows(terms.len())
+            .position(|window| window == terms.as_slice())
+        {
+            let matched = &segment.words[start..start + terms.len()];
+            return Some(TranscriptionSpan {
+                start_time: matched[0].start_time,
+                end_time: matched[matched.len() - 1].end_time,
+                text: matched
+                    .iter()
+                    .map(|word| word.text.trim())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            });
+        }
+    }
+
+    segments
+        .iter()
+        .find(|segment| {
+            let text = segment.text.to_lowercase();
+            terms.iter().all(|term| text.contains(term.as_str()))
+        })
+        .map(|segment| TranscriptionSpan {
+            start_time: segment.start_time,
+            end_time: segment.end_time,
+            text: segment.text.trim().to_string(),
+        })
+}
+
+/// Reads an embedding stored by `vec_f32`, which is little endian `f32`s.
+fn embedding_from_bytes(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Normalised mean of speaker embeddings, so that each one counts the same whatever its
+/// magnitude. `None` without embeddings.
+fn speaker_centroid(embeddings: impl IntoIterator<Item = Vec<f32>>) -> Option<Vec<f32>> {
+    let mut centroid: Option<Vec<f32>> = None;
+    for embedding in embeddings {
+        let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        let sum = centroid.get_or_insert_with(|| vec![0.0; embedding.len()]);
+        if sum.len() != embedding.len() {
+            continue;
+        }
+        for (total, x) in sum.iter_mut().zip(&embedding) {
+            *total += x / norm;
+        }
+    }
+
+    let mut centroid = centroid?;
+    let norm = centroid.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return None;
+    }
+    centroid.iter_mut().for_each(|x| *x /= norm);
+    Some(centroid)
+}
+
+/// Lowercases a word and drops its punctuation, so words can be compared across transcriptions.
+pub fn normalize_word(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric() || *c == '\'')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Converts a row of a caller-written statement to JSON, blobs become null.
+fn raw_sql_row(row: &SqliteRow) -> serde_json::Map<String, serde_json::Value> {
+    let mut map = serde_json::Map::new();
+    for (i, column) in row.columns().iter().enumerate() {
+        if let Ok(value) = row.try_get_raw(i) {
+            let json_value = match value.type_info().name() {
+                "TEXT" => {
+                    let s: String = row.try_get(i).unwrap_or_default();
+                    serde_json::Value::String(s)
+                }
+                "INTEGER" => {
+                    let i: i64 = row.try_get(i).unwrap_or_default();
+                    serde_json::Value::Number(i.into())
+                }
+                "REAL" => {
+                    let f: f64 = row.try_get(i).unwrap_or_default();
+                    serde_json::Value::Number(serde_json::Number::from_f64(f).unwrap_or(0.into()))
+                }
+                _ => serde_json::Value::Null,
+            };
+            map.insert(column.name().to_string(), json_value);
+        }
+    }
+    map
+}
+
+/// Builds the `frames_fts` query matching the frame metadata filters, empty if there are none.
+fn frame_fts_query(
+    app_name: Option<&str>,
+    window_name: Option<&str>,
+    browser_url: Option<&str>,
+    focused: Option<bool>,
+    frame_name: Option<&str>,
+) -> String {
+    let mut frame_fts_parts = Vec::new();
+
+    if let Some(app) = app_name {
+        if !app.is_empty() {
+            frame_fts_parts.push(format!("app_name:{}", app));
+        }
+    }
+    if let Some(window) = window_name {
+        if !window.is_empty() {
+            frame_fts_parts.push(format!("window_name:{}", window));
+        }
+    }
+    if let Some(browser) = browser_url {
+        if !browser.is_empty() {
+            frame_fts_parts.push(format!("browser_url:{}", browser));
+        }
+    }
+    if let Some(is_focused) = focused {
+        frame_fts_parts.push(format!("focused:{}", if is_focused { "1" } else { "0" }));
+    }
+    if let Some(frame_name) = frame_name {
+        if !frame_name.is_empty() {
+            frame_fts_parts.push(format!("name:{}", frame_name));
+        }
+    }
+
+    frame_fts_parts.join(" ")
+}
+
+/// Dampens the weight of the top ranks in reciprocal-rank fusion, 60 is the usual choice.
+const RRF_K: f64 = 60.0;
+
+/// Identifies the same content across the ranked lists fused by hybrid search.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum FusionKey {
+    Frame(i64),
+    AudioTranscription {
+        audio_chunk_id: i64,
+        offset_index: i64,
+    },
+    Ui(i64),
+}
+
+impl FusionKey {
+    fn of(result: &SearchResult) -> Self {
+        match result {
+            SearchResult::OCR(ocr) => Self::Frame(ocr.frame_id),
+            SearchResult::Audio(audio) => Self::AudioTranscription {
+                audio_chunk_id: audio.audio_chunk_id,
+                offset_index: audio.offset_index,
+            },
+            SearchResult::UI(ui) => Self::Ui(ui.id),
+        }
+    }
+}
+
+fn result_timestamp(result: &SearchResult) -> DateTime<Utc> {
+    match result {
+        SearchResult::OCR(ocr) => ocr.timestamp,
+        SearchResult::Audio(audio) => audio.timestamp,
+        SearchResult::UI(ui) => ui.timestamp,
+    }
+}
+
+fn ocr_result_from_raw(raw: OCRResultRaw) -> OCRResult {
+    OCRResult {
+        frame_id: raw.frame_id,
+        ocr_text: raw.ocr_text,
+        text_json: raw.text_json,
+        timestamp: raw.timestamp,
+        frame_name: raw.frame_name,
+        file_path: raw.file_path,
+        offset_index: raw.offset_index,
+        app_name: raw.app_name,
+        ocr_engine: raw.ocr_engine,
+        window_name: raw.window_name,
+        tags: raw
+            .tags
+            .map(|t| t.split(',').map(String::from).collect())
+            .unwrap_or_default(),
+        browser_url: raw.browser_url,
+        focused: raw.focused,
+    }
+}
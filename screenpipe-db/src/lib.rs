@@ -1,9 +1,11 @@
+mod data_import;
 mod db;
 mod migration_worker;
 mod types;
 mod video_db;
 
-pub use db::DatabaseManager;
+pub use data_import::DataImport;
+pub use db::{normalize_word, DatabaseManager};
 pub use migration_worker::{
     create_migration_worker, MigrationCommand, MigrationConfig, MigrationResponse, MigrationStatus,
     MigrationWorker,
@@ -0,0 +1,21 @@
+-- Create audio_transcription_embeddings table
+CREATE TABLE IF NOT EXISTS audio_transcription_embeddings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    audio_transcription_id INTEGER NOT NULL,
+    embedding BLOB NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (audio_transcription_id) REFERENCES audio_transcriptions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_audio_transcription_embeddings_transcription_id
+    ON audio_transcription_embeddings(audio_transcription_id);
+
+-- foreign keys are not enforced, so remove embeddings of deleted transcriptions explicitly
+CREATE TRIGGER IF NOT EXISTS audio_transcriptions_delete_embeddings
+AFTER DELETE ON audio_transcriptions
+BEGIN
+    DELETE FROM audio_transcription_embeddings WHERE audio_transcription_id = OLD.id;
+END;
+
+-- the embedding worker looks up which frames still need an embedding
+CREATE INDEX IF NOT EXISTS idx_ocr_text_embeddings_frame_id ON ocr_text_embeddings(frame_id);
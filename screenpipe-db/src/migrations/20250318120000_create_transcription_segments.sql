@@ -0,0 +1,23 @@
+-- Segment and word timings of audio transcriptions, as reported by the transcription engine.
+-- Times are seconds from the start of the audio chunk file.
+CREATE TABLE IF NOT EXISTS transcription_segments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    audio_transcription_id INTEGER NOT NULL,
+    segment_index INTEGER NOT NULL,
+    start_time REAL NOT NULL,
+    end_time REAL NOT NULL,
+    text TEXT NOT NULL,
+    -- JSON array of {"start_time", "end_time", "text", "confidence"}
+    words TEXT NOT NULL DEFAULT '[]',
+    FOREIGN KEY (audio_transcription_id) REFERENCES audio_transcriptions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_transcription_segments_transcription_id
+    ON transcription_segments(audio_transcription_id, segment_index);
+
+-- foreign keys are not enforced, so remove segments of deleted transcriptions explicitly
+CREATE TRIGGER IF NOT EXISTS audio_transcriptions_delete_segments
+AFTER DELETE ON audio_transcriptions
+BEGIN
+    DELETE FROM transcription_segments WHERE audio_transcription_id = OLD.id;
+END;
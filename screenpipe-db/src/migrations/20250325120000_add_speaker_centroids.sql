@@ -0,0 +1,8 @@
+-- Normalised mean of a speaker's embeddings, matched alongside the embeddings themselves.
+-- Recomputed when embeddings are enrolled or speakers are merged.
+ALTER TABLE speakers ADD COLUMN centroid BLOB;
+
+-- Speakers created or confirmed by the user from clips of their voice
+ALTER TABLE speakers ADD COLUMN enrolled BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_speaker_embeddings_speaker_id ON speaker_embeddings(speaker_id);
@@ -0,0 +1,3 @@
+-- Engine an audio chunk was last re-transcribed with when no speech was found in it. Such
+-- chunks keep their transcriptions and are not picked up again for the same engine.
+ALTER TABLE audio_chunks ADD COLUMN retranscribed_with TEXT;
@@ -0,0 +1,11 @@
+-- Model each embedding was made with. Embeddings of different models can't be compared, so
+-- searches only use the current model's and the others are re-embedded.
+ALTER TABLE ocr_text_embeddings ADD COLUMN model TEXT;
+ALTER TABLE audio_transcription_embeddings ADD COLUMN model TEXT;
+
+-- before the embedding worker OCR text was only embedded by the built-in model
+UPDATE ocr_text_embeddings SET model = 'jinaai/jina-embeddings-v2-base-en' WHERE model IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_ocr_text_embeddings_model ON ocr_text_embeddings(model, frame_id);
+CREATE INDEX IF NOT EXISTS idx_audio_transcription_embeddings_model
+    ON audio_transcription_embeddings(model, audio_transcription_id);
@@ -0,0 +1,3 @@
+-- Codec and bitrate an audio chunk is stored with, e.g. "opus 24k". NULL for chunks recorded
+-- before it was tracked, their codec is told by the file extension and their bitrate unknown.
+ALTER TABLE audio_chunks ADD COLUMN encoding TEXT;
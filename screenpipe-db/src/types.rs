@@ -86,6 +86,7 @@ pub enum ContentType {
 
 #[derive(FromRow)]
 pub struct AudioResultRaw {
+    pub audio_transcription_id: i64,
     pub audio_chunk_id: i64,
     pub transcription: String,
     pub timestamp: DateTime<Utc>,
@@ -107,6 +108,35 @@ pub struct Speaker {
     pub metadata: String,
 }
 
+/// Cosine distance under which a voice is attributed to a known speaker.
+pub const DEFAULT_SPEAKER_MATCH_THRESHOLD: f64 = 0.5;
+
+/// The known speaker closest to a voice.
+#[derive(Debug, Clone)]
+pub struct SpeakerMatch {
+    pub speaker: Speaker,
+    /// Cosine distance between the voice and the closest embedding or centroid of the speaker
+    pub distance: f64,
+}
+
+/// A speaker with the normalised mean of their voice embeddings.
+#[derive(Debug, Clone)]
+pub struct SpeakerCentroid {
+    pub speaker: Speaker,
+    pub enrolled: bool,
+    pub centroid: Vec<f32>,
+}
+
+/// Where a transcription was recorded, to enroll the speaker heard in it.
+#[derive(Debug, Clone, FromRow)]
+pub struct SpeakerAudioClip {
+    pub audio_transcription_id: i64,
+    pub file_path: String,
+    /// Seconds from the start of the audio file, `None` if the whole file was transcribed
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+}
+
 #[derive(OaSchema, Clone, Eq, PartialEq, Hash, Serialize, Debug, Deserialize)]
 pub enum DeviceType {
     Input,
@@ -115,6 +145,7 @@ pub enum DeviceType {
 
 #[derive(OaSchema, Debug, Serialize, Deserialize)]
 pub struct AudioResult {
+    pub audio_transcription_id: i64,
     pub audio_chunk_id: i64,
     pub transcription: String,
     pub timestamp: DateTime<Utc>,
@@ -127,6 +158,37 @@ pub struct AudioResult {
     pub speaker: Option<Speaker>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    /// Where the search query was spoken, if the engine reported timings.
+    pub matched_segment: Option<TranscriptionSpan>,
+}
+
+/// A segment of an audio transcription as reported by the transcription engine. Times are
+/// seconds from the start of the audio chunk file.
+#[derive(OaSchema, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionSegment {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub text: String,
+    #[serde(default)]
+    pub words: Vec<TranscriptionWord>,
+}
+
+#[derive(OaSchema, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionWord {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub text: String,
+    #[serde(default)]
+    pub confidence: Option<f64>,
+}
+
+/// The part of a transcription that matched a search query. Times are seconds from the start
+/// of the audio chunk file.
+#[derive(OaSchema, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionSpan {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub text: String,
 }
 
 #[derive(OaSchema, Debug, Deserialize, PartialEq)]
@@ -218,6 +280,58 @@ pub struct AudioChunksResponse {
     pub timestamp: DateTime<Utc>,
 }
 
+/// An audio transcription with what is needed to place it in a transcript. `start_time` and
+/// `end_time` are seconds from the start of the audio chunk.
+#[derive(OaSchema, Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub audio_transcription_id: i64,
+    pub audio_chunk_id: i64,
+    /// When the recording of the audio chunk started
+    pub chunk_timestamp: DateTime<Utc>,
+    pub timestamp: DateTime<Utc>,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+    pub transcription: String,
+    pub device_name: String,
+    pub is_input_device: bool,
+    pub speaker_id: Option<i64>,
+    pub speaker_name: Option<String>,
+}
+
+/// An audio chunk whose transcriptions were not all made by the engine it is re-transcribed
+/// with. `timestamp` is when its first transcription was recorded.
+#[derive(Debug, Clone, FromRow)]
+pub struct AudioChunkToRetranscribe {
+    pub id: i64,
+    pub file_path: String,
+    pub device_name: String,
+    pub is_input_device: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A transcription replacing the ones stored for an audio chunk. Times are seconds from the
+/// start of the chunk.
+#[derive(Debug, Clone)]
+pub struct RetranscribedAudio {
+    pub transcription: String,
+    pub speaker_id: Option<i64>,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub segments: Vec<TranscriptionSegment>,
+    /// Engine that made this transcription when it isn't the one it was requested from, e.g.
+    /// Whisper after a failed Deepgram request
+    pub fallback_engine: Option<String>,
+}
+
+/// The speaker of a stored transcription and when it was said, in seconds from the start of
+/// its audio chunk.
+#[derive(Debug, Clone, PartialEq, FromRow)]
+pub struct SpeakerSpan {
+    pub speaker_id: i64,
+    pub start_time: f64,
+    pub end_time: f64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OcrTextBlock {
     pub block_num: String,
@@ -323,3 +437,189 @@ impl Default for CustomOcrConfig {
         }
     }
 }
+
+/// Summary of rows removed by a delete operation, along with the media files
+/// that no longer have any rows referencing them and can be removed from disk.
+#[derive(OaSchema, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DeletedContent {
+    pub frames: u64,
+    pub ocr_text: u64,
+    pub audio_transcriptions: u64,
+    pub ui_monitoring: u64,
+    pub video_files: Vec<String>,
+    pub audio_files: Vec<String>,
+    /// Video files that still hold frames outside the deleted range, with the
+    /// positions of the frames that were deleted from them.
+    pub partial_video_files: Vec<PartialVideoFile>,
+}
+
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize)]
+pub struct PartialVideoFile {
+    pub file_path: String,
+    pub offset_indexes: Vec<i64>,
+}
+
+impl DeletedContent {
+    pub fn merge(&mut self, other: DeletedContent) {
+        self.frames += other.frames;
+        self.ocr_text += other.ocr_text;
+        self.audio_transcriptions += other.audio_transcriptions;
+        self.ui_monitoring += other.ui_monitoring;
+        self.video_files.extend(other.video_files);
+        self.audio_files.extend(other.audio_files);
+
+        for partial in other.partial_video_files {
+            match self
+                .partial_video_files
+                .iter_mut()
+                .find(|p| p.file_path == partial.file_path)
+            {
+                Some(existing) => existing.offset_indexes.extend(partial.offset_indexes),
+                None => self.partial_video_files.push(partial),
+            }
+        }
+        // a file that lost its last frame in a later batch is deleted, not redacted
+        let video_files = &self.video_files;
+        self.partial_video_files
+            .retain(|p| !video_files.contains(&p.file_path));
+    }
+}
+
+/// A video chunk referenced by exported frames.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedVideoChunk {
+    pub id: i64,
+    pub file_path: String,
+    pub device_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedFrame {
+    pub id: i64,
+    pub video_chunk_id: i64,
+    pub offset_index: i64,
+    pub timestamp: DateTime<Utc>,
+    pub name: Option<String>,
+    pub browser_url: Option<String>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub focused: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedOcrText {
+    pub frame_id: i64,
+    pub text: String,
+    pub text_json: Option<String>,
+    pub app_name: String,
+    pub ocr_engine: String,
+    pub window_name: Option<String>,
+    pub focused: Option<bool>,
+}
+
+/// An audio chunk referenced by exported transcriptions.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedAudioChunk {
+    pub id: i64,
+    pub file_path: String,
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedAudioTranscription {
+    pub id: i64,
+    pub audio_chunk_id: i64,
+    pub offset_index: i64,
+    pub timestamp: DateTime<Utc>,
+    pub transcription: String,
+    pub device: String,
+    pub is_input_device: bool,
+    pub speaker_id: Option<i64>,
+    pub transcription_engine: String,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+}
+
+/// A segment of an exported audio transcription. `words` is the JSON array stored in the
+/// database.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedTranscriptionSegment {
+    pub audio_transcription_id: i64,
+    pub segment_index: i64,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub text: String,
+    pub words: String,
+}
+
+/// A speaker of exported transcriptions.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedSpeaker {
+    pub id: i64,
+    pub name: Option<String>,
+    pub metadata: Option<String>,
+    pub hallucination: Option<bool>,
+}
+
+/// A tag on an exported frame (`content_type` "vision", `id` is the frame id) or audio chunk
+/// (`content_type` "audio", `id` is the audio chunk id).
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedTag {
+    pub content_type: String,
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedUiMonitoring {
+    pub id: i64,
+    pub text_output: String,
+    pub timestamp: DateTime<Utc>,
+    pub app: String,
+    pub window: String,
+    pub initial_traversal_at: Option<DateTime<Utc>>,
+}
+
+/// Rows added by an import. Rows that were already in the database are counted as skipped.
+#[derive(OaSchema, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ImportedContent {
+    pub video_chunks: u64,
+    pub frames: u64,
+    pub ocr_text: u64,
+    pub audio_chunks: u64,
+    pub audio_transcriptions: u64,
+    #[serde(default)]
+    pub transcription_segments: u64,
+    pub speakers: u64,
+    pub tags: u64,
+    pub ui_monitoring: u64,
+    pub skipped: u64,
+}
+
+/// Limits for a statement run with `DatabaseManager::query_raw_sql`.
+#[derive(Debug, Clone)]
+pub struct RawSqlOptions {
+    /// Rows beyond this are dropped and the result is marked as truncated.
+    pub max_rows: usize,
+    /// The statement is interrupted once it runs longer than this.
+    pub timeout: std::time::Duration,
+    /// Run on the writable connection instead of the read-only one.
+    pub allow_writes: bool,
+}
+
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize)]
+pub struct RawSqlColumn {
+    pub name: String,
+    /// SQLite type of the column, e.g. INTEGER, TEXT, REAL, BLOB or NULL.
+    #[serde(rename = "type")]
+    pub type_name: String,
+}
+
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize)]
+pub struct RawSqlResult {
+    pub columns: Vec<RawSqlColumn>,
+    /// One object per row, keyed by column name.
+    pub rows: Vec<serde_json::Value>,
+    /// Whether rows were dropped because the statement returned more than the row cap.
+    pub truncated: bool,
+}
@@ -4,7 +4,9 @@ mod tests {
 
     use chrono::Utc;
     use screenpipe_db::{
-        AudioDevice, ContentType, DatabaseManager, DeviceType, Frame, OcrEngine, SearchResult,
+        AudioDevice, ContentType, DatabaseManager, DeviceType, Frame, OcrEngine, RawSqlOptions,
+        RetranscribedAudio, SearchResult, SpeakerSpan, TagContentType, TranscriptionSegment,
+        TranscriptionSpan, TranscriptionWord,
     };
 
     async fn setup_test_db() -> DatabaseManager {
@@ -146,6 +148,475 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_search_audio_returns_matched_segment() {
+        let db = setup_test_db().await;
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        let transcription_id = db
+            .insert_audio_transcription(
+                audio_chunk_id,
+                "Good morning. The quick brown fox.",
+                0,
+                "",
+                &AudioDevice {
+                    name: "test".to_string(),
+                    device_type: DeviceType::Input,
+                },
+                None,
+                Some(10.0),
+                Some(14.0),
+            )
+            .await
+            .unwrap();
+
+        let word = |start_time, end_time, text: &str| TranscriptionWord {
+            start_time,
+            end_time,
+            text: text.to_string(),
+            confidence: Some(0.9),
+        };
+        let segments = vec![
+            TranscriptionSegment {
+                start_time: 10.0,
+                end_time: 11.5,
+                text: "Good morning.".to_string(),
+                words: vec![word(10.0, 10.6, "Good"), word(10.6, 11.5, "morning.")],
+            },
+            TranscriptionSegment {
+                start_time: 12.0,
+                end_time: 14.0,
+                text: "The quick brown fox.".to_string(),
+                words: vec![
+                    word(12.0, 12.3, "The"),
+                    word(12.3, 12.8, "quick"),
+                    word(12.8, 13.4, "brown"),
+                    word(13.4, 14.0, "fox."),
+                ],
+            },
+        ];
+        db.insert_transcription_segments(transcription_id, &segments)
+            .await
+            .unwrap();
+        // storing again replaces the previous segments
+        db.insert_transcription_segments(transcription_id, &segments)
+            .await
+            .unwrap();
+
+        let stored = db
+            .get_transcription_segments(&[transcription_id])
+            .await
+            .unwrap();
+        assert_eq!(stored[&transcription_id], segments);
+
+        let results = db
+            .search_audio("quick brown", 10, 0, None, None, None, None, None, false)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].audio_transcription_id, transcription_id);
+        assert_eq!(
+            results[0].matched_segment,
+            Some(TranscriptionSpan {
+                start_time: 12.3,
+                end_time: 13.4,
+                text: "quick brown".to_string(),
+            })
+        );
+
+        // terms that aren't said together match the segment containing all of them
+        let results = db
+            .search_audio("fox quick", 10, 0, None, None, None, None, None, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            results[0].matched_segment,
+            Some(TranscriptionSpan {
+                start_time: 12.0,
+                end_time: 14.0,
+                text: "The quick brown fox.".to_string(),
+            })
+        );
+
+        // rewriting the transcription of the chunk replaces its segments too
+        db.update_audio_transcription(audio_chunk_id, "Good morning.")
+            .await
+            .unwrap();
+        db.update_transcription_segments(audio_chunk_id, &segments[..1])
+            .await
+            .unwrap();
+        let stored = db
+            .get_transcription_segments(&[transcription_id])
+            .await
+            .unwrap();
+        assert_eq!(stored[&transcription_id], segments[..1].to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_replace_audio_transcriptions() {
+        let db = setup_test_db().await;
+        let device = AudioDevice {
+            name: "test".to_string(),
+            device_type: DeviceType::Output,
+        };
+        let speaker = db.insert_speaker(&vec![0.1; 512]).await.unwrap();
+        let old_chunk_id = db.insert_audio_chunk("old_audio.mp4").await.unwrap();
+        for (text, start_time) in [("helo wrld", 0.0), ("quik fox", 5.0)] {
+            db.insert_audio_transcription(
+                old_chunk_id,
+                text,
+                0,
+                "WhisperTinyQuantized",
+                &device,
+                Some(speaker.id),
+                Some(start_time),
+                Some(start_time + 5.0),
+            )
+            .await
+            .unwrap();
+        }
+        let new_chunk_id = db.insert_audio_chunk("new_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(
+            new_chunk_id,
+            "already good",
+            0,
+            "WhisperLargeV3Turbo",
+            &device,
+            None,
+            Some(0.0),
+            Some(5.0),
+        )
+        .await
+        .unwrap();
+
+        // only chunks transcribed by another engine are picked up
+        assert_eq!(
+            db.count_audio_chunks_to_retranscribe("WhisperLargeV3Turbo", 0)
+                .await
+                .unwrap(),
+            1
+        );
+        let chunks = db
+            .get_audio_chunks_to_retranscribe("WhisperLargeV3Turbo", 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].id, old_chunk_id);
+        assert_eq!(chunks[0].file_path, "old_audio.mp4");
+        assert!(!chunks[0].is_input_device);
+        assert!(db
+            .get_audio_chunks_to_retranscribe("WhisperLargeV3Turbo", old_chunk_id, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.get_audio_chunk_speakers(old_chunk_id).await.unwrap(),
+            vec![
+                SpeakerSpan {
+                    speaker_id: speaker.id,
+                    start_time: 0.0,
+                    end_time: 5.0,
+                },
+                SpeakerSpan {
+                    speaker_id: speaker.id,
+                    start_time: 5.0,
+                    end_time: 10.0,
+                },
+            ]
+        );
+
+        let segments = vec![TranscriptionSegment {
+            start_time: 0.0,
+            end_time: 4.0,
+            text: "hello world, the quick fox".to_string(),
+            words: vec![],
+        }];
+        db.replace_audio_transcriptions(
+            &chunks[0],
+            "WhisperLargeV3Turbo",
+            &[RetranscribedAudio {
+                transcription: "hello world, the quick fox".to_string(),
+                speaker_id: None,
+                start_time: 0.0,
+                end_time: 10.0,
+                segments: segments.clone(),
+                fallback_engine: None,
+            }],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            db.count_audio_chunks_to_retranscribe("WhisperLargeV3Turbo", 0)
+                .await
+                .unwrap(),
+            0
+        );
+
+        // the full text index follows the new transcription
+        assert!(db
+            .search_audio("wrld", 10, 0, None, None, None, None, None, false)
+            .await
+            .unwrap()
+            .is_empty());
+        let results = db
+            .search_audio("quick fox", 10, 0, None, None, None, None, None, false)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].audio_chunk_id, old_chunk_id);
+        assert_eq!(results[0].transcription_engine, "WhisperLargeV3Turbo");
+        assert_eq!(results[0].timestamp, chunks[0].timestamp);
+        assert_eq!(results[0].device_type, DeviceType::Output);
+
+        let stored = db
+            .get_transcription_segments(&[results[0].audio_transcription_id])
+            .await
+            .unwrap();
+        assert_eq!(stored[&results[0].audio_transcription_id], segments);
+    }
+
+    #[tokio::test]
+    async fn test_retranscribed_chunks_are_not_picked_up_again() {
+        let db = setup_test_db().await;
+        let device = AudioDevice {
+            name: "test".to_string(),
+            device_type: DeviceType::Input,
+        };
+        let mut chunk_ids = Vec::new();
+        for file_path in ["silent.mp4", "speech.mp4"] {
+            let chunk_id = db.insert_audio_chunk(file_path).await.unwrap();
+            db.insert_audio_transcription(
+                chunk_id,
+                "thank you for watching",
+                0,
+                "WhisperTinyQuantized",
+                &device,
+                None,
+                Some(0.0),
+                Some(5.0),
+            )
+            .await
+            .unwrap();
+            chunk_ids.push(chunk_id);
+        }
+        let chunks = db
+            .get_audio_chunks_to_retranscribe("Deepgram", 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 2);
+
+        // no speech found: the transcriptions stay, the chunk is skipped for this engine only
+        db.mark_audio_chunk_retranscribed(chunk_ids[0], "Deepgram")
+            .await
+            .unwrap();
+        assert_eq!(
+            db.count_audio_chunks_to_retranscribe("Deepgram", 0)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            db.count_audio_chunks_to_retranscribe("WhisperLargeV3Turbo", 0)
+                .await
+                .unwrap(),
+            2
+        );
+
+        // transcriptions made by a fallback are stored with the engine that made them
+        db.replace_audio_transcriptions(
+            &chunks[1],
+            "Deepgram",
+            &[RetranscribedAudio {
+                transcription: "the quick brown fox".to_string(),
+                speaker_id: None,
+                start_time: 0.0,
+                end_time: 5.0,
+                segments: vec![],
+                fallback_engine: Some("WhisperLargeV3TurboQuantized".to_string()),
+            }],
+        )
+        .await
+        .unwrap();
+        let results = db
+            .search_audio("quick fox", 10, 0, None, None, None, None, None, false)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].transcription_engine,
+            "WhisperLargeV3TurboQuantized"
+        );
+        // the chunk was re-transcribed with the selected engine all the same
+        assert!(db
+            .get_audio_chunks_to_retranscribe("Deepgram", 0, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.count_audio_chunks_to_retranscribe("Deepgram", 0)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            db.count_audio_chunks_to_retranscribe("WhisperLargeV3Turbo", 0)
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_imported_audio() {
+        let db = setup_test_db().await;
+        let recorded_at = Utc::now() - chrono::Duration::days(30);
+        let device = AudioDevice {
+            name: "voice memo".to_string(),
+            device_type: DeviceType::Input,
+        };
+        let transcription = |text: &str, start_time: f64, end_time: f64| RetranscribedAudio {
+            transcription: text.to_string(),
+            speaker_id: None,
+            start_time,
+            end_time,
+            segments: vec![TranscriptionSegment {
+                start_time,
+                end_time,
+                text: text.to_string(),
+                words: vec![],
+            }],
+            fallback_engine: None,
+        };
+
+        let audio_chunk_id = db
+            .insert_imported_audio(
+                "memo.m4a",
+                recorded_at,
+                &device,
+                "WhisperLargeV3Turbo",
+                &[
+                    transcription("buy milk on the way home", 0.0, 4.0),
+                    transcription("call the dentist tomorrow", 62.5, 66.0),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let results = db
+            .search_audio("dentist", 10, 0, None, None, None, None, None, false)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].audio_chunk_id, audio_chunk_id);
+        assert_eq!(results[0].file_path, "memo.m4a");
+        assert_eq!(results[0].device_name, "voice memo");
+        assert_eq!(results[0].device_type, DeviceType::Input);
+        // transcriptions are placed at their offset in the file
+        assert_eq!(
+            results[0].timestamp,
+            recorded_at + chrono::Duration::milliseconds(62_500)
+        );
+
+        let stored = db
+            .get_transcription_segments(&[results[0].audio_transcription_id])
+            .await
+            .unwrap();
+        assert_eq!(
+            stored[&results[0].audio_transcription_id][0].start_time,
+            62.5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_audio_chunks_to_recompress() {
+        let db = setup_test_db().await;
+        let aac = db.insert_audio_chunk("mic_2024-01-01.mp4").await.unwrap();
+        let _opus = db.insert_audio_chunk("mic_2024-01-02.ogg").await.unwrap();
+        let wav = db.insert_audio_chunk("mic_2024-01-03.WAV").await.unwrap();
+        let later = Utc::now() + chrono::Duration::minutes(1);
+
+        let chunks = db
+            .get_audio_chunks_to_recompress("opus 24k", "ogg", later, 0, 10)
+            .await
+            .unwrap();
+        let ids: Vec<i64> = chunks.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![aac, wav]);
+
+        // chunks recorded after the cutoff are left alone
+        let earlier = Utc::now() - chrono::Duration::minutes(1);
+        assert!(db
+            .get_audio_chunks_to_recompress("opus 24k", "ogg", earlier, 0, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        db.update_audio_chunk_file(aac, "mic_2024-01-01.ogg", "opus 24k")
+            .await
+            .unwrap();
+        let chunks = db
+            .get_audio_chunks_to_recompress("opus 24k", "ogg", later, 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].id, wav);
+    }
+
+    #[tokio::test]
+    async fn test_audio_chunks_to_recompress_at_another_bitrate() {
+        let db = setup_test_db().await;
+        let recorded_at = Utc::now() - chrono::Duration::hours(1);
+        let low = db
+            .insert_audio_chunk_at("mic_1.ogg", recorded_at, Some("opus 24k"))
+            .await
+            .unwrap();
+        let high = db
+            .insert_audio_chunk_at("mic_2.ogg", recorded_at, Some("opus 64k"))
+            .await
+            .unwrap();
+        // recorded before encodings were tracked, its bitrate is unknown
+        let _untracked = db
+            .insert_audio_chunk_at("mic_3.ogg", recorded_at, None)
+            .await
+            .unwrap();
+
+        let chunks = db
+            .get_audio_chunks_to_recompress("opus 24k", "ogg", Utc::now(), 0, 10)
+            .await
+            .unwrap();
+        let ids: Vec<i64> = chunks.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![high]);
+
+        let chunks = db
+            .get_audio_chunks_to_recompress("opus 64k", "ogg", Utc::now(), 0, 10)
+            .await
+            .unwrap();
+        let ids: Vec<i64> = chunks.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![low]);
+    }
+
+    #[tokio::test]
+    async fn test_audio_chunk_keeps_recording_start() {
+        let db = setup_test_db().await;
+        let recorded_at = Utc::now() - chrono::Duration::seconds(30);
+        let id = db
+            .get_or_insert_audio_chunk("mic.mp4", recorded_at, Some("aac 64k"))
+            .await
+            .unwrap();
+        // a later segment of the same file doesn't move the chunk
+        let again = db
+            .get_or_insert_audio_chunk("mic.mp4", Utc::now(), Some("aac 64k"))
+            .await
+            .unwrap();
+        assert_eq!(again, id);
+
+        let chunks = db
+            .get_audio_chunks_to_recompress("opus 24k", "ogg", Utc::now(), 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].timestamp, recorded_at);
+    }
+
     #[tokio::test]
     async fn test_update_and_search_audio() {
         let db = setup_test_db().await;
@@ -743,6 +1214,174 @@ mod tests {
         assert_eq!(speaker.unwrap().id, 1);
     }
 
+    #[tokio::test]
+    async fn test_enroll_and_match_speaker() {
+        let db = setup_test_db().await;
+        let voice = |axis: usize, other: usize, mix: f32| {
+            let mut embedding = vec![0.0; 512];
+            embedding[axis] = 1.0;
+            embedding[other] = mix;
+            embedding
+        };
+
+        // an unknown voice is left unmatched
+        assert!(db
+            .match_speaker(&voice(0, 1, 0.0), 0.5)
+            .await
+            .unwrap()
+            .is_none());
+
+        let alice = db
+            .enroll_speaker(None, Some("alice"), &[voice(0, 1, 0.2), voice(0, 2, 0.2)])
+            .await
+            .unwrap();
+        assert_eq!(alice.name, "alice");
+        let bob = db.insert_speaker(&voice(3, 4, 0.0)).await.unwrap();
+
+        let speaker_match = db
+            .match_speaker(&voice(0, 1, 0.1), 0.5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(speaker_match.speaker.id, alice.id);
+        assert!(speaker_match.distance < 0.05);
+        // an enrolled speaker wins over a slightly closer one only recognised in recordings
+        let alice_again = db.insert_speaker(&voice(0, 1, 0.1)).await.unwrap();
+        assert_eq!(
+            db.match_speaker(&voice(0, 1, 0.1), 0.5)
+                .await
+                .unwrap()
+                .unwrap()
+                .speaker
+                .id,
+            alice.id
+        );
+        db.delete_speaker(alice_again.id).await.unwrap();
+        // but not over one that is clearly closer
+        let carol = db.insert_speaker(&voice(0, 1, 0.6)).await.unwrap();
+        let speaker_match = db
+            .match_speaker(&voice(0, 1, 0.6), 0.5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(speaker_match.speaker.id, carol.id);
+        db.delete_speaker(carol.id).await.unwrap();
+        // enrolling a speaker that doesn't exist leaves no embeddings behind
+        let embeddings_before: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM speaker_embeddings")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            db.enroll_speaker(Some(9999), None, &[voice(5, 6, 0.2)])
+                .await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        let embeddings_after: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM speaker_embeddings")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(embeddings_before, embeddings_after);
+        // the threshold decides how far a voice may be from a known speaker
+        assert!(db
+            .match_speaker(&voice(0, 3, 0.9), 0.1)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            db.match_speaker(&voice(0, 3, 0.9), 0.5)
+                .await
+                .unwrap()
+                .unwrap()
+                .speaker
+                .id,
+            alice.id
+        );
+
+        // merged speakers are recognised by the voices of both
+        db.merge_speakers(alice.id, bob.id).await.unwrap();
+        let speaker_match = db
+            .match_speaker(&voice(3, 4, 0.1), 0.5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(speaker_match.speaker.id, alice.id);
+        let enrolled: bool = sqlx::query_scalar("SELECT enrolled FROM speakers WHERE id = ?1")
+            .bind(alice.id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert!(enrolled);
+        let centroid: Option<Vec<u8>> =
+            sqlx::query_scalar("SELECT centroid FROM speakers WHERE id = ?1")
+                .bind(alice.id)
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(centroid.unwrap().len(), 512 * 4);
+    }
+
+    #[tokio::test]
+    async fn test_get_speaker_centroids() {
+        let db = setup_test_db().await;
+        let voice = |axis: usize| {
+            let mut embedding = vec![0.0; 512];
+            embedding[axis] = 2.0;
+            embedding
+        };
+
+        let alice = db
+            .enroll_speaker(None, Some("alice"), &[voice(0), voice(1)])
+            .await
+            .unwrap();
+        let bob = db.insert_speaker(&voice(2)).await.unwrap();
+        let noise = db.insert_speaker(&voice(3)).await.unwrap();
+        db.mark_speaker_as_hallucination(noise.id).await.unwrap();
+
+        let centroids = db.get_speaker_centroids().await.unwrap();
+        assert_eq!(centroids.len(), 2);
+        assert_eq!(centroids[0].speaker.id, alice.id);
+        assert_eq!(centroids[0].speaker.name, "alice");
+        assert!(centroids[0].enrolled);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((centroids[0].centroid[0] - half).abs() < 1e-6);
+        assert!((centroids[0].centroid[1] - half).abs() < 1e-6);
+        assert_eq!(centroids[1].speaker.id, bob.id);
+        assert!(!centroids[1].enrolled);
+        assert_eq!(centroids[1].centroid[2], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_get_speaker_audio_clips() {
+        let db = setup_test_db().await;
+        let audio_chunk_id = db.insert_audio_chunk("speaker_audio.mp4").await.unwrap();
+        let transcription_id = db
+            .insert_audio_transcription(
+                audio_chunk_id,
+                "hi, it's alice",
+                0,
+                "",
+                &AudioDevice {
+                    name: "test".to_string(),
+                    device_type: DeviceType::Input,
+                },
+                None,
+                Some(2.0),
+                Some(6.5),
+            )
+            .await
+            .unwrap();
+
+        let clips = db
+            .get_speaker_audio_clips(&[transcription_id, transcription_id + 1])
+            .await
+            .unwrap();
+        assert_eq!(clips.len(), 1);
+        assert_eq!(clips[0].audio_transcription_id, transcription_id);
+        assert_eq!(clips[0].file_path, "speaker_audio.mp4");
+        assert_eq!(clips[0].start_time, Some(2.0));
+        assert_eq!(clips[0].end_time, Some(6.5));
+    }
+
     #[tokio::test]
     async fn test_update_speaker_metadata() {
         let db = setup_test_db().await;
@@ -1445,4 +2084,796 @@ mod tests {
             .unwrap();
         assert_eq!(count, 0, "Should count zero results for non-matching query");
     }
+
+    #[tokio::test]
+    async fn test_delete_data_before() {
+        let db = setup_test_db().await;
+        let old = Utc::now() - chrono::Duration::days(30);
+
+        // old frame in its own chunk, recent frame in another chunk
+        db.insert_video_chunk("old_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let old_frame_id = db
+            .insert_frame(
+                "test_device",
+                Some(old),
+                None,
+                Some("test"),
+                Some(""),
+                false,
+            )
+            .await
+            .unwrap();
+        db.insert_ocr_text(
+            old_frame_id,
+            "old secret",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+        db.add_tags(
+            old_frame_id,
+            TagContentType::Vision,
+            vec!["old".to_string()],
+        )
+        .await
+        .unwrap();
+
+        db.insert_video_chunk("new_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let new_frame_id = db
+            .insert_frame("test_device", None, None, Some("test"), Some(""), false)
+            .await
+            .unwrap();
+        db.insert_ocr_text(
+            new_frame_id,
+            "new secret",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+
+        let deleted = db
+            .delete_frames_before(Utc::now() - chrono::Duration::days(7))
+            .await
+            .unwrap();
+        assert_eq!(deleted.frames, 1);
+        assert_eq!(deleted.ocr_text, 1);
+        assert_eq!(deleted.video_files, vec!["old_video.mp4".to_string()]);
+
+        let results = db
+            .search(
+                "secret",
+                ContentType::OCR,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        if let SearchResult::OCR(ocr_result) = &results[0] {
+            assert_eq!(ocr_result.frame_id, new_frame_id);
+        } else {
+            panic!("Expected OCR result");
+        }
+
+        let fts_rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ocr_text_fts")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(fts_rows, 1);
+        let tag_rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM vision_tags")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(tag_rows, 0);
+
+        // audio chunks are stamped with the insertion time, so use a cutoff in the future
+        let audio_chunk_id = db.insert_audio_chunk("old_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(
+            audio_chunk_id,
+            "old conversation",
+            0,
+            "",
+            &AudioDevice {
+                name: "test".to_string(),
+                device_type: DeviceType::Input,
+            },
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let deleted = db
+            .delete_audio_before(Utc::now() + chrono::Duration::minutes(1))
+            .await
+            .unwrap();
+        assert_eq!(deleted.audio_transcriptions, 1);
+        assert_eq!(deleted.audio_files, vec!["old_audio.mp4".to_string()]);
+
+        let audio_fts_rows: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM audio_transcriptions_fts")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(audio_fts_rows, 0);
+
+        sqlx::query(
+            "INSERT INTO ui_monitoring (text_output, timestamp, app, window) VALUES (?, ?, ?, ?)",
+        )
+        .bind("old ui text")
+        .bind(old)
+        .bind("test_app")
+        .bind("test_window")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let deleted = db
+            .delete_ui_monitoring_before(Utc::now() - chrono::Duration::days(7))
+            .await
+            .unwrap();
+        assert_eq!(deleted.ui_monitoring, 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_time_range() {
+        let db = setup_test_db().await;
+        let now = Utc::now();
+        let start = now - chrono::Duration::hours(2);
+        let end = now - chrono::Duration::minutes(30);
+
+        // chunk recorded entirely inside the range
+        db.insert_video_chunk("inside_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let inside_frame_id = db
+            .insert_frame(
+                "test_device",
+                Some(now - chrono::Duration::minutes(90)),
+                None,
+                Some("test"),
+                Some(""),
+                false,
+            )
+            .await
+            .unwrap();
+        db.insert_ocr_text(
+            inside_frame_id,
+            "password manager",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+
+        // chunk that straddles the range: only its middle frame is deleted
+        db.insert_video_chunk("partial_video.mp4", "test_device")
+            .await
+            .unwrap();
+        for (timestamp, text) in [
+            (now - chrono::Duration::hours(3), "before range"),
+            (now - chrono::Duration::hours(1), "password manager"),
+            (now, "after range"),
+        ] {
+            let frame_id = db
+                .insert_frame(
+                    "test_device",
+                    Some(timestamp),
+                    None,
+                    Some("test"),
+                    Some(""),
+                    false,
+                )
+                .await
+                .unwrap();
+            db.insert_ocr_text(frame_id, text, "", Arc::new(OcrEngine::Tesseract))
+                .await
+                .unwrap();
+        }
+
+        // one audio chunk transcribed inside the range, one outside
+        let device = AudioDevice {
+            name: "test".to_string(),
+            device_type: DeviceType::Input,
+        };
+        let inside_audio_id = db.insert_audio_chunk("inside_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(
+            inside_audio_id,
+            "hr conversation",
+            0,
+            "",
+            &device,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        sqlx::query("UPDATE audio_transcriptions SET timestamp = ?1 WHERE audio_chunk_id = ?2")
+            .bind(now - chrono::Duration::hours(1))
+            .bind(inside_audio_id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let outside_audio_id = db.insert_audio_chunk("outside_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(
+            outside_audio_id,
+            "standup",
+            0,
+            "",
+            &device,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        for timestamp in [now - chrono::Duration::hours(1), now] {
+            sqlx::query(
+                "INSERT INTO ui_monitoring (text_output, timestamp, app, window) VALUES (?, ?, ?, ?)",
+            )
+            .bind("ui text")
+            .bind(timestamp)
+            .bind("test_app")
+            .bind("test_window")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        }
+
+        let deleted = db.delete_time_range(start, end).await.unwrap();
+        assert_eq!(deleted.frames, 2);
+        assert_eq!(deleted.ocr_text, 2);
+        assert_eq!(deleted.video_files, vec!["inside_video.mp4".to_string()]);
+        assert_eq!(deleted.partial_video_files.len(), 1);
+        assert_eq!(
+            deleted.partial_video_files[0].file_path,
+            "partial_video.mp4".to_string()
+        );
+        assert_eq!(deleted.partial_video_files[0].offset_indexes, vec![1]);
+        assert_eq!(deleted.audio_transcriptions, 1);
+        assert_eq!(deleted.audio_files, vec!["inside_audio.mp4".to_string()]);
+        assert_eq!(deleted.ui_monitoring, 1);
+
+        let results = db
+            .search(
+                "password",
+                ContentType::All,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+
+        let remaining_frames: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM frames")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining_frames, 2);
+        let ocr_fts_rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ocr_text_fts")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(ocr_fts_rows, 2);
+        let audio_fts_rows: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM audio_transcriptions_fts")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(audio_fts_rows, 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_time_range_across_batches() {
+        let db = setup_test_db().await;
+        let now = Utc::now();
+        let inside = now - chrono::Duration::hours(1);
+
+        // more frames than one delete batch holds, so both chunks span several batches
+        let insert_frames =
+            |chunk_id: i64, offsets: std::ops::Range<i64>, timestamp: chrono::DateTime<Utc>| {
+                sqlx::query(
+                    r#"
+                WITH RECURSIVE n(i) AS (SELECT ?2 UNION ALL SELECT i + 1 FROM n WHERE i + 1 < ?3)
+                INSERT INTO frames (video_chunk_id, offset_index, timestamp)
+                SELECT ?1, i, ?4 FROM n
+                "#,
+                )
+                .bind(chunk_id)
+                .bind(offsets.start)
+                .bind(offsets.end)
+                .bind(timestamp)
+                .execute(&db.pool)
+            };
+        let orphaned_chunk = db
+            .insert_video_chunk("orphaned_video.mp4", "test_device")
+            .await
+            .unwrap();
+        insert_frames(orphaned_chunk, 0..1500, inside)
+            .await
+            .unwrap();
+        let partial_chunk = db
+            .insert_video_chunk("partial_video.mp4", "test_device")
+            .await
+            .unwrap();
+        insert_frames(partial_chunk, 0..1000, inside).await.unwrap();
+        insert_frames(partial_chunk, 1000..1001, now).await.unwrap();
+
+        let deleted = db
+            .delete_time_range(
+                now - chrono::Duration::hours(2),
+                now - chrono::Duration::minutes(30),
+            )
+            .await
+            .unwrap();
+        assert_eq!(deleted.frames, 2500);
+        assert_eq!(deleted.video_files, vec!["orphaned_video.mp4".to_string()]);
+        assert_eq!(deleted.partial_video_files.len(), 1);
+        assert_eq!(
+            deleted.partial_video_files[0].file_path,
+            "partial_video.mp4"
+        );
+        assert_eq!(
+            deleted.partial_video_files[0].offset_indexes,
+            (0..1000).collect::<Vec<i64>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_content_without_embeddings() {
+        let db = setup_test_db().await;
+        let device = AudioDevice {
+            name: "test".to_string(),
+            device_type: DeviceType::Input,
+        };
+
+        let _ = db
+            .insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let frame_id = db
+            .insert_frame("test_device", None, None, None, None, false)
+            .await
+            .unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "quarterly report",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+        let empty_frame_id = db
+            .insert_frame("test_device", None, None, None, None, false)
+            .await
+            .unwrap();
+        db.insert_ocr_text(empty_frame_id, "  ", "", Arc::new(OcrEngine::Tesseract))
+            .await
+            .unwrap();
+
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        let transcription_id = db
+            .insert_audio_transcription(
+                audio_chunk_id,
+                "let's ship it",
+                0,
+                "",
+                &device,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        // rows with only whitespace are never embedded
+        let ocr = db
+            .get_ocr_text_without_embeddings("model-a", 10, &[])
+            .await
+            .unwrap();
+        assert_eq!(ocr, vec![(frame_id, "quarterly report".to_string())]);
+        let audio = db
+            .get_audio_transcriptions_without_embeddings("model-a", 10, &[])
+            .await
+            .unwrap();
+        assert_eq!(audio, vec![(transcription_id, "let's ship it".to_string())]);
+
+        // skipped rows are left out
+        assert!(db
+            .get_ocr_text_without_embeddings("model-a", 10, &[frame_id])
+            .await
+            .unwrap()
+            .is_empty());
+
+        db.insert_ocr_text_embeddings("model-a", &[(frame_id, vec![0.1; 768])])
+            .await
+            .unwrap();
+        db.insert_audio_transcription_embeddings("model-a", &[(transcription_id, vec![0.2; 768])])
+            .await
+            .unwrap();
+        assert!(db
+            .get_ocr_text_without_embeddings("model-a", 10, &[])
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db
+            .get_audio_transcriptions_without_embeddings("model-a", 10, &[])
+            .await
+            .unwrap()
+            .is_empty());
+
+        // embeddings of another model are redone, replacing the old ones
+        assert_eq!(
+            db.get_ocr_text_without_embeddings("model-b", 10, &[])
+                .await
+                .unwrap(),
+            vec![(frame_id, "quarterly report".to_string())]
+        );
+        db.insert_ocr_text_embeddings("model-b", &[(frame_id, vec![0.3; 768])])
+            .await
+            .unwrap();
+        let models: Vec<String> =
+            sqlx::query_scalar("SELECT model FROM ocr_text_embeddings WHERE frame_id = ?1")
+                .bind(frame_id)
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(models, vec!["model-b".to_string()]);
+
+        // embeddings go away with their transcription
+        sqlx::query("DELETE FROM audio_transcriptions WHERE id = ?1")
+            .bind(transcription_id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let (audio_embeddings,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM audio_transcription_embeddings")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(audio_embeddings, 0);
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search() {
+        let db = setup_test_db().await;
+        let device = AudioDevice {
+            name: "test".to_string(),
+            device_type: DeviceType::Input,
+        };
+
+        let _ = db
+            .insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let mut frame_ids = Vec::new();
+        for text in ["budget spreadsheet", "lunch plans", "budget meeting notes"] {
+            let frame_id = db
+                .insert_frame("test_device", None, None, None, None, false)
+                .await
+                .unwrap();
+            db.insert_ocr_text(frame_id, text, "", Arc::new(OcrEngine::Tesseract))
+                .await
+                .unwrap();
+            frame_ids.push(frame_id);
+        }
+        // the last frame has no embedding yet and can only be found by keyword
+        db.insert_ocr_text_embeddings(
+            "model-a",
+            &[
+                (frame_ids[0], vec![1.0, 0.0, 0.0]),
+                (frame_ids[1], vec![0.0, 1.0, 0.0]),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        let transcription_id = db
+            .insert_audio_transcription(
+                audio_chunk_id,
+                "how much money is left",
+                0,
+                "",
+                &device,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        db.insert_audio_transcription_embeddings(
+            "model-a",
+            &[(transcription_id, vec![0.9, 0.1, 0.0])],
+        )
+        .await
+        .unwrap();
+
+        let search = |content_type: ContentType, offset: u32| {
+            db.search_hybrid(
+                "budget",
+                "model-a",
+                &[1.0, 0.0, 0.0],
+                0.5,
+                content_type,
+                10,
+                offset,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+        };
+
+        let (results, total) = search(ContentType::All, 0).await.unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(results.len(), 3);
+
+        // matched by keyword and meaning ranks first, the unrelated frame is left out
+        match &results[0].0 {
+            SearchResult::OCR(ocr) => assert_eq!(ocr.frame_id, frame_ids[0]),
+            _ => panic!("expected OCR result"),
+        }
+        assert!(results.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert!(results.iter().any(|(result, _)| matches!(
+            result,
+            SearchResult::OCR(ocr) if ocr.frame_id == frame_ids[2]
+        )));
+        assert!(results
+            .iter()
+            .any(|(result, _)| matches!(result, SearchResult::Audio(_))));
+        assert!(!results.iter().any(|(result, _)| matches!(
+            result,
+            SearchResult::OCR(ocr) if ocr.frame_id == frame_ids[1]
+        )));
+
+        let (results, _) = search(ContentType::Audio, 0).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0].0, SearchResult::Audio(_)));
+
+        let (results, total) = search(ContentType::All, 2).await.unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(results.len(), 1);
+
+        // embeddings of another model are not compared with the query
+        let (results, _) = db
+            .search_hybrid(
+                "money",
+                "model-b",
+                &[1.0, 0.0, 0.0],
+                0.5,
+                ContentType::Audio,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let (results, _) = db
+            .search_hybrid(
+                "dollars",
+                "model-b",
+                &[1.0, 0.0, 0.0],
+                0.5,
+                ContentType::Audio,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_matches_segments_of_vector_results() {
+        let db = setup_test_db().await;
+        let device = AudioDevice {
+            name: "test".to_string(),
+            device_type: DeviceType::Input,
+        };
+
+        let mut transcription_ids = Vec::new();
+        for (name, text) in [("a.mp4", "money"), ("b.mp4", "how much money is left")] {
+            let audio_chunk_id = db.insert_audio_chunk(name).await.unwrap();
+            let transcription_id = db
+                .insert_audio_transcription(
+                    audio_chunk_id,
+                    text,
+                    0,
+                    "",
+                    &device,
+                    None,
+                    Some(0.0),
+                    Some(2.0),
+                )
+                .await
+                .unwrap();
+            db.insert_transcription_segments(
+                transcription_id,
+                &[TranscriptionSegment {
+                    start_time: 0.0,
+                    end_time: 2.0,
+                    text: text.to_string(),
+                    words: Vec::new(),
+                }],
+            )
+            .await
+            .unwrap();
+            transcription_ids.push(transcription_id);
+        }
+        sqlx::query("UPDATE audio_transcriptions SET timestamp = ?1 WHERE id = ?2")
+            .bind(Utc::now() - chrono::Duration::hours(1))
+            .bind(transcription_ids[0])
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        // only the second transcription is close in meaning
+        db.insert_audio_transcription_embeddings(
+            "model-a",
+            &[
+                (transcription_ids[0], vec![0.0, 1.0, 0.0]),
+                (transcription_ids[1], vec![1.0, 0.0, 0.0]),
+            ],
+        )
+        .await
+        .unwrap();
+
+        // keyword search ranks the first one higher, so the second only comes from vector search
+        let (results, _) = db
+            .search_hybrid(
+                "money",
+                "model-a",
+                &[1.0, 0.0, 0.0],
+                0.5,
+                ContentType::Audio,
+                1,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        match &results[0].0 {
+            SearchResult::Audio(audio) => {
+                assert_eq!(audio.audio_transcription_id, transcription_ids[1]);
+                assert_eq!(
+                    audio
+                        .matched_segment
+                        .as_ref()
+                        .map(|span| span.text.as_str()),
+                    Some("money")
+                );
+            }
+            _ => panic!("expected audio result"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_raw_sql() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video_file.mp4", "test_device")
+            .await
+            .unwrap();
+        for _ in 0..3 {
+            db.insert_frame("test_device", None, None, Some("code"), None, false)
+                .await
+                .unwrap();
+        }
+
+        let options = RawSqlOptions {
+            max_rows: 2,
+            timeout: std::time::Duration::from_secs(5),
+            allow_writes: false,
+        };
+        let result = db
+            .query_raw_sql(
+                "SELECT id, app_name, 1.5 AS ratio FROM frames WHERE app_name = ? ORDER BY id",
+                &[serde_json::json!("code")],
+                &options,
+            )
+            .await
+            .unwrap();
+        assert!(result.truncated);
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(result.rows[0]["app_name"], "code");
+        let types: Vec<_> = result
+            .columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.type_name.as_str()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![("id", "INTEGER"), ("app_name", "TEXT"), ("ratio", "REAL")]
+        );
+
+        // statements that modify the database fail on the read-only connection
+        assert!(db
+            .query_raw_sql("DELETE FROM frames", &[], &options)
+            .await
+            .is_err());
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM frames")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+
+        // long running statements are interrupted
+        let options = RawSqlOptions {
+            timeout: std::time::Duration::from_millis(100),
+            ..options
+        };
+        let started = std::time::Instant::now();
+        assert!(db
+            .query_raw_sql(
+                "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT COUNT(*) FROM n",
+                &[],
+                &options,
+            )
+            .await
+            .is_err());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
 }
@@ -26,7 +26,7 @@ use walkdir::WalkDir;
 
 use crate::{
     cli::CliOcrEngine,
-    text_embeds::generate_embedding,
+    text_embeds::{embedding_backend, generate_embedding},
     video_utils::{extract_frames_from_video, get_video_metadata, VideoMetadataOverrides},
 };
 
@@ -205,7 +205,10 @@ pub async fn handle_index_command(
                     Ok(emb) => {
                         debug!("generated embedding for frame {}", frame_ids[idx]);
                         if let Err(e) = db
-                            .insert_embeddings(frame_ids[idx], serde_json::to_string(&emb)?)
+                            .insert_ocr_text_embeddings(
+                                embedding_backend().model_name(),
+                                &[(frame_ids[idx], emb)],
+                            )
                             .await
                         {
                             error!("error batch inserting embeddings: {}", e);
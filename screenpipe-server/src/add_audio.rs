@@ -0,0 +1,291 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use screenpipe_audio::{
+    audio_manager::AudioManagerOptions, transcription::file_transcriber::FileTranscriber,
+};
+use screenpipe_core::encryption::{encrypt_to_file, media_encryption_key};
+use screenpipe_db::{AudioDevice, DatabaseManager, DeviceType};
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tracing::{error, info};
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+use crate::{cli::OutputFormat, video_utils::get_video_metadata};
+
+const AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "m4a", "aac", "flac", "ogg"];
+
+#[derive(Debug, Deserialize)]
+pub struct AudioMetadataOverrides {
+    pub overrides: Vec<AudioMetadataItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AudioMetadataItem {
+    pub file_path: String, // Direct file path
+    pub metadata: AudioMetadataOverride,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AudioMetadataOverride {
+    /// When the recording started
+    pub creation_time: Option<DateTime<Utc>>,
+    pub device_name: Option<String>,
+}
+
+/// Transcribes the audio files found under `path` and stores them as audio chunks, so recorded
+/// calls, voice memos and podcasts become searchable next to the screen history. A file is
+/// dated by its metadata, its name or its creation time unless `metadata_override` says
+/// otherwise. Files without speech are skipped.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_audio_index_command(
+    screenpipe_dir: PathBuf,
+    path: String,
+    pattern: Option<String>,
+    db: Arc<DatabaseManager>,
+    output_format: OutputFormat,
+    options: AudioManagerOptions,
+    device_name: String,
+    metadata_override: Option<PathBuf>,
+    copy_files: bool,
+) -> Result<()> {
+    let metadata_overrides = if let Some(path) = metadata_override {
+        let content = fs::read_to_string(path).await?;
+        Some(serde_json::from_str::<AudioMetadataOverrides>(&content)?)
+    } else {
+        None
+    };
+
+    let audio_files = find_audio_files(&path, pattern.as_deref())?;
+    info!("found {} audio files to process", audio_files.len());
+
+    if let Some(ref overrides) = metadata_overrides {
+        let unmatched_files: Vec<_> = audio_files
+            .iter()
+            .filter(|audio_path| {
+                let file_str = audio_path.to_string_lossy();
+                !overrides
+                    .overrides
+                    .iter()
+                    .any(|item| item.file_path == file_str)
+            })
+            .collect();
+        if !unmatched_files.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Missing metadata overrides for files: {:?}",
+                unmatched_files
+            ));
+        }
+    }
+
+    let engine = options.transcription_engine.to_string();
+    let transcriber = FileTranscriber::new(options).await?;
+
+    let mut imported_files = 0;
+    let mut skipped_files = 0;
+    let mut failed_files = 0;
+    let mut total_transcriptions = 0;
+
+    if output_format == OutputFormat::Json {
+        println!("{{\"version\":1,\"stream\":[");
+    }
+
+    for audio_path in audio_files {
+        info!("processing audio: {}", audio_path.display());
+
+        let file_str = audio_path.to_string_lossy().to_string();
+        let override_item = metadata_overrides.as_ref().and_then(|overrides| {
+            overrides
+                .overrides
+                .iter()
+                .find(|item| item.file_path == file_str)
+                .map(|item| &item.metadata)
+        });
+        let recorded_at = match override_item.and_then(|item| item.creation_time) {
+            Some(creation_time) => creation_time,
+            None => match get_video_metadata(&file_str).await {
+                Ok(metadata) => metadata.creation_time,
+                Err(e) => {
+                    error!("failed to read metadata of {}: {}", file_str, e);
+                    failed_files += 1;
+                    continue;
+                }
+            },
+        };
+        let device = AudioDevice {
+            name: override_item
+                .and_then(|item| item.device_name.clone())
+                .unwrap_or_else(|| device_name.clone()),
+            device_type: DeviceType::Input,
+        };
+
+        // transcribe the original, the copy may be encrypted
+        let transcriptions = match transcriber
+            .transcribe(&db, &audio_path, &device.name, &[])
+            .await
+        {
+            Ok(transcriptions) => transcriptions,
+            Err(e) => {
+                error!("failed to transcribe {}: {}", file_str, e);
+                failed_files += 1;
+                continue;
+            }
+        };
+        if transcriptions.is_empty() {
+            info!("no speech found in {}, skipping", file_str);
+            skipped_files += 1;
+            continue;
+        }
+
+        let stored_path = if copy_files {
+            match copy_audio(&screenpipe_dir, &audio_path).await {
+                Ok(target_path) => target_path,
+                Err(e) => {
+                    error!("failed to copy {}: {}", file_str, e);
+                    failed_files += 1;
+                    continue;
+                }
+            }
+        } else {
+            audio_path.clone()
+        };
+
+        let audio_chunk_id = match db
+            .insert_imported_audio(
+                &stored_path.to_string_lossy(),
+                recorded_at,
+                &device,
+                &engine,
+                &transcriptions,
+            )
+            .await
+        {
+            Ok(audio_chunk_id) => audio_chunk_id,
+            Err(e) => {
+                error!("failed to store {}: {}", file_str, e);
+                if copy_files {
+                    let _ = fs::remove_file(&stored_path).await;
+                }
+                failed_files += 1;
+                continue;
+            }
+        };
+
+        match output_format {
+            OutputFormat::Json => {
+                if imported_files > 0 {
+                    print!(",");
+                }
+                print!(
+                    "{}",
+                    serde_json::to_string(&json!({
+                        "type": "audio",
+                        "data": {
+                            "audio_chunk_id": audio_chunk_id,
+                            "file_path": file_str,
+                            "timestamp": recorded_at,
+                            "device_name": device.name,
+                            "transcriptions": transcriptions.len(),
+                        }
+                    }))?
+                );
+            }
+            OutputFormat::Text => {
+                info!(
+                    "imported {} as audio chunk {} with {} transcriptions",
+                    file_str,
+                    audio_chunk_id,
+                    transcriptions.len()
+                );
+            }
+        }
+
+        imported_files += 1;
+        total_transcriptions += transcriptions.len();
+    }
+
+    match output_format {
+        OutputFormat::Json => {
+            if imported_files > 0 {
+                print!(",");
+            }
+            print!(
+                "{}",
+                serde_json::to_string(&json!({
+                    "type": "summary",
+                    "data": {
+                        "imported_files": imported_files,
+                        "skipped_files": skipped_files,
+                        "failed_files": failed_files,
+                        "total_transcriptions": total_transcriptions
+                    }
+                }))?
+            );
+            println!("]}}");
+        }
+        OutputFormat::Text => {
+            info!(
+                "imported {} audio files with {} transcriptions, {} without speech, {} failed",
+                imported_files, total_transcriptions, skipped_files, failed_files
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies an audio file into the data directory under a new name, encrypted if media
+/// encryption is on.
+async fn copy_audio(screenpipe_dir: &Path, audio_path: &Path) -> Result<PathBuf> {
+    let ext = audio_path.extension().unwrap_or_default();
+    let new_filename = format!("{}.{}", Uuid::new_v4(), ext.to_string_lossy());
+    let target_path = screenpipe_dir.join("data").join(new_filename);
+
+    info!("copying audio to: {}", target_path.display());
+    let copied: Result<()> = async {
+        match media_encryption_key() {
+            Some(key) => {
+                let source = fs::File::open(audio_path).await?;
+                encrypt_to_file(key, source, &target_path.to_string_lossy()).await?;
+            }
+            None => {
+                fs::copy(audio_path, &target_path).await?;
+            }
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = copied {
+        // don't leave a partial copy behind
+        let _ = fs::remove_file(&target_path).await;
+        return Err(e);
+    }
+    Ok(target_path)
+}
+
+fn find_audio_files(root: &str, pattern: Option<&str>) -> Result<Vec<PathBuf>> {
+    let regex = pattern.map(Regex::new).transpose()?;
+
+    let audio_files = WalkDir::new(root)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.as_str()))
+        })
+        .filter(|path| match regex {
+            Some(ref regex) => regex.is_match(&path.to_string_lossy()),
+            None => true,
+        })
+        .collect();
+
+    Ok(audio_files)
+}
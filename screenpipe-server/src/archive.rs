@@ -0,0 +1,588 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use screenpipe_core::encryption::{media_encryption_key, read_media_file, seal_media};
+use screenpipe_db::{
+    DataImport, DatabaseManager, ExportedAudioChunk, ExportedAudioTranscription, ExportedFrame,
+    ExportedOcrText, ExportedSpeaker, ExportedTag, ExportedTranscriptionSegment,
+    ExportedUiMonitoring, ExportedVideoChunk, ImportedContent,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter, Lines};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Bumped when the layout changes in a way older versions can't import.
+const ARCHIVE_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+const MEDIA_DIR: &str = "media";
+const ZSTD_LEVEL: i32 = 3;
+/// Entries waiting to be written to an archive, so reading chunks stays ahead of compressing
+/// them without holding many in memory
+const ARCHIVE_QUEUE: usize = 4;
+
+const VIDEO_CHUNKS_FILE: &str = "video_chunks.jsonl";
+const FRAMES_FILE: &str = "frames.jsonl";
+const OCR_TEXT_FILE: &str = "ocr_text.jsonl";
+const AUDIO_CHUNKS_FILE: &str = "audio_chunks.jsonl";
+const AUDIO_TRANSCRIPTIONS_FILE: &str = "audio_transcriptions.jsonl";
+/// Not in archives written before segments were exported.
+const TRANSCRIPTION_SEGMENTS_FILE: &str = "transcription_segments.jsonl";
+const SPEAKERS_FILE: &str = "speakers.jsonl";
+const TAGS_FILE: &str = "tags.jsonl";
+const UI_MONITORING_FILE: &str = "ui_monitoring.jsonl";
+
+/// Describes an archive, stored next to one JSONL file per table and the chunks in `media/`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Time covered by the chunks in `media/`. They are exported whole, so this can start
+    /// before `start` and end after `end`
+    #[serde(default)]
+    pub media_start: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub media_end: Option<DateTime<Utc>>,
+    pub counts: ArchiveCounts,
+}
+
+/// Rows and chunks in an archive.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ArchiveCounts {
+    pub video_chunks: usize,
+    pub frames: usize,
+    pub ocr_text: usize,
+    pub audio_chunks: usize,
+    pub audio_transcriptions: usize,
+    #[serde(default)]
+    pub transcription_segments: usize,
+    pub speakers: usize,
+    pub tags: usize,
+    pub ui_monitoring: usize,
+    pub media_files: usize,
+}
+
+/// What exporting a time range did.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ExportReport {
+    pub archive: String,
+    pub archive_bytes: u64,
+    pub counts: ArchiveCounts,
+    /// Referenced chunks that no longer exist in the data directory.
+    pub media_files_missing: usize,
+    /// Time covered by the exported chunks, see [`ArchiveManifest::media_start`].
+    pub media_start: Option<DateTime<Utc>>,
+    pub media_end: Option<DateTime<Utc>>,
+    pub duration_ms: u64,
+}
+
+/// What importing an archive did.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ImportReport {
+    pub imported: ImportedContent,
+    pub media_files_written: usize,
+    pub duration_ms: u64,
+}
+
+/// Writes everything recorded in `[start, end)` to a `.tar.zst` archive at `out`: one JSONL file
+/// per table, the video and audio chunks the rows reference and a manifest. Chunks are copied
+/// whole, not trimmed to the range, and the manifest records the time they actually cover.
+///
+/// The archive is not encrypted, so that it can be imported on another machine. With
+/// encryption at rest on, exporting is refused unless `decrypt` is set, and decrypted chunks go
+/// straight into the archive rather than through a staging directory.
+pub async fn export_archive(
+    db: &DatabaseManager,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    out: &Path,
+    decrypt: bool,
+) -> Result<ExportReport> {
+    if start >= end {
+        return Err(anyhow!("start must be before end"));
+    }
+    if media_encryption_key().is_some() {
+        if !decrypt {
+            return Err(anyhow!(
+                "data is encrypted at rest and the archive would hold it decrypted, pass --decrypt to export anyway"
+            ));
+        }
+        warn!(
+            "exporting decrypted data to {:?}, the archive is not encrypted",
+            out
+        );
+    }
+    let started = Instant::now();
+    let staging = tempfile::tempdir()?;
+
+    let mut report = ExportReport {
+        archive: out.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+
+    if let Some(parent) = out.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let partial = PathBuf::from(format!("{}.partial", out.display()));
+    let (sender, receiver) = mpsc::channel(ARCHIVE_QUEUE);
+    let archive = partial.clone();
+    let packer = tokio::task::spawn_blocking(move || pack(receiver, &archive));
+
+    let written = write_archive(db, start, end, staging.path(), &sender, &mut report).await;
+    drop(sender);
+    // an error of the packer explains why sending to it failed, so it comes first
+    if let Err(e) = packer.await?.and(written) {
+        if let Err(e) = tokio::fs::remove_file(&partial).await {
+            debug!("failed to remove incomplete archive {:?}: {}", partial, e);
+        }
+        return Err(e);
+    }
+    tokio::fs::rename(&partial, out).await?;
+
+    report.archive_bytes = tokio::fs::metadata(out).await?.len();
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    info!(
+        "exported {} frames and {} audio transcriptions from {} to {} to {:?} ({:.2} MB) in {}ms",
+        report.counts.frames,
+        report.counts.audio_transcriptions,
+        start,
+        end,
+        out,
+        report.archive_bytes as f64 / (1024.0 * 1024.0),
+        report.duration_ms
+    );
+
+    Ok(report)
+}
+
+/// Sends the chunks of `[start, end)` to the archive as they are read, then the tables, written
+/// to JSONL files in `dir`, and the manifest.
+async fn write_archive(
+    db: &DatabaseManager,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    dir: &Path,
+    archive: &mpsc::Sender<ArchiveEntry>,
+    report: &mut ExportReport,
+) -> Result<()> {
+    let mut media_names = HashSet::new();
+
+    let mut writer = JsonlWriter::create(&dir.join(SPEAKERS_FILE)).await?;
+    let mut rows = db.export_speakers(start, end);
+    while let Some(row) = rows.next().await {
+        writer.write::<ExportedSpeaker>(&row?).await?;
+    }
+    report.counts.speakers = writer.finish().await?;
+
+    let mut writer = JsonlWriter::create(&dir.join(VIDEO_CHUNKS_FILE)).await?;
+    let mut rows = db.export_video_chunks(start, end);
+    while let Some(row) = rows.next().await {
+        let chunk: ExportedVideoChunk = row?;
+        export_media(&chunk.file_path, archive, &mut media_names, report).await?;
+        writer.write(&chunk).await?;
+    }
+    report.counts.video_chunks = writer.finish().await?;
+
+    let mut writer = JsonlWriter::create(&dir.join(FRAMES_FILE)).await?;
+    let mut rows = db.export_frames(start, end);
+    while let Some(row) = rows.next().await {
+        writer.write::<ExportedFrame>(&row?).await?;
+    }
+    report.counts.frames = writer.finish().await?;
+
+    let mut writer = JsonlWriter::create(&dir.join(OCR_TEXT_FILE)).await?;
+    let mut rows = db.export_ocr_text(start, end);
+    while let Some(row) = rows.next().await {
+        writer.write::<ExportedOcrText>(&row?).await?;
+    }
+    report.counts.ocr_text = writer.finish().await?;
+
+    let mut writer = JsonlWriter::create(&dir.join(AUDIO_CHUNKS_FILE)).await?;
+    let mut rows = db.export_audio_chunks(start, end);
+    while let Some(row) = rows.next().await {
+        let chunk: ExportedAudioChunk = row?;
+        export_media(&chunk.file_path, archive, &mut media_names, report).await?;
+        writer.write(&chunk).await?;
+    }
+    report.counts.audio_chunks = writer.finish().await?;
+
+    let mut writer = JsonlWriter::create(&dir.join(AUDIO_TRANSCRIPTIONS_FILE)).await?;
+    let mut rows = db.export_audio_transcriptions(start, end);
+    while let Some(row) = rows.next().await {
+        writer.write::<ExportedAudioTranscription>(&row?).await?;
+    }
+    report.counts.audio_transcriptions = writer.finish().await?;
+
+    let mut writer = JsonlWriter::create(&dir.join(TRANSCRIPTION_SEGMENTS_FILE)).await?;
+    let mut rows = db.export_transcription_segments(start, end);
+    while let Some(row) = rows.next().await {
+        writer.write::<ExportedTranscriptionSegment>(&row?).await?;
+    }
+    report.counts.transcription_segments = writer.finish().await?;
+
+    let mut writer = JsonlWriter::create(&dir.join(TAGS_FILE)).await?;
+    let mut rows = db.export_tags(start, end);
+    while let Some(row) = rows.next().await {
+        writer.write::<ExportedTag>(&row?).await?;
+    }
+    report.counts.tags = writer.finish().await?;
+
+    let mut writer = JsonlWriter::create(&dir.join(UI_MONITORING_FILE)).await?;
+    let mut rows = db.export_ui_monitoring(start, end);
+    while let Some(row) = rows.next().await {
+        writer.write::<ExportedUiMonitoring>(&row?).await?;
+    }
+    report.counts.ui_monitoring = writer.finish().await?;
+
+    report.counts.media_files = media_names.len();
+    if let Some((media_start, media_end)) = db.export_media_range(start, end).await? {
+        report.media_start = Some(media_start);
+        report.media_end = Some(media_end);
+    }
+    let manifest = ArchiveManifest {
+        version: ARCHIVE_VERSION,
+        exported_at: Utc::now(),
+        start,
+        end,
+        media_start: report.media_start,
+        media_end: report.media_end,
+        counts: report.counts.clone(),
+    };
+    tokio::fs::write(
+        dir.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )
+    .await?;
+
+    for name in [
+        SPEAKERS_FILE,
+        VIDEO_CHUNKS_FILE,
+        FRAMES_FILE,
+        OCR_TEXT_FILE,
+        AUDIO_CHUNKS_FILE,
+        AUDIO_TRANSCRIPTIONS_FILE,
+        TRANSCRIPTION_SEGMENTS_FILE,
+        TAGS_FILE,
+        UI_MONITORING_FILE,
+        MANIFEST_FILE,
+    ] {
+        send_entry(
+            archive,
+            ArchiveEntry::File {
+                name: name.to_string(),
+                path: dir.join(name),
+            },
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Imports an archive written by [`export_archive`] into `db`, placing its chunks in
+/// `data_dir`. Ids are remapped, and chunks and rows that are already in the database are
+/// skipped, so importing an archive twice or into the database it came from adds nothing.
+/// Rows are committed in batches so that recording goes on meanwhile, an import that fails
+/// keeps what was committed and importing the archive again adds the rest. Chunks are
+/// encrypted if media encryption is enabled.
+pub async fn import_archive(
+    db: &DatabaseManager,
+    archive: &Path,
+    data_dir: &Path,
+) -> Result<ImportReport> {
+    let started = Instant::now();
+    let staging = tempfile::tempdir()?;
+    let dir = staging.path().to_path_buf();
+    let (source, target) = (archive.to_path_buf(), dir.clone());
+    tokio::task::spawn_blocking(move || unpack(&source, &target)).await??;
+
+    let manifest: ArchiveManifest = match tokio::fs::read(dir.join(MANIFEST_FILE)).await {
+        Ok(bytes) => serde_json::from_slice(&bytes)?,
+        Err(e) => return Err(anyhow!("{:?} is not a screenpipe archive: {}", archive, e)),
+    };
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(anyhow!(
+            "archive version {} is newer than this version of screenpipe supports ({})",
+            manifest.version,
+            ARCHIVE_VERSION
+        ));
+    }
+
+    let media_dir = data_dir.join("data");
+    tokio::fs::create_dir_all(&media_dir).await?;
+
+    let mut import = db.begin_import().await?;
+    let mut written = Vec::new();
+    let result = match import_rows(&mut import, &dir, &media_dir, &mut written).await {
+        Ok(()) => {
+            let committed = import.committed_batches();
+            import
+                .commit()
+                .await
+                .map_err(|e| (anyhow::Error::from(e), committed))
+        }
+        Err(e) => Err((e, import.committed_batches())),
+    };
+    match result {
+        Ok(imported) => {
+            let report = ImportReport {
+                imported,
+                media_files_written: written.len(),
+                duration_ms: started.elapsed().as_millis() as u64,
+            };
+            info!(
+                "imported {} frames and {} audio transcriptions from {:?} ({} rows skipped) in {}ms",
+                report.imported.frames,
+                report.imported.audio_transcriptions,
+                archive,
+                report.imported.skipped,
+                report.duration_ms
+            );
+            Ok(report)
+        }
+        Err((e, committed)) => {
+            // the rows of the last batch were rolled back, so the chunks written for them are
+            // unreferenced
+            for (_, path) in written.into_iter().filter(|(batch, _)| *batch >= committed) {
+                if let Err(e) = tokio::fs::remove_file(&path).await {
+                    warn!("failed to remove imported media file {:?}: {}", path, e);
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Adds the archive's rows, parents first, and writes the chunks of newly inserted chunk rows
+/// into `media_dir`. Written chunks are listed with the batch their row was added in.
+async fn import_rows(
+    import: &mut DataImport<'_>,
+    dir: &Path,
+    media_dir: &Path,
+    written: &mut Vec<(usize, PathBuf)>,
+) -> Result<()> {
+    let mut rows = open_jsonl(&dir.join(SPEAKERS_FILE)).await?;
+    while let Some(speaker) = next_row::<ExportedSpeaker>(&mut rows).await? {
+        import.add_speaker(&speaker).await?;
+    }
+
+    let mut rows = open_jsonl(&dir.join(VIDEO_CHUNKS_FILE)).await?;
+    while let Some(chunk) = next_row::<ExportedVideoChunk>(&mut rows).await? {
+        let (name, file_path) = media_destination(&chunk.file_path, media_dir)?;
+        if import
+            .add_video_chunk(&chunk, &file_path.to_string_lossy())
+            .await?
+        {
+            import_media(dir, &name, &file_path, import.committed_batches(), written).await?;
+        }
+    }
+
+    let mut rows = open_jsonl(&dir.join(FRAMES_FILE)).await?;
+    while let Some(frame) = next_row::<ExportedFrame>(&mut rows).await? {
+        import.add_frame(&frame).await?;
+    }
+
+    let mut rows = open_jsonl(&dir.join(OCR_TEXT_FILE)).await?;
+    while let Some(ocr) = next_row::<ExportedOcrText>(&mut rows).await? {
+        import.add_ocr_text(&ocr).await?;
+    }
+
+    let mut rows = open_jsonl(&dir.join(AUDIO_CHUNKS_FILE)).await?;
+    while let Some(chunk) = next_row::<ExportedAudioChunk>(&mut rows).await? {
+        let (name, file_path) = media_destination(&chunk.file_path, media_dir)?;
+        if import
+            .add_audio_chunk(&chunk, &file_path.to_string_lossy())
+            .await?
+        {
+            import_media(dir, &name, &file_path, import.committed_batches(), written).await?;
+        }
+    }
+
+    let mut rows = open_jsonl(&dir.join(AUDIO_TRANSCRIPTIONS_FILE)).await?;
+    while let Some(transcription) = next_row::<ExportedAudioTranscription>(&mut rows).await? {
+        import.add_audio_transcription(&transcription).await?;
+    }
+
+    let segments_path = dir.join(TRANSCRIPTION_SEGMENTS_FILE);
+    if tokio::fs::try_exists(&segments_path).await? {
+        let mut rows = open_jsonl(&segments_path).await?;
+        while let Some(segment) = next_row::<ExportedTranscriptionSegment>(&mut rows).await? {
+            import.add_transcription_segment(&segment).await?;
+        }
+    }
+
+    let mut rows = open_jsonl(&dir.join(TAGS_FILE)).await?;
+    while let Some(tag) = next_row::<ExportedTag>(&mut rows).await? {
+        import.add_tag(&tag).await?;
+    }
+
+    let mut rows = open_jsonl(&dir.join(UI_MONITORING_FILE)).await?;
+    while let Some(ui) = next_row::<ExportedUiMonitoring>(&mut rows).await? {
+        import.add_ui_monitoring(&ui).await?;
+    }
+
+    Ok(())
+}
+
+/// Sends a chunk to the archive, decrypted.
+async fn export_media(
+    path: &str,
+    archive: &mpsc::Sender<ArchiveEntry>,
+    media_names: &mut HashSet<String>,
+    report: &mut ExportReport,
+) -> Result<()> {
+    // chunk file names carry the device and the time they were recorded, so they are unique
+    let Some(name) = Path::new(path).file_name() else {
+        return Ok(());
+    };
+    let name = name.to_string_lossy().to_string();
+    if media_names.contains(&name) {
+        return Ok(());
+    }
+
+    match read_media_file(path).await {
+        Ok(data) => {
+            send_entry(
+                archive,
+                ArchiveEntry::Data {
+                    name: format!("{}/{}", MEDIA_DIR, name),
+                    data,
+                },
+            )
+            .await?;
+            media_names.insert(name);
+        }
+        Err(e) => {
+            debug!("media file not exported: {}: {}", path, e);
+            report.media_files_missing += 1;
+        }
+    }
+    Ok(())
+}
+
+async fn send_entry(archive: &mpsc::Sender<ArchiveEntry>, entry: ArchiveEntry) -> Result<()> {
+    archive
+        .send(entry)
+        .await
+        .map_err(|_| anyhow!("the archive writer stopped"))
+}
+
+/// Writes a chunk from the archive to `file_path` unless a file is already there.
+async fn import_media(
+    dir: &Path,
+    name: &str,
+    file_path: &Path,
+    batch: usize,
+    written: &mut Vec<(usize, PathBuf)>,
+) -> Result<()> {
+    let source = dir.join(MEDIA_DIR).join(name);
+    if !tokio::fs::try_exists(&source).await? {
+        debug!("archive has no media for {}, importing its rows only", name);
+        return Ok(());
+    }
+    if tokio::fs::try_exists(file_path).await? {
+        return Ok(());
+    }
+
+    let data = seal_media(tokio::fs::read(&source).await?)?;
+    let partial = PathBuf::from(format!("{}.partial", file_path.display()));
+    tokio::fs::write(&partial, data).await?;
+    tokio::fs::rename(&partial, file_path).await?;
+    written.push((batch, file_path.to_path_buf()));
+    Ok(())
+}
+
+/// The chunk's file name and where it goes in the data directory.
+fn media_destination(path: &str, media_dir: &Path) -> Result<(String, PathBuf)> {
+    let name = path
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .ok_or_else(|| anyhow!("invalid media path in archive: {}", path))?;
+    Ok((name.to_string(), media_dir.join(name)))
+}
+
+struct JsonlWriter {
+    writer: BufWriter<File>,
+    rows: usize,
+}
+
+impl JsonlWriter {
+    async fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path).await?),
+            rows: 0,
+        })
+    }
+
+    async fn write<T: Serialize>(&mut self, row: &T) -> Result<()> {
+        let mut line = serde_json::to_vec(row)?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<usize> {
+        self.writer.flush().await?;
+        Ok(self.rows)
+    }
+}
+
+async fn open_jsonl(path: &Path) -> Result<Lines<BufReader<File>>> {
+    let file = File::open(path)
+        .await
+        .map_err(|e| anyhow!("archive is missing {:?}: {}", path.file_name(), e))?;
+    Ok(BufReader::new(file).lines())
+}
+
+async fn next_row<T: DeserializeOwned>(lines: &mut Lines<BufReader<File>>) -> Result<Option<T>> {
+    while let Some(line) = lines.next_line().await? {
+        if !line.trim().is_empty() {
+            return Ok(Some(serde_json::from_str(&line)?));
+        }
+    }
+    Ok(None)
+}
+
+/// A file of the archive, by its path in the archive.
+enum ArchiveEntry {
+    Data { name: String, data: Vec<u8> },
+    File { name: String, path: PathBuf },
+}
+
+/// Writes the entries received until the sender is dropped to a `.tar.zst` archive.
+fn pack(mut entries: mpsc::Receiver<ArchiveEntry>, archive: &Path) -> Result<()> {
+    let file = std::fs::File::create(archive)?;
+    let encoder = zstd::Encoder::new(file, ZSTD_LEVEL)?;
+    let mut builder = tar::Builder::new(encoder);
+    let mtime = Utc::now().timestamp().max(0) as u64;
+    while let Some(entry) = entries.blocking_recv() {
+        match entry {
+            ArchiveEntry::Data { name, data } => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(mtime);
+                builder.append_data(&mut header, name, data.as_slice())?;
+            }
+            ArchiveEntry::File { name, path } => builder.append_path_with_name(path, name)?,
+        }
+    }
+    builder.into_inner()?.finish()?.sync_all()?;
+    Ok(())
+}
+
+fn unpack(archive: &Path, target: &Path) -> Result<()> {
+    let file = std::fs::File::open(archive)?;
+    let decoder = zstd::Decoder::new(file)?;
+    // entries that would land outside `target` are skipped by `unpack`
+    tar::Archive::new(decoder).unpack(target)?;
+    Ok(())
+}
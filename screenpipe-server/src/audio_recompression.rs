@@ -0,0 +1,168 @@
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use screenpipe_audio::core::encoding::{recompress_audio_file, AudioEncoding};
+use screenpipe_db::DatabaseManager;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// Chunks fetched from the database at a time
+const BATCH_SIZE: i64 = 50;
+/// Chunks younger than this may still be in use by the transcription pipeline
+const MIN_CHUNK_AGE_MINUTES: i64 = 10;
+
+/// What a single recompression pass converted.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RecompressionReport {
+    pub chunks_converted: usize,
+    pub chunks_failed: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub duration_ms: u128,
+}
+
+/// Re-encodes the stored audio chunks that aren't in `encoding` yet, e.g. after switching
+/// from the default aac to opus or lowering the bitrate. Chunks recorded before their encoding
+/// was tracked are only re-encoded to another codec. The database is pointed at the new file
+/// before the old one is deleted. Only files inside `data_dir` are touched, chunks imported in place
+/// from elsewhere are left alone.
+///
+/// A chunk that fails is added to `failed` and skipped by later passes, instead of being
+/// retried forever.
+pub async fn run_recompression_pass(
+    db: &DatabaseManager,
+    data_dir: &Path,
+    encoding: AudioEncoding,
+    failed: &mut HashSet<i64>,
+) -> Result<RecompressionReport> {
+    let start = Instant::now();
+    let mut report = RecompressionReport::default();
+    let before = Utc::now() - ChronoDuration::minutes(MIN_CHUNK_AGE_MINUTES);
+    let mut after_id = 0;
+
+    loop {
+        let chunks = db
+            .get_audio_chunks_to_recompress(
+                &encoding.to_string(),
+                encoding.codec.extension(),
+                before,
+                after_id,
+                BATCH_SIZE,
+            )
+            .await?;
+        let Some(last) = chunks.last() else {
+            break;
+        };
+        after_id = last.id;
+
+        for chunk in chunks {
+            let path = PathBuf::from(&chunk.file_path);
+            if !path.starts_with(data_dir) || failed.contains(&chunk.id) {
+                continue;
+            }
+            let size_before = match tokio::fs::metadata(&path).await {
+                Ok(metadata) => metadata.len(),
+                Err(e) => {
+                    debug!("skipping audio chunk {}: {}", chunk.file_path, e);
+                    continue;
+                }
+            };
+
+            let source = path.clone();
+            let new_path =
+                match tokio::task::spawn_blocking(move || recompress_audio_file(&source, encoding))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result)
+                {
+                    Ok(new_path) => new_path,
+                    Err(e) => {
+                        warn!(
+                            "failed to recompress audio chunk {}: {}",
+                            chunk.file_path, e
+                        );
+                        failed.insert(chunk.id);
+                        report.chunks_failed += 1;
+                        continue;
+                    }
+                };
+
+            if let Err(e) = db
+                .update_audio_chunk_file(
+                    chunk.id,
+                    &new_path.to_string_lossy(),
+                    &encoding.to_string(),
+                )
+                .await
+            {
+                warn!(
+                    "failed to point audio chunk {} at its recompressed file: {}",
+                    chunk.id, e
+                );
+                // the original file is still the one referenced, unless it was replaced in place
+                if new_path != path {
+                    let _ = tokio::fs::remove_file(&new_path).await;
+                }
+                failed.insert(chunk.id);
+                report.chunks_failed += 1;
+                continue;
+            }
+            // a chunk re-encoded at another bitrate was replaced in place
+            if new_path != path {
+                if let Err(e) = tokio::fs::remove_file(&path).await {
+                    warn!(
+                        "failed to remove recompressed audio chunk {:?}: {}",
+                        path, e
+                    );
+                }
+            }
+
+            report.chunks_converted += 1;
+            report.bytes_before += size_before;
+            report.bytes_after += tokio::fs::metadata(&new_path)
+                .await
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+        }
+    }
+
+    report.duration_ms = start.elapsed().as_millis();
+    Ok(report)
+}
+
+pub fn start_recompression_task(
+    db: Arc<DatabaseManager>,
+    data_dir: PathBuf,
+    encoding: AudioEncoding,
+    interval: Duration,
+) -> JoinHandle<()> {
+    info!("starting audio recompression task ({})", encoding);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        let mut failed = HashSet::new();
+        loop {
+            ticker.tick().await;
+
+            match run_recompression_pass(&db, &data_dir, encoding, &mut failed).await {
+                Ok(report) => {
+                    if report.chunks_converted > 0 || report.chunks_failed > 0 {
+                        info!(
+                            "recompressed {} audio chunks ({} failed), {:.2} MB to {:.2} MB in {}ms",
+                            report.chunks_converted,
+                            report.chunks_failed,
+                            report.bytes_before as f64 / (1024.0 * 1024.0),
+                            report.bytes_after as f64 / (1024.0 * 1024.0),
+                            report.duration_ms
+                        );
+                    }
+                }
+                Err(e) => error!("audio recompression pass failed: {}", e),
+            }
+        }
+    })
+}
@@ -0,0 +1,453 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use screenpipe_db::DatabaseManager;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+const SNAPSHOTS_DIR: &str = "snapshots";
+const MEDIA_DIR: &str = "media";
+const MANIFEST_FILE: &str = "manifest.json";
+const DATABASE_FILE: &str = "db.sqlite";
+
+/// Describes one snapshot in a backup directory. Written last, so a snapshot without a manifest
+/// is incomplete and ignored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub database_bytes: u64,
+    pub database_sha256: String,
+    pub media: Vec<BackupMediaFile>,
+}
+
+/// A video or audio chunk referenced by a snapshot. Chunks are stored once in the backup's
+/// media directory and shared by all snapshots that reference them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupMediaFile {
+    /// Path the database refers to, the file is restored there.
+    pub path: String,
+    /// File name in the backup's media directory.
+    pub name: String,
+    pub bytes: u64,
+}
+
+/// What creating a snapshot did.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct BackupReport {
+    pub snapshot_id: String,
+    pub database_bytes: u64,
+    pub media_files: usize,
+    pub media_files_copied: usize,
+    pub media_bytes_copied: u64,
+    /// Referenced chunks that no longer exist in the data directory.
+    pub media_files_missing: usize,
+    pub snapshots_pruned: usize,
+    pub duration_ms: u64,
+}
+
+/// What restoring a snapshot did.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RestoreReport {
+    pub snapshot_id: String,
+    /// Where the database that was replaced was moved to.
+    pub previous_database: Option<String>,
+    pub media_files_restored: usize,
+    pub media_files_unchanged: usize,
+    pub duration_ms: u64,
+}
+
+/// Creates a snapshot of the database and copies chunks that are not in the backup yet into
+/// `target`. The database copy is consistent even while screenpipe is recording. If `keep` is
+/// set, older snapshots are pruned so that at most `keep` remain.
+pub async fn create_backup(
+    db: &DatabaseManager,
+    db_key: Option<&str>,
+    target: &Path,
+    keep: Option<usize>,
+) -> Result<BackupReport> {
+    let started = Instant::now();
+    let created_at = Utc::now();
+    let id = created_at.format("%Y%m%dT%H%M%S%.3fZ").to_string();
+
+    let snapshot_dir = target.join(SNAPSHOTS_DIR).join(&id);
+    if tokio::fs::try_exists(&snapshot_dir).await? {
+        return Err(anyhow!("snapshot {} already exists", id));
+    }
+    tokio::fs::create_dir_all(&snapshot_dir).await?;
+    tokio::fs::create_dir_all(target.join(MEDIA_DIR)).await?;
+
+    let mut report = match write_snapshot(db, db_key, target, &id, created_at).await {
+        Ok(report) => report,
+        Err(e) => {
+            if let Err(e) = tokio::fs::remove_dir_all(&snapshot_dir).await {
+                warn!("failed to remove incomplete snapshot {}: {}", id, e);
+            }
+            return Err(e);
+        }
+    };
+
+    if let Some(keep) = keep {
+        report.snapshots_pruned = prune_backups(target, keep).await?;
+    }
+
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    info!(
+        "created backup snapshot {} with {} media files ({} copied, {:.2} MB) in {}ms",
+        report.snapshot_id,
+        report.media_files,
+        report.media_files_copied,
+        report.media_bytes_copied as f64 / (1024.0 * 1024.0),
+        report.duration_ms
+    );
+
+    Ok(report)
+}
+
+/// Copies the database and new chunks, then writes the manifest that marks the snapshot as
+/// complete.
+async fn write_snapshot(
+    db: &DatabaseManager,
+    db_key: Option<&str>,
+    target: &Path,
+    id: &str,
+    created_at: DateTime<Utc>,
+) -> Result<BackupReport> {
+    let snapshot_dir = target.join(SNAPSHOTS_DIR).join(id);
+    let database_path = snapshot_dir.join(DATABASE_FILE);
+    db.backup_into(&database_path.to_string_lossy()).await?;
+
+    // list chunks from the snapshot itself, so the manifest matches what was backed up
+    let snapshot_db = open_database(&database_path, db_key).await?;
+    let problems = snapshot_db.integrity_check().await?;
+    let media_paths = snapshot_db.list_media_files().await?;
+    snapshot_db.pool.close().await;
+    if !problems.is_empty() {
+        return Err(anyhow!(
+            "snapshot {} failed the integrity check: {}",
+            id,
+            problems.join(", ")
+        ));
+    }
+
+    let mut report = BackupReport {
+        snapshot_id: id.to_string(),
+        ..Default::default()
+    };
+    let mut media = Vec::new();
+    for path in media_paths {
+        let Some(name) = Path::new(&path).file_name() else {
+            continue;
+        };
+        // chunk file names carry the device and the time they were recorded, so they are unique
+        let name = name.to_string_lossy().to_string();
+
+        let bytes = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("media file no longer exists, not backed up: {}", path);
+                report.media_files_missing += 1;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // chunks that were still being recorded during the last backup have grown since
+        let backup_path = target.join(MEDIA_DIR).join(&name);
+        let backed_up = matches!(
+            tokio::fs::metadata(&backup_path).await,
+            Ok(metadata) if metadata.len() == bytes
+        );
+        if !backed_up {
+            copy_atomically(Path::new(&path), &backup_path).await?;
+            report.media_files_copied += 1;
+            report.media_bytes_copied += bytes;
+        }
+
+        media.push(BackupMediaFile { path, name, bytes });
+    }
+    report.media_files = media.len();
+
+    let manifest = BackupManifest {
+        id: id.to_string(),
+        created_at,
+        database_bytes: tokio::fs::metadata(&database_path).await?.len(),
+        database_sha256: sha256_file(&database_path).await?,
+        media,
+    };
+    report.database_bytes = manifest.database_bytes;
+    tokio::fs::write(
+        snapshot_dir.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )
+    .await?;
+
+    Ok(report)
+}
+
+/// Complete snapshots in `target`, oldest first.
+pub async fn list_backups(target: &Path) -> Result<Vec<BackupManifest>> {
+    let snapshots_dir = target.join(SNAPSHOTS_DIR);
+    if !tokio::fs::try_exists(&snapshots_dir).await? {
+        return Ok(Vec::new());
+    }
+
+    let mut manifests = Vec::new();
+    let mut entries = tokio::fs::read_dir(&snapshots_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let manifest_path = entry.path().join(MANIFEST_FILE);
+        match tokio::fs::read(&manifest_path).await {
+            Ok(bytes) => match serde_json::from_slice::<BackupManifest>(&bytes) {
+                Ok(manifest) => manifests.push(manifest),
+                Err(e) => warn!("ignoring unreadable manifest {:?}: {}", manifest_path, e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("ignoring incomplete snapshot {:?}", entry.path());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    manifests.sort_by_key(|manifest| manifest.created_at);
+    Ok(manifests)
+}
+
+/// Picks the snapshot to restore: the one named `id`, else the latest one taken at or before
+/// `at`, else the latest one.
+pub fn find_backup(
+    manifests: &[BackupManifest],
+    id: Option<&str>,
+    at: Option<DateTime<Utc>>,
+) -> Option<BackupManifest> {
+    match (id, at) {
+        (Some(id), _) => manifests.iter().find(|manifest| manifest.id == id),
+        (None, Some(at)) => manifests
+            .iter()
+            .rev()
+            .find(|manifest| manifest.created_at <= at),
+        (None, None) => manifests.last(),
+    }
+    .cloned()
+}
+
+/// Restores a snapshot into `data_dir`. Fails if screenpipe is running, it would keep writing to
+/// the database being replaced. The current database is moved aside rather than deleted, chunks
+/// referenced by the snapshot are copied back where they are missing or differ, and newer chunks
+/// are left in place. Fails if the restored database or media don't match the manifest.
+pub async fn restore_backup(
+    target: &Path,
+    manifest: &BackupManifest,
+    data_dir: &Path,
+    db_key: Option<&str>,
+) -> Result<RestoreReport> {
+    let started = Instant::now();
+    if let Some(pid) = running_screenpipe() {
+        return Err(anyhow!(
+            "screenpipe is running (pid {}), stop it before restoring a backup",
+            pid
+        ));
+    }
+    let snapshot_dir = target.join(SNAPSHOTS_DIR).join(&manifest.id);
+    let snapshot_database = snapshot_dir.join(DATABASE_FILE);
+
+    // don't replace a working database with a damaged copy
+    let sha256 = sha256_file(&snapshot_database).await?;
+    if sha256 != manifest.database_sha256 {
+        return Err(anyhow!(
+            "database of snapshot {} does not match its manifest, the backup is damaged",
+            manifest.id
+        ));
+    }
+    for file in &manifest.media {
+        let backup_path = target.join(MEDIA_DIR).join(&file.name);
+        let bytes = tokio::fs::metadata(&backup_path)
+            .await
+            .map_err(|e| anyhow!("media file {} is missing from the backup: {}", file.name, e))?
+            .len();
+        if bytes < file.bytes {
+            return Err(anyhow!(
+                "media file {} in the backup is smaller than when snapshot {} was taken",
+                file.name,
+                manifest.id
+            ));
+        }
+    }
+
+    let mut report = RestoreReport {
+        snapshot_id: manifest.id.clone(),
+        ..Default::default()
+    };
+
+    tokio::fs::create_dir_all(data_dir).await?;
+    let database_path = data_dir.join(DATABASE_FILE);
+    if tokio::fs::try_exists(&database_path).await? {
+        let suffix = format!("before-restore-{}", Utc::now().format("%Y%m%dT%H%M%SZ"));
+        for extension in ["", "-wal", "-shm"] {
+            let path = PathBuf::from(format!("{}{}", database_path.display(), extension));
+            if tokio::fs::try_exists(&path).await? {
+                let moved = PathBuf::from(format!("{}.{}", path.display(), suffix));
+                tokio::fs::rename(&path, &moved).await?;
+                if extension.is_empty() {
+                    report.previous_database = Some(moved.to_string_lossy().to_string());
+                }
+            }
+        }
+    }
+    copy_atomically(&snapshot_database, &database_path).await?;
+
+    for file in &manifest.media {
+        // a chunk that was still being recorded when the snapshot was taken may have grown since
+        let restored = matches!(
+            tokio::fs::metadata(&file.path).await,
+            Ok(metadata) if metadata.len() >= file.bytes
+        );
+        if restored {
+            report.media_files_unchanged += 1;
+            continue;
+        }
+        if let Some(parent) = Path::new(&file.path).parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        copy_atomically(
+            &target.join(MEDIA_DIR).join(&file.name),
+            Path::new(&file.path),
+        )
+        .await?;
+        report.media_files_restored += 1;
+    }
+
+    verify_restore(&database_path, manifest, db_key).await?;
+
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    info!(
+        "restored backup snapshot {} ({} media files restored, {} unchanged)",
+        report.snapshot_id, report.media_files_restored, report.media_files_unchanged
+    );
+
+    Ok(report)
+}
+
+async fn verify_restore(
+    database_path: &Path,
+    manifest: &BackupManifest,
+    db_key: Option<&str>,
+) -> Result<()> {
+    // opening it with DatabaseManager would migrate it and switch its journal mode
+    let problems =
+        DatabaseManager::check_database_file(&database_path.to_string_lossy(), db_key).await?;
+    if !problems.is_empty() {
+        return Err(anyhow!(
+            "restored database failed the integrity check: {}",
+            problems.join(", ")
+        ));
+    }
+
+    for file in &manifest.media {
+        let bytes = tokio::fs::metadata(&file.path)
+            .await
+            .map_err(|e| anyhow!("restored media file {} is missing: {}", file.path, e))?
+            .len();
+        if bytes < file.bytes {
+            return Err(anyhow!("restored media file {} is incomplete", file.path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes all but the `keep` newest snapshots, along with the chunks only they referenced.
+async fn prune_backups(target: &Path, keep: usize) -> Result<usize> {
+    let manifests = list_backups(target).await?;
+    if manifests.len() <= keep {
+        return Ok(0);
+    }
+
+    let (pruned, kept) = manifests.split_at(manifests.len() - keep);
+    for manifest in pruned {
+        tokio::fs::remove_dir_all(target.join(SNAPSHOTS_DIR).join(&manifest.id)).await?;
+    }
+
+    let referenced: HashSet<&str> = kept
+        .iter()
+        .flat_map(|manifest| manifest.media.iter().map(|file| file.name.as_str()))
+        .collect();
+    let mut entries = tokio::fs::read_dir(target.join(MEDIA_DIR)).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !referenced.contains(name.as_str()) {
+            if let Err(e) = tokio::fs::remove_file(entry.path()).await {
+                warn!("failed to remove backed up media file {}: {}", name, e);
+            }
+        }
+    }
+
+    Ok(pruned.len())
+}
+
+/// Pid of another screenpipe process, if one is running.
+fn running_screenpipe() -> Option<u32> {
+    let own_pid = Pid::from_u32(std::process::id());
+    let mut sys = System::new();
+    sys.refresh_processes();
+    sys.processes()
+        .values()
+        // threads of this process may be listed too, with their process as parent
+        .filter(|process| process.pid() != own_pid && process.parent() != Some(own_pid))
+        .find(|process| matches!(process.name(), "screenpipe" | "screenpipe.exe"))
+        .map(|process| process.pid().as_u32())
+}
+
+async fn open_database(path: &Path, db_key: Option<&str>) -> Result<DatabaseManager> {
+    Ok(DatabaseManager::new_with_key(&path.to_string_lossy(), db_key).await?)
+}
+
+/// Copies through a temporary file, so an interrupted copy never looks complete.
+async fn copy_atomically(from: &Path, to: &Path) -> Result<()> {
+    let partial = PathBuf::from(format!("{}.partial", to.display()));
+    tokio::fs::copy(from, &partial).await?;
+    tokio::fs::rename(&partial, to).await?;
+    Ok(())
+}
+
+async fn sha256_file(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await?
+}
+
+/// Creates a snapshot in `target` every `interval`, keeping the `keep` newest, until the task is
+/// aborted.
+pub fn start_backup_task(
+    db: Arc<DatabaseManager>,
+    db_key: Option<String>,
+    target: PathBuf,
+    keep: usize,
+    interval: Duration,
+) -> JoinHandle<()> {
+    info!(
+        "starting backup task to {:?} every {:?}, keeping {} snapshots",
+        target, interval, keep
+    );
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+
+            if let Err(e) = create_backup(&db, db_key.as_deref(), &target, Some(keep)).await {
+                error!("backup failed: {}", e);
+            }
+        }
+    })
+}
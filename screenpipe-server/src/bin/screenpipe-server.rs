@@ -2,29 +2,50 @@ use clap::Parser;
 #[allow(unused_imports)]
 use colored::Colorize;
 use dirs::home_dir;
-use reqwest::Client;
 use futures::pin_mut;
 use port_check::is_local_ipv4_port_free;
 use screenpipe_audio::{
-    audio_manager::AudioManagerBuilder,
+    audio_manager::{AudioManagerBuilder, AudioManagerOptions},
     core::device::{
         default_input_device, default_output_device, list_audio_devices, parse_audio_device,
     },
+    core::encoding::AudioEncoding,
+    processing::AudioProcessingConfig,
+    speaker::clustering::recluster_speakers,
+    transcription::retranscription::{
+        create_retranscription_worker, RetranscriptionCommand, RetranscriptionConfig,
+        RetranscriptionStatus,
+    },
+};
+use screenpipe_core::{
+    encryption::{set_media_encryption_key, EncryptionKey},
+    find_ffmpeg_path,
 };
-use screenpipe_core::find_ffmpeg_path;
 use screenpipe_db::{
     create_migration_worker, DatabaseManager, MigrationCommand, MigrationConfig, MigrationStatus,
 };
 use screenpipe_server::{
     cli::{
-        AudioCommand, Cli, CliAudioTranscriptionEngine, CliOcrEngine, Command, MigrationSubCommand,
-        OutputFormat, PipeCommand, VisionCommand, McpCommand,
+        AudioCommand, BackupCommand, Cli, CliAudioTranscriptionEngine, CliOcrEngine, Command,
+        DataCommand, ENCRYPTION_UNAVAILABLE,
+        MigrationSubCommand, OutputFormat, PipeCommand, SpeakersCommand, VisionCommand, McpCommand,
     },
+    create_backup, delete_time_range, export_archive, find_backup, handle_audio_index_command,
     handle_index_command,
+    import_archive, list_backups, restore_backup,
+    mcp::{serve_stdio, McpBackend, McpServer},
     pipe_manager::PipeInfo,
-    start_continuous_recording, watch_pid, PipeManager, ResourceMonitor, SCServer,
+    start_backup_task, start_continuous_recording, start_embedding_task, start_recompression_task,
+    start_capture_rate_task, start_retention_task,
+    text_embeds::set_embedding_backend,
+    video_cache::FrameCache,
+    watch_pid, PipeManager, ResourceMonitor, RetentionConfig, SCServer,
+};
+use screenpipe_vision::{
+    capture_rate::{CaptureRate, CaptureRateConfig},
+    capture_screenshot_by_window::WindowPattern, frame_diff::FrameDiffConfig,
+    incremental_ocr::IncrementalOcrConfig, monitor::list_monitors, privacy::PrivacyFilter,
 };
-use screenpipe_vision::monitor::list_monitors;
 #[cfg(target_os = "macos")]
 use screenpipe_vision::run_ui;
 use serde_json::{json, Value};
@@ -39,9 +60,6 @@ use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{fmt, EnvFilter};
 use tracing_subscriber::{prelude::__tracing_subscriber_SubscriberExt, Layer};
-use serde::Deserialize;
-use std::path::Path;
-use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
 
 const DISPLAY: &str = r"
                                             _          
@@ -53,16 +71,6 @@ const DISPLAY: &str = r"
 
 ";
 
-// Add the struct definition with proper derive attributes
-#[derive(Deserialize, Debug)]
-struct GitHubContent {
-    name: String,
-    path: String,
-    download_url: Option<String>,
-    #[serde(rename = "type")]
-    content_type: String,
-}
-
 fn get_base_dir(custom_path: &Option<String>) -> anyhow::Result<PathBuf> {
     let default_path = home_dir()
         .ok_or_else(|| anyhow::anyhow!("failed to get home directory"))?
@@ -225,6 +233,10 @@ async fn main() -> anyhow::Result<()> {
             output: OutputFormat::Text,
             ..
         }) => true,
+        // the stdio mcp server owns stdout
+        Some(Command::Mcp {
+            subcommand: McpCommand::Serve { .. },
+        }) => false,
         _ => true,
     };
 
@@ -235,6 +247,18 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
+    // encryption at rest is opt-in, the same key protects the database and the recorded media
+    let encryption_key = EncryptionKey::load(cli.encryption_key_file.as_deref())?;
+    if encryption_key.is_some() && !cfg!(feature = "encryption") {
+        // the key came from SCREENPIPE_ENCRYPTION_KEY, the flag is rejected while parsing
+        return Err(anyhow::anyhow!(ENCRYPTION_UNAVAILABLE));
+    }
+    if let Some(key) = &encryption_key {
+        set_media_encryption_key(key.clone())?;
+    }
+    set_embedding_backend(cli.embedding_backend_config())?;
+    let db_key = encryption_key.as_ref().map(|key| key.to_hex());
+
     let pipe_manager = Arc::new(PipeManager::new(local_data_dir_clone.clone()));
     if let Some(ref command) = cli.command {
         match command {
@@ -317,10 +341,10 @@ async fn main() -> anyhow::Result<()> {
                 // Initialize the database
                 let local_data_dir = get_base_dir(data_dir)?;
                 let db = Arc::new(
-                    DatabaseManager::new(&format!(
-                        "{}/db.sqlite",
-                        local_data_dir.to_string_lossy()
-                    ))
+                    DatabaseManager::new_with_key(
+                        &format!("{}/db.sqlite", local_data_dir.to_string_lossy()),
+                        db_key.as_deref(),
+                    )
                     .await
                     .map_err(|e| {
                         error!("failed to initialize database: {:?}", e);
@@ -489,6 +513,135 @@ async fn main() -> anyhow::Result<()> {
 
                 return Ok(());
             }
+            Command::Retranscribe {
+                engine,
+                data_dir,
+                subcommand,
+                output,
+                batch_size,
+                chunk_delay_ms,
+                continue_on_error,
+            } => {
+                let local_data_dir = get_base_dir(data_dir)?;
+                let db = Arc::new(
+                    DatabaseManager::new_with_key(
+                        &format!("{}/db.sqlite", local_data_dir.to_string_lossy()),
+                        db_key.as_deref(),
+                    )
+                    .await
+                    .map_err(|e| {
+                        error!("failed to initialize database: {:?}", e);
+                        e
+                    })?,
+                );
+
+                let options = AudioManagerOptions {
+                    transcription_engine: Arc::new((*engine).into()),
+                    languages: cli.unique_languages().unwrap(),
+                    deepgram_api_key: cli.deepgram_api_key.clone(),
+                    openai_compatible_url: cli.openai_compatible_url.clone(),
+                    openai_compatible_model: cli.openai_compatible_model.clone(),
+                    openai_compatible_api_key: cli.openai_compatible_api_key.clone(),
+                    speaker_match_threshold: cli.speaker_match_threshold,
+                    ..Default::default()
+                };
+                let config =
+                    RetranscriptionConfig::new(*batch_size, *chunk_delay_ms, *continue_on_error);
+                let (cmd_tx, mut status_rx, worker_handle) =
+                    create_retranscription_worker(db, options, Some(config));
+
+                let cmd = match subcommand {
+                    Some(MigrationSubCommand::Start) => RetranscriptionCommand::Start,
+                    Some(MigrationSubCommand::Pause) => RetranscriptionCommand::Pause,
+                    Some(MigrationSubCommand::Stop) => RetranscriptionCommand::Stop,
+                    Some(MigrationSubCommand::Status) | None => RetranscriptionCommand::Status,
+                };
+                let track_progress = matches!(cmd, RetranscriptionCommand::Start);
+
+                if let Err(e) = cmd_tx.send(cmd).await {
+                    error!("failed to send command to re-transcription worker: {}", e);
+                    return Err(anyhow::anyhow!(
+                        "Failed to send command to re-transcription worker"
+                    ));
+                }
+
+                let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
+                while let Some(response) = status_rx.recv().await {
+                    match output {
+                        OutputFormat::Json => {
+                            println!("{}", serde_json::to_string_pretty(&response.status)?);
+                        }
+                        OutputFormat::Text => match &response.status {
+                            RetranscriptionStatus::Running {
+                                total_chunks,
+                                processed_chunks,
+                                failed_chunks,
+                                ..
+                            } => {
+                                info!(
+                                    "Re-transcribing audio chunks: {}/{} ({:.2}%), {} failed",
+                                    processed_chunks,
+                                    total_chunks,
+                                    if *total_chunks > 0 {
+                                        (*processed_chunks as f64 / *total_chunks as f64) * 100.0
+                                    } else {
+                                        0.0
+                                    },
+                                    failed_chunks
+                                );
+                            }
+                            RetranscriptionStatus::Completed {
+                                engine,
+                                total_chunks,
+                                failed_chunks,
+                                duration_secs,
+                            } => {
+                                info!(
+                                    "Re-transcription with {} completed: {} audio chunks in {} seconds, {} failed",
+                                    engine, total_chunks, duration_secs, failed_chunks
+                                );
+                            }
+                            RetranscriptionStatus::Failed {
+                                total_chunks,
+                                processed_chunks,
+                                error,
+                                ..
+                            } => {
+                                error!(
+                                    "Re-transcription failed: {}/{} audio chunks processed. Error: {}",
+                                    processed_chunks, total_chunks, error
+                                );
+                            }
+                            status => {
+                                info!("Re-transcription status: {:?}", status);
+                            }
+                        },
+                    }
+
+                    // keep polling until the job is over
+                    let finished = matches!(
+                        response.status,
+                        RetranscriptionStatus::Completed { .. }
+                            | RetranscriptionStatus::Failed { .. }
+                            | RetranscriptionStatus::Stopped { .. }
+                    );
+                    if !track_progress || finished {
+                        break;
+                    }
+                    interval.tick().await;
+                    if let Err(e) = cmd_tx.send(RetranscriptionCommand::Status).await {
+                        error!("failed to send status command: {}", e);
+                        break;
+                    }
+                }
+
+                drop(cmd_tx);
+                if let Err(e) = worker_handle.await {
+                    error!("error waiting for worker to finish: {}", e);
+                }
+
+                return Ok(());
+            }
             Command::Add {
                 path,
                 output,
@@ -517,10 +670,10 @@ async fn main() -> anyhow::Result<()> {
                 }
 
                 let db = Arc::new(
-                    DatabaseManager::new(&format!(
-                        "{}/db.sqlite",
-                        local_data_dir.to_string_lossy()
-                    ))
+                    DatabaseManager::new_with_key(
+                        &format!("{}/db.sqlite", local_data_dir.to_string_lossy()),
+                        db_key.as_deref(),
+                    )
                     .await
                     .map_err(|e| {
                         error!("failed to initialize database: {:?}", e);
@@ -541,10 +694,371 @@ async fn main() -> anyhow::Result<()> {
                 .await?;
                 return Ok(());
             }
+            Command::AddAudio {
+                path,
+                data_dir,
+                output,
+                pattern,
+                engine,
+                device_name,
+                metadata_override,
+                copy_files,
+            } => {
+                let local_data_dir = get_base_dir(data_dir)?;
+                let db = Arc::new(
+                    DatabaseManager::new_with_key(
+                        &format!("{}/db.sqlite", local_data_dir.to_string_lossy()),
+                        db_key.as_deref(),
+                    )
+                    .await
+                    .map_err(|e| {
+                        error!("failed to initialize database: {:?}", e);
+                        e
+                    })?,
+                );
+
+                let options = AudioManagerOptions {
+                    transcription_engine: Arc::new((*engine).into()),
+                    languages: cli.unique_languages().unwrap(),
+                    deepgram_api_key: cli.deepgram_api_key.clone(),
+                    openai_compatible_url: cli.openai_compatible_url.clone(),
+                    openai_compatible_model: cli.openai_compatible_model.clone(),
+                    openai_compatible_api_key: cli.openai_compatible_api_key.clone(),
+                    speaker_match_threshold: cli.speaker_match_threshold,
+                    ..Default::default()
+                };
+                handle_audio_index_command(
+                    local_data_dir,
+                    path.to_string(),
+                    pattern.clone(),
+                    db,
+                    output.clone(),
+                    options,
+                    device_name.clone(),
+                    metadata_override.clone(),
+                    *copy_files,
+                )
+                .await?;
+                return Ok(());
+            }
             Command::Mcp { subcommand } => {
                 handle_mcp_command(subcommand, &local_data_dir_clone).await?;
                 return Ok(());
             }
+            Command::Speakers { subcommand } => match subcommand {
+                SpeakersCommand::Recluster {
+                    threshold,
+                    dry_run,
+                    data_dir,
+                    output,
+                } => {
+                    let local_data_dir = get_base_dir(data_dir)?;
+                    let db = DatabaseManager::new_with_key(
+                        &format!("{}/db.sqlite", local_data_dir.to_string_lossy()),
+                        db_key.as_deref(),
+                    )
+                    .await
+                    .map_err(|e| {
+                        error!("failed to initialize database: {:?}", e);
+                        e
+                    })?;
+
+                    let merge_sets = recluster_speakers(&db, *threshold, *dry_run).await?;
+                    match output {
+                        OutputFormat::Json => println!(
+                            "{}",
+                            serde_json::to_string_pretty(&json!({
+                                "data": merge_sets,
+                                "applied": !dry_run,
+                                "success": true
+                            }))?
+                        ),
+                        OutputFormat::Text => {
+                            if merge_sets.is_empty() {
+                                println!("no speakers to merge");
+                            }
+                            for merge_set in &merge_sets {
+                                println!(
+                                    "speaker {}: {} speakers {:?}",
+                                    merge_set.speaker_to_keep_id,
+                                    if *dry_run { "would merge" } else { "merged" },
+                                    merge_set.speaker_ids_to_merge
+                                );
+                            }
+                        }
+                    }
+                    return Ok(());
+                }
+            },
+            Command::Data { subcommand } => match subcommand {
+                DataCommand::Delete {
+                    start,
+                    end,
+                    data_dir,
+                    output,
+                } => {
+                    if start >= end {
+                        return Err(anyhow::anyhow!("--start must be before --end"));
+                    }
+
+                    let local_data_dir = get_base_dir(data_dir)?;
+                    let db = Arc::new(
+                        DatabaseManager::new_with_key(
+                            &format!("{}/db.sqlite", local_data_dir.to_string_lossy()),
+                            db_key.as_deref(),
+                        )
+                        .await
+                        .map_err(|e| {
+                            error!("failed to initialize database: {:?}", e);
+                            e
+                        })?,
+                    );
+
+                    // the frame cache keeps extracted frames on disk, so clear the range there too
+                    let frame_cache = match FrameCache::new(local_data_dir.join("data"), db.clone()).await {
+                        Ok(cache) => Some(cache),
+                        Err(e) => {
+                            warn!("failed to open frame cache, cached frames are kept: {}", e);
+                            None
+                        }
+                    };
+
+                    let report = delete_time_range(&db, frame_cache.as_ref(), *start, *end).await?;
+                    match output {
+                        OutputFormat::Json => println!(
+                            "{}",
+                            serde_json::to_string_pretty(&json!({
+                                "data": report,
+                                "success": true
+                            }))?
+                        ),
+                        OutputFormat::Text => {
+                            println!("deleted data between {} and {}:", start, end);
+                            println!("  frames: {}", report.frames_deleted);
+                            println!("  ocr texts: {}", report.ocr_text_deleted);
+                            println!("  audio transcriptions: {}", report.audio_transcriptions_deleted);
+                            println!("  ui entries: {}", report.ui_monitoring_deleted);
+                            println!(
+                                "  video files: {} removed, {} redacted",
+                                report.video_files_deleted, report.video_files_redacted
+                            );
+                            println!("  audio files: {} removed", report.audio_files_deleted);
+                            for file in &report.failed_video_files {
+                                println!("  failed to redact {}, run the command again once it is no longer recording", file);
+                            }
+                        }
+                    }
+                    return Ok(());
+                }
+            },
+            Command::Backup { subcommand } => match subcommand {
+                BackupCommand::Create {
+                    target,
+                    keep,
+                    data_dir,
+                    output,
+                } => {
+                    let local_data_dir = get_base_dir(data_dir)?;
+                    let db = DatabaseManager::new_with_key(
+                        &format!("{}/db.sqlite", local_data_dir.to_string_lossy()),
+                        db_key.as_deref(),
+                    )
+                    .await
+                    .map_err(|e| {
+                        error!("failed to initialize database: {:?}", e);
+                        e
+                    })?;
+
+                    let report = create_backup(&db, db_key.as_deref(), target, *keep).await?;
+                    match output {
+                        OutputFormat::Json => println!(
+                            "{}",
+                            serde_json::to_string_pretty(&json!({
+                                "data": report,
+                                "success": true
+                            }))?
+                        ),
+                        OutputFormat::Text => {
+                            println!("created snapshot {} in {}", report.snapshot_id, target.display());
+                            println!("  database: {:.2} MB", report.database_bytes as f64 / (1024.0 * 1024.0));
+                            println!(
+                                "  media files: {} ({} new, {:.2} MB copied)",
+                                report.media_files,
+                                report.media_files_copied,
+                                report.media_bytes_copied as f64 / (1024.0 * 1024.0)
+                            );
+                            if report.media_files_missing > 0 {
+                                println!("  {} media files no longer exist and were skipped", report.media_files_missing);
+                            }
+                            if report.snapshots_pruned > 0 {
+                                println!("  pruned {} old snapshots", report.snapshots_pruned);
+                            }
+                        }
+                    }
+                    return Ok(());
+                }
+                BackupCommand::List { target, output } => {
+                    let manifests = list_backups(target).await?;
+                    match output {
+                        OutputFormat::Json => println!(
+                            "{}",
+                            serde_json::to_string_pretty(&json!({
+                                "data": manifests.iter().map(|manifest| json!({
+                                    "id": manifest.id,
+                                    "created_at": manifest.created_at,
+                                    "database_bytes": manifest.database_bytes,
+                                    "media_files": manifest.media.len(),
+                                    "media_bytes": manifest.media.iter().map(|file| file.bytes).sum::<u64>(),
+                                })).collect::<Vec<_>>(),
+                                "success": true
+                            }))?
+                        ),
+                        OutputFormat::Text => {
+                            if manifests.is_empty() {
+                                println!("no snapshots in {}", target.display());
+                            }
+                            for manifest in &manifests {
+                                println!(
+                                    "{}  {}  {} media files, {:.2} MB",
+                                    manifest.id,
+                                    manifest.created_at.to_rfc3339(),
+                                    manifest.media.len(),
+                                    (manifest.database_bytes
+                                        + manifest.media.iter().map(|file| file.bytes).sum::<u64>())
+                                        as f64
+                                        / (1024.0 * 1024.0)
+                                );
+                            }
+                        }
+                    }
+                    return Ok(());
+                }
+                BackupCommand::Restore {
+                    target,
+                    snapshot,
+                    at,
+                    data_dir,
+                    output,
+                } => {
+                    let manifests = list_backups(target).await?;
+                    let manifest = find_backup(&manifests, snapshot.as_deref(), *at)
+                        .ok_or_else(|| anyhow::anyhow!("no matching snapshot in {}", target.display()))?;
+
+                    let local_data_dir = get_base_dir(data_dir)?;
+                    let report = restore_backup(target, &manifest, &local_data_dir, db_key.as_deref()).await?;
+                    match output {
+                        OutputFormat::Json => println!(
+                            "{}",
+                            serde_json::to_string_pretty(&json!({
+                                "data": report,
+                                "success": true
+                            }))?
+                        ),
+                        OutputFormat::Text => {
+                            println!("restored snapshot {} taken at {}", report.snapshot_id, manifest.created_at.to_rfc3339());
+                            if let Some(previous) = &report.previous_database {
+                                println!("  previous database moved to {}", previous);
+                            }
+                            println!(
+                                "  media files: {} restored, {} already in place",
+                                report.media_files_restored, report.media_files_unchanged
+                            );
+                            println!("  integrity check passed");
+                        }
+                    }
+                    return Ok(());
+                }
+            },
+            Command::Export {
+                start,
+                end,
+                out,
+                decrypt,
+                data_dir,
+                output,
+            } => {
+                let local_data_dir = get_base_dir(data_dir)?;
+                let db = DatabaseManager::new_with_key(
+                    &format!("{}/db.sqlite", local_data_dir.to_string_lossy()),
+                    db_key.as_deref(),
+                )
+                .await
+                .map_err(|e| {
+                    error!("failed to initialize database: {:?}", e);
+                    e
+                })?;
+
+                let report = export_archive(&db, *start, *end, out, *decrypt).await?;
+                match output {
+                    OutputFormat::Json => println!(
+                        "{}",
+                        serde_json::to_string_pretty(&json!({
+                            "data": report,
+                            "success": true
+                        }))?
+                    ),
+                    OutputFormat::Text => {
+                        println!("exported {} to {} into {}", start.to_rfc3339(), end.to_rfc3339(), report.archive);
+                        println!("  frames: {} ({} with text)", report.counts.frames, report.counts.ocr_text);
+                        println!("  audio transcriptions: {}", report.counts.audio_transcriptions);
+                        println!("  ui monitoring entries: {}", report.counts.ui_monitoring);
+                        println!(
+                            "  media files: {} ({:.2} MB archive)",
+                            report.counts.media_files,
+                            report.archive_bytes as f64 / (1024.0 * 1024.0)
+                        );
+                        if let (Some(media_start), Some(media_end)) = (report.media_start, report.media_end) {
+                            println!("  media covers {} to {}", media_start.to_rfc3339(), media_end.to_rfc3339());
+                        }
+                        if report.media_files_missing > 0 {
+                            println!("  {} media files no longer exist and were skipped", report.media_files_missing);
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            Command::Import {
+                archive,
+                data_dir,
+                output,
+            } => {
+                let local_data_dir = get_base_dir(data_dir)?;
+                let db = DatabaseManager::new_with_key(
+                    &format!("{}/db.sqlite", local_data_dir.to_string_lossy()),
+                    db_key.as_deref(),
+                )
+                .await
+                .map_err(|e| {
+                    error!("failed to initialize database: {:?}", e);
+                    e
+                })?;
+
+                let report = import_archive(&db, archive, &local_data_dir).await?;
+                match output {
+                    OutputFormat::Json => println!(
+                        "{}",
+                        serde_json::to_string_pretty(&json!({
+                            "data": report,
+                            "success": true
+                        }))?
+                    ),
+                    OutputFormat::Text => {
+                        let imported = &report.imported;
+                        println!("imported {}", archive.display());
+                        println!("  frames: {} ({} with text)", imported.frames, imported.ocr_text);
+                        println!("  audio transcriptions: {}", imported.audio_transcriptions);
+                        println!("  ui monitoring entries: {}", imported.ui_monitoring);
+                        println!(
+                            "  chunks: {} video, {} audio ({} media files written)",
+                            imported.video_chunks, imported.audio_chunks, report.media_files_written
+                        );
+                        if imported.skipped > 0 {
+                            println!("  {} rows were already in the database and were skipped", imported.skipped);
+                        }
+                    }
+                }
+                return Ok(());
+            }
         }
     }
 
@@ -620,16 +1134,61 @@ async fn main() -> anyhow::Result<()> {
     resource_monitor.start_monitoring(Duration::from_secs(30), Some(Duration::from_secs(60)));
 
     let db = Arc::new(
-        DatabaseManager::new(&format!("{}/db.sqlite", local_data_dir.to_string_lossy()))
-            .await
-            .map_err(|e| {
-                eprintln!("failed to initialize database: {:?}", e);
-                e
-            })?,
+        DatabaseManager::new_with_key(
+            &format!("{}/db.sqlite", local_data_dir.to_string_lossy()),
+            db_key.as_deref(),
+        )
+        .await
+        .map_err(|e| {
+            eprintln!("failed to initialize database: {:?}", e);
+            e
+        })?,
     );
 
     let db_server = db.clone();
 
+    let retention_config = RetentionConfig::new(
+        cli.retention_days,
+        cli.vision_retention_days,
+        cli.audio_retention_days,
+        cli.ui_retention_days,
+    );
+    if retention_config.is_enabled() {
+        start_retention_task(
+            db.clone(),
+            retention_config.clone(),
+            Duration::from_secs(60 * 60),
+        );
+    }
+
+    let audio_encoding = AudioEncoding::new(cli.audio_codec.clone().into(), cli.audio_bitrate);
+    if cli.recompress_audio && !cli.disable_audio {
+        start_recompression_task(
+            db.clone(),
+            local_data_dir.join("data"),
+            audio_encoding,
+            Duration::from_secs(60 * 60),
+        );
+    }
+
+    if let Some(backup_dir) = &cli.backup_dir {
+        start_backup_task(
+            db.clone(),
+            db_key.clone(),
+            backup_dir.clone(),
+            cli.backup_keep,
+            Duration::from_secs(cli.backup_interval_hours.max(1) * 60 * 60),
+        );
+    }
+
+    if cli.enable_embeddings {
+        start_embedding_task(
+            db.clone(),
+            cli.embedding_backend_config(),
+            Duration::from_secs(30),
+        );
+    }
+
     let warning_ocr_engine_clone = cli.ocr_engine.clone();
     let warning_audio_transcription_engine_clone = cli.audio_transcription_engine.clone();
     let monitor_ids = if cli.monitor_id.is_empty() {
@@ -667,8 +1226,73 @@ async fn main() -> anyhow::Result<()> {
         eprintln!("invalid fps value: {}. using default of 1.0", cli.fps);
         1.0
     };
+    if cli.adaptive_fps
+        && !(cli.min_fps.is_finite()
+            && cli.max_fps.is_finite()
+            && cli.min_fps > 0.0
+            && cli.min_fps <= cli.max_fps)
+    {
+        return Err(anyhow::anyhow!(
+            "invalid adaptive fps range: {} to {}, expected 0 < min fps <= max fps",
+            cli.min_fps,
+            cli.max_fps
+        ));
+    }
+    let capture_rate = Arc::new(if cli.adaptive_fps {
+        CaptureRate::new(CaptureRateConfig {
+            fps,
+            adaptive: true,
+            min_fps: cli.min_fps,
+            max_fps: cli.max_fps,
+        })
+    } else {
+        CaptureRate::fixed(fps)
+    });
+    if cli.adaptive_fps && !cli.disable_vision {
+        start_capture_rate_task(
+            capture_rate.clone(),
+            resource_monitor.clone(),
+            Duration::from_secs(10),
+        );
+    }
+    let capture_rate_clone = capture_rate.clone();
 
     let audio_chunk_duration = Duration::from_secs(cli.audio_chunk_duration);
+    for pattern in cli.ignored_windows.iter().chain(&cli.included_windows) {
+        pattern
+            .parse::<WindowPattern>()
+            .map_err(|e| anyhow::anyhow!(e))?;
+    }
+    let privacy_filter = Arc::new(
+        PrivacyFilter::new(cli.mask_sensitive_content, &cli.skip_frames_with_windows)
+            .map_err(|e| anyhow::anyhow!(e))?,
+    );
+    if let Some(threshold) = cli.frame_diff_threshold {
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(anyhow::anyhow!(
+                "invalid frame diff threshold: {}, expected a value from 0.0 to 1.0",
+                threshold
+            ));
+        }
+    }
+    let frame_diff = FrameDiffConfig {
+        algorithm: cli.frame_diff.clone().into(),
+        threshold: cli.frame_diff_threshold,
+        per_window: cli.frame_diff_per_window,
+    };
+    if cli.incremental_ocr && cli.ocr_snapshot_interval == 0 {
+        return Err(anyhow::anyhow!(
+            "invalid ocr snapshot interval: 0, expected at least 1 incremental pass"
+        ));
+    }
+    let incremental_ocr = IncrementalOcrConfig {
+        enabled: cli.incremental_ocr,
+        snapshot_interval: cli.ocr_snapshot_interval,
+        ..Default::default()
+    };
+
+    let audio_processing =
+        AudioProcessingConfig::parse(&cli.audio_processing).map_err(|e| anyhow::anyhow!(e))?;
 
     let mut audio_manager_builder = AudioManagerBuilder::new()
         .audio_chunk_duration(audio_chunk_duration)
@@ -677,8 +1301,15 @@ async fn main() -> anyhow::Result<()> {
         .languages(languages.clone())
         .transcription_engine(cli.audio_transcription_engine.into())
         .realtime(cli.enable_realtime_audio_transcription)
+        .realtime_engine(cli.realtime_transcription_engine.clone().into())
         .enabled_devices(audio_devices)
         .deepgram_api_key(cli.deepgram_api_key.clone())
+        .openai_compatible_url(cli.openai_compatible_url.clone())
+        .openai_compatible_model(cli.openai_compatible_model.clone())
+        .openai_compatible_api_key(cli.openai_compatible_api_key.clone())
+        .speaker_match_threshold(cli.speaker_match_threshold)
+        .audio_processing(audio_processing)
+        .audio_encoding(audio_encoding)
         .output_path(PathBuf::from(output_path_clone.clone().to_string()));
 
     let audio_manager = match audio_manager_builder.build(db.clone()).await {
@@ -698,6 +1329,7 @@ async fn main() -> anyhow::Result<()> {
                     db_clone.clone(),
                     output_path_clone.clone(),
                     fps,
+                    capture_rate_clone.clone(),
                     Duration::from_secs(cli.video_chunk_duration),
                     Arc::new(cli.ocr_engine.clone().into()),
                     monitor_ids_clone.clone(),
@@ -706,6 +1338,10 @@ async fn main() -> anyhow::Result<()> {
                     &vision_handle,
                     &cli.ignored_windows,
                     &cli.included_windows,
+                    cli.window_redaction.clone().into(),
+                    privacy_filter.clone(),
+                    frame_diff,
+                    incremental_ocr,
                     languages_clone.clone(),
                     cli.capture_unfocused_windows,
                     cli.enable_realtime_audio_transcription,
@@ -731,14 +1367,10 @@ async fn main() -> anyhow::Result<()> {
     debug!("LLM initializing");
 
     #[cfg(feature = "llm")]
-    let _llm = {
-        match cli.enable_llm {
-            true => Some(screenpipe_core::LLM::new(
-                screenpipe_core::ModelName::Llama,
-            )?),
-            false => None,
-        }
-    };
+    if cli.enable_llm {
+        let llm = screenpipe_core::LLM::new(screenpipe_core::ModelName::Llama)?;
+        screenpipe_server::llm::chat_endpoint::set_chat_model(Arc::new(llm))?;
+    }
 
     #[cfg(feature = "llm")]
     debug!("LLM initialized");
@@ -752,6 +1384,9 @@ async fn main() -> anyhow::Result<()> {
         cli.disable_audio,
         cli.enable_ui_monitoring,
         audio_manager.clone(),
+        cli.admin_token.clone(),
+        Some(capture_rate),
+        cli.enable_mcp_operator_tools,
     );
 
     // print screenpipe in gradient
@@ -771,6 +1406,25 @@ async fn main() -> anyhow::Result<()> {
     println!("│ setting                │ value                              │");
     println!("├────────────────────────┼────────────────────────────────────┤");
     println!("│ fps                    │ {:<34} │", cli.fps);
+    println!(
+        "│ frame diff             │ {:<34} │",
+        format!(
+            "{} < {}{}",
+            frame_diff.algorithm,
+            frame_diff.threshold(),
+            if frame_diff.per_window {
+                ", per window"
+            } else {
+                ""
+            }
+        )
+    );
+    if cli.adaptive_fps {
+        println!(
+            "│ adaptive fps           │ {:<34} │",
+            format!("{} to {}", cli.min_fps, cli.max_fps)
+        );
+    }
     println!(
         "│ audio chunk duration   │ {:<34} │",
         format!("{} seconds", cli.audio_chunk_duration)
@@ -784,6 +1438,26 @@ async fn main() -> anyhow::Result<()> {
         "│ realtime audio enabled │ {:<34} │",
         cli.enable_realtime_audio_transcription
     );
+    println!(
+        "│ realtime audio engine  │ {:<34} │",
+        format!("{:?}", cli.realtime_transcription_engine)
+    );
+    println!(
+        "│ audio processing       │ {:<34} │",
+        if cli.audio_processing.is_empty() {
+            "none".to_string()
+        } else {
+            cli.audio_processing.join(" ")
+        }
+    );
+    println!(
+        "│ audio encoding         │ {:<34} │",
+        format!(
+            "{}{}",
+            audio_encoding,
+            if cli.recompress_audio { ", recompress" } else { "" }
+        )
+    );
     println!("│ audio disabled         │ {:<34} │", cli.disable_audio);
     println!("│ vision disabled        │ {:<34} │", cli.disable_vision);
     println!(
@@ -794,6 +1468,12 @@ async fn main() -> anyhow::Result<()> {
         "│ ocr engine             │ {:<34} │",
         format!("{:?}", ocr_engine_clone)
     );
+    if cli.incremental_ocr {
+        println!(
+            "│ incremental ocr        │ {:<34} │",
+            format!("full every {} passes", cli.ocr_snapshot_interval)
+        );
+    }
     println!(
         "│ vad engine             │ {:<34} │",
         format!("{:?}", vad_engine_clone)
@@ -814,6 +1494,14 @@ async fn main() -> anyhow::Result<()> {
     println!("│ local llm              │ {:<34} │", cli.enable_llm);
 
     println!("│ use pii removal        │ {:<34} │", cli.use_pii_removal);
+    println!(
+        "│ mask sensitive content │ {:<34} │",
+        cli.mask_sensitive_content
+    );
+    println!(
+        "│ skip frames with wins  │ {:<34} │",
+        format_cell(&format!("{:?}", &cli.skip_frames_with_windows), VALUE_WIDTH)
+    );
     println!(
         "│ ignored windows        │ {:<34} │",
         format_cell(&format!("{:?}", &ignored_windows_clone), VALUE_WIDTH)
@@ -822,6 +1510,10 @@ async fn main() -> anyhow::Result<()> {
         "│ included windows       │ {:<34} │",
         format_cell(&format!("{:?}", &included_windows_clone), VALUE_WIDTH)
     );
+    println!(
+        "│ window redaction       │ {:<34} │",
+        format!("{:?}", cli.window_redaction)
+    );
     println!(
         "│ ui monitoring          │ {:<34} │",
         cli.enable_ui_monitoring
@@ -834,6 +1526,60 @@ async fn main() -> anyhow::Result<()> {
         "│ capture unfocused wins │ {:<34} │",
         cli.capture_unfocused_windows
     );
+    println!(
+        "│ data retention         │ {:<34} │",
+        format_cell(
+            &if retention_config.is_enabled() {
+                let days = |d: Option<u64>| d.map_or("forever".to_string(), |d| format!("{}d", d));
+                format!(
+                    "vision: {}, audio: {}, ui: {}",
+                    days(retention_config.vision_days),
+                    days(retention_config.audio_days),
+                    days(retention_config.ui_days)
+                )
+            } else {
+                "keep forever".to_string()
+            },
+            VALUE_WIDTH
+        )
+    );
+    println!(
+        "│ embeddings             │ {:<34} │",
+        format_cell(
+            &if cli.enable_embeddings {
+                cli.embedding_backend_config().to_string()
+            } else {
+                "disabled".to_string()
+            },
+            VALUE_WIDTH
+        )
+    );
+    println!(
+        "│ backups                │ {:<34} │",
+        format_cell(
+            &match &cli.backup_dir {
+                Some(dir) => format!(
+                    "every {}h to {}",
+                    cli.backup_interval_hours.max(1),
+                    dir.display()
+                ),
+                None => "disabled".to_string(),
+            },
+            VALUE_WIDTH
+        )
+    );
+    println!(
+        "│ encryption at rest     │ {:<34} │",
+        encryption_key.is_some()
+    );
+    println!(
+        "│ raw sql writes         │ {:<34} │",
+        if cli.admin_token.is_some() {
+            "with admin token"
+        } else {
+            "disabled"
+        }
+    );
     println!(
         "│ auto-destruct pid      │ {:<34} │",
         cli.auto_destruct_pid.unwrap_or(0)
@@ -1411,20 +2157,19 @@ async fn handle_pipe_command(
 }
 
 pub async fn handle_mcp_command(command: &McpCommand, local_data_dir: &PathBuf) -> Result<(), anyhow::Error> {
-    let client = Client::new();
-
-    // Check if Python is installed
-    if !is_command_available("python") || !is_command_available("python3") {
-        warn!("note: python is not installed. please install it from the official website: https://www.python.org/");
-    }
-
-    // Check if uv is installed
-    if !is_command_available("uv") {
-        warn!("note: uv is not installed. please install it using the instructions at: https://docs.astral.sh/uv/#installation");
-    }
-
     match command {
-        McpCommand::Setup { directory, output, port, update, purge } => {
+        McpCommand::Serve {
+            port,
+            enable_operator_tools,
+        } => {
+            // stdout carries the protocol, so nothing else may be printed here
+            serve_stdio(McpServer::new(
+                McpBackend::http(*port),
+                *enable_operator_tools,
+            ))
+            .await?;
+        }
+        McpCommand::Setup { directory, output, port, update: _, purge } => {
             let mcp_dir = directory
                 .as_ref()
                 .map(PathBuf::from)
@@ -1472,28 +2217,15 @@ pub async fn handle_mcp_command(command: &McpCommand, local_data_dir: &PathBuf)
                 return Ok(());
             }
 
-            let should_download = if mcp_dir.exists() {
-                if *update {
-                    tokio::fs::remove_dir_all(&mcp_dir).await?;
-                    true
-                } else {
-                    let mut entries = tokio::fs::read_dir(&mcp_dir).await?;
-                    entries.next_entry().await?.is_none()
-                }
-            } else {
-                true
-            };
-
-            // Create config regardless of download status
+            // the mcp server is built into this binary, clients launch it over stdio
+            let screenpipe_path = env::current_exe()?;
             let config = json!({
                 "mcpServers": {
                     "screenpipe": {
-                        "command": "uv",
+                        "command": screenpipe_path.to_string_lossy(),
                         "args": [
-                            "--directory",
-                            mcp_dir.to_string_lossy().to_string(),
-                            "run",
-                            "screenpipe-mcp",
+                            "mcp",
+                            "serve",
                             "--port",
                             port.to_string()
                         ]
@@ -1501,66 +2233,11 @@ pub async fn handle_mcp_command(command: &McpCommand, local_data_dir: &PathBuf)
                 }
             });
 
-            let run_command = format!(
-                "uv --directory {} run screenpipe-mcp --port {}",
-                mcp_dir.to_string_lossy(),
-                port
-            );
+            let run_command = format!("{} mcp serve --port {}", screenpipe_path.display(), port);
+            let http_url = format!("http://localhost:{}/mcp", port);
 
             let config_path = mcp_dir.join("config.json");
-
-            if should_download {
-                tokio::fs::create_dir_all(&mcp_dir).await?;
-                
-                // Log the start of the download process
-                info!("starting download process for MCP directory");
-
-                let owner = "mediar-ai";
-                let repo = "screenpipe";
-                let branch = "main";
-                let target_dir = "screenpipe-integrations/screenpipe-mcp";
-
-                let api_url = format!(
-                    "https://api.github.com/repos/{}/{}/contents/{}?ref={}",
-                    owner, repo, target_dir, branch
-                );
-
-                // Setup ctrl+c handler
-                let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-                let cancel_handle = tokio::spawn(async move {
-                    if signal::ctrl_c().await.is_ok() {
-                        let _ = tx.send(()).await;
-                    }
-                });
-
-                // Download with cancellation support
-                let download_result = tokio::select! {
-                    result = download_mcp_directory(&client, &api_url, &mcp_dir) => result,
-                    _ = rx.recv() => {
-                        info!("Received ctrl+c, canceling download...");
-                        Err(anyhow::anyhow!("Download cancelled by user"))
-                    }
-                };
-
-                // Clean up cancel handler
-                cancel_handle.abort();
-
-                // Handle download result
-                match download_result {
-                    Ok(_) => {
-                        tokio::fs::write(&config_path, serde_json::to_string_pretty(&config)?).await?;
-                    }
-                    Err(e) => {
-                        // Clean up on failure
-                        if mcp_dir.exists() {
-                            let _ = tokio::fs::remove_dir_all(&mcp_dir).await;
-                        }
-                        return Err(e);
-                    }
-                }
-            }
-
-            // Always create/update config.json regardless of download
+            tokio::fs::create_dir_all(&mcp_dir).await?;
             tokio::fs::write(&config_path, serde_json::to_string_pretty(&config)?).await?;
 
             match output {
@@ -1568,26 +2245,23 @@ pub async fn handle_mcp_command(command: &McpCommand, local_data_dir: &PathBuf)
                     "{}",
                     serde_json::to_string_pretty(&json!({
                         "data": {
-                            "message": if should_download { "MCP setup completed successfully" } else { "MCP files already exist" },
+                            "message": "MCP setup completed successfully",
                             "config": config,
                             "config_path": config_path.to_string_lossy(),
                             "directory": mcp_dir.to_string_lossy(),
-                            "port": port
+                            "port": port,
+                            "url": http_url
                         },
                         "success": true
                     }))?
                 ),
                 OutputFormat::Text => {
-                    if should_download {
-                        println!("MCP setup completed successfully");
-                    } else {
-                        println!("MCP files already exist at: {}", mcp_dir.display());
-                        println!("Use --update flag to force update or --purge to start fresh");
-                    }
+                    println!("MCP setup completed successfully");
                     println!("Directory: {}", mcp_dir.display());
                     println!("Config file: {}", config_path.display());
-                    println!("\nTo run the MCP server, use this command:");
+                    println!("\nTo run the MCP server over stdio, use this command:");
                     println!("$ {}", run_command);
+                    println!("\nClients that support streamable HTTP can connect to {} while screenpipe is running", http_url);
                 }
             }
         }
@@ -1595,85 +2269,3 @@ pub async fn handle_mcp_command(command: &McpCommand, local_data_dir: &PathBuf)
 
     Ok(())
 }
-
-async fn download_mcp_directory(
-    client: &Client,
-    api_url: &str,
-    target_dir: &Path,
-) -> Result<(), anyhow::Error> {
-    let mut headers = HeaderMap::new();
-    headers.insert(USER_AGENT, HeaderValue::from_static("screenpipe-cli"));
-
-    let response = client
-        .get(api_url)
-        .headers(headers)
-        .send()
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to send request: {}", e))?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(anyhow::anyhow!("GitHub API error (status {}): {}", status, error_text));
-    }
-
-    let contents: Vec<GitHubContent> = response
-        .json()
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to parse GitHub API response: {}", e))?;
-
-    for item in contents {
-        let target_path = target_dir.join(&item.name);
-        
-        match item.content_type.as_str() {
-            "file" => {
-                if let Some(download_url) = item.download_url {
-                    let file_response = client
-                        .get(&download_url)
-                        .send()
-                        .await
-                        .map_err(|e| anyhow::anyhow!("Failed to download file {}: {}", download_url, e))?;
-
-                    let content = file_response
-                        .bytes()
-                        .await
-                        .map_err(|e| anyhow::anyhow!("Failed to get file content: {}", e))?;
-
-                    tokio::fs::write(&target_path, content)
-                        .await
-                        .map_err(|e| anyhow::anyhow!("Failed to write file {}: {}", target_path.display(), e))?;
-
-                    debug!("Downloaded file: {}", target_path.display());
-                }
-            }
-            "dir" => {
-                tokio::fs::create_dir_all(&target_path)
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Failed to create directory {}: {}", target_path.display(), e))?;
-
-                let subdir_api_url = format!(
-                    "https://api.github.com/repos/{}/{}/contents/{}?ref={}",
-                    "mediar-ai", "screenpipe", item.path, "main"
-                );
-                
-                // Fix recursion with Box::pin
-                let future = Box::pin(download_mcp_directory(client, &subdir_api_url, &target_path));
-                future.await?;
-            }
-            _ => {
-                warn!("Skipping unsupported content type: {}", item.content_type);
-            }
-        }
-    }
-
-    Ok(())
-}
-
-// Helper function to check if a command is available
-fn is_command_available(command: &str) -> bool {
-    std::process::Command::new(command)
-        .arg("--version")
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
-}
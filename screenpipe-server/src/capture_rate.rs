@@ -0,0 +1,118 @@
+use crate::ResourceMonitor;
+use screenpipe_vision::capture_rate::CaptureRate;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+
+/// Whether the machine runs on battery, `None` when it can't be told, e.g. on desktops without
+/// one.
+pub async fn on_battery_power() -> Option<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        linux_on_battery_power()
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos_on_battery_power().await
+    }
+    #[cfg(target_os = "windows")]
+    {
+        windows_on_battery_power()
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn linux_on_battery_power() -> Option<bool> {
+    let mut on_battery = None;
+    for entry in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let path = entry.path();
+        let read = |name: &str| {
+            std::fs::read_to_string(path.join(name))
+                .map(|value| value.trim().to_string())
+                .unwrap_or_default()
+        };
+        match read("type").as_str() {
+            "Mains" | "USB" if read("online") == "1" => return Some(false),
+            "Battery" => {
+                on_battery = Some(on_battery.unwrap_or(false) || read("status") == "Discharging")
+            }
+            _ => {}
+        }
+    }
+    on_battery
+}
+
+#[cfg(target_os = "macos")]
+async fn macos_on_battery_power() -> Option<bool> {
+    let output = tokio::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .await
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    if output.contains("'Battery Power'") {
+        Some(true)
+    } else if output.contains("'AC Power'") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn windows_on_battery_power() -> Option<bool> {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status = SYSTEM_POWER_STATUS::default();
+    unsafe { GetSystemPowerStatus(&mut status) }.ok()?;
+    match status.ACLineStatus {
+        0 => Some(true),
+        1 => Some(false),
+        _ => None,
+    }
+}
+
+/// Keeps the power state and the system CPU usage measured by the resource monitor up to date
+/// in `capture_rate`, which lowers its ceiling on battery and under CPU pressure.
+pub fn start_capture_rate_task(
+    capture_rate: Arc<CaptureRate>,
+    resource_monitor: Arc<ResourceMonitor>,
+    interval: Duration,
+) -> JoinHandle<()> {
+    let config = capture_rate.config();
+    info!(
+        "starting adaptive capture rate task ({} to {} fps)",
+        config.min_fps, config.max_fps
+    );
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+
+            let ceiling = capture_rate.ceiling();
+            let on_battery = on_battery_power().await.unwrap_or(false);
+            let cpu_usage = resource_monitor.system_cpu_usage();
+            capture_rate.set_on_battery(on_battery);
+            capture_rate.set_cpu_usage(cpu_usage);
+
+            if capture_rate.ceiling() != ceiling {
+                info!(
+                    "capture rate ceiling is now {} fps (on battery: {}, cpu: {:.0}%)",
+                    capture_rate.ceiling(),
+                    on_battery,
+                    cpu_usage
+                );
+            }
+            debug!(
+                "effective capture rate: {:?} fps",
+                capture_rate.effective_fps()
+            );
+        }
+    })
+}
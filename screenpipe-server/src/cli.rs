@@ -1,14 +1,16 @@
 use std::{path::PathBuf, sync::Arc};
+use chrono::{DateTime, Utc};
 
 use clap::{Parser, Subcommand, ValueHint};
 use clap_complete::{generate, Shell};
 use clap::CommandFactory;
-use screenpipe_audio::{vad::{VadSensitivity, VadEngineEnum}, core::engine::AudioTranscriptionEngine as CoreAudioTranscriptionEngine};
-use screenpipe_vision::{custom_ocr::CustomOcrConfig, utils::OcrEngine as CoreOcrEngine};
+use screenpipe_audio::{vad::{VadSensitivity, VadEngineEnum}, core::engine::{AudioTranscriptionEngine as CoreAudioTranscriptionEngine, RealtimeTranscriptionEngine}, core::encoding::AudioCodec};
+use screenpipe_vision::{custom_ocr::CustomOcrConfig, frame_diff::FrameDiffAlgorithm, redaction::WindowRedaction, utils::OcrEngine as CoreOcrEngine};
 use clap::ValueEnum;
 use screenpipe_core::Language;
 use screenpipe_db::OcrEngine as DBOcrEngine;
 use screenpipe_db::CustomOcrConfig as DBCustomOcrConfig;
+use crate::text_embeds::{EmbeddingBackend, DEFAULT_OLLAMA_EMBEDDING_MODEL, DEFAULT_OLLAMA_URL};
 #[derive(Clone, Debug, ValueEnum, PartialEq)]
 pub enum CliAudioTranscriptionEngine {
     #[clap(name = "deepgram")]
@@ -25,6 +27,8 @@ pub enum CliAudioTranscriptionEngine {
     WhisperLargeV3Turbo,
     #[clap(name = "whisper-large-v3-turbo-quantized")]
     WhisperLargeV3TurboQuantized,
+    #[clap(name = "openai-compatible")]
+    OpenAiCompatible,
 }
 
 impl From<CliAudioTranscriptionEngine> for CoreAudioTranscriptionEngine {
@@ -41,10 +45,106 @@ impl From<CliAudioTranscriptionEngine> for CoreAudioTranscriptionEngine {
             CliAudioTranscriptionEngine::WhisperLargeV3TurboQuantized => {
                 CoreAudioTranscriptionEngine::WhisperLargeV3TurboQuantized
             }
+            CliAudioTranscriptionEngine::OpenAiCompatible => {
+                CoreAudioTranscriptionEngine::OpenAiCompatible
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliRealtimeTranscriptionEngine {
+    /// Stream audio to Deepgram
+    Deepgram,
+    /// Run the local Whisper model, works offline
+    Whisper,
+}
+
+impl From<CliRealtimeTranscriptionEngine> for RealtimeTranscriptionEngine {
+    fn from(cli_engine: CliRealtimeTranscriptionEngine) -> Self {
+        match cli_engine {
+            CliRealtimeTranscriptionEngine::Deepgram => RealtimeTranscriptionEngine::Deepgram,
+            CliRealtimeTranscriptionEngine::Whisper => RealtimeTranscriptionEngine::Whisper,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliAudioCodec {
+    /// AAC in mp4 files
+    Aac,
+    /// Opus in ogg files, the smallest files for speech
+    Opus,
+    /// Lossless flac files
+    Flac,
+    Mp3,
+    /// Uncompressed wav files
+    Wav,
+}
+
+impl From<CliAudioCodec> for AudioCodec {
+    fn from(cli_codec: CliAudioCodec) -> Self {
+        match cli_codec {
+            CliAudioCodec::Aac => AudioCodec::Aac,
+            CliAudioCodec::Opus => AudioCodec::Opus,
+            CliAudioCodec::Flac => AudioCodec::Flac,
+            CliAudioCodec::Mp3 => AudioCodec::Mp3,
+            CliAudioCodec::Wav => AudioCodec::Wav,
         }
     }
 }
 
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliWindowRedaction {
+    /// Record frames as captured, filtered windows are only left out of OCR
+    Off,
+    /// Paint filtered windows black
+    Black,
+    /// Blur filtered windows beyond recognition
+    Blur,
+}
+
+impl From<CliWindowRedaction> for WindowRedaction {
+    fn from(cli_redaction: CliWindowRedaction) -> Self {
+        match cli_redaction {
+            CliWindowRedaction::Off => WindowRedaction::Off,
+            CliWindowRedaction::Black => WindowRedaction::Black,
+            CliWindowRedaction::Blur => WindowRedaction::Blur,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliFrameDiff {
+    /// Histogram distance and SSIM of the full resolution frames
+    HistogramSsim,
+    /// Difference hash, the cheapest, misses changes too small to move a 16x16 grid
+    Dhash,
+    /// Perceptual hash, cheap and robust to noise, misses small changes like dhash
+    Phash,
+    /// SSIM of frames shrunk to 640 pixels wide
+    Ssim,
+}
+
+impl From<CliFrameDiff> for FrameDiffAlgorithm {
+    fn from(cli_frame_diff: CliFrameDiff) -> Self {
+        match cli_frame_diff {
+            CliFrameDiff::HistogramSsim => FrameDiffAlgorithm::HistogramSsim,
+            CliFrameDiff::Dhash => FrameDiffAlgorithm::DHash,
+            CliFrameDiff::Phash => FrameDiffAlgorithm::PHash,
+            CliFrameDiff::Ssim => FrameDiffAlgorithm::Ssim,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliEmbeddingBackend {
+    /// Run the embedding model in-process
+    Local,
+    /// Use an Ollama server
+    Ollama,
+}
+
 #[derive(Clone, Debug, ValueEnum, PartialEq)]
 pub enum CliOcrEngine {
     Unstructured,
@@ -150,6 +250,35 @@ pub struct Cli {
     #[cfg_attr(not(target_os = "macos"), arg(short, long, default_value_t = 1.0))]
     #[cfg_attr(target_os = "macos", arg(short, long, default_value_t = 0.5))] 
     pub fps: f64, // ! not crazy about this (inconsistent behaviour across platforms) see https://github.com/mediar-ai/screenpipe/issues/173
+
+    /// Adapt the capture rate to activity: capture faster while the screen changes and back off
+    /// on a static screen, between --min-fps and --max-fps, starting from --fps. The ceiling is
+    /// halved on battery and under high CPU usage
+    #[arg(long, default_value_t = false)]
+    pub adaptive_fps: bool,
+
+    /// Lowest capture rate with --adaptive-fps
+    #[arg(long, default_value_t = 0.1)]
+    pub min_fps: f64,
+
+    /// Highest capture rate with --adaptive-fps
+    #[arg(long, default_value_t = 2.0)]
+    pub max_fps: f64,
+
+    /// How consecutive frames are compared to skip the ones that didn't change. The hashes and
+    /// downscaled ssim cost a fraction of the default on large screens, see the vision benchmark
+    #[arg(long, value_enum, default_value_t = CliFrameDiff::HistogramSsim)]
+    pub frame_diff: CliFrameDiff,
+
+    /// Frames differing less than this from the last recorded one are skipped, from 0.0 to 1.0.
+    /// Defaults to 0.006 for histogram-ssim and ssim, 0.005 for dhash and 0.03 for phash
+    #[arg(long)]
+    pub frame_diff_threshold: Option<f64>,
+
+    /// Also compare each captured window with the same window in the last recorded frame, so that
+    /// small changes in a window, like a new chat message, aren't lost in the whole screen
+    #[arg(long, default_value_t = false)]
+    pub frame_diff_per_window: bool,
     
     /// Audio chunk duration in seconds
     #[arg(short = 'd', long, default_value_t = 30)]
@@ -171,6 +300,28 @@ pub struct Cli {
     #[arg(short = 'r', long)]
     pub realtime_audio_device: Vec<String>,
 
+    /// Processing applied to recorded audio before it is stored and transcribed, a comma
+    /// separated list of agc (gain control), denoise (noise suppression) and aec (echo
+    /// cancellation against the output devices, input devices only), or none.
+    /// Prefix with a device name to set it for one device, e.g. "MacBook Pro Microphone (input)=agc,aec"
+    /// (can be specified multiple times)
+    #[arg(long)]
+    pub audio_processing: Vec<String>,
+
+    /// Codec audio chunks are stored with
+    #[arg(long, value_enum, default_value_t = CliAudioCodec::Aac)]
+    pub audio_codec: CliAudioCodec,
+
+    /// Bitrate of stored audio chunks in kbit/s, defaults to 64 for aac and mp3 and 24 for opus.
+    /// Ignored by flac and wav
+    #[arg(long)]
+    pub audio_bitrate: Option<u32>,
+
+    /// Re-encode audio chunks recorded with another codec or bitrate to --audio-codec and
+    /// --audio-bitrate in the background
+    #[arg(long, default_value_t = false)]
+    pub recompress_audio: bool,
+
     /// Data directory. Default to $HOME/.screenpipe
     #[arg(long, value_hint = ValueHint::DirPath)]
     pub data_dir: Option<String>,
@@ -184,6 +335,7 @@ pub struct Cli {
     /// WhisperTiny is a local, lightweight transcription model, recommended for high data privacy.
     /// WhisperDistilLargeV3 is a local, lightweight transcription model (-a whisper-large), recommended for higher quality audio than tiny.
     /// WhisperLargeV3Turbo is a local, lightweight transcription model (-a whisper-large-v3-turbo), recommended for higher quality audio than tiny.
+    /// OpenAiCompatible sends audio to a server implementing OpenAI's transcription API (-a openai-compatible), e.g. a local faster-whisper-server, see --openai-compatible-url.
     #[arg(short = 'a', long, value_enum, default_value_t = CliAudioTranscriptionEngine::WhisperTinyQuantized)]
     pub audio_transcription_engine: CliAudioTranscriptionEngine,
 
@@ -191,6 +343,11 @@ pub struct Cli {
     #[arg(long, default_value_t = false)]
     pub enable_realtime_audio_transcription: bool,
 
+    /// Engine for realtime audio transcription. Whisper runs the local model of the audio
+    /// transcription engine (Whisper Large V3 Turbo if that is not a Whisper model) and needs no network
+    #[arg(long, value_enum, default_value_t = CliRealtimeTranscriptionEngine::Deepgram)]
+    pub realtime_transcription_engine: CliRealtimeTranscriptionEngine,
+
     /// Enable realtime vision
     #[arg(long, default_value_t = true)]
     pub enable_realtime_vision: bool,
@@ -214,6 +371,16 @@ pub struct Cli {
     )]
     pub ocr_engine: CliOcrEngine,
 
+    /// Only OCR the regions of each window that changed since it was last OCR'd, and merge what
+    /// is read into its previous text. Works with the engines that locate the text they read,
+    /// tesseract and apple-native, the others always OCR whole windows
+    #[arg(long, default_value_t = false)]
+    pub incremental_ocr: bool,
+
+    /// With --incremental-ocr, OCR each window in full again after this many incremental passes
+    #[arg(long, default_value_t = 30)]
+    pub ocr_snapshot_interval: u32,
+
     /// Monitor IDs to use, these will be used to select the monitors to record
     #[arg(short = 'm', long)]
     pub monitor_id: Vec<u32>,
@@ -225,6 +392,16 @@ pub struct Cli {
     #[arg(long, default_value_t = false)]
     pub use_pii_removal: bool,
 
+    /// Mask secrets (API keys, JWTs, private keys) and PII (credit cards, SSNs, emails) found by OCR
+    /// in the recorded frames, the OCR text and the text positions before they are stored
+    #[arg(long, default_value_t = false)]
+    pub mask_sensitive_content: bool,
+
+    /// Don't record frames while a window on the monitor matches this pattern, focused or not, matched like --ignored-windows, example:
+    /// --skip-frames-with-windows "Incognito" --skip-frames-with-windows "Private Browsing"
+    #[arg(long)]
+    pub skip_frames_with_windows: Vec<String>,
+
     /// Disable vision recording
     #[arg(long, default_value_t = false)]
     pub disable_vision: bool,
@@ -233,18 +410,27 @@ pub struct Cli {
     #[arg(long, value_enum, default_value_t = CliVadEngine::Silero)] // Silero or WebRtc
     pub vad_engine: CliVadEngine,
 
-    /// List of windows to ignore (by title) for screen recording - we use contains to match, example:
+    /// List of windows to ignore (by app name or title) for screen recording - we use contains to match, example:
     /// --ignored-windows "Spotify" --ignored-windows "Bit" will ignore both "Bitwarden" and "Bittorrent"
-    /// --ignored-windows "x" will ignore "Home / X" and "SpaceX"
+    /// --ignored-windows "x" will ignore "Home / X" and "SpaceX".
+    /// Prefix with glob: to match whole names with * and ? wildcards, e.g. --ignored-windows "glob:*password*",
+    /// or with regex: for a regular expression, e.g. --ignored-windows "regex:^(1password|bitwarden)$".
+    /// Ignored windows are never recorded, even if they are also included
     #[arg(long)]
     pub ignored_windows: Vec<String>,
 
-    /// List of windows to include (by title) for screen recording - we use contains to match, example:
+    /// List of windows to include (by app name or title) for screen recording, only these are recorded if set.
+    /// Matched like --ignored-windows, example:
     /// --included-windows "Chrome" will include "Google Chrome"
     /// --included-windows "WhatsApp" will include "WhatsApp"
     #[arg(long)]
     pub included_windows: Vec<String>,
 
+    /// Also hide ignored windows, and windows not included when --included-windows is set, in the
+    /// recorded video instead of only leaving them out of OCR
+    #[arg(long, value_enum, default_value_t = CliWindowRedaction::Off)]
+    pub window_redaction: CliWindowRedaction,
+
     /// Video chunk duration in seconds
     #[arg(long, default_value_t = 60)]
     pub video_chunk_duration: u64,
@@ -253,6 +439,18 @@ pub struct Cli {
     #[arg(long = "deepgram-api-key")]
     pub deepgram_api_key: Option<String>,
 
+    /// Base URL of the server used by the openai-compatible transcription engine, e.g. http://localhost:8000
+    #[arg(long)]
+    pub openai_compatible_url: Option<String>,
+
+    /// Model requested from the openai-compatible transcription server
+    #[arg(long)]
+    pub openai_compatible_model: Option<String>,
+
+    /// API key sent to the openai-compatible transcription server, if it requires one
+    #[arg(long, env = "OPENAI_COMPATIBLE_API_KEY", hide_env_values = true)]
+    pub openai_compatible_api_key: Option<String>,
+
     /// PID to watch for auto-destruction. If provided, screenpipe will stop when this PID is no longer running.
     #[arg(long)]
     pub auto_destruct_pid: Option<u32>,
@@ -261,11 +459,15 @@ pub struct Cli {
     #[arg(long, value_enum, default_value_t = CliVadSensitivity::High)]
     pub vad_sensitivity: CliVadSensitivity,
 
+    /// Cosine distance (0 to 2) under which a voice is attributed to a known speaker. Lower values create more speakers, higher values merge different voices
+    #[arg(long, default_value_t = 0.5)]
+    pub speaker_match_threshold: f64,
+
     /// Disable telemetry
     #[arg(long, default_value_t = false)]
     pub disable_telemetry: bool,
 
-    /// Enable Local LLM API
+    /// Enable Local LLM API, served on /v1/chat/completions
     #[arg(long, default_value_t = false)]
     pub enable_llm: bool,
 
@@ -281,6 +483,73 @@ pub struct Cli {
     #[arg(long, default_value_t = false)]
     pub capture_unfocused_windows: bool,
 
+    /// Delete recorded data (frames, OCR text, audio transcriptions, UI monitoring and the
+    /// corresponding video/audio files) older than this many days. Data is kept forever if not set
+    #[arg(long)]
+    pub retention_days: Option<u64>,
+
+    /// Retention period in days for screen recordings (frames, OCR text, video files), overrides --retention-days
+    #[arg(long)]
+    pub vision_retention_days: Option<u64>,
+
+    /// Retention period in days for audio recordings (transcriptions, audio files), overrides --retention-days
+    #[arg(long)]
+    pub audio_retention_days: Option<u64>,
+
+    /// Retention period in days for UI monitoring data, overrides --retention-days
+    #[arg(long)]
+    pub ui_retention_days: Option<u64>,
+
+    /// Directory to back up the database and new video/audio chunks to on a schedule, see `screenpipe backup`
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    pub backup_dir: Option<PathBuf>,
+
+    /// Hours between scheduled backups
+    #[arg(long, default_value_t = 24)]
+    pub backup_interval_hours: u64,
+
+    /// Number of scheduled backup snapshots to keep, older ones are deleted
+    #[arg(long, default_value_t = 7)]
+    pub backup_keep: usize,
+
+    /// File containing the hex encoded 32 byte key used to encrypt the database and recorded
+    /// media. Can also be set with the SCREENPIPE_ENCRYPTION_KEY environment variable
+    #[arg(
+        long,
+        global = true,
+        value_hint = ValueHint::FilePath,
+        value_parser = parse_encryption_key_file
+    )]
+    pub encryption_key_file: Option<PathBuf>,
+
+    /// Token callers send as `admin_token` to change data irreversibly: `DELETE /data`, applying
+    /// `/speakers/recluster`, controlling `/audio/retranscribe` and writing through `/raw_sql`.
+    /// Without it those are disabled
+    #[arg(long, env = "SCREENPIPE_ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+
+    /// Offer the tools that click, type, press keys and open applications or urls on `/mcp`.
+    /// Without it MCP clients can only search and read
+    #[arg(long, default_value_t = false)]
+    pub enable_mcp_operator_tools: bool,
+
+    /// Generate embeddings for OCR text and audio transcriptions in the background, including
+    /// data recorded before this was enabled. Semantic search only finds embedded content
+    #[arg(long, default_value_t = false)]
+    pub enable_embeddings: bool,
+
+    /// Where embeddings are generated, for the background worker, semantic search and `add --use-embedding`
+    #[arg(long, global = true, value_enum, default_value_t = CliEmbeddingBackend::Local)]
+    pub embedding_backend: CliEmbeddingBackend,
+
+    /// Ollama server used by the ollama embedding backend
+    #[arg(long, global = true, default_value = DEFAULT_OLLAMA_URL)]
+    pub ollama_url: String,
+
+    /// Ollama model used by the ollama embedding backend
+    #[arg(long, global = true, default_value = DEFAULT_OLLAMA_EMBEDDING_MODEL)]
+    pub ollama_embedding_model: String,
+
     #[command(subcommand)]
     pub command: Option<Command>,
 
@@ -289,6 +558,16 @@ pub struct Cli {
 
 
 impl Cli {
+    pub fn embedding_backend_config(&self) -> EmbeddingBackend {
+        match self.embedding_backend {
+            CliEmbeddingBackend::Local => EmbeddingBackend::Local,
+            CliEmbeddingBackend::Ollama => EmbeddingBackend::Ollama {
+                url: self.ollama_url.clone(),
+                model: self.ollama_embedding_model.clone(),
+            },
+        }
+    }
+
     pub fn unique_languages(&self) -> Result<Vec<Language>, String> {
         let mut unique_langs = std::collections::HashSet::new();
         for lang in &self.language {
@@ -305,6 +584,16 @@ impl Cli {
     }
 }
 
+/// Encrypting the database needs SQLCipher, which only the `encryption` feature links.
+pub const ENCRYPTION_UNAVAILABLE: &str = "encryption at rest needs screenpipe built with the `encryption` feature (cargo build --features encryption)";
+
+fn parse_encryption_key_file(value: &str) -> Result<PathBuf, String> {
+    if !cfg!(feature = "encryption") {
+        return Err(ENCRYPTION_UNAVAILABLE.to_string());
+    }
+    Ok(PathBuf::from(value))
+}
+
 #[derive(Subcommand)]
 pub enum Command {
     /// Audio device management commands
@@ -327,7 +616,7 @@ pub enum Command {
         #[command(subcommand)]
         subcommand: McpCommand,
     },
-    /// Add video files to existing screenpipe data (OCR only) - DOES NOT SUPPORT AUDIO
+    /// Add video files to existing screenpipe data (OCR only) - use `add-audio` for audio files
     Add {
         /// Path to folder containing video files
         path: String,
@@ -356,6 +645,33 @@ pub enum Command {
         #[arg(long, default_value_t = false)]
         use_embedding: bool,
     },
+    /// Add audio files (calls, voice memos, podcasts) to existing screenpipe data.
+    /// Languages and engine credentials are taken from the top-level flags
+    AddAudio {
+        /// Path to folder containing audio files (wav, mp3, m4a, aac, flac, ogg)
+        path: String,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// Regex pattern to filter files (e.g. "calls/.*\.m4a$")
+        #[arg(long)]
+        pattern: Option<String>,
+        /// Engine to transcribe with
+        #[arg(short = 'a', long, value_enum, default_value_t = CliAudioTranscriptionEngine::WhisperLargeV3Turbo)]
+        engine: CliAudioTranscriptionEngine,
+        /// Device name stored with the transcriptions
+        #[arg(long, default_value = "imported audio")]
+        device_name: String,
+        /// Path to JSON file containing metadata overrides (creation_time, device_name)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        metadata_override: Option<PathBuf>,
+        /// Copy audio files to screenpipe data directory
+        #[arg(long, default_value_t = true)]
+        copy_files: bool,
+    },
     /// Run data migrations in the background
     Migrate {
         /// The name of the migration to run
@@ -380,6 +696,79 @@ pub enum Command {
         #[arg(long, default_value_t = true)]
         continue_on_error: bool,
     },
+    /// Re-transcribe stored audio with another engine, replacing the existing transcriptions.
+    /// Languages and engine credentials are taken from the top-level flags
+    Retranscribe {
+        /// Engine to re-transcribe with. Audio already transcribed by it is skipped
+        #[arg(short = 'a', long, value_enum, default_value_t = CliAudioTranscriptionEngine::WhisperLargeV3Turbo)]
+        engine: CliAudioTranscriptionEngine,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// The subcommand for the re-transcription job
+        #[command(subcommand)]
+        subcommand: Option<MigrationSubCommand>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// Number of audio chunks fetched at a time
+        #[arg(long, default_value_t = 20)]
+        batch_size: i64,
+        /// Delay between audio chunks in milliseconds
+        #[arg(long, default_value_t = 100)]
+        chunk_delay_ms: u64,
+        /// Continue processing if errors occur
+        #[arg(long, default_value_t = true)]
+        continue_on_error: bool,
+    },
+    /// Speaker management commands
+    Speakers {
+        #[command(subcommand)]
+        subcommand: SpeakersCommand,
+    },
+    /// Manage recorded data
+    Data {
+        #[command(subcommand)]
+        subcommand: DataCommand,
+    },
+    /// Back up and restore the database and recorded media
+    Backup {
+        #[command(subcommand)]
+        subcommand: BackupCommand,
+    },
+    /// Export everything captured in a time range, including the recorded media, to a portable .tar.zst archive
+    Export {
+        /// Start of the range (RFC 3339, e.g. 2024-05-01T09:00:00Z)
+        #[arg(long)]
+        start: DateTime<Utc>,
+        /// End of the range, exclusive (RFC 3339)
+        #[arg(long)]
+        end: DateTime<Utc>,
+        /// Archive to write, e.g. archive.tar.zst
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        out: PathBuf,
+        /// Export even though encryption at rest is on. The archive holds the data decrypted
+        #[arg(long, default_value_t = false)]
+        decrypt: bool,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Import an archive written by `screenpipe export`. Data that is already in the database is skipped
+    Import {
+        /// Archive to import
+        #[arg(value_hint = ValueHint::FilePath)]
+        archive: PathBuf,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
     /// Generate shell completions
     Completions {
         /// The shell to generate completions for
@@ -400,6 +789,91 @@ pub enum MigrationSubCommand {
     Status,
 }
 
+#[derive(Subcommand)]
+pub enum SpeakersCommand {
+    /// Group the voices of all stored speakers and merge speakers that are the same person.
+    /// Differently named speakers are never merged
+    Recluster {
+        /// Average cosine distance (0 to 2) under which speakers are merged
+        #[arg(long, default_value_t = 0.4)]
+        threshold: f64,
+        /// Only show the speakers that would be merged
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DataCommand {
+    /// Delete everything captured in a time range, including the recorded media
+    Delete {
+        /// Start of the range (RFC 3339, e.g. 2024-05-01T09:00:00Z)
+        #[arg(long)]
+        start: DateTime<Utc>,
+        /// End of the range, exclusive (RFC 3339)
+        #[arg(long)]
+        end: DateTime<Utc>,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommand {
+    /// Snapshot the database and copy video/audio chunks that are not backed up yet. Safe while screenpipe is running
+    Create {
+        /// Backup directory
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        target: PathBuf,
+        /// Delete older snapshots so that only this many remain
+        #[arg(long)]
+        keep: Option<usize>,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// List the snapshots in a backup directory
+    List {
+        /// Backup directory
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        target: PathBuf,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Restore a snapshot, the latest one by default. Stop screenpipe first
+    Restore {
+        /// Backup directory
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        target: PathBuf,
+        /// Id of the snapshot to restore, as shown by `backup list`
+        #[arg(long, conflicts_with = "at")]
+        snapshot: Option<String>,
+        /// Restore the latest snapshot taken at or before this time (RFC 3339)
+        #[arg(long)]
+        at: Option<DateTime<Utc>>,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum AudioCommand {
     /// List available audio devices
@@ -526,13 +1000,22 @@ pub enum McpCommand {
         /// Server port
         #[arg(short = 'p', long, default_value_t = 3030)]
         port: u16,
-        /// Force update existing files
-        #[arg(long)]
+        /// No longer needed, the config file is always rewritten
+        #[arg(long, hide = true)]
         update: bool,
         /// Purge existing MCP directory before setup
         #[arg(long)]
         purge: bool,
     },
+    /// Run the MCP server over stdio, forwarding tool calls to a running screenpipe server
+    Serve {
+        /// Port of the screenpipe server
+        #[arg(short = 'p', long, default_value_t = 3030)]
+        port: u16,
+        /// Offer the tools that click, type, press keys and open applications or urls
+        #[arg(long, default_value_t = false)]
+        enable_operator_tools: bool,
+    },
 }
 
 #[derive(Clone, Debug, ValueEnum, PartialEq)]
@@ -5,7 +5,12 @@ use screenpipe_core::pii_removal::remove_pii;
 use screenpipe_core::Language;
 use screenpipe_db::{DatabaseManager, Speaker};
 use screenpipe_events::{poll_meetings_events, send_event};
+use screenpipe_vision::capture_rate::CaptureRate;
 use screenpipe_vision::core::WindowOcr;
+use screenpipe_vision::frame_diff::FrameDiffConfig;
+use screenpipe_vision::incremental_ocr::IncrementalOcrConfig;
+use screenpipe_vision::privacy::PrivacyFilter;
+use screenpipe_vision::redaction::WindowRedaction;
 use screenpipe_vision::OcrEngine;
 use std::sync::Arc;
 use std::time::Duration;
@@ -17,6 +22,7 @@ pub async fn start_continuous_recording(
     db: Arc<DatabaseManager>,
     output_path: Arc<String>,
     fps: f64,
+    capture_rate: Arc<CaptureRate>,
     video_chunk_duration: Duration,
     ocr_engine: Arc<OcrEngine>,
     monitor_ids: Vec<u32>,
@@ -25,6 +31,10 @@ pub async fn start_continuous_recording(
     vision_handle: &Handle,
     ignored_windows: &[String],
     include_windows: &[String],
+    window_redaction: WindowRedaction,
+    privacy_filter: Arc<PrivacyFilter>,
+    frame_diff: FrameDiffConfig,
+    incremental_ocr: IncrementalOcrConfig,
     languages: Vec<Language>,
     capture_unfocused_windows: bool,
     realtime_vision: bool,
@@ -39,6 +49,8 @@ pub async fn start_continuous_recording(
                 let ocr_engine = Arc::clone(&ocr_engine);
                 let ignored_windows_video = ignored_windows.to_vec();
                 let include_windows_video = include_windows.to_vec();
+                let privacy_filter = Arc::clone(&privacy_filter);
+                let capture_rate = Arc::clone(&capture_rate);
 
                 let languages = languages.clone();
 
@@ -51,11 +63,16 @@ pub async fn start_continuous_recording(
                             db_manager_video.clone(),
                             output_path_video.clone(),
                             fps,
+                            capture_rate.clone(),
                             ocr_engine.clone(),
                             monitor_id,
                             use_pii_removal,
                             &ignored_windows_video,
                             &include_windows_video,
+                            window_redaction,
+                            privacy_filter.clone(),
+                            frame_diff,
+                            incremental_ocr,
                             video_chunk_duration,
                             languages.clone(),
                             capture_unfocused_windows,
@@ -115,11 +132,16 @@ async fn record_video(
     db: Arc<DatabaseManager>,
     output_path: Arc<String>,
     fps: f64,
+    capture_rate: Arc<CaptureRate>,
     ocr_engine: Arc<OcrEngine>,
     monitor_id: u32,
     use_pii_removal: bool,
     ignored_windows: &[String],
     include_windows: &[String],
+    window_redaction: WindowRedaction,
+    privacy_filter: Arc<PrivacyFilter>,
+    frame_diff: FrameDiffConfig,
+    incremental_ocr: IncrementalOcrConfig,
     video_chunk_duration: Duration,
     languages: Vec<Language>,
     capture_unfocused_windows: bool,
@@ -162,12 +184,17 @@ async fn record_video(
     let video_capture = VideoCapture::new(
         &output_path,
         fps,
+        capture_rate,
         video_chunk_duration,
         new_chunk_callback,
         Arc::clone(&ocr_engine),
         monitor_id,
         ignored_windows,
         include_windows,
+        window_redaction,
+        privacy_filter,
+        frame_diff,
+        incremental_ocr,
         languages,
         capture_unfocused_windows,
     );
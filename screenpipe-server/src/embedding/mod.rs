@@ -1 +1,2 @@
 pub mod embedding_endpoint;
+pub mod worker;
@@ -0,0 +1,219 @@
+use crate::text_embeds::{BackendUnreachable, EmbeddingBackend};
+use anyhow::Result;
+use screenpipe_db::DatabaseManager;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// Rows embedded per backend call.
+const BATCH_SIZE: usize = 32;
+
+// ocr of a full screen can be very long, the start of it is enough to capture what it is about
+const MAX_TEXT_CHARS: usize = 4000;
+
+/// How long a row that failed to embed is skipped before it is tried again.
+const FAILED_ROW_RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// Rows of each kind remembered as failed, the oldest are tried again first beyond that.
+const MAX_FAILED_ROWS: usize = 1000;
+
+/// Longest wait between passes while the backend is unreachable.
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+/// What a single embedding pass added.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct EmbeddingReport {
+    pub ocr_text_embedded: usize,
+    pub audio_transcriptions_embedded: usize,
+    pub failed: usize,
+}
+
+impl EmbeddingReport {
+    fn embedded(&self) -> usize {
+        self.ocr_text_embedded + self.audio_transcriptions_embedded
+    }
+}
+
+/// Rows that could not be embedded, kept so they are not retried on every pass. Each is tried
+/// again after [`FAILED_ROW_RETRY_AFTER`], or once the worker restarts.
+#[derive(Debug, Default)]
+pub struct FailedRows {
+    ocr_frame_ids: SkippedRows,
+    audio_transcription_ids: SkippedRows,
+}
+
+#[derive(Debug, Default)]
+struct SkippedRows(HashMap<i64, Instant>);
+
+impl SkippedRows {
+    /// Ids to skip now, forgetting the ones that are due for another try.
+    fn active(&mut self) -> Vec<i64> {
+        self.0
+            .retain(|_, failed_at| failed_at.elapsed() < FAILED_ROW_RETRY_AFTER);
+        self.0.keys().copied().collect()
+    }
+
+    fn extend(&mut self, ids: Vec<i64>) {
+        let now = Instant::now();
+        self.0.extend(ids.into_iter().map(|id| (id, now)));
+        if self.0.len() > MAX_FAILED_ROWS {
+            let mut by_age: Vec<(i64, Instant)> =
+                self.0.iter().map(|(id, at)| (*id, *at)).collect();
+            by_age.sort_by_key(|(_, at)| *at);
+            for (id, _) in &by_age[..by_age.len() - MAX_FAILED_ROWS] {
+                self.0.remove(id);
+            }
+        }
+    }
+}
+
+/// Embeds one batch of OCR text and one batch of audio transcriptions that have no embedding
+/// made by the backend's model yet, newest first. New rows, historical ones and ones embedded
+/// by another model are picked up the same way.
+pub async fn run_embedding_pass(
+    db: &DatabaseManager,
+    backend: &EmbeddingBackend,
+    failed: &mut FailedRows,
+) -> Result<EmbeddingReport> {
+    let mut report = EmbeddingReport::default();
+    let model = backend.model_name();
+
+    let skip = failed.ocr_frame_ids.active();
+    let rows = db
+        .get_ocr_text_without_embeddings(model, BATCH_SIZE as i64, &skip)
+        .await?;
+    let (embeddings, failed_ids) = embed_rows(backend, rows).await?;
+    if !embeddings.is_empty() {
+        db.insert_ocr_text_embeddings(model, &embeddings).await?;
+    }
+    report.ocr_text_embedded = embeddings.len();
+    report.failed += failed_ids.len();
+    failed.ocr_frame_ids.extend(failed_ids);
+
+    let skip = failed.audio_transcription_ids.active();
+    let rows = db
+        .get_audio_transcriptions_without_embeddings(model, BATCH_SIZE as i64, &skip)
+        .await?;
+    let (embeddings, failed_ids) = embed_rows(backend, rows).await?;
+    if !embeddings.is_empty() {
+        db.insert_audio_transcription_embeddings(model, &embeddings)
+            .await?;
+    }
+    report.audio_transcriptions_embedded = embeddings.len();
+    report.failed += failed_ids.len();
+    failed.audio_transcription_ids.extend(failed_ids);
+
+    Ok(report)
+}
+
+/// Embeds `(id, text)` rows, returning the embeddings and the ids that failed. If the whole
+/// batch fails the rows are tried one by one, so a single bad row doesn't hold back the rest.
+/// An error is returned, and no row is marked as failed, if the backend is unusable, e.g.
+/// Ollama is not running.
+async fn embed_rows(
+    backend: &EmbeddingBackend,
+    rows: Vec<(i64, String)>,
+) -> Result<(Vec<(i64, Vec<f32>)>, Vec<i64>)> {
+    if rows.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let (ids, texts): (Vec<i64>, Vec<String>) = rows
+        .into_iter()
+        .map(|(id, text)| (id, truncate(text)))
+        .unzip();
+
+    let batch_error = match backend.embed(&texts).await {
+        Ok(embeddings) => return Ok((ids.into_iter().zip(embeddings).collect(), Vec::new())),
+        Err(e) => e,
+    };
+    if batch_error.is::<BackendUnreachable>() {
+        return Err(batch_error);
+    }
+    debug!(
+        "embedding batch of {} failed, retrying one by one: {}",
+        texts.len(),
+        batch_error
+    );
+
+    let mut embeddings = Vec::new();
+    let mut failed = Vec::new();
+    for (id, text) in ids.into_iter().zip(texts) {
+        match backend.embed(std::slice::from_ref(&text)).await {
+            Ok(mut embedding) => match embedding.pop() {
+                Some(embedding) => embeddings.push((id, embedding)),
+                None => failed.push(id),
+            },
+            Err(e) if e.is::<BackendUnreachable>() => return Err(e),
+            Err(e) => {
+                warn!("failed to embed row {}: {}", id, e);
+                failed.push(id);
+            }
+        }
+    }
+
+    if embeddings.is_empty() {
+        // nothing worked, most likely the backend itself is down, so retry these later
+        return Err(batch_error);
+    }
+    Ok((embeddings, failed))
+}
+
+fn truncate(mut text: String) -> String {
+    if let Some((index, _)) = text.char_indices().nth(MAX_TEXT_CHARS) {
+        text.truncate(index);
+    }
+    text
+}
+
+/// Keeps embedding new and historical content in the background until the task is aborted.
+/// Batches are processed back to back while there is a backlog, otherwise the worker checks for
+/// new content every `interval`. While passes fail, e.g. because the backend is unreachable, the
+/// wait doubles up to [`MAX_BACKOFF`].
+pub fn start_embedding_task(
+    db: Arc<DatabaseManager>,
+    backend: EmbeddingBackend,
+    interval: Duration,
+) -> JoinHandle<()> {
+    info!("starting embedding worker with {} backend", backend);
+
+    tokio::spawn(async move {
+        let mut failed = FailedRows::default();
+        let mut total = 0;
+        let mut backoff = interval;
+
+        loop {
+            match run_embedding_pass(&db, &backend, &mut failed).await {
+                Ok(report) if report.embedded() > 0 => {
+                    backoff = interval;
+                    total += report.embedded();
+                    debug!(
+                        "embedded {} ocr texts and {} audio transcriptions ({} total, {} failed)",
+                        report.ocr_text_embedded,
+                        report.audio_transcriptions_embedded,
+                        total,
+                        report.failed
+                    );
+                    // more backlog is likely, keep going
+                    continue;
+                }
+                Ok(_) => backoff = interval,
+                Err(e) => {
+                    error!(
+                        "embedding pass failed, retrying in {}s: {}",
+                        backoff.as_secs(),
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF.max(interval));
+                    continue;
+                }
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
@@ -1,23 +1,50 @@
 mod add;
+mod add_audio;
+mod archive;
+mod audio_recompression;
 mod auto_destruct;
+mod backup;
+mod capture_rate;
 pub mod chunking;
 pub mod cli;
 pub mod core;
 pub mod filtering;
+#[cfg(feature = "llm")]
+pub mod llm;
+pub mod mcp;
 pub mod pipe_manager;
 mod resource_monitor;
+mod retention;
 mod server;
 pub mod text_embeds;
+mod transcript;
 mod video;
 pub mod video_cache;
 pub mod video_utils;
 pub use add::handle_index_command;
+pub use add_audio::handle_audio_index_command;
+pub use archive::{
+    export_archive, import_archive, ArchiveCounts, ArchiveManifest, ExportReport, ImportReport,
+};
+pub use audio_recompression::{
+    run_recompression_pass, start_recompression_task, RecompressionReport,
+};
 pub use auto_destruct::watch_pid;
+pub use backup::{
+    create_backup, find_backup, list_backups, restore_backup, start_backup_task, BackupManifest,
+    BackupMediaFile, BackupReport, RestoreReport,
+};
 pub use axum::Json as JsonResponse;
+pub use capture_rate::{on_battery_power, start_capture_rate_task};
 pub use cli::Cli;
 pub use core::start_continuous_recording;
+pub use embedding::worker::{run_embedding_pass, start_embedding_task, EmbeddingReport};
 pub use pipe_manager::PipeManager;
 pub use resource_monitor::{ResourceMonitor, RestartSignal};
+pub use retention::{
+    delete_time_range, run_retention_pass, start_retention_task, RetentionConfig, RetentionReport,
+    TimeRangeDeletionReport,
+};
 pub use screenpipe_core::Language;
 pub use server::health_check;
 pub use server::AppState;
@@ -26,5 +53,6 @@ pub use server::HealthCheckResponse;
 pub use server::PaginatedResponse;
 pub use server::SCServer;
 pub use server::{api_list_monitors, MonitorInfo};
+pub use transcript::{render_transcript, stitch_transcript, TranscriptFormat, TranscriptLine};
 pub use video::VideoCapture;
 pub mod embedding;
@@ -0,0 +1,166 @@
+use axum::{
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use chrono::Utc;
+use futures::{stream, Stream};
+use once_cell::sync::OnceCell;
+use screenpipe_core::{
+    ChatMessageDelta, ChatRequest, ChatResponseChunk, ChatResponseChunkChoice, LLM,
+};
+use serde_json::json;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+static CHAT_MODEL: OnceCell<Arc<LLM>> = OnceCell::new();
+
+/// Serves `model` on `/v1/chat/completions` for the rest of the process.
+pub fn set_chat_model(model: Arc<LLM>) -> anyhow::Result<()> {
+    CHAT_MODEL
+        .set(model)
+        .map_err(|_| anyhow::anyhow!("chat model is already set"))
+}
+
+// OpenAI-like chat completions, streamed as server-sent events when `stream` is set
+pub async fn chat_completions(Json(request): Json<ChatRequest>) -> Response {
+    let Some(model) = CHAT_MODEL.get().cloned() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"error": "local llm is not enabled, start screenpipe with --enable-llm"})),
+        )
+            .into_response();
+    };
+
+    if request.messages.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "messages must not be empty"})),
+        )
+            .into_response();
+    }
+
+    debug!(
+        "processing chat completion request with {} messages, stream: {}",
+        request.messages.len(),
+        request.stream
+    );
+
+    if request.stream {
+        return stream_chat(model, request).into_response();
+    }
+
+    // generation is cpu/gpu bound, keep it off the async workers
+    match tokio::task::spawn_blocking(move || model.chat(request)).await {
+        Ok(Ok(response)) => Json(response).into_response(),
+        Ok(Err(e)) => {
+            error!("chat completion failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("chat completion failed: {}", e)})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("chat completion task failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "chat completion failed"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+struct ChunkContext {
+    id: String,
+    created: i64,
+    model: String,
+}
+
+impl ChunkContext {
+    fn event(&self, delta: ChatMessageDelta, finish_reason: Option<String>) -> Event {
+        let chunk = ChatResponseChunk {
+            id: self.id.clone(),
+            object: "chat.completion.chunk".to_string(),
+            created: self.created,
+            model: self.model.clone(),
+            system_fingerprint: "".to_string(),
+            choices: vec![ChatResponseChunkChoice {
+                index: 0,
+                delta,
+                logprobs: None,
+                finish_reason,
+            }],
+        };
+        Event::default().json_data(chunk).unwrap_or_default()
+    }
+}
+
+fn stream_chat(
+    model: Arc<LLM>,
+    request: ChatRequest,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (tx, rx) = mpsc::channel::<Event>(32);
+    let context = ChunkContext {
+        id: format!("chatcmpl-{}", Uuid::new_v4().simple()),
+        created: Utc::now().timestamp(),
+        model: model.model_id().to_string(),
+    };
+
+    tokio::task::spawn_blocking(move || {
+        let role = ChatMessageDelta {
+            role: Some("assistant".to_string()),
+            content: None,
+        };
+        if tx.blocking_send(context.event(role, None)).is_err() {
+            return;
+        }
+
+        let result = model.chat_stream(request, &mut |token| {
+            let delta = ChatMessageDelta {
+                role: None,
+                content: Some(token.to_string()),
+            };
+            tx.blocking_send(context.event(delta, None))
+                .map_err(|_| anyhow::anyhow!("client disconnected"))
+        });
+
+        let last = match result {
+            Ok(response) => {
+                let finish_reason = response
+                    .choices
+                    .into_iter()
+                    .next()
+                    .map(|choice| choice.finish_reason);
+                context.event(ChatMessageDelta::default(), finish_reason)
+            }
+            Err(_) if tx.is_closed() => {
+                debug!("chat completion stream closed by the client");
+                return;
+            }
+            Err(e) => {
+                error!("chat completion failed: {}", e);
+                Event::default()
+                    .json_data(
+                        json!({"error": {"message": format!("chat completion failed: {}", e)}}),
+                    )
+                    .unwrap_or_default()
+            }
+        };
+
+        let _ = tx.blocking_send(last);
+        let _ = tx.blocking_send(Event::default().data("[DONE]"));
+    });
+
+    let stream = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|event| (Ok(event), rx))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,754 @@
+//! Model Context Protocol server exposing the screenpipe API to MCP clients.
+//!
+//! Tools map to existing API routes. Their input schemas are built from the OpenAPI document
+//! oasgen generates for the request types, so they stay in sync with the handlers. The server
+//! speaks streamable HTTP on `/mcp` of the screenpipe server and stdio via `screenpipe mcp serve`.
+
+use anyhow::{anyhow, Result};
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::State,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::OnceCell;
+use tower::ServiceExt;
+use tracing::{debug, error, info};
+
+pub const LATEST_PROTOCOL_VERSION: &str = "2025-03-26";
+const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &[LATEST_PROTOCOL_VERSION, "2024-11-05"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+// $refs nested deeper than this are left as plain objects, which also guards against cycles
+const MAX_SCHEMA_DEPTH: usize = 8;
+
+struct ToolSpec {
+    name: &'static str,
+    description: &'static str,
+    method: Method,
+    /// Route of the tool in axum syntax, path parameters are taken from the tool arguments.
+    path: &'static str,
+    /// Drives the mouse, keyboard or other applications, only offered when enabled explicitly.
+    operator: bool,
+}
+
+const TOOLS: &[ToolSpec] = &[
+    ToolSpec {
+        name: "search",
+        description: "Search screen text (OCR), audio transcriptions and UI elements captured by screenpipe. Filter by content type, time range, app, window, text length and speakers.",
+        method: Method::GET,
+        path: "/search",
+        operator: false,
+    },
+    ToolSpec {
+        name: "semantic-search",
+        description: "Search screen text by meaning using embeddings instead of exact keywords.",
+        method: Method::GET,
+        path: "/semantic-search",
+        operator: false,
+    },
+    ToolSpec {
+        name: "get-frame",
+        description: "Get the screenshot of a captured frame by its id, e.g. a frame_id returned by search.",
+        method: Method::GET,
+        path: "/frames/:frame_id",
+        operator: false,
+    },
+    ToolSpec {
+        name: "list-unnamed-speakers",
+        description: "List speakers that have not been named yet, with sample audio transcriptions.",
+        method: Method::GET,
+        path: "/speakers/unnamed",
+        operator: false,
+    },
+    ToolSpec {
+        name: "search-speakers",
+        description: "Find speakers whose name starts with the given prefix.",
+        method: Method::GET,
+        path: "/speakers/search",
+        operator: false,
+    },
+    ToolSpec {
+        name: "find-elements",
+        description: "Find UI elements in an application by role, text or other selectors.",
+        method: Method::POST,
+        path: "/experimental/operator",
+        operator: false,
+    },
+    ToolSpec {
+        name: "click-element",
+        description: "Click a UI element matching the selector.",
+        method: Method::POST,
+        path: "/experimental/operator/click",
+        operator: true,
+    },
+    ToolSpec {
+        name: "type-text",
+        description: "Type text into a UI element matching the selector.",
+        method: Method::POST,
+        path: "/experimental/operator/type",
+        operator: true,
+    },
+    ToolSpec {
+        name: "press-key",
+        description: "Press a key combination on a UI element matching the selector.",
+        method: Method::POST,
+        path: "/experimental/operator/press-key",
+        operator: true,
+    },
+    ToolSpec {
+        name: "get-text",
+        description: "Get the text displayed in an application.",
+        method: Method::POST,
+        path: "/experimental/operator/get_text",
+        operator: false,
+    },
+    ToolSpec {
+        name: "scroll-element",
+        description: "Scroll a UI element matching the selector.",
+        method: Method::POST,
+        path: "/experimental/operator/scroll",
+        operator: true,
+    },
+    ToolSpec {
+        name: "list-interactable-elements",
+        description: "List the elements of an application that can be interacted with, with an index usable by the *-by-index tools.",
+        method: Method::POST,
+        path: "/experimental/operator/list-interactable-elements",
+        operator: false,
+    },
+    ToolSpec {
+        name: "click-by-index",
+        description: "Click an element by its index from list-interactable-elements.",
+        method: Method::POST,
+        path: "/experimental/operator/click-by-index",
+        operator: true,
+    },
+    ToolSpec {
+        name: "type-by-index",
+        description: "Type text into an element by its index from list-interactable-elements.",
+        method: Method::POST,
+        path: "/experimental/operator/type-by-index",
+        operator: true,
+    },
+    ToolSpec {
+        name: "press-key-by-index",
+        description: "Press a key combination on an element by its index from list-interactable-elements.",
+        method: Method::POST,
+        path: "/experimental/operator/press-key-by-index",
+        operator: true,
+    },
+    ToolSpec {
+        name: "open-application",
+        description: "Open an application by name.",
+        method: Method::POST,
+        path: "/experimental/operator/open-application",
+        operator: true,
+    },
+    ToolSpec {
+        name: "open-url",
+        description: "Open a URL, optionally in a specific browser.",
+        method: Method::POST,
+        path: "/experimental/operator/open-url",
+        operator: true,
+    },
+    ToolSpec {
+        name: "pixel-control",
+        description: "Control the mouse and keyboard directly: move, click, type text or press keys.",
+        method: Method::POST,
+        path: "/experimental/operator/pixel",
+        operator: true,
+    },
+];
+
+/// Where tool calls are sent.
+#[derive(Clone)]
+pub enum McpBackend {
+    /// The API router of this process, used when serving `/mcp` from the screenpipe server.
+    Router(Router),
+    /// A running screenpipe server, used by the stdio transport.
+    Http {
+        client: reqwest::Client,
+        base_url: String,
+    },
+}
+
+struct ApiResponse {
+    status: StatusCode,
+    content_type: String,
+    body: Bytes,
+}
+
+impl McpBackend {
+    pub fn http(port: u16) -> Self {
+        Self::Http {
+            client: reqwest::Client::new(),
+            base_url: format!("http://localhost:{}", port),
+        }
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<&Value>,
+    ) -> Result<ApiResponse> {
+        match self {
+            Self::Router(router) => {
+                let mut request = Request::builder().method(method).uri(uri);
+                let body = match body {
+                    Some(body) => {
+                        request = request.header(header::CONTENT_TYPE, "application/json");
+                        Body::from(serde_json::to_vec(body)?)
+                    }
+                    None => Body::empty(),
+                };
+
+                let response = router.clone().oneshot(request.body(body)?).await?;
+                let status = response.status();
+                let content_type = content_type(response.headers());
+                let body = to_bytes(response.into_body(), usize::MAX).await?;
+                Ok(ApiResponse {
+                    status,
+                    content_type,
+                    body,
+                })
+            }
+            Self::Http { client, base_url } => {
+                let mut request = client.request(method, format!("{}{}", base_url, uri));
+                if let Some(body) = body {
+                    request = request.json(body);
+                }
+
+                let response = request.send().await.map_err(|e| {
+                    anyhow!(
+                        "failed to reach screenpipe at {}, is it running? {}",
+                        base_url,
+                        e
+                    )
+                })?;
+                let status = response.status();
+                let content_type = content_type(response.headers());
+                let body = response.bytes().await?;
+                Ok(ApiResponse {
+                    status,
+                    content_type,
+                    body,
+                })
+            }
+        }
+    }
+}
+
+fn content_type(headers: &header::HeaderMap) -> String {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+struct Tool {
+    spec: &'static ToolSpec,
+    input_schema: Value,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+pub struct McpServer {
+    backend: McpBackend,
+    operator_tools: bool,
+    tools: OnceCell<Vec<Tool>>,
+}
+
+impl McpServer {
+    /// `operator_tools` also offers the tools that click, type and open applications or urls.
+    pub fn new(backend: McpBackend, operator_tools: bool) -> Self {
+        Self {
+            backend,
+            operator_tools,
+            tools: OnceCell::new(),
+        }
+    }
+
+    /// Handles a JSON-RPC message or batch, returning the response if one is due.
+    /// Notifications and responses from the client get none.
+    pub async fn handle_message(&self, message: Value) -> Option<Value> {
+        match message {
+            Value::Array(messages) => {
+                let mut responses = Vec::new();
+                for message in messages {
+                    if let Some(response) = self.handle_single(message).await {
+                        responses.push(response);
+                    }
+                }
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            message => self.handle_single(message).await,
+        }
+    }
+
+    async fn handle_single(&self, message: Value) -> Option<Value> {
+        let Value::Object(mut message) = message else {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(INVALID_REQUEST, "message must be an object"),
+            ));
+        };
+
+        let Some(method) = message
+            .get("method")
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+        else {
+            // a response to a request we never send
+            return None;
+        };
+        let params = message.remove("params").unwrap_or(Value::Null);
+        let Some(id) = message.remove("id") else {
+            debug!("mcp notification: {}", method);
+            return None;
+        };
+
+        Some(match self.dispatch(&method, params).await {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(e) => error_response(id, e),
+        })
+    }
+
+    async fn dispatch(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => {
+                let requested = params
+                    .get("protocolVersion")
+                    .and_then(Value::as_str)
+                    .unwrap_or(LATEST_PROTOCOL_VERSION);
+                let protocol_version = if SUPPORTED_PROTOCOL_VERSIONS.contains(&requested) {
+                    requested
+                } else {
+                    LATEST_PROTOCOL_VERSION
+                };
+
+                Ok(json!({
+                    "protocolVersion": protocol_version,
+                    "capabilities": {"tools": {"listChanged": false}},
+                    "serverInfo": {
+                        "name": "screenpipe",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }))
+            }
+            "ping" => Ok(json!({})),
+            "tools/list" => {
+                let tools = self.tools().await?;
+                Ok(json!({
+                    "tools": tools
+                        .iter()
+                        .map(|tool| json!({
+                            "name": tool.spec.name,
+                            "description": tool.spec.description,
+                            "inputSchema": tool.input_schema,
+                        }))
+                        .collect::<Vec<_>>(),
+                }))
+            }
+            "tools/call" => self.call_tool(params).await,
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("method not found: {}", method),
+            )),
+        }
+    }
+
+    async fn tools(&self) -> Result<&[Tool], RpcError> {
+        self.tools
+            .get_or_try_init(|| async {
+                let spec = self.fetch_openapi_spec().await?;
+                Ok::<_, anyhow::Error>(
+                    TOOLS
+                        .iter()
+                        .filter(|tool| self.operator_tools || !tool.operator)
+                        .map(|tool| Tool {
+                            spec: tool,
+                            input_schema: input_schema(&spec, tool),
+                        })
+                        .collect(),
+                )
+            })
+            .await
+            .map(Vec::as_slice)
+            .map_err(|e| {
+                error!("failed to load mcp tools: {}", e);
+                RpcError::new(INTERNAL_ERROR, format!("failed to load tools: {}", e))
+            })
+    }
+
+    async fn fetch_openapi_spec(&self) -> Result<Value> {
+        let response = self
+            .backend
+            .request(Method::GET, "/openapi.json", None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(anyhow!(
+                "openapi spec request failed with status {}",
+                response.status
+            ));
+        }
+        Ok(serde_json::from_slice(&response.body)?)
+    }
+
+    async fn call_tool(&self, params: Value) -> Result<Value, RpcError> {
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing tool name"))?;
+        let tool = self
+            .tools()
+            .await?
+            .iter()
+            .find(|tool| tool.spec.name == name)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("unknown tool: {}", name)))?;
+
+        let mut arguments = match params.get("arguments") {
+            Some(Value::Object(arguments)) => arguments.clone(),
+            None | Some(Value::Null) => Map::new(),
+            Some(_) => {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    "tool arguments must be an object",
+                ))
+            }
+        };
+
+        let mut path = Vec::new();
+        for segment in tool.spec.path.split('/') {
+            match segment.strip_prefix(':') {
+                Some(param) => {
+                    let value = arguments.remove(param).ok_or_else(|| {
+                        RpcError::new(INVALID_PARAMS, format!("missing argument: {}", param))
+                    })?;
+                    path.push(query_value(&value));
+                }
+                None => path.push(segment.to_string()),
+            }
+        }
+        let mut uri = path.join("/");
+
+        let body = if tool.spec.method == Method::POST {
+            Some(Value::Object(arguments))
+        } else {
+            let mut url = reqwest::Url::parse("http://localhost/").expect("valid url");
+            {
+                let mut query = url.query_pairs_mut();
+                for (key, value) in &arguments {
+                    if !value.is_null() {
+                        query.append_pair(key, &query_value(value));
+                    }
+                }
+            }
+            if let Some(query) = url.query().filter(|query| !query.is_empty()) {
+                uri = format!("{}?{}", uri, query);
+            }
+            None
+        };
+
+        info!("mcp tool call: {} {} {}", name, tool.spec.method, uri);
+        let response = match self
+            .backend
+            .request(tool.spec.method.clone(), &uri, body.as_ref())
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => return Ok(tool_result(vec![text_content(e.to_string())], true)),
+        };
+
+        if !response.status.is_success() {
+            let message = format!(
+                "request failed with status {}: {}",
+                response.status,
+                String::from_utf8_lossy(&response.body)
+            );
+            return Ok(tool_result(vec![text_content(message)], true));
+        }
+
+        let content = if response.content_type.starts_with("image/") {
+            json!({
+                "type": "image",
+                "data": BASE64.encode(&response.body),
+                "mimeType": response.content_type,
+            })
+        } else {
+            match serde_json::from_slice::<Value>(&response.body) {
+                Ok(value) => text_content(serde_json::to_string_pretty(&value).unwrap_or_default()),
+                Err(_) => text_content(String::from_utf8_lossy(&response.body).into_owned()),
+            }
+        };
+        Ok(tool_result(vec![content], false))
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": error.code, "message": error.message},
+    })
+}
+
+fn text_content(text: String) -> Value {
+    json!({"type": "text", "text": text})
+}
+
+fn tool_result(content: Vec<Value>, is_error: bool) -> Value {
+    json!({"content": content, "isError": is_error})
+}
+
+/// Formats an argument the way the query extractors expect it, lists are comma separated.
+fn query_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values.iter().map(query_value).collect::<Vec<_>>().join(","),
+        value => value.to_string(),
+    }
+}
+
+/// Builds the input schema of a tool from the parameters and request body of its route.
+fn input_schema(spec: &Value, tool: &ToolSpec) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+
+    if let Some(operation) = find_operation(spec, tool) {
+        let parameters = operation
+            .get("parameters")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        for parameter in parameters {
+            let parameter = resolve_refs(spec, &parameter, 0);
+            let Some(name) = parameter.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            let mut schema = parameter
+                .get("schema")
+                .cloned()
+                .unwrap_or_else(|| json!({}));
+
+            // a whole query struct passed as one parameter, expose its fields instead
+            if let Some(Value::Object(fields)) = schema.get("properties") {
+                properties.extend(fields.clone());
+                if let Some(Value::Array(fields)) = schema.get("required") {
+                    required.extend(fields.iter().cloned());
+                }
+                continue;
+            }
+
+            if let (Some(description), Value::Object(schema)) =
+                (parameter.get("description"), &mut schema)
+            {
+                schema.insert("description".to_string(), description.clone());
+            }
+            properties.insert(name.to_string(), schema);
+            if parameter.get("required").and_then(Value::as_bool) == Some(true) {
+                required.push(json!(name));
+            }
+        }
+
+        if let Some(body) = operation.pointer("/requestBody/content/application~1json/schema") {
+            let body = resolve_refs(spec, body, 0);
+            if let Some(Value::Object(fields)) = body.get("properties") {
+                properties.extend(fields.clone());
+            }
+            if let Some(Value::Array(fields)) = body.get("required") {
+                required.extend(fields.iter().cloned());
+            }
+        }
+    }
+
+    for param in tool.path_params() {
+        properties
+            .entry(param.to_string())
+            .or_insert_with(|| json!({"type": "string"}));
+        if !required.iter().any(|name| name == param) {
+            required.push(json!(param));
+        }
+    }
+
+    let mut schema = json!({"type": "object", "properties": properties});
+    if !required.is_empty() {
+        schema["required"] = Value::Array(required);
+    }
+    schema
+}
+
+impl ToolSpec {
+    fn path_params(&self) -> impl Iterator<Item = &'static str> {
+        self.path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix(':'))
+    }
+}
+
+/// Finds the operation of a tool's route, OpenAPI writes path parameters as `{name}`.
+fn find_operation<'a>(spec: &'a Value, tool: &ToolSpec) -> Option<&'a Value> {
+    let method = tool.method.as_str().to_lowercase();
+    spec.get("paths")?
+        .as_object()?
+        .iter()
+        .find(|(path, _)| {
+            let path = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix('{') {
+                    Some(param) => format!(":{}", param.trim_end_matches('}')),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            path == tool.path
+        })
+        .and_then(|(_, item)| item.get(&method))
+}
+
+/// Inlines `#/components/schemas` references so clients get self-contained schemas.
+fn resolve_refs(spec: &Value, value: &Value, depth: usize) -> Value {
+    match value {
+        Value::Object(object) => {
+            if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+                let target = reference
+                    .strip_prefix('#')
+                    .and_then(|pointer| spec.pointer(pointer));
+                return match target {
+                    Some(target) if depth < MAX_SCHEMA_DEPTH => {
+                        resolve_refs(spec, target, depth + 1)
+                    }
+                    _ => json!({"type": "object"}),
+                };
+            }
+            Value::Object(
+                object
+                    .iter()
+                    .map(|(key, value)| (key.clone(), resolve_refs(spec, value, depth)))
+                    .collect(),
+            )
+        }
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| resolve_refs(spec, value, depth))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+/// Routes for the streamable HTTP transport, tool calls go to `api` in-process.
+pub fn router(api: Router, operator_tools: bool) -> Router {
+    Router::new()
+        .route("/mcp", post(handle_http))
+        .with_state(Arc::new(McpServer::new(
+            McpBackend::Router(api),
+            operator_tools,
+        )))
+}
+
+/// Browsers send an `Origin` on cross-site requests, only local pages may talk to the server.
+/// Requests without one come from native clients and are allowed.
+pub(crate) fn is_local_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return true;
+    };
+    let Some(url) = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| reqwest::Url::parse(origin).ok())
+    else {
+        return false;
+    };
+    matches!(
+        url.host_str(),
+        Some("localhost" | "127.0.0.1" | "[::1]" | "tauri.localhost")
+    )
+}
+
+async fn handle_http(
+    State(server): State<Arc<McpServer>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !is_local_origin(&headers) {
+        let error = RpcError::new(INVALID_REQUEST, "origin not allowed");
+        return (
+            StatusCode::FORBIDDEN,
+            axum::Json(error_response(Value::Null, error)),
+        )
+            .into_response();
+    }
+
+    let message = match serde_json::from_slice::<Value>(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            let error = RpcError::new(PARSE_ERROR, format!("invalid json: {}", e));
+            return (
+                StatusCode::BAD_REQUEST,
+                axum::Json(error_response(Value::Null, error)),
+            )
+                .into_response();
+        }
+    };
+
+    match server.handle_message(message).await {
+        Some(response) => axum::Json(response).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// Serves MCP over stdin/stdout with one JSON-RPC message per line until stdin closes.
+pub async fn serve_stdio(server: McpServer) -> Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle_message(message).await,
+            Err(e) => Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, format!("invalid json: {}", e)),
+            )),
+        };
+
+        if let Some(response) = response {
+            let mut out = serde_json::to_vec(&response)?;
+            out.push(b'\n');
+            stdout.write_all(&out).await?;
+            stdout.flush().await?;
+        }
+    }
+
+    Ok(())
+}
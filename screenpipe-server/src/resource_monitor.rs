@@ -9,9 +9,10 @@ use std::io::Read;
 use std::io::Seek;
 use std::io::SeekFrom;
 use std::io::Write;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use sysinfo::{PidExt, ProcessExt, System, SystemExt};
+use sysinfo::{CpuExt, PidExt, ProcessExt, System, SystemExt};
 use tracing::debug;
 use tracing::trace;
 use tracing::{error, info, warn};
@@ -22,6 +23,8 @@ pub struct ResourceMonitor {
     posthog_client: Option<Client>,
     posthog_enabled: bool,
     distinct_id: String,
+    /// CPU usage of the whole system at the last refresh, in percent, as f32 bits
+    system_cpu_usage: AtomicU32,
 }
 
 pub enum RestartSignal {
@@ -67,6 +70,7 @@ impl ResourceMonitor {
             posthog_client,
             posthog_enabled: telemetry_enabled,
             distinct_id,
+            system_cpu_usage: AtomicU32::new(0f32.to_bits()),
         })
     }
 
@@ -251,6 +255,9 @@ impl ResourceMonitor {
                 tokio::select! {
                     _ = tokio::time::sleep(interval) => {
                         sys.refresh_all();
+                        monitor
+                            .system_cpu_usage
+                            .store(sys.global_cpu_info().cpu_usage().to_bits(), Ordering::Relaxed);
                         let now = Instant::now();
                         let should_send_to_posthog = now.duration_since(last_posthog_update) >= posthog_interval;
 
@@ -267,6 +274,11 @@ impl ResourceMonitor {
         });
     }
 
+    /// CPU usage of the whole system, averaged since the previous refresh, in percent.
+    pub fn system_cpu_usage(&self) -> f32 {
+        f32::from_bits(self.system_cpu_usage.load(Ordering::Relaxed))
+    }
+
     // New method for logging without PostHog
     async fn log_status_local(&self, sys: &System) {
         let metrics = self.collect_metrics(sys).await;
@@ -0,0 +1,241 @@
+use crate::video_cache::FrameCache;
+use crate::video_utils::redact_video_frames;
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use oasgen::OaSchema;
+use screenpipe_db::{DatabaseManager, DeletedContent};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// How long each kind of recorded content is kept before it is deleted.
+/// `None` means the content is kept forever.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionConfig {
+    pub vision_days: Option<u64>,
+    pub audio_days: Option<u64>,
+    pub ui_days: Option<u64>,
+}
+
+impl RetentionConfig {
+    /// Builds a config from a global retention period and optional per-content-type overrides.
+    pub fn new(
+        retention_days: Option<u64>,
+        vision_days: Option<u64>,
+        audio_days: Option<u64>,
+        ui_days: Option<u64>,
+    ) -> Self {
+        Self {
+            vision_days: vision_days.or(retention_days),
+            audio_days: audio_days.or(retention_days),
+            ui_days: ui_days.or(retention_days),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.vision_days.is_some() || self.audio_days.is_some() || self.ui_days.is_some()
+    }
+}
+
+/// What a single retention pass removed.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RetentionReport {
+    pub frames_deleted: u64,
+    pub ocr_text_deleted: u64,
+    pub audio_transcriptions_deleted: u64,
+    pub ui_monitoring_deleted: u64,
+    pub video_files_deleted: usize,
+    pub audio_files_deleted: usize,
+    pub bytes_reclaimed: u64,
+    pub duration_ms: u128,
+}
+
+/// Deletes everything older than the configured retention periods, including the media files
+/// that are no longer referenced by the database.
+pub async fn run_retention_pass(
+    db: &DatabaseManager,
+    config: &RetentionConfig,
+) -> Result<RetentionReport> {
+    let start = Instant::now();
+    let mut deleted = DeletedContent::default();
+
+    if let Some(cutoff) = config.vision_days.and_then(cutoff_for) {
+        deleted.merge(db.delete_frames_before(cutoff).await?);
+    }
+    if let Some(cutoff) = config.audio_days.and_then(cutoff_for) {
+        deleted.merge(db.delete_audio_before(cutoff).await?);
+    }
+    if let Some(cutoff) = config.ui_days.and_then(cutoff_for) {
+        deleted.merge(db.delete_ui_monitoring_before(cutoff).await?);
+    }
+
+    let (video_files_deleted, video_bytes) = remove_media_files(&deleted.video_files).await;
+    let (audio_files_deleted, audio_bytes) = remove_media_files(&deleted.audio_files).await;
+
+    Ok(RetentionReport {
+        frames_deleted: deleted.frames,
+        ocr_text_deleted: deleted.ocr_text,
+        audio_transcriptions_deleted: deleted.audio_transcriptions,
+        ui_monitoring_deleted: deleted.ui_monitoring,
+        video_files_deleted,
+        audio_files_deleted,
+        bytes_reclaimed: video_bytes + audio_bytes,
+        duration_ms: start.elapsed().as_millis(),
+    })
+}
+
+/// What deleting a time range removed.
+#[derive(OaSchema, Debug, Default, Clone, Serialize)]
+pub struct TimeRangeDeletionReport {
+    pub frames_deleted: u64,
+    pub ocr_text_deleted: u64,
+    pub audio_transcriptions_deleted: u64,
+    pub ui_monitoring_deleted: u64,
+    pub video_files_deleted: usize,
+    pub video_files_redacted: usize,
+    pub audio_files_deleted: usize,
+    pub bytes_reclaimed: u64,
+    /// Video files whose deleted frames could not be blanked out, e.g. because they are still
+    /// being recorded. Running the deletion again once they are finished is safe.
+    pub failed_video_files: Vec<String>,
+    pub duration_ms: u64,
+}
+
+/// Erases everything captured between `start` and `end`. Rows are removed from the database,
+/// media files that only held data from the range are deleted, and video files that also hold
+/// frames outside the range are re-encoded with the deleted frames blanked out.
+pub async fn delete_time_range(
+    db: &DatabaseManager,
+    frame_cache: Option<&FrameCache>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<TimeRangeDeletionReport> {
+    let started = Instant::now();
+    let deleted = db.delete_time_range(start, end).await?;
+
+    let (video_files_deleted, video_bytes) = remove_media_files(&deleted.video_files).await;
+    let (audio_files_deleted, audio_bytes) = remove_media_files(&deleted.audio_files).await;
+
+    let mut video_files_redacted = 0;
+    let mut failed_video_files = Vec::new();
+    for partial in &deleted.partial_video_files {
+        match redact_video_frames(&partial.file_path, &partial.offset_indexes).await {
+            Ok(_) => video_files_redacted += 1,
+            Err(e) => {
+                warn!("failed to redact frames in {}: {}", partial.file_path, e);
+                failed_video_files.push(partial.file_path.clone());
+            }
+        }
+    }
+
+    if let Some(frame_cache) = frame_cache {
+        if let Err(e) = frame_cache.invalidate_range(start, end).await {
+            warn!(
+                "failed to remove deleted frames from the frame cache: {}",
+                e
+            );
+        }
+    }
+
+    let report = TimeRangeDeletionReport {
+        frames_deleted: deleted.frames,
+        ocr_text_deleted: deleted.ocr_text,
+        audio_transcriptions_deleted: deleted.audio_transcriptions,
+        ui_monitoring_deleted: deleted.ui_monitoring,
+        video_files_deleted,
+        video_files_redacted,
+        audio_files_deleted,
+        bytes_reclaimed: video_bytes + audio_bytes,
+        failed_video_files,
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+
+    info!(
+        "deleted data between {} and {}: {} frames, {} audio transcriptions, {} ui entries, {} video files removed, {} redacted",
+        start,
+        end,
+        report.frames_deleted,
+        report.audio_transcriptions_deleted,
+        report.ui_monitoring_deleted,
+        report.video_files_deleted,
+        report.video_files_redacted
+    );
+
+    Ok(report)
+}
+
+/// Runs a retention pass right away and then every `interval` until the task is aborted.
+pub fn start_retention_task(
+    db: Arc<DatabaseManager>,
+    config: RetentionConfig,
+    interval: Duration,
+) -> JoinHandle<()> {
+    info!(
+        "starting data retention task (vision: {:?} days, audio: {:?} days, ui: {:?} days)",
+        config.vision_days, config.audio_days, config.ui_days
+    );
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+
+            match run_retention_pass(&db, &config).await {
+                Ok(report) => {
+                    info!(
+                        "retention pass removed {} frames, {} ocr texts, {} audio transcriptions, {} ui entries, {} video files and {} audio files, reclaimed {:.2} MB in {}ms",
+                        report.frames_deleted,
+                        report.ocr_text_deleted,
+                        report.audio_transcriptions_deleted,
+                        report.ui_monitoring_deleted,
+                        report.video_files_deleted,
+                        report.audio_files_deleted,
+                        report.bytes_reclaimed as f64 / (1024.0 * 1024.0),
+                        report.duration_ms
+                    );
+                }
+                Err(e) => error!("retention pass failed: {}", e),
+            }
+        }
+    })
+}
+
+/// Removes the given files from disk, returning how many were removed and how many bytes that freed.
+/// Files that are already gone are skipped silently.
+pub(crate) async fn remove_media_files(paths: &[String]) -> (usize, u64) {
+    let mut removed = 0;
+    let mut bytes = 0;
+
+    for path in paths {
+        let size = match tokio::fs::metadata(path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("media file already removed: {}", path);
+                continue;
+            }
+            Err(e) => {
+                warn!("failed to read metadata for {}: {}", path, e);
+                0
+            }
+        };
+
+        match tokio::fs::remove_file(path).await {
+            Ok(_) => {
+                removed += 1;
+                bytes += size;
+            }
+            Err(e) => warn!("failed to remove media file {}: {}", path, e),
+        }
+    }
+
+    (removed, bytes)
+}
+
+/// Oldest timestamp to keep for a retention period, or `None` if the period reaches
+/// further back than chrono can represent (i.e. nothing is old enough to delete).
+fn cutoff_for(days: u64) -> Option<DateTime<Utc>> {
+    let days = ChronoDuration::try_days(i64::try_from(days).ok()?)?;
+    Utc::now().checked_sub_signed(days)
+}
@@ -2,21 +2,22 @@ use axum::{
     body::Body,
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        Json, Path, Query, State,
+        DefaultBodyLimit, Json, Path, Query, State,
     },
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Json as JsonResponse, Response},
     routing::get,
     serve, Router,
 };
 use oasgen::{oasgen, OaSchema, Server};
 
+use screenpipe_core::encryption::{media_encryption_key, read_media_file};
 use screenpipe_core::Desktop;
 
 use chrono::TimeZone;
 use screenpipe_db::{
-    ContentType, DatabaseManager, FrameData, Order, SearchMatch, SearchResult, Speaker,
-    TagContentType,
+    ContentType, DatabaseManager, FrameData, Order, RawSqlOptions, SearchMatch, SearchResult,
+    Speaker, TagContentType, TranscriptionSpan,
 };
 
 use tokio_util::io::ReaderStream;
@@ -32,7 +33,12 @@ use screenpipe_events::{send_event, subscribe_to_all_events, Event as Screenpipe
 
 use crate::{
     embedding::embedding_endpoint::create_embeddings,
-    video::{finish_ffmpeg_process, start_ffmpeg_process, write_frame_to_ffmpeg, MAX_FPS},
+    mcp,
+    retention::{delete_time_range, TimeRangeDeletionReport},
+    transcript::{render_transcript, stitch_transcript, TranscriptFormat},
+    video::{
+        finish_ffmpeg_process, start_ffmpeg_process, write_frame_to_ffmpeg, FrameTiming, MAX_FPS,
+    },
     video_cache::{AudioEntry, DeviceFrame, FrameCache, FrameMetadata, TimeSeriesFrame},
     video_utils::{
         extract_frame, extract_frame_from_video, extract_high_quality_frame, merge_videos,
@@ -40,20 +46,32 @@ use crate::{
     },
     PipeManager,
 };
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use chrono::{DateTime, Utc};
 use screenpipe_audio::{
     audio_manager::AudioManager,
-    core::device::{
-        default_input_device, default_output_device, list_audio_devices, AudioDevice, DeviceType,
+    core::{
+        device::{
+            default_input_device, default_output_device, list_audio_devices, AudioDevice,
+            DeviceType,
+        },
+        engine::AudioTranscriptionEngine,
+    },
+    speaker::{
+        clustering::{recluster_speakers, DEFAULT_CLUSTERING_THRESHOLD},
+        enrollment::SpeakerClip,
     },
+    transcription::retranscription::RetranscriptionCommand,
 };
 use tracing::{debug, error, info};
 
+use screenpipe_vision::capture_rate::CaptureRate;
 use screenpipe_vision::monitor::{get_monitor_by_id, list_monitors};
 use screenpipe_vision::OcrEngine;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{json, Value};
 use std::{
+    io::Write,
     net::SocketAddr,
     num::NonZeroUsize,
     path::PathBuf,
@@ -75,10 +93,12 @@ use tower_http::{cors::CorsLayer, trace::DefaultMakeSpan};
 use enigo::{Enigo, Key, Settings};
 use std::str::FromStr;
 
-use crate::text_embeds::generate_embedding;
+use crate::text_embeds::{embedding_backend, generate_embedding};
 
 use screenpipe_core::UIElement;
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
+use subtle::ConstantTimeEq;
 use uuid::Uuid; // or sentry::protocol::Uuid depending on which you want to use
 
 pub type FrameImageCache = LruCache<i64, (String, Instant)>;
@@ -95,6 +115,9 @@ pub struct AppState {
     pub frame_cache: Option<Arc<FrameCache>>,
     pub frame_image_cache: Option<Arc<Mutex<FrameImageCache>>>,
     pub element_cache: Arc<Mutex<Option<(Vec<UIElement>, Instant, String)>>>,
+    /// Lets `/raw_sql` callers that send it modify the database.
+    pub admin_token: Option<String>,
+    pub capture_rate: Option<Arc<CaptureRate>>,
 }
 
 // Update the SearchQuery struct
@@ -130,8 +153,23 @@ pub(crate) struct SearchQuery {
     focused: Option<bool>,
     #[serde(default)]
     browser_url: Option<String>,
+    #[serde(default)]
+    mode: SearchMode,
+}
+
+#[derive(OaSchema, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Full text search, newest first
+    #[default]
+    Keyword,
+    /// Full text and embedding search merged with reciprocal-rank fusion, most relevant first
+    Hybrid,
 }
 
+// hybrid search is more permissive than /semantic-search, fusion already ranks weak matches low
+const HYBRID_SEARCH_MAX_DISTANCE: f32 = 0.5;
+
 #[derive(OaSchema, Deserialize)]
 pub(crate) struct PaginationQuery {
     #[serde(default = "default_limit")]
@@ -208,6 +246,8 @@ pub struct OCRContent {
     pub frame_name: Option<String>,
     pub browser_url: Option<String>,
     pub focused: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
 }
 
 #[derive(OaSchema, Serialize, Deserialize, Debug)]
@@ -223,6 +263,11 @@ pub struct AudioContent {
     pub speaker: Option<Speaker>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// Timing of the part of the transcription that matched the query, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matched_segment: Option<TranscriptionSpan>,
 }
 
 #[derive(OaSchema, Serialize, Deserialize, Debug)]
@@ -237,6 +282,8 @@ pub struct UiContent {
     pub offset_index: i64,
     pub frame_name: Option<String>,
     pub browser_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
 }
 
 #[derive(OaSchema, Serialize)]
@@ -292,6 +339,27 @@ pub struct HealthCheckResponse {
     pub message: String,
     pub verbose_instructions: Option<String>,
     pub device_status_details: Option<String>,
+    pub capture_rate: Option<CaptureRateStatus>,
+}
+
+/// Rate the screens are captured at, which changes with activity, power and CPU usage when
+/// adaptive.
+#[derive(Serialize, OaSchema, Deserialize)]
+pub struct CaptureRateStatus {
+    pub adaptive: bool,
+    pub min_fps: f64,
+    pub max_fps: f64,
+    /// Highest rate allowed right now
+    pub ceiling_fps: f64,
+    pub on_battery: bool,
+    pub cpu_usage: f32,
+    pub monitors: Vec<MonitorCaptureRate>,
+}
+
+#[derive(Serialize, OaSchema, Deserialize)]
+pub struct MonitorCaptureRate {
+    pub monitor_id: u32,
+    pub fps: f64,
 }
 
 #[derive(OaSchema, Serialize, Deserialize)]
@@ -307,7 +375,7 @@ pub(crate) async fn search(
     State(state): State<Arc<AppState>>,
 ) -> Result<JsonResponse<SearchResponse>, (StatusCode, JsonResponse<serde_json::Value>)> {
     info!(
-        "received search request: query='{}', content_type={:?}, limit={}, offset={}, start_time={:?}, end_time={:?}, app_name={:?}, window_name={:?}, min_length={:?}, max_length={:?}, speaker_ids={:?}, frame_name={:?}, browser_url={:?}, focused={:?}",
+        "received search request: query='{}', content_type={:?}, limit={}, offset={}, start_time={:?}, end_time={:?}, app_name={:?}, window_name={:?}, min_length={:?}, max_length={:?}, speaker_ids={:?}, frame_name={:?}, browser_url={:?}, focused={:?}, mode={:?}",
         query.q.as_deref().unwrap_or(""),
         query.content_type,
         query.pagination.limit,
@@ -322,56 +390,80 @@ pub(crate) async fn search(
         query.frame_name,
         query.browser_url,
         query.focused,
+        query.mode,
     );
 
     let query_str = query.q.as_deref().unwrap_or("");
 
     let content_type = query.content_type.clone();
 
-    let (results, total) = try_join(
-        state.db.search(
-            query_str,
-            content_type.clone(),
-            query.pagination.limit,
-            query.pagination.offset,
-            query.start_time,
-            query.end_time,
-            query.app_name.as_deref(),
-            query.window_name.as_deref(),
-            query.min_length,
-            query.max_length,
-            query.speaker_ids.clone(),
-            query.frame_name.as_deref(),
-            query.browser_url.as_deref(),
-            query.focused,
-        ),
-        state.db.count_search_results(
-            query_str,
-            content_type,
-            query.start_time,
-            query.end_time,
-            query.app_name.as_deref(),
-            query.window_name.as_deref(),
-            query.min_length,
-            query.max_length,
-            query.speaker_ids.clone(),
-            query.frame_name.as_deref(),
-            query.browser_url.as_deref(),
-            query.focused,
-        ),
-    )
-    .await
-    .map_err(|e| {
-        error!("failed to perform search operations: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            JsonResponse(json!({"error": format!("failed to perform search operations: {}", e)})),
-        )
-    })?;
+    let (results, total) = match query.mode {
+        SearchMode::Keyword => {
+            let (results, total) = try_join(
+                state.db.search(
+                    query_str,
+                    content_type.clone(),
+                    query.pagination.limit,
+                    query.pagination.offset,
+                    query.start_time,
+                    query.end_time,
+                    query.app_name.as_deref(),
+                    query.window_name.as_deref(),
+                    query.min_length,
+                    query.max_length,
+                    query.speaker_ids.clone(),
+                    query.frame_name.as_deref(),
+                    query.browser_url.as_deref(),
+                    query.focused,
+                ),
+                state.db.count_search_results(
+                    query_str,
+                    content_type,
+                    query.start_time,
+                    query.end_time,
+                    query.app_name.as_deref(),
+                    query.window_name.as_deref(),
+                    query.min_length,
+                    query.max_length,
+                    query.speaker_ids.clone(),
+                    query.frame_name.as_deref(),
+                    query.browser_url.as_deref(),
+                    query.focused,
+                ),
+            )
+            .await
+            .map_err(|e| {
+                error!("failed to perform search operations: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    JsonResponse(
+                        json!({"error": format!("failed to perform search operations: {}", e)}),
+                    ),
+                )
+            })?;
+            (
+                results
+                    .into_iter()
+                    .map(|result| (result, None))
+                    .collect::<Vec<_>>(),
+                total as i64,
+            )
+        }
+        SearchMode::Hybrid => {
+            let (results, total) = hybrid_search(&state, &query).await?;
+            (
+                results
+                    .into_iter()
+                    .map(|(result, score)| (result, Some(score)))
+                    .collect::<Vec<_>>(),
+                total as i64,
+            )
+        }
+    };
 
     let mut content_items: Vec<ContentItem> = results
         .iter()
-        .map(|result| match result {
+        .map(|(result, score)| match result {
             SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
                 frame_id: ocr.frame_id,
                 text: ocr.ocr_text.clone(),
@@ -385,6 +477,7 @@ pub(crate) async fn search(
                 frame_name: Some(ocr.frame_name.clone()),
                 browser_url: ocr.browser_url.clone(),
                 focused: ocr.focused,
+                score: *score,
             }),
             SearchResult::Audio(audio) => ContentItem::Audio(AudioContent {
                 chunk_id: audio.audio_chunk_id,
@@ -398,6 +491,8 @@ pub(crate) async fn search(
                 speaker: audio.speaker.clone(),
                 start_time: audio.start_time,
                 end_time: audio.end_time,
+                score: *score,
+                matched_segment: audio.matched_segment.clone(),
             }),
             SearchResult::UI(ui) => ContentItem::UI(UiContent {
                 id: ui.id,
@@ -410,6 +505,7 @@ pub(crate) async fn search(
                 offset_index: ui.offset_index,
                 frame_name: ui.frame_name.clone(),
                 browser_url: ui.browser_url.clone(),
+                score: *score,
             }),
         })
         .collect();
@@ -445,11 +541,62 @@ pub(crate) async fn search(
         pagination: PaginationInfo {
             limit: query.pagination.limit,
             offset: query.pagination.offset,
-            total: total as i64,
+            total,
         },
     }))
 }
 
+async fn hybrid_search(
+    state: &AppState,
+    query: &SearchQuery,
+) -> Result<(Vec<(SearchResult, f64)>, usize), (StatusCode, JsonResponse<serde_json::Value>)> {
+    let query_str = query.q.as_deref().unwrap_or("").trim();
+    if query_str.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "q is required for hybrid search"})),
+        ));
+    }
+
+    let embedding = generate_embedding(query_str, 0).await.map_err(|e| {
+        error!("failed to generate embedding: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("failed to generate embedding: {}", e)})),
+        )
+    })?;
+
+    state
+        .db
+        .search_hybrid(
+            query_str,
+            embedding_backend().model_name(),
+            &embedding,
+            HYBRID_SEARCH_MAX_DISTANCE,
+            query.content_type.clone(),
+            query.pagination.limit,
+            query.pagination.offset,
+            query.start_time,
+            query.end_time,
+            query.app_name.as_deref(),
+            query.window_name.as_deref(),
+            query.min_length,
+            query.max_length,
+            query.speaker_ids.clone(),
+            query.frame_name.as_deref(),
+            query.browser_url.as_deref(),
+            query.focused,
+        )
+        .await
+        .map_err(|e| {
+            error!("failed to perform hybrid search: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("failed to perform hybrid search: {}", e)})),
+            )
+        })
+}
+
 #[oasgen]
 pub(crate) async fn api_list_audio_devices(
     State(_state): State<Arc<AppState>>,
@@ -767,6 +914,30 @@ pub async fn health_check(State(state): State<Arc<AppState>>) -> JsonResponse<He
         message,
         verbose_instructions,
         device_status_details,
+        capture_rate: state
+            .capture_rate
+            .as_ref()
+            .filter(|_| !state.vision_disabled)
+            .map(|capture_rate| {
+                let config = capture_rate.config();
+                CaptureRateStatus {
+                    adaptive: config.adaptive,
+                    min_fps: config.min_fps,
+                    max_fps: config.max_fps,
+                    ceiling_fps: if config.adaptive {
+                        capture_rate.ceiling()
+                    } else {
+                        config.fps
+                    },
+                    on_battery: capture_rate.on_battery(),
+                    cpu_usage: capture_rate.cpu_usage(),
+                    monitors: capture_rate
+                        .effective_fps()
+                        .into_iter()
+                        .map(|(monitor_id, fps)| MonitorCaptureRate { monitor_id, fps })
+                        .collect(),
+                }
+            }),
     })
 }
 
@@ -1042,6 +1213,9 @@ pub struct SCServer {
     vision_disabled: bool,
     audio_disabled: bool,
     ui_monitoring_enabled: bool,
+    admin_token: Option<String>,
+    capture_rate: Option<Arc<CaptureRate>>,
+    mcp_operator_tools: bool,
 }
 
 impl SCServer {
@@ -1055,6 +1229,9 @@ impl SCServer {
         audio_disabled: bool,
         ui_monitoring_enabled: bool,
         audio_manager: Arc<AudioManager>,
+        admin_token: Option<String>,
+        capture_rate: Option<Arc<CaptureRate>>,
+        mcp_operator_tools: bool,
     ) -> Self {
         SCServer {
             db,
@@ -1065,6 +1242,9 @@ impl SCServer {
             audio_disabled,
             ui_monitoring_enabled,
             audio_manager,
+            admin_token,
+            capture_rate,
+            mcp_operator_tools,
         }
     }
 
@@ -1114,6 +1294,8 @@ impl SCServer {
                 None
             },
             element_cache: Arc::new(Mutex::new(None)),
+            admin_token: self.admin_token.clone(),
+            capture_rate: self.capture_rate.clone(),
         });
 
         let cors = CorsLayer::new()
@@ -1127,6 +1309,7 @@ impl SCServer {
         let server = Server::axum()
             .get("/search", search)
             .get("/audio/list", api_list_audio_devices)
+            .get("/audio/transcript", get_audio_transcript)
             .get("/vision/list", api_list_monitors)
             .post("/tags/:content_type/:id", add_tags)
             .delete("/tags/:content_type/:id", remove_tags)
@@ -1144,12 +1327,14 @@ impl SCServer {
             .get("/health", health_check)
             .post("/raw_sql", execute_raw_sql)
             .post("/add", add_to_database)
+            .delete("/data", delete_data_handler)
             .get("/speakers/unnamed", get_unnamed_speakers_handler)
             .post("/speakers/update", update_speaker_handler)
             .get("/speakers/search", search_speakers_handler)
             .post("/speakers/delete", delete_speaker_handler)
             .post("/speakers/hallucination", mark_as_hallucination_handler)
             .post("/speakers/merge", merge_speakers_handler)
+            .post("/speakers/recluster", recluster_speakers_handler)
             .get("/speakers/similar", get_similar_speakers_handler)
             .post("/experimental/frames/merge", merge_frames_handler)
             .get("/experimental/validate/media", validate_media_handler)
@@ -1189,20 +1374,44 @@ impl SCServer {
             .post("/v1/embeddings", create_embeddings)
             .post("/audio/device/start", start_audio_device)
             .post("/audio/device/stop", stop_audio_device)
+            .post("/audio/retranscribe/start", start_retranscription)
+            .post("/audio/retranscribe/pause", pause_retranscription)
+            .post("/audio/retranscribe/stop", stop_retranscription)
+            .get("/audio/retranscribe/status", get_retranscription_status)
             .route_yaml_spec("/openapi.yaml")
             .route_json_spec("/openapi.json")
             .freeze();
 
         // Build the main router with all routes
-        Router::new()
+        let api = Router::new()
             .merge(server.into_router())
             // NOTE: websockerts and sse is not supported by openapi so we move it down here
             .route("/stream/frames", get(stream_frames_handler))
             .route("/ws/events", get(ws_events_handler))
             .route("/ws/health", get(ws_health_handler))
             .route("/frames/export", get(handle_video_export_ws))
-            .with_state(app_state)
+            // uploaded clips are base64 encoded in the body, so it lives outside the openapi
+            // routes to get a larger body limit
+            .route(
+                "/speakers/enroll",
+                axum::routing::post(enroll_speaker_handler)
+                    .layer(DefaultBodyLimit::max(ENROLL_BODY_LIMIT)),
+            );
+
+        // streamed as sse, so it lives outside the openapi routes too
+        #[cfg(feature = "llm")]
+        let api = api.route(
+            "/v1/chat/completions",
+            axum::routing::post(crate::llm::chat_endpoint::chat_completions),
+        );
+
+        let api = api.with_state(app_state);
+
+        // the mcp endpoint calls the api in-process, so it gets its own copy of the routes.
+        // it checks the origin itself and is merged after the cors layer so browsers can't reach it
+        api.clone()
             .layer(cors)
+            .merge(mcp::router(api, self.mcp_operator_tools))
             .layer(TraceLayer::new_for_http().make_span_with(DefaultMakeSpan::default()))
     }
 }
@@ -1243,17 +1452,193 @@ async fn validate_media_handler(
 #[derive(OaSchema, Deserialize)]
 struct RawSqlQuery {
     query: String,
+    /// Values bound to the `?` placeholders of the query, in order
+    #[serde(default)]
+    params: Vec<Value>,
+    /// At most this many rows are returned, capped at 10000
+    #[serde(default)]
+    max_rows: Option<usize>,
+    /// The query is interrupted after this long, capped at 10 seconds
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+    /// Return `{columns, rows, truncated}` instead of a bare array of rows. Only this shape
+    /// has the column types and tells whether rows were dropped
+    #[serde(default)]
+    include_columns: bool,
+    /// The server's `--admin-token`. Without it the query runs on a read-only connection and
+    /// is only accepted from native clients and local pages
+    #[serde(default)]
+    admin_token: Option<String>,
 }
 
+const RAW_SQL_MAX_ROWS: usize = 10_000;
+const RAW_SQL_MAX_TIMEOUT: Duration = Duration::from_secs(10);
+
+// SQLite primary result codes, see https://www.sqlite.org/rescode.html
+const SQLITE_ERROR: i32 = 1;
+const SQLITE_READONLY: i32 = 8;
+const SQLITE_INTERRUPT: i32 = 9;
+
 #[oasgen]
 async fn execute_raw_sql(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     JsonResponse(payload): JsonResponse<RawSqlQuery>,
 ) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
-    match state.db.execute_raw_sql(&payload.query).await {
-        Ok(result) => Ok(JsonResponse(result)),
+    let allow_writes = match (&state.admin_token, &payload.admin_token) {
+        // any page could otherwise read the whole history through the browser
+        (_, None) if !mcp::is_local_origin(&headers) => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                JsonResponse(
+                    json!({"error": "queries from other origins require the admin token"}),
+                ),
+            ));
+        }
+        (_, None) => false,
+        (Some(expected), Some(given)) if tokens_match(expected, given) => true,
+        _ => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                JsonResponse(json!({"error": "invalid admin token"})),
+            ));
+        }
+    };
+    let options = RawSqlOptions {
+        max_rows: payload
+            .max_rows
+            .unwrap_or(RAW_SQL_MAX_ROWS)
+            .min(RAW_SQL_MAX_ROWS),
+        timeout: payload
+            .timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(RAW_SQL_MAX_TIMEOUT)
+            .min(RAW_SQL_MAX_TIMEOUT),
+        allow_writes,
+    };
+
+    match state
+        .db
+        .query_raw_sql(&payload.query, &payload.params, &options)
+        .await
+    {
+        Ok(result) if payload.include_columns => Ok(JsonResponse(json!(result))),
+        Ok(result) => Ok(JsonResponse(Value::Array(result.rows))),
+        Err(e) => {
+            let code = e
+                .as_database_error()
+                .and_then(|e| e.code())
+                .and_then(|code| code.parse::<i32>().ok())
+                .map(|code| code & 0xff);
+            let (status, message) = match code {
+                Some(SQLITE_READONLY) => (
+                    StatusCode::FORBIDDEN,
+                    "the query modifies the database, which requires the admin token".to_string(),
+                ),
+                Some(SQLITE_INTERRUPT) => (
+                    StatusCode::REQUEST_TIMEOUT,
+                    format!(
+                        "the query was interrupted after {}ms",
+                        options.timeout.as_millis()
+                    ),
+                ),
+                Some(SQLITE_ERROR) => (StatusCode::BAD_REQUEST, e.to_string()),
+                _ => {
+                    error!("Failed to execute raw SQL query: {}", e);
+                    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                }
+            };
+            Err((status, JsonResponse(json!({"error": message}))))
+        }
+    }
+}
+
+/// Compares digests of the tokens in constant time, so neither the token nor its length can be
+/// guessed from response times.
+fn tokens_match(expected: &str, given: &str) -> bool {
+    let expected = Sha256::digest(expected.as_bytes());
+    let given = Sha256::digest(given.as_bytes());
+    expected.as_slice().ct_eq(given.as_slice()).into()
+}
+
+/// Rejects the request unless it comes from a native client or a local page, and the server has
+/// an `--admin-token` the caller sent.
+fn require_local_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+    given: Option<&str>,
+) -> Result<(), (StatusCode, JsonResponse<Value>)> {
+    if !mcp::is_local_origin(headers) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            JsonResponse(json!({"error": "origin not allowed"})),
+        ));
+    }
+    require_admin_token(state, given)
+}
+
+/// Rejects the request unless the server has an `--admin-token` and the caller sent it.
+fn require_admin_token(
+    state: &AppState,
+    given: Option<&str>,
+) -> Result<(), (StatusCode, JsonResponse<Value>)> {
+    match (&state.admin_token, given) {
+        (Some(expected), Some(given)) if tokens_match(expected, given) => Ok(()),
+        (None, _) => Err((
+            StatusCode::FORBIDDEN,
+            JsonResponse(
+                json!({"error": "this endpoint is disabled unless the server is started with --admin-token"}),
+            ),
+        )),
+        _ => Err((
+            StatusCode::UNAUTHORIZED,
+            JsonResponse(json!({"error": "invalid admin token"})),
+        )),
+    }
+}
+
+#[derive(OaSchema, Deserialize)]
+struct DeleteDataQuery {
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    /// The server's `--admin-token`, required because the deletion can't be undone
+    admin_token: Option<String>,
+}
+
+#[oasgen]
+async fn delete_data_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DeleteDataQuery>,
+) -> Result<JsonResponse<TimeRangeDeletionReport>, (StatusCode, JsonResponse<Value>)> {
+    require_admin_token(&state, query.admin_token.as_deref())?;
+    if query.start_time >= query.end_time {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "start_time must be before end_time"})),
+        ));
+    }
+
+    match delete_time_range(
+        &state.db,
+        state.frame_cache.as_deref(),
+        query.start_time,
+        query.end_time,
+    )
+    .await
+    {
+        Ok(report) => {
+            // extracted frames are cached by id only, so drop them all rather than serve deleted ones
+            if let Some(cache) = &state.frame_image_cache {
+                let mut cache = cache.lock().await;
+                for (_, (file_path, _)) in cache.iter() {
+                    let _ = tokio::fs::remove_file(file_path).await;
+                }
+                cache.clear();
+            }
+            Ok(JsonResponse(report))
+        }
         Err(e) => {
-            error!("Failed to execute raw SQL query: {}", e);
+            error!("Failed to delete data: {}", e);
             Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 JsonResponse(json!({"error": e.to_string()})),
@@ -1349,8 +1734,8 @@ async fn add_frame_to_db(
     Ok(())
 }
 
-fn encode_frame_from_file_path(file_path: &str) -> Result<Vec<u8>, anyhow::Error> {
-    let image = image::open(file_path)?;
+async fn encode_frame_from_file_path(file_path: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let image = image::load_from_memory(&read_media_file(file_path).await?)?;
     let mut buffer = Vec::new();
     image.write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)?;
     Ok(buffer)
@@ -1361,14 +1746,15 @@ async fn write_frames_to_video(
     video_file_path: &str,
     fps: f64,
 ) -> Result<(), anyhow::Error> {
-    let mut ffmpeg_child = start_ffmpeg_process(video_file_path, fps).await?;
+    let mut ffmpeg_child =
+        start_ffmpeg_process(video_file_path, FrameTiming::Constant(fps)).await?;
     let mut ffmpeg_stdin = ffmpeg_child
         .stdin
         .take()
         .expect("Failed to open stdin for FFmpeg");
 
     for frame in frames {
-        let encoded_frame = encode_frame_from_file_path(&frame.file_path)?;
+        let encoded_frame = encode_frame_from_file_path(&frame.file_path).await?;
         if let Err(e) = write_frame_to_ffmpeg(&mut ffmpeg_stdin, &encoded_frame).await {
             error!("Failed to write frame to FFmpeg: {}", e);
             return Err(e);
@@ -1860,6 +2246,124 @@ async fn merge_speakers_handler(
     Ok(JsonResponse(json!({"success": true})))
 }
 
+/// Largest body accepted by `/speakers/enroll`, leaving room for a few minutes of uploaded
+/// audio. Other routes keep axum's 2MB default.
+const ENROLL_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
+async fn enroll_speaker_handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<EnrollSpeakerRequest>,
+) -> Result<JsonResponse<Speaker>, (StatusCode, JsonResponse<Value>)> {
+    let bad_request = |error: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": error})),
+        )
+    };
+    if payload.speaker_id.is_none() && payload.name.is_none() {
+        return Err(bad_request(
+            "a name is required to enroll a new speaker".to_string(),
+        ));
+    }
+
+    let recorded_clips = state
+        .db
+        .get_speaker_audio_clips(&payload.audio_transcription_ids)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })?;
+    if recorded_clips.len() < payload.audio_transcription_ids.len() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": "audio transcription not found"})),
+        ));
+    }
+    let mut clips: Vec<SpeakerClip> = recorded_clips
+        .into_iter()
+        .map(|clip| SpeakerClip {
+            path: PathBuf::from(clip.file_path),
+            start_time: clip.start_time,
+            end_time: clip.end_time,
+        })
+        .collect();
+
+    // uploaded files only need to exist while the embeddings are computed
+    let mut uploads = Vec::new();
+    for audio_file in &payload.audio_files {
+        let bytes = BASE64
+            .decode(audio_file)
+            .map_err(|e| bad_request(format!("invalid audio file: {}", e)))?;
+        let mut upload = tempfile::NamedTempFile::new().map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })?;
+        upload.write_all(&bytes).map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })?;
+        clips.push(SpeakerClip {
+            path: upload.path().to_path_buf(),
+            start_time: None,
+            end_time: None,
+        });
+        uploads.push(upload);
+    }
+
+    let speaker = state
+        .audio_manager
+        .enroll_speaker(payload.speaker_id, payload.name, clips)
+        .await
+        .map_err(|e| match e.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": "speaker not found"})),
+            ),
+            _ => bad_request(e.to_string()),
+        })?;
+
+    Ok(JsonResponse(speaker))
+}
+
+#[oasgen]
+async fn recluster_speakers_handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ReclusterSpeakersRequest>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let threshold = payload.threshold.unwrap_or(DEFAULT_CLUSTERING_THRESHOLD);
+    if !(0.0..=2.0).contains(&threshold) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "threshold must be between 0 and 2"})),
+        ));
+    }
+
+    if payload.apply {
+        require_admin_token(&state, payload.admin_token.as_deref())?;
+    }
+
+    let merge_sets = recluster_speakers(&state.db, threshold, !payload.apply)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })?;
+
+    Ok(JsonResponse(json!({
+        "merge_sets": merge_sets,
+        "applied": payload.apply,
+    })))
+}
+
 #[oasgen]
 async fn get_similar_speakers_handler(
     State(state): State<Arc<AppState>>,
@@ -1881,6 +2385,61 @@ async fn get_similar_speakers_handler(
 
     Ok(JsonResponse(similar_speakers))
 }
+
+#[derive(OaSchema, Deserialize, Debug)]
+pub struct AudioTranscriptQuery {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    #[serde(default)]
+    format: TranscriptFormat,
+}
+
+#[oasgen]
+async fn get_audio_transcript(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AudioTranscriptQuery>,
+) -> Result<Response<Body>, (StatusCode, JsonResponse<Value>)> {
+    if query.start >= query.end {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "start must be before end"})),
+        ));
+    }
+
+    let internal_error = |e: sqlx::Error| {
+        error!("failed to build audio transcript: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": e.to_string()})),
+        )
+    };
+    let entries = state
+        .db
+        .get_transcript_entries(query.start, query.end)
+        .await
+        .map_err(internal_error)?;
+    let ids: Vec<i64> = entries.iter().map(|e| e.audio_transcription_id).collect();
+    let segments = state
+        .db
+        .get_transcription_segments(&ids)
+        .await
+        .map_err(internal_error)?;
+
+    let lines = stitch_transcript(&entries, &segments);
+    Response::builder()
+        .header("content-type", query.format.content_type())
+        .body(Body::from(render_transcript(
+            &lines,
+            query.format,
+            query.start,
+        )))
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to create response: {}", e)})),
+            )
+        })
+}
 // #[derive(OaSchema, Deserialize)]
 // pub struct AudioDeviceControlRequest {
 //     device_name: String,
@@ -2024,7 +2583,12 @@ async fn semantic_search_handler(
     // Search database for similar embeddings
     match state
         .db
-        .search_similar_embeddings(embedding, limit, threshold)
+        .search_similar_embeddings(
+            embedding_backend().model_name(),
+            embedding,
+            limit,
+            threshold,
+        )
         .await
     {
         Ok(results) => {
@@ -2332,6 +2896,80 @@ async fn stop_audio(
     }
 }
 
+#[derive(OaSchema, Deserialize)]
+pub(crate) struct RetranscribeRequest {
+    /// Engine to re-transcribe with, e.g. "WhisperLargeV3Turbo". Defaults to the engine
+    /// audio is recorded with.
+    #[serde(default)]
+    engine: Option<String>,
+    /// The server's `--admin-token`, required because stored transcriptions are replaced
+    #[serde(default)]
+    admin_token: Option<String>,
+}
+
+#[derive(OaSchema, Deserialize)]
+pub(crate) struct AdminTokenQuery {
+    /// The server's `--admin-token`
+    admin_token: Option<String>,
+}
+
+async fn send_retranscription_command(
+    state: &AppState,
+    command: RetranscriptionCommand,
+    engine: Option<AudioTranscriptionEngine>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    match state.audio_manager.retranscribe(command, engine).await {
+        Ok(status) => Ok(JsonResponse(json!(status))),
+        Err(e) => Err((
+            StatusCode::CONFLICT,
+            JsonResponse(json!({"error": e.to_string()})),
+        )),
+    }
+}
+
+#[oasgen]
+async fn start_retranscription(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(payload): Json<RetranscribeRequest>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    require_local_admin(&state, &headers, payload.admin_token.as_deref())?;
+    let engine = payload
+        .engine
+        .as_deref()
+        .map(AudioTranscriptionEngine::from_str)
+        .transpose()
+        .map_err(|e| (StatusCode::BAD_REQUEST, JsonResponse(json!({"error": e}))))?;
+    send_retranscription_command(&state, RetranscriptionCommand::Start, engine).await
+}
+
+#[oasgen]
+async fn pause_retranscription(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<AdminTokenQuery>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    require_local_admin(&state, &headers, query.admin_token.as_deref())?;
+    send_retranscription_command(&state, RetranscriptionCommand::Pause, None).await
+}
+
+#[oasgen]
+async fn stop_retranscription(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<AdminTokenQuery>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    require_local_admin(&state, &headers, query.admin_token.as_deref())?;
+    send_retranscription_command(&state, RetranscriptionCommand::Stop, None).await
+}
+
+#[oasgen]
+async fn get_retranscription_status(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    send_retranscription_command(&state, RetranscriptionCommand::Status, None).await
+}
+
 pub async fn handle_video_export_ws(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
@@ -2484,7 +3122,8 @@ async fn handle_video_export(
 
     // Create video
     match write_frames_to_video(&frames, output_path.to_str().unwrap(), payload.fps).await {
-        Ok(_) => match tokio::fs::read(&output_path).await {
+        // the video is encrypted like any other written by ffmpeg when recording is
+        Ok(_) => match read_media_file(output_path.to_str().unwrap()).await {
             Ok(video_data) => {
                 let _ = socket
                     .send(Message::Text(
@@ -2824,6 +3463,10 @@ pub async fn get_frame_data(
 }
 
 async fn serve_file(path: &str) -> Result<Response, (StatusCode, JsonResponse<Value>)> {
+    if media_encryption_key().is_some() {
+        return serve_sealed_file(path).await;
+    }
+
     match File::open(path).await {
         Ok(file) => {
             let stream = ReaderStream::new(file);
@@ -2849,6 +3492,27 @@ async fn serve_file(path: &str) -> Result<Response, (StatusCode, JsonResponse<Va
     }
 }
 
+/// Extracted frames are encrypted at rest when recording is, they are only decrypted in memory.
+async fn serve_sealed_file(path: &str) -> Result<Response, (StatusCode, JsonResponse<Value>)> {
+    let data = read_media_file(path).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to read file: {}", e)})),
+        )
+    })?;
+
+    Response::builder()
+        .header("content-type", "image/jpeg")
+        .header("cache-control", "no-store")
+        .body(Body::from(data))
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to create response: {}", e)})),
+            )
+        })
+}
+
 // Add these new functions before stream_frames_handler
 async fn fetch_and_process_frames(
     db: Arc<DatabaseManager>,
@@ -3093,6 +3757,33 @@ struct MergeSpeakersRequest {
     speaker_to_merge_id: i64,
 }
 
+#[derive(OaSchema, Deserialize, Debug)]
+struct EnrollSpeakerRequest {
+    /// Speaker to add the voice to, a new speaker is created if missing
+    speaker_id: Option<i64>,
+    /// Name of the speaker, required for a new speaker
+    name: Option<String>,
+    /// Transcriptions in which only this speaker is heard
+    #[serde(default)]
+    audio_transcription_ids: Vec<i64>,
+    /// Base64 encoded audio files of the speaker, e.g. wav or mp3
+    #[serde(default)]
+    audio_files: Vec<String>,
+}
+
+#[derive(OaSchema, Deserialize, Debug)]
+struct ReclusterSpeakersRequest {
+    /// Average cosine distance under which speakers are merged, 0.4 by default
+    threshold: Option<f64>,
+    /// Merge the speakers, which can't be undone. Otherwise only the speakers that would be
+    /// merged are returned
+    #[serde(default)]
+    apply: bool,
+    /// The server's `--admin-token`, required with `apply`
+    #[serde(default)]
+    admin_token: Option<String>,
+}
+
 #[derive(Debug, OaSchema, Deserialize)]
 pub struct PurgePipeRequest {}
 
@@ -1,49 +1,155 @@
 use anyhow::Result;
+use once_cell::sync::OnceCell;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info};
+use tracing::{debug, error};
+
+use crate::embedding::embedding_endpoint::get_or_initialize_model;
+
+pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+pub const DEFAULT_OLLAMA_EMBEDDING_MODEL: &str = "nomic-embed-text";
+/// Model of [`EmbeddingBackend::Local`], as stored with its embeddings.
+pub const LOCAL_EMBEDDING_MODEL: &str = "jinaai/jina-embeddings-v2-base-en";
+
+static EMBEDDING_BACKEND: OnceCell<EmbeddingBackend> = OnceCell::new();
+
+/// Where text embeddings are generated. Embeddings from different models live in different
+/// vector spaces, so each is stored with its [`model_name`](Self::model_name) and only the
+/// current model's are searched; the embedding worker redoes the others.
+#[derive(Debug, Clone, Default)]
+pub enum EmbeddingBackend {
+    /// jina-embeddings-v2-base-en running in-process.
+    #[default]
+    Local,
+    /// An Ollama server, e.g. with nomic-embed-text.
+    Ollama { url: String, model: String },
+}
+
+impl std::fmt::Display for EmbeddingBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::Ollama { url, model } => write!(f, "ollama ({} at {})", model, url),
+        }
+    }
+}
+
+/// The backend can't be reached at all, e.g. Ollama is not running, as opposed to failing on
+/// particular texts.
+#[derive(Debug)]
+pub struct BackendUnreachable(String);
+
+impl std::fmt::Display for BackendUnreachable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "embedding backend unreachable: {}", self.0)
+    }
+}
+
+impl std::error::Error for BackendUnreachable {}
 
 #[derive(Debug, Serialize)]
-struct OllamaRequest {
-    model: String,
-    prompt: String,
+struct OllamaRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
 }
 
 #[derive(Debug, Deserialize)]
 struct OllamaResponse {
-    embedding: Vec<f32>,
+    embeddings: Vec<Vec<f32>>,
 }
 
-/// Generates embeddings for text using Ollama's nomic-embed-text model
-pub async fn generate_embedding(text: &str, frame_id: i64) -> Result<Vec<f32>> {
-    let client = Client::new();
-    
-    debug!("generating embedding for frame_id: {}, text: {}", frame_id, text);
-
-    // Check if Ollama server is running
-    if let Err(e) = client.get("http://localhost:11434/api/version").send().await {
-        error!("ollama server not running: {}", e);
-        return Err(anyhow::anyhow!("ollama server not running"));
+impl EmbeddingBackend {
+    /// Name of the model embeddings are made with. It is stored with each embedding, so that
+    /// embeddings of different models are never compared.
+    pub fn model_name(&self) -> &str {
+        match self {
+            Self::Local => LOCAL_EMBEDDING_MODEL,
+            Self::Ollama { model, .. } => model,
+        }
     }
 
-    let request = OllamaRequest {
-        model: "nomic-embed-text".to_string(),
-        prompt: text.to_string(),
-    };
+    /// Generates one embedding per text, in order. Fails with [`BackendUnreachable`] if the
+    /// backend can't be used at all.
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
 
-    let response = client
-        .post("http://localhost:11434/api/embeddings")
-        .json(&request)
-        .send()
-        .await?;
+        match self {
+            Self::Local => {
+                let model = get_or_initialize_model()
+                    .await
+                    .map_err(|e| BackendUnreachable(format!("failed to load the model: {}", e)))?;
+                let texts = texts.to_vec();
+                // inference is cpu/gpu bound, keep it off the async workers
+                tokio::task::spawn_blocking(move || {
+                    let model = model.blocking_lock();
+                    if texts.len() == 1 {
+                        Ok(vec![model.generate_embedding(&texts[0])?])
+                    } else {
+                        model.generate_batch_embeddings(&texts)
+                    }
+                })
+                .await?
+            }
+            Self::Ollama { url, model } => {
+                let response = Client::new()
+                    .post(format!("{}/api/embed", url.trim_end_matches('/')))
+                    .json(&OllamaRequest {
+                        model,
+                        input: texts,
+                    })
+                    .send()
+                    .await
+                    .map_err(|e| {
+                        error!("ollama server not reachable at {}: {}", url, e);
+                        BackendUnreachable(format!("ollama server not reachable at {}", url))
+                    })?;
 
-    if !response.status().is_success() {
-        error!("failed to generate embedding: {}", response.status());
-        return Err(anyhow::anyhow!("failed to generate embedding"));
+                if !response.status().is_success() {
+                    error!("failed to generate embedding: {}", response.status());
+                    return Err(anyhow::anyhow!(
+                        "ollama returned {} when generating embeddings",
+                        response.status()
+                    ));
+                }
+
+                let response = response.json::<OllamaResponse>().await?;
+                if response.embeddings.len() != texts.len() {
+                    return Err(anyhow::anyhow!(
+                        "ollama returned {} embeddings for {} texts",
+                        response.embeddings.len(),
+                        texts.len()
+                    ));
+                }
+                Ok(response.embeddings)
+            }
+        }
     }
+}
+
+/// Sets the backend used for embeddings for the rest of the process. Defaults to
+/// [`EmbeddingBackend::Local`] if never called.
+pub fn set_embedding_backend(backend: EmbeddingBackend) -> Result<()> {
+    EMBEDDING_BACKEND
+        .set(backend)
+        .map_err(|_| anyhow::anyhow!("embedding backend is already set"))
+}
+
+pub fn embedding_backend() -> &'static EmbeddingBackend {
+    EMBEDDING_BACKEND.get_or_init(EmbeddingBackend::default)
+}
+
+/// Generates an embedding for text with the configured backend
+pub async fn generate_embedding(text: &str, frame_id: i64) -> Result<Vec<f32>> {
+    debug!(
+        "generating embedding for frame_id: {}, text: {}",
+        frame_id, text
+    );
 
-    let embedding = response.json::<OllamaResponse>().await?;
-    info!("generated embedding for frame_id: {}", frame_id);
-    
-    Ok(embedding.embedding)
+    embedding_backend()
+        .embed(&[text.to_string()])
+        .await?
+        .pop()
+        .ok_or_else(|| anyhow::anyhow!("no embedding generated"))
 }
@@ -0,0 +1,216 @@
+use chrono::{DateTime, Duration, Utc};
+use oasgen::OaSchema;
+use screenpipe_db::{normalize_word, TranscriptEntry, TranscriptionSegment};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Consecutive chunks of a device overlap by about two seconds, so the start of a
+/// transcription can repeat up to this many words of the previous one.
+const MAX_OVERLAP_WORDS: usize = 12;
+
+#[derive(OaSchema, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    #[default]
+    Srt,
+    Vtt,
+    Txt,
+    Json,
+}
+
+impl TranscriptFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Srt => "application/x-subrip; charset=utf-8",
+            TranscriptFormat::Vtt => "text/vtt; charset=utf-8",
+            TranscriptFormat::Txt => "text/plain; charset=utf-8",
+            TranscriptFormat::Json => "application/json",
+        }
+    }
+}
+
+/// One speaker-labelled line of a transcript.
+#[derive(OaSchema, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptLine {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub speaker: String,
+    pub speaker_id: Option<i64>,
+    pub device_name: String,
+    pub text: String,
+}
+
+/// Merges the transcriptions of all devices into one transcript ordered by time.
+///
+/// Transcriptions with stored segments become one line per segment. Words a transcription
+/// repeats from the end of the previous one on the same device, because the chunks overlap,
+/// are dropped.
+pub fn stitch_transcript(
+    entries: &[TranscriptEntry],
+    segments: &HashMap<i64, Vec<TranscriptionSegment>>,
+) -> Vec<TranscriptLine> {
+    let mut lines = Vec::new();
+    // the last words said on each device
+    let mut previous_words: HashMap<&str, Vec<String>> = HashMap::new();
+
+    for entry in entries {
+        let pieces: Vec<(Option<f64>, Option<f64>, &str)> =
+            match segments.get(&entry.audio_transcription_id) {
+                Some(segments) if !segments.is_empty() => segments
+                    .iter()
+                    .map(|segment| {
+                        (
+                            Some(segment.start_time),
+                            Some(segment.end_time),
+                            segment.text.as_str(),
+                        )
+                    })
+                    .collect(),
+                _ => vec![(
+                    entry.start_time,
+                    entry.end_time,
+                    entry.transcription.as_str(),
+                )],
+            };
+
+        for (start_time, end_time, text) in pieces {
+            let previous = previous_words
+                .entry(entry.device_name.as_str())
+                .or_default();
+            let words: Vec<&str> = text.split_whitespace().collect();
+            let words = &words[overlapping_words(previous, &words)..];
+            if words.is_empty() {
+                continue;
+            }
+
+            previous.extend(words.iter().map(|word| normalize_word(word)));
+            let excess = previous.len().saturating_sub(MAX_OVERLAP_WORDS);
+            previous.drain(..excess);
+
+            let start = offset_time(entry.chunk_timestamp, start_time).unwrap_or(entry.timestamp);
+            let end = offset_time(entry.chunk_timestamp, end_time)
+                .unwrap_or(start)
+                .max(start);
+            lines.push(TranscriptLine {
+                start,
+                end,
+                speaker: speaker_label(entry),
+                speaker_id: entry.speaker_id,
+                device_name: entry.device_name.clone(),
+                text: words.join(" "),
+            });
+        }
+    }
+
+    // stable, so lines of a device keep their order
+    lines.sort_by_key(|line| line.start);
+    lines
+}
+
+/// Renders a transcript, subtitle times are relative to `origin`.
+pub fn render_transcript(
+    lines: &[TranscriptLine],
+    format: TranscriptFormat,
+    origin: DateTime<Utc>,
+) -> String {
+    match format {
+        TranscriptFormat::Srt => lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                format!(
+                    "{}\n{} --> {}\n{}: {}\n\n",
+                    i + 1,
+                    cue_time(line.start, origin, ','),
+                    cue_time(line.end, origin, ','),
+                    line.speaker,
+                    line.text
+                )
+            })
+            .collect(),
+        TranscriptFormat::Vtt => {
+            let mut out = String::from("WEBVTT\n\n");
+            for line in lines {
+                out.push_str(&format!(
+                    "{} --> {}\n<v {}>{}\n\n",
+                    cue_time(line.start, origin, '.'),
+                    cue_time(line.end, origin, '.'),
+                    escape_vtt(&line.speaker),
+                    escape_vtt(&line.text)
+                ));
+            }
+            out
+        }
+        TranscriptFormat::Txt => {
+            let mut out = String::new();
+            let mut previous_speaker: Option<&str> = None;
+            for line in lines {
+                // consecutive lines of a speaker make one paragraph
+                if previous_speaker == Some(line.speaker.as_str()) {
+                    out.pop();
+                    out.push(' ');
+                } else {
+                    if previous_speaker.is_some() {
+                        out.push('\n');
+                    }
+                    let time = cue_time(line.start, origin, '.');
+                    let time = time
+                        .rsplit_once('.')
+                        .map_or(time.as_str(), |(time, _)| time);
+                    out.push_str(&format!("[{}] {}: ", time, line.speaker));
+                }
+                out.push_str(&line.text);
+                out.push('\n');
+                previous_speaker = Some(line.speaker.as_str());
+            }
+            out
+        }
+        TranscriptFormat::Json => serde_json::to_string(lines).unwrap_or_else(|_| "[]".to_string()),
+    }
+}
+
+/// Number of words at the start of `words` that repeat the end of `previous`. Single words
+/// are left alone, people do repeat themselves.
+fn overlapping_words(previous: &[String], words: &[&str]) -> usize {
+    let max = MAX_OVERLAP_WORDS.min(previous.len()).min(words.len());
+    (2..=max)
+        .rev()
+        .find(|&n| {
+            previous[previous.len() - n..]
+                .iter()
+                .zip(&words[..n])
+                .all(|(previous, word)| *previous == normalize_word(word))
+        })
+        .unwrap_or(0)
+}
+
+fn speaker_label(entry: &TranscriptEntry) -> String {
+    match (&entry.speaker_name, entry.speaker_id) {
+        (Some(name), _) if !name.is_empty() => name.clone(),
+        (_, Some(id)) => format!("Speaker {}", id),
+        _ => entry.device_name.clone(),
+    }
+}
+
+fn offset_time(chunk_timestamp: DateTime<Utc>, seconds: Option<f64>) -> Option<DateTime<Utc>> {
+    seconds.map(|seconds| chunk_timestamp + Duration::milliseconds((seconds * 1000.0) as i64))
+}
+
+/// Formats `time - origin` as HH:MM:SS followed by `separator` and milliseconds.
+fn cue_time(time: DateTime<Utc>, origin: DateTime<Utc>, separator: char) -> String {
+    let ms = (time - origin).num_milliseconds().max(0);
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
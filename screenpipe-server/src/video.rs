@@ -1,10 +1,13 @@
 use chrono::Utc;
 use crossbeam::queue::ArrayQueue;
 use image::ImageFormat::{self};
+use screenpipe_core::encryption::{encrypt_to_file, media_encryption_key};
 use screenpipe_core::{find_ffmpeg_path, Language};
 use screenpipe_vision::monitor::get_monitor_by_id;
 use screenpipe_vision::{
-    capture_screenshot_by_window::WindowFilters, continuous_capture, CaptureResult, OcrEngine,
+    capture_rate::CaptureRate, capture_screenshot_by_window::WindowFilters, continuous_capture,
+    frame_diff::FrameDiffConfig, incremental_ocr::IncrementalOcrConfig, privacy::PrivacyFilter,
+    redaction::WindowRedaction, CaptureResult, OcrEngine,
 };
 use std::borrow::Cow;
 use std::path::PathBuf;
@@ -41,12 +44,17 @@ impl VideoCapture {
     pub fn new(
         output_path: &str,
         fps: f64,
+        capture_rate: Arc<CaptureRate>,
         video_chunk_duration: Duration,
         new_chunk_callback: impl Fn(&str) + Send + Sync + 'static,
         ocr_engine: Arc<OcrEngine>,
         monitor_id: u32,
         ignore_list: &[String],
         include_list: &[String],
+        window_redaction: WindowRedaction,
+        privacy_filter: Arc<PrivacyFilter>,
+        frame_diff: FrameDiffConfig,
+        incremental_ocr: IncrementalOcrConfig,
         languages: Vec<Language>,
         capture_unfocused_windows: bool,
     ) -> Self {
@@ -56,7 +64,15 @@ impl VideoCapture {
             warn!("Invalid FPS value: {}. Using default of 1.0", fps);
             1.0
         };
-        let interval = Duration::from_secs_f64(1.0 / fps);
+        // an adaptive capture changes rate all the time, so its chunks are cut by time and the
+        // video thread keeps up with the highest rate
+        let rate_config = capture_rate.config();
+        let chunk_by_time = rate_config.adaptive;
+        let video_fps = if chunk_by_time {
+            rate_config.max_fps
+        } else {
+            fps
+        };
         let video_frame_queue = Arc::new(ArrayQueue::new(MAX_QUEUE_SIZE));
         let ocr_frame_queue = Arc::new(ArrayQueue::new(MAX_QUEUE_SIZE));
         let new_chunk_callback = Arc::new(new_chunk_callback);
@@ -72,15 +88,17 @@ impl VideoCapture {
         let capture_video_frame_queue = video_frame_queue.clone();
         let capture_ocr_frame_queue = ocr_frame_queue.clone();
         let (result_sender, mut result_receiver) = channel(512);
-        let window_filters = Arc::new(WindowFilters::new(ignore_list, include_list));
+        let window_filters = Arc::new(
+            WindowFilters::new(ignore_list, include_list).with_redaction(window_redaction),
+        );
 
         // Add parameters for monitoring restart
         let capture_ocr_engine = ocr_engine.clone();
         let capture_monitor_available = monitor_available.clone();
         let capture_window_filters = window_filters.clone();
+        let capture_privacy_filter = privacy_filter;
         let capture_languages = languages.clone();
         let capture_result_sender = result_sender.clone();
-        let capture_interval = interval;
         let capture_unfocused = capture_unfocused_windows;
 
         // Store task handles for health monitoring
@@ -105,10 +123,13 @@ impl VideoCapture {
 
                 match continuous_capture(
                     capture_result_sender.clone(),
-                    capture_interval,
+                    capture_rate.clone(),
                     (*capture_ocr_engine).clone(),
                     monitor_id,
                     capture_window_filters.clone(),
+                    capture_privacy_filter.clone(),
+                    frame_diff,
+                    incremental_ocr,
                     capture_languages.clone(),
                     capture_unfocused,
                 )
@@ -239,10 +260,11 @@ impl VideoCapture {
             match save_frames_as_video(
                 &video_frame_queue_clone,
                 &output_path,
-                fps,
+                video_fps,
                 new_chunk_callback_clone,
                 monitor_id,
                 video_chunk_duration,
+                chunk_by_time,
             )
             .await
             {
@@ -337,30 +359,50 @@ impl VideoCapture {
     }
 }
 
-pub async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child, anyhow::Error> {
-    // Overriding fps with max fps if over the max and warning user
-    let fps = if fps > MAX_FPS {
-        warn!("Overriding FPS from {} to {}", fps, MAX_FPS);
-        MAX_FPS
-    } else {
-        fps
-    };
+/// Rate the frames written to an ffmpeg process are encoded at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameTiming {
+    /// Frames are spaced evenly at this rate
+    Constant(f64),
+    /// Frames keep the time they are written at, for captures whose rate varies. They are
+    /// written as far apart as they were captured, see `process_frames`
+    WallClock,
+}
 
+pub async fn start_ffmpeg_process(
+    output_file: &str,
+    timing: FrameTiming,
+) -> Result<Child, anyhow::Error> {
     info!("Starting FFmpeg process for file: {}", output_file);
-    let fps_str = fps.to_string();
     let mut command = Command::new(find_ffmpeg_path().unwrap());
-    let mut args = vec![
-        "-f",
-        "image2pipe",
-        "-vcodec",
-        "png",
-        "-r",
-        &fps_str,
+    let mut args = vec!["-f", "image2pipe", "-vcodec", "png"];
+    let fps_str;
+    match timing {
+        FrameTiming::Constant(fps) => {
+            // Overriding fps with max fps if over the max and warning user
+            let fps = if fps > MAX_FPS {
+                warn!("Overriding FPS from {} to {}", fps, MAX_FPS);
+                MAX_FPS
+            } else {
+                fps
+            };
+            fps_str = fps.to_string();
+            args.extend_from_slice(&["-r", &fps_str]);
+        }
+        FrameTiming::WallClock => {
+            args.extend_from_slice(&["-use_wallclock_as_timestamps", "1"]);
+        }
+    }
+    args.extend_from_slice(&[
         "-i",
         "-",
         "-vf",
         "pad=width=ceil(iw/2)*2:height=ceil(ih/2)*2",
-    ];
+    ]);
+    if timing == FrameTiming::WallClock {
+        // keep the real timestamps instead of duplicating or dropping frames to a constant rate
+        args.extend_from_slice(&["-fps_mode", "vfr"]);
+    }
 
     args.extend_from_slice(&[
         "-vcodec",
@@ -373,7 +415,21 @@ pub async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child,
         "23",
     ]);
 
-    args.extend_from_slice(&["-pix_fmt", "yuv420p", output_file]);
+    let encryption_key = media_encryption_key();
+    if encryption_key.is_some() {
+        // encrypted chunks are streamed through us, so ffmpeg writes a fragmented mp4 to stdout
+        args.extend_from_slice(&[
+            "-pix_fmt",
+            "yuv420p",
+            "-movflags",
+            "frag_keyframe+empty_moov",
+            "-f",
+            "mp4",
+            "pipe:1",
+        ]);
+    } else {
+        args.extend_from_slice(&["-pix_fmt", "yuv420p", output_file]);
+    }
 
     command
         .args(&args)
@@ -383,9 +439,22 @@ pub async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child,
 
     debug!("FFmpeg command: {:?}", command);
 
-    let child = command.spawn()?;
+    let mut child = command.spawn()?;
     debug!("FFmpeg process spawned");
 
+    if let Some(key) = encryption_key {
+        let stdout = child.stdout.take().expect("Failed to open stdout");
+        let output_file = output_file.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = encrypt_to_file(key, stdout, &output_file).await {
+                error!(
+                    "Failed to write encrypted video chunk {}: {}",
+                    output_file, e
+                );
+            }
+        });
+    }
+
     Ok(child)
 }
 
@@ -412,13 +481,22 @@ async fn save_frames_as_video(
     new_chunk_callback: Arc<dyn Fn(&str) + Send + Sync>,
     monitor_id: u32,
     video_chunk_duration: Duration,
+    chunk_by_time: bool,
 ) -> Result<(), anyhow::Error> {
     info!(
         "Starting save_frames_as_video function for monitor {}",
         monitor_id
     );
+    // when chunks are cut by time this only caps them, at the highest rate
     let frames_per_video = (fps * video_chunk_duration.as_secs_f64()).ceil() as usize;
+    let timing = if chunk_by_time {
+        FrameTiming::WallClock
+    } else {
+        FrameTiming::Constant(fps)
+    };
     let mut frame_count = 0;
+    let mut chunk_deadline = None;
+    let mut chunk_clock = None;
     let mut current_ffmpeg: Option<Child> = None;
     let mut current_stdin: Option<ChildStdin> = None;
 
@@ -430,7 +508,9 @@ async fn save_frames_as_video(
     let stats_interval = Duration::from_secs(60);
 
     loop {
-        if frame_count >= frames_per_video || current_ffmpeg.is_none() {
+        let chunk_expired =
+            chunk_deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline);
+        if frame_count >= frames_per_video || chunk_expired || current_ffmpeg.is_none() {
             if let Some(child) = current_ffmpeg.take() {
                 info!(
                     "Finishing FFmpeg process for monitor {} after {} frames",
@@ -453,12 +533,13 @@ async fn save_frames_as_video(
             );
             new_chunk_callback(&output_file);
 
-            match start_ffmpeg_process(&output_file, fps).await {
+            match start_ffmpeg_process(&output_file, timing).await {
                 Ok(mut child) => {
                     let mut stdin = child.stdin.take().expect("Failed to open stdin");
                     spawn_ffmpeg_loggers(child.stderr.take(), child.stdout.take());
 
                     debug!("Writing first frame to FFmpeg for monitor {}", monitor_id);
+                    let written_at = std::time::Instant::now();
                     if let Err(e) = write_frame_to_ffmpeg(&mut stdin, &buffer).await {
                         error!(
                             "Failed to write first frame to ffmpeg for monitor {}: {}",
@@ -468,6 +549,9 @@ async fn save_frames_as_video(
                     }
                     frame_count += 1;
                     frames_total += 1;
+                    chunk_deadline =
+                        chunk_by_time.then(|| std::time::Instant::now() + video_chunk_duration);
+                    chunk_clock = chunk_by_time.then_some((first_frame.timestamp, written_at));
 
                     current_ffmpeg = Some(child);
                     current_stdin = Some(stdin);
@@ -510,6 +594,8 @@ async fn save_frames_as_video(
             &mut current_stdin,
             &mut frame_count,
             frames_per_video,
+            chunk_deadline,
+            chunk_clock,
             fps,
         )
         .await;
@@ -570,12 +656,23 @@ async fn process_frames(
     current_stdin: &mut Option<ChildStdin>,
     frame_count: &mut usize,
     frames_per_video: usize,
+    chunk_deadline: Option<std::time::Instant>,
+    chunk_clock: Option<(std::time::Instant, std::time::Instant)>,
     fps: f64,
 ) {
     let write_timeout = Duration::from_secs_f64(1.0 / fps);
-    while *frame_count < frames_per_video {
+    while *frame_count < frames_per_video
+        && chunk_deadline.map_or(true, |deadline| std::time::Instant::now() < deadline)
+    {
         if let Some(frame) = frame_queue.pop() {
             let buffer = encode_frame(&frame);
+            // ffmpeg timestamps frames when it reads them, so writing each one as long after the
+            // first frame of the chunk as it was captured gives it its capture time as pts.
+            // A frame that is already late is written right away
+            if let Some((first_captured, first_written)) = chunk_clock {
+                let due = first_written + frame.timestamp.saturating_duration_since(first_captured);
+                tokio::time::sleep_until(due.into()).await;
+            }
             if let Some(stdin) = current_stdin.as_mut() {
                 if let Err(e) = write_frame_with_retry(stdin, &buffer).await {
                     error!("Failed to write frame to ffmpeg after max retries: {}", e);
@@ -1,7 +1,9 @@
+use crate::video_utils::MediaInput;
 use anyhow::Result;
 use bincode;
 use chrono::{DateTime, Duration, Utc};
 use dirs::cache_dir;
+use screenpipe_core::encryption::{open_media, seal_media};
 use screenpipe_core::find_ffmpeg_path;
 use screenpipe_db::{DatabaseManager, FrameData, OCREntry};
 use serde::{Deserialize, Serialize};
@@ -80,6 +82,11 @@ enum CacheMessage {
         cache_key: String,
         response: GetFrameResponse,
     },
+    Invalidate {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        response: oneshot::Sender<Result<()>>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -229,6 +236,9 @@ impl FrameDiskCache {
         hasher.update(frame_data);
         let checksum = format!("{:x}", hasher.finalize());
 
+        // cached frames are encrypted like the video chunks they come from
+        let stored_data = seal_media(frame_data.to_vec())?;
+
         let cached_frame = CachedFrame {
             timestamp,
             device_id: device_id.to_string(),
@@ -244,7 +254,7 @@ impl FrameDiskCache {
                     .join(" "),
                 ocr_text: device_data.text.clone(),
             },
-            frame_size: frame_data.len() as u64,
+            frame_size: stored_data.len() as u64,
             compression: CompressionType::Jpeg {
                 quality: self.config.compression_quality,
             },
@@ -253,7 +263,7 @@ impl FrameDiskCache {
             audio_entries: audio_entries.to_vec(),
         };
 
-        fs::write(&frame_path, frame_data).await?;
+        fs::write(&frame_path, &stored_data).await?;
 
         self.entries.insert(
             (timestamp, device_id.to_string()),
@@ -264,7 +274,7 @@ impl FrameDiskCache {
             },
         );
 
-        self.total_size += frame_data.len() as u64;
+        self.total_size += stored_data.len() as u64;
         self.save_index().await?;
 
         Ok(())
@@ -304,7 +314,7 @@ impl FrameDiskCache {
 
             if should_verify {
                 debug!("verifying checksum for cached frame");
-                let frame_data = fs::read(&frame_path).await?;
+                let frame_data = open_media(fs::read(&frame_path).await?)?;
                 let mut hasher = Sha256::new();
                 hasher.update(&frame_data);
                 let checksum = format!("{:x}", hasher.finalize());
@@ -321,7 +331,7 @@ impl FrameDiskCache {
                 )))
             } else {
                 // Fast path - skip checksum verification
-                let frame_data = fs::read(&frame_path).await?;
+                let frame_data = open_media(fs::read(&frame_path).await?)?;
                 Ok(Some((
                     frame_data,
                     entry.frame.metadata.clone(),
@@ -342,6 +352,27 @@ impl FrameDiskCache {
         ))
     }
 
+    /// Removes cached frames captured between `start` (inclusive) and `end` (exclusive).
+    async fn remove_range(&mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<()> {
+        let keys: Vec<_> = self
+            .entries
+            .keys()
+            .filter(|(timestamp, _)| *timestamp >= start && *timestamp < end)
+            .cloned()
+            .collect();
+
+        for key in keys {
+            if let Some(entry) = self.entries.remove(&key) {
+                self.total_size = self.total_size.saturating_sub(entry.frame.frame_size);
+                if let Err(e) = fs::remove_file(&entry.path).await {
+                    debug!("failed to remove cached frame: {}", e);
+                }
+            }
+        }
+
+        self.save_index().await
+    }
+
     async fn cleanup(&mut self) -> Result<()> {
         debug!("starting cache cleanup");
 
@@ -414,6 +445,14 @@ async fn run_cache_manager(mut cache: FrameDiskCache, mut rx: mpsc::Receiver<Cac
                         let result = cache.get_frame_data(&cache_key).await;
                         let _ = response.send(result);
                     }
+                    CacheMessage::Invalidate {
+                        start,
+                        end,
+                        response,
+                    } => {
+                        let result = cache.remove_range(start, end).await;
+                        let _ = response.send(result);
+                    }
                 }
             }
             _ = cleanup_interval.tick() => {
@@ -566,6 +605,20 @@ impl FrameCache {
         Ok(())
     }
 
+    /// Drops cached frames captured between `start` (inclusive) and `end` (exclusive), e.g. after
+    /// that range was deleted from the database.
+    pub async fn invalidate_range(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<()> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.cache_tx
+            .send(CacheMessage::Invalidate {
+                start,
+                end,
+                response: response_tx,
+            })
+            .await?;
+        response_rx.await?
+    }
+
     pub async fn get_frames(
         &self,
         timestamp: DateTime<Utc>,
@@ -647,60 +700,67 @@ async fn extract_frame(
     frame_tx: FrameChannel,
     cache_tx: mpsc::Sender<CacheMessage>,
 ) -> Result<usize> {
-    if !is_video_file_complete(&ffmpeg, &video_file_path).await? {
+    let input = MediaInput::open(&video_file_path).await?;
+    if !is_video_file_complete(&ffmpeg, &video_file_path, &input).await? {
         debug!("skipping incomplete video file: {}", video_file_path);
         return Ok(0);
     }
 
-    // Get source FPS from video metadata
-    let source_fps = match get_video_fps(&ffmpeg, &video_file_path).await {
-        Ok(fps) => fps,
-        Err(e) => {
-            error!("failed to get video fps, using default 1fps: {}", e);
-            1.0
-        }
-    };
-
     let temp_dir = tempfile::tempdir()?;
     let output_pattern = temp_dir.path().join("frame%d.jpg");
 
     // Reduce frame rate even further for older content
     let frame_interval = if is_older_than_24h(&tasks[0].0.timestamp) {
-        (source_fps / 0.05).round() as i64 // 1 frame every 20 seconds for older content
+        Duration::seconds(20) // 1 frame every 20 seconds for older content
     } else {
-        (source_fps / 0.1).round() as i64 // 1 frame every 10 seconds for recent content
+        Duration::seconds(10) // 1 frame every 10 seconds for recent content
     };
 
     debug!(
-        "extracting frames with interval {} (source: {}fps, target: {}fps)",
-        frame_interval, source_fps, 0.1
+        "extracting frames with interval {}s",
+        frame_interval.num_seconds()
     );
 
-    // Calculate which frames to extract
-    let frame_positions: Vec<String> = tasks
-        .iter()
-        .filter_map(|(frame, _)| {
-            // Only select frames that align with our target FPS
-            if frame.offset_index % frame_interval == 0 {
-                Some(frame.offset_index.to_string())
-            } else {
-                None
+    // Frames are spaced by when they were captured, chunks of an adaptive capture have no
+    // constant rate to space them by index
+    let mut last_kept: Option<DateTime<Utc>> = None;
+    let tasks: Vec<(FrameData, OCREntry)> = tasks
+        .into_iter()
+        .filter(|(frame, _)| {
+            let keep = last_kept.map_or(true, |last| {
+                (frame.timestamp - last).abs() >= frame_interval
+            });
+            if keep {
+                last_kept = Some(frame.timestamp);
             }
+            keep
         })
         .collect();
 
-    if frame_positions.is_empty() {
+    if tasks.is_empty() {
         debug!("no frames to extract after applying fps filter");
         return Ok(0);
     }
 
+    // ffmpeg writes the selected frames in the order they are in the video
+    let mut frame_positions: Vec<i64> = tasks.iter().map(|(frame, _)| frame.offset_index).collect();
+    frame_positions.sort_unstable();
+    frame_positions.dedup();
+
     // Join frame numbers with commas and wrap in select filter
-    let select_filter = format!("select='eq(n,{})'", frame_positions.join(")+eq(n,"));
+    let select_filter = format!(
+        "select='eq(n,{})'",
+        frame_positions
+            .iter()
+            .map(|position| position.to_string())
+            .collect::<Vec<_>>()
+            .join(")+eq(n,")
+    );
 
     let mut cmd = Command::new(&ffmpeg);
     cmd.args([
         "-i",
-        &video_file_path,
+        input.arg(),
         "-vf",
         &format!("{},format=yuv420p,scale=iw*0.8:ih*0.8", select_filter),
         "-strict",
@@ -724,31 +784,29 @@ async fn extract_frame(
 
     debug!("running ffmpeg command: {:?}", cmd);
 
-    let output = cmd.output().await?;
+    let output = input.output(&mut cmd).await?;
     if !output.status.success() {
         error!("ffmpeg error: {}", String::from_utf8_lossy(&output.stderr));
         return Ok(0);
     }
 
     let mut processed = 0;
-    let mut entries = tokio::fs::read_dir(temp_dir.path()).await?;
-    let mut all_frames = Vec::new();
-
-    while let Some(entry) = entries.next_entry().await? {
-        let frame_data = tokio::fs::read(entry.path()).await?;
-        all_frames.push(frame_data);
+    let mut all_frames = HashMap::new();
+    for (index, position) in frame_positions.iter().enumerate() {
+        let frame_path = temp_dir.path().join(format!("frame{}.jpg", index + 1));
+        if let Ok(frame_data) = tokio::fs::read(&frame_path).await {
+            all_frames.insert(*position, frame_data);
+        }
     }
 
     debug!("extracted {} frames from video", all_frames.len());
 
     for (task_index, (chunk, device_data)) in tasks.iter().enumerate() {
-        if task_index >= all_frames.len() {
-            debug!("warning: ran out of frames at index {}", task_index);
-            break;
-        }
+        let Some(frame_data) = all_frames.get(&chunk.offset_index) else {
+            debug!("warning: frame {} missing from video", chunk.offset_index);
+            continue;
+        };
 
-        let (frame, _) = &tasks[task_index];
-        let frame_data = &all_frames[task_index];
         let cache_key = format!("{}||{}", chunk.timestamp, device_data.device_name);
         debug!("processing frame {} with key {}", task_index, cache_key);
 
@@ -777,7 +835,7 @@ async fn extract_frame(
                 error: None,
                 timestamp: chunk.timestamp,
                 frame_data: vec![DeviceFrame {
-                    frame_id: frame.frame_id,
+                    frame_id: chunk.frame_id,
                     device_id: device_data.device_name.clone(),
                     image_data: frame_data.clone(),
                     metadata: FrameMetadata {
@@ -814,7 +872,11 @@ async fn extract_frame(
     Ok(processed)
 }
 
-async fn is_video_file_complete(ffmpeg_path: &PathBuf, file_path: &str) -> Result<bool> {
+async fn is_video_file_complete(
+    ffmpeg_path: &PathBuf,
+    file_path: &str,
+    input: &MediaInput,
+) -> Result<bool> {
     if let Ok(metadata) = tokio::fs::metadata(file_path).await {
         if let Ok(modified) = metadata.modified() {
             let age = SystemTime::now()
@@ -826,11 +888,9 @@ async fn is_video_file_complete(ffmpeg_path: &PathBuf, file_path: &str) -> Resul
         }
     }
 
-    match Command::new(ffmpeg_path)
-        .args(["-v", "error", "-i", file_path, "-f", "null", "-"])
-        .output()
-        .await
-    {
+    let mut command = Command::new(ffmpeg_path);
+    command.args(["-v", "error", "-i", input.arg(), "-f", "null", "-"]);
+    match input.output(&mut command).await {
         Ok(output) => {
             let is_complete = output.status.success();
             if !is_complete {
@@ -986,30 +1046,6 @@ impl OrderedFrameStreamer {
     }
 }
 
-async fn get_video_fps(ffmpeg_path: &PathBuf, video_path: &str) -> Result<f64> {
-    let output = Command::new(ffmpeg_path)
-        .args(["-i", video_path])
-        .output()
-        .await?;
-
-    // ffmpeg outputs metadata to stderr by design
-    let metadata = String::from_utf8_lossy(&output.stderr);
-
-    // Look for fps info in patterns like: "23.98 fps" or "30 fps" or "29.97 fps"
-    let fps = metadata
-        .lines()
-        .find(|line| line.contains("fps") && !line.contains("Stream"))
-        .and_then(|line| {
-            line.split_whitespace()
-                .find(|&word| word.parse::<f64>().is_ok())
-                .and_then(|n| n.parse::<f64>().ok())
-        })
-        .unwrap_or(1.0);
-
-    debug!("detected fps from video metadata: {}", fps);
-    Ok(fps)
-}
-
 fn is_older_than_24h(timestamp: &DateTime<Utc>) -> bool {
     Utc::now() - *timestamp > Duration::hours(24)
 }
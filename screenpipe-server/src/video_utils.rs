@@ -4,12 +4,17 @@ use chrono::NaiveDateTime;
 use chrono::{DateTime, Utc};
 use image::DynamicImage;
 use oasgen::OaSchema;
+use screenpipe_core::encryption::{
+    encrypt, is_encrypted_file, media_encryption_key, read_media_file, seal_media,
+};
 use screenpipe_core::find_ffmpeg_path;
 use screenpipe_db::VideoMetadata as DBVideoMetadata;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::path::PathBuf;
-use tokio::io::AsyncReadExt;
+use std::process::{Output, Stdio};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use tracing::{debug, error, info};
 use uuid::Uuid;
@@ -36,8 +41,56 @@ struct Stream {
     r_frame_rate: String,
 }
 
+/// A recorded media file used as ffmpeg input. Encrypted files are decrypted in memory and piped
+/// to ffmpeg through stdin, so their plaintext never touches the disk.
+#[derive(Clone)]
+pub(crate) enum MediaInput {
+    File(String),
+    Decrypted(Arc<Vec<u8>>),
+}
+
+impl MediaInput {
+    pub(crate) async fn open(file_path: &str) -> Result<Self> {
+        if is_encrypted_file(file_path).await? {
+            Ok(Self::Decrypted(Arc::new(read_media_file(file_path).await?)))
+        } else {
+            Ok(Self::File(file_path.to_string()))
+        }
+    }
+
+    /// The input to pass to ffmpeg or ffprobe.
+    pub(crate) fn arg(&self) -> &str {
+        match self {
+            Self::File(file_path) => file_path,
+            Self::Decrypted(_) => "pipe:0",
+        }
+    }
+
+    /// Runs `command`, feeding it the decrypted data if needed, and collects its output.
+    pub(crate) async fn output(&self, command: &mut Command) -> Result<Output> {
+        let data = match self {
+            Self::File(_) => return Ok(command.output().await?),
+            Self::Decrypted(data) => data.clone(),
+        };
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("failed to open stdin");
+        tokio::spawn(async move {
+            // ffmpeg stops reading once it has the frames it needs, so a broken pipe is fine
+            let _ = stdin.write_all(&data).await;
+        });
+
+        Ok(child.wait_with_output().await?)
+    }
+}
+
 pub async fn extract_frame(file_path: &str, offset_index: i64) -> Result<String> {
     let ffmpeg_path = find_ffmpeg_path().expect("failed to find ffmpeg path");
+    let input = MediaInput::open(file_path).await?;
 
     let offset_seconds = offset_index as f64 / 1000.0;
     let offset_str = format!("{:.3}", offset_seconds);
@@ -53,7 +106,7 @@ pub async fn extract_frame(file_path: &str, offset_index: i64) -> Result<String>
             "-ss",
             &offset_str,
             "-i",
-            file_path,
+            input.arg(),
             "-vf",
             "scale=iw*0.75:ih*0.75", // Scale down to 75% of original size
             "-vframes",
@@ -71,20 +124,13 @@ pub async fn extract_frame(file_path: &str, offset_index: i64) -> Result<String>
 
     debug!("ffmpeg command: {:?}", command);
 
-    let mut child = command.spawn()?;
-    let mut stdout = child.stdout.take().expect("failed to open stdout");
-    let mut stderr = child.stderr.take().expect("failed to open stderr");
-
-    let mut frame_data = Vec::new();
-    stdout.read_to_end(&mut frame_data).await?;
-
-    let status = child.wait().await?;
-    if !status.success() {
-        let mut error_message = String::new();
-        stderr.read_to_string(&mut error_message).await?;
+    let output = input.output(&mut command).await?;
+    if !output.status.success() {
+        let error_message = String::from_utf8_lossy(&output.stderr);
         info!("ffmpeg error: {}", error_message);
         return Err(anyhow::anyhow!("ffmpeg process failed: {}", error_message));
     }
+    let frame_data = output.stdout;
 
     if frame_data.is_empty() {
         return Err(anyhow::anyhow!("failed to extract frame: no data received"));
@@ -128,6 +174,99 @@ pub async fn validate_media(file_path: &str) -> Result<()> {
     }
 }
 
+/// Re-encodes a video chunk with the frames at `offset_indexes` painted black, replacing the
+/// original file. Frame positions and timestamps are preserved so the offsets of the remaining
+/// frames stay valid.
+pub async fn redact_video_frames(file_path: &str, offset_indexes: &[i64]) -> Result<()> {
+    let mut indexes = offset_indexes.to_vec();
+    indexes.sort_unstable();
+    indexes.dedup();
+
+    // collapse consecutive offsets into ranges to keep the filter expression short
+    let mut ranges: Vec<(i64, i64)> = Vec::new();
+    for index in indexes {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == index => *end = index,
+            _ => ranges.push((index, index)),
+        }
+    }
+    if ranges.is_empty() {
+        return Ok(());
+    }
+
+    let enable = ranges
+        .iter()
+        .map(|(start, end)| format!("between(n,{},{})", start, end))
+        .collect::<Vec<_>>()
+        .join("+");
+    let filter = format!(
+        "drawbox=x=0:y=0:w=iw:h=ih:color=black:t=fill:enable='{}'",
+        enable
+    );
+
+    let path = Path::new(file_path);
+    let temp_path = path.with_file_name(format!("redacted_{}.mp4", Uuid::new_v4()));
+
+    let input = MediaInput::open(file_path).await?;
+    let encryption_key = media_encryption_key();
+
+    let ffmpeg_path = find_ffmpeg_path().expect("failed to find ffmpeg path");
+    let mut command = Command::new(ffmpeg_path);
+    command.args([
+        "-i",
+        input.arg(),
+        "-vf",
+        &filter,
+        // one output frame per input frame at its original time, so stored offsets and the
+        // timestamps of variable rate chunks stay valid
+        "-fps_mode",
+        "passthrough",
+        "-copyts",
+        "-map_metadata",
+        "0",
+        "-vcodec",
+        "libx265",
+        "-tag:v",
+        "hvc1",
+        "-preset",
+        "ultrafast",
+        "-crf",
+        "23",
+        "-pix_fmt",
+        "yuv420p",
+        "-y",
+    ]);
+    if encryption_key.is_some() {
+        command.args([
+            "-movflags",
+            "frag_keyframe+empty_moov",
+            "-f",
+            "mp4",
+            "pipe:1",
+        ]);
+    } else {
+        command.arg(temp_path.to_str().unwrap());
+    }
+    let output = input.output(&mut command).await?;
+
+    if !output.status.success() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(anyhow::anyhow!(
+            "ffmpeg failed to redact {}: {}",
+            file_path,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    if let Some(key) = encryption_key {
+        tokio::fs::write(&temp_path, encrypt(key, &output.stdout)?).await?;
+    }
+
+    tokio::fs::rename(&temp_path, path).await?;
+    debug!("redacted {} frame ranges in {}", ranges.len(), file_path);
+
+    Ok(())
+}
+
 pub async fn merge_videos(
     request: MergeVideosRequest,
     output_dir: PathBuf,
@@ -237,7 +376,8 @@ pub async fn extract_frames_from_video(
     }
 
     // Get source FPS and calculate target FPS
-    let source_fps = match get_video_fps(&ffmpeg_path, video_path.to_str().unwrap()).await {
+    let input = MediaInput::File(video_path.to_string_lossy().into_owned());
+    let source_fps = match get_video_fps(&ffmpeg_path, &input).await {
         Ok(fps) => fps,
         Err(e) => {
             debug!("failed to get video fps, using default 1fps: {}", e);
@@ -307,23 +447,41 @@ pub async fn extract_frames_from_video(
     Ok(frames)
 }
 
-async fn get_video_fps(ffmpeg_path: &PathBuf, video_path: &str) -> Result<f64> {
+async fn get_video_fps(ffmpeg_path: &PathBuf, input: &MediaInput) -> Result<f64> {
+    let (fps, _) = get_frame_rates(ffmpeg_path, input).await?;
+    let fps = fps.unwrap_or(1.0);
+
+    debug!("Video FPS: {}", fps);
+    Ok(fps)
+}
+
+/// The rate of a video chunk if its frames are evenly spaced, `None` for chunks of an
+/// adaptive capture whose frames keep the time they were captured at.
+async fn get_constant_fps(ffmpeg_path: &PathBuf, input: &MediaInput) -> Result<Option<f64>> {
+    let (fps, average_fps) = get_frame_rates(ffmpeg_path, input).await?;
+    Ok(fps.filter(|fps| average_fps.is_some_and(|average| (fps - average).abs() < fps * 0.01)))
+}
+
+/// The base and average frame rates of the first video stream.
+async fn get_frame_rates(
+    ffmpeg_path: &PathBuf,
+    input: &MediaInput,
+) -> Result<(Option<f64>, Option<f64>)> {
     let ffprobe_path = ffmpeg_path.with_file_name("ffprobe");
 
-    let output = Command::new(&ffprobe_path)
-        .args([
-            "-v",
-            "quiet",
-            "-print_format",
-            "json",
-            "-select_streams",
-            "v:0", // Select first video stream
-            "-show_entries",
-            "stream=r_frame_rate", // Only request frame rate information
-            video_path,
-        ])
-        .output()
-        .await?;
+    let mut command = Command::new(&ffprobe_path);
+    command.args([
+        "-v",
+        "quiet",
+        "-print_format",
+        "json",
+        "-select_streams",
+        "v:0", // Select first video stream
+        "-show_entries",
+        "stream=r_frame_rate,avg_frame_rate", // Only request frame rate information
+        input.arg(),
+    ]);
+    let output = input.output(&mut command).await?;
 
     if !output.status.success() {
         let error = String::from_utf8_lossy(&output.stderr);
@@ -335,25 +493,70 @@ async fn get_video_fps(ffmpeg_path: &PathBuf, video_path: &str) -> Result<f64> {
 
     // Parse the simplified JSON output
     let parsed: serde_json::Value = serde_json::from_str(&stdout)?;
-
-    let fps = parsed
+    let stream = parsed
         .get("streams")
         .and_then(|streams| streams.as_array())
-        .and_then(|streams| streams.first())
-        .and_then(|stream| stream.get("r_frame_rate"))
-        .and_then(|rate| rate.as_str())
-        .and_then(|rate| {
-            let parts: Vec<f64> = rate.split('/').filter_map(|n| n.parse().ok()).collect();
-            if parts.len() == 2 && parts[1] != 0.0 {
-                Some(parts[0] / parts[1])
-            } else {
-                None
+        .and_then(|streams| streams.first());
+
+    let rate = |key: &str| {
+        stream
+            .and_then(|stream| stream.get(key))
+            .and_then(|rate| rate.as_str())
+            .and_then(|rate| {
+                let parts: Vec<f64> = rate.split('/').filter_map(|n| n.parse().ok()).collect();
+                if parts.len() == 2 && parts[1] != 0.0 {
+                    Some(parts[0] / parts[1])
+                } else {
+                    None
+                }
+            })
+    };
+    Ok((rate("r_frame_rate"), rate("avg_frame_rate")))
+}
+
+/// Where to find one frame of a video chunk.
+enum FramePosition {
+    /// Time to seek to, in chunks recorded at a constant rate
+    Time(String),
+    /// Index of the frame, in chunks of an adaptive capture which have no rate to seek by. Every
+    /// frame up to it is decoded
+    Index(i64),
+}
+
+impl FramePosition {
+    async fn find(ffmpeg_path: &PathBuf, input: &MediaInput, offset_index: i64) -> Self {
+        match get_constant_fps(ffmpeg_path, input).await {
+            Ok(Some(fps)) => {
+                // half a frame early, so rounding can't land on the next frame
+                let offset_seconds = (offset_index as f64 - 0.5).max(0.0) / fps;
+                Self::Time(format!("{:.3}", offset_seconds))
             }
-        })
-        .unwrap_or(1.0);
+            Ok(None) => Self::Index(offset_index),
+            Err(e) => {
+                error!(
+                    "failed to get video fps, selecting the frame by index: {}",
+                    e
+                );
+                Self::Index(offset_index)
+            }
+        }
+    }
 
-    debug!("Video FPS: {}", fps);
-    Ok(fps)
+    /// Arguments to put before the input.
+    fn seek_args(&self) -> Vec<&str> {
+        match self {
+            Self::Time(offset) => vec!["-ss", offset],
+            Self::Index(_) => Vec::new(),
+        }
+    }
+
+    /// `filters` applied to the frame only.
+    fn filter(&self, filters: &str) -> String {
+        match self {
+            Self::Time(_) => filters.to_string(),
+            Self::Index(index) => format!("select='eq(n,{})',{}", index, filters),
+        }
+    }
 }
 
 fn parse_time_from_filename(path: &str) -> Option<DateTime<Utc>> {
@@ -554,19 +757,13 @@ impl VideoMetadataOverride {
     }
 }
 
+/// Extracts a frame of a video chunk to a jpeg file and returns its path. The file is sealed
+/// like the recorded media, read it back with `read_media_file`.
 pub async fn extract_frame_from_video(file_path: &str, offset_index: i64) -> Result<String> {
     let ffmpeg_path = find_ffmpeg_path().expect("failed to find ffmpeg path");
+    let input = MediaInput::open(file_path).await?;
 
-    let source_fps = match get_video_fps(&ffmpeg_path, file_path).await {
-        Ok(fps) => fps,
-        Err(e) => {
-            error!("failed to get video fps, using default 1fps: {}", e);
-            1.0
-        }
-    };
-
-    let offset_seconds = offset_index as f64 * source_fps;
-    let offset_str = format!("{:.3}", offset_seconds);
+    let position = FramePosition::find(&ffmpeg_path, &input, offset_index).await;
 
     // Create a temporary directory for frames if it doesn't exist
     let frames_dir = PathBuf::from("/tmp/screenpipe_frames");
@@ -577,22 +774,22 @@ pub async fn extract_frame_from_video(file_path: &str, offset_index: i64) -> Res
     let output_path = frames_dir.join(&frame_filename);
 
     debug!(
-        "extracting frame from {} at offset {} and fps {} to {}",
+        "extracting frame {} from {} to {}",
+        offset_index,
         file_path,
-        offset_str,
-        source_fps,
         output_path.display()
     );
 
     let mut command = Command::new(ffmpeg_path);
     command
+        .args(position.seek_args())
         .args([
-            "-ss",
-            &offset_str,
             "-i",
-            file_path,
+            input.arg(),
             "-vf",
-            "scale=iw:ih,format=yuvj420p", // Add format conversion
+            &position.filter("scale=iw:ih,format=yuvj420p"),
+            "-fps_mode",
+            "passthrough",
             "-vframes",
             "1",
             "-c:v",
@@ -603,15 +800,17 @@ pub async fn extract_frame_from_video(file_path: &str, offset_index: i64) -> Res
             "yuvj420p", // Ensure proper pixel format
             "-q:v",
             "10",
-            "-y", // Force overwrite
-            output_path.to_str().unwrap(),
+            // the frame comes back through stdout so it can be encrypted before it is written
+            "-f",
+            "image2pipe",
+            "pipe:1",
         ])
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped());
 
     debug!("ffmpeg command: {:?}", command);
 
-    let output = command.output().await?;
+    let output = input.output(&mut command).await?;
 
     if !output.status.success() {
         let error_message = String::from_utf8_lossy(&output.stderr);
@@ -619,9 +818,13 @@ pub async fn extract_frame_from_video(file_path: &str, offset_index: i64) -> Res
         return Err(anyhow::anyhow!("ffmpeg process failed: {}", error_message));
     }
 
-    if !output_path.exists() {
-        return Err(anyhow::anyhow!("failed to extract frame: file not created"));
+    if output.stdout.is_empty() {
+        return Err(anyhow::anyhow!(
+            "failed to extract frame: no frame {} in video",
+            offset_index
+        ));
     }
+    tokio::fs::write(&output_path, seal_media(output.stdout)?).await?;
 
     // Schedule cleanup of old frames (files older than 1 hour)
     tokio::spawn(async move {
@@ -654,22 +857,17 @@ async fn cleanup_old_frames(frames_dir: &PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Extracts a frame of a video chunk to a lossless png in `output_dir` and returns its path.
+/// The file is sealed like the recorded media.
 pub async fn extract_high_quality_frame(
     file_path: &str,
     offset_index: i64,
     output_dir: &Path,
 ) -> Result<String> {
     let ffmpeg_path = find_ffmpeg_path().expect("failed to find ffmpeg path");
+    let input = MediaInput::open(file_path).await?;
 
-    let source_fps = match get_video_fps(&ffmpeg_path, file_path).await {
-        Ok(fps) => fps,
-        Err(e) => {
-            error!("failed to get video fps, using default 1fps: {}", e);
-            1.0
-        }
-    };
-
-    let frame_time = offset_index as f64 * source_fps;
+    let position = FramePosition::find(&ffmpeg_path, &input, offset_index).await;
 
     let frame_filename = format!(
         "frame_{}_{}.png",
@@ -679,18 +877,17 @@ pub async fn extract_high_quality_frame(
     let output_path = output_dir.join(frame_filename);
 
     let mut command = Command::new(&ffmpeg_path);
+    command.args(["-y", "-loglevel", "error"]);
+    command.args(position.seek_args());
     command.args([
-        "-y",
-        "-loglevel",
-        "error",
-        "-ss",
-        &frame_time.to_string(),
         "-i",
-        file_path,
+        input.arg(),
+        "-vf",
+        &position.filter("scale=3840:2160:flags=lanczos"),
+        "-fps_mode",
+        "passthrough",
         "-vframes",
         "1",
-        "-vf",
-        "scale=3840:2160:flags=lanczos",
         "-c:v",
         "png",
         "-compression_level",
@@ -699,15 +896,24 @@ pub async fn extract_high_quality_frame(
         "veryslow",
         "-qscale:v",
         "1",
-        output_path.to_str().unwrap(),
+        "-f",
+        "image2pipe",
+        "pipe:1",
     ]);
+    command
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
 
-    let output = command.output().await?;
+    let output = input.output(&mut command).await?;
     if !output.status.success() {
         let error_msg = String::from_utf8_lossy(&output.stderr);
         error!("FFmpeg failed: {}", error_msg);
         return Err(anyhow::anyhow!("FFmpeg failed: {}", error_msg));
     }
+    if output.stdout.is_empty() {
+        return Err(anyhow::anyhow!("no frame {} in video", offset_index));
+    }
+    tokio::fs::write(&output_path, seal_media(output.stdout)?).await?;
 
     Ok(output_path.to_str().unwrap().to_string())
 }
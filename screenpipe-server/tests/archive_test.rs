@@ -0,0 +1,297 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use screenpipe_db::{
+        AudioDevice, DatabaseManager, DeviceType, OcrEngine, TagContentType, TranscriptionSegment,
+    };
+    use screenpipe_server::{export_archive, import_archive};
+    use std::path::Path;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    async fn open_db(data_dir: &Path) -> DatabaseManager {
+        DatabaseManager::new(&data_dir.join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap()
+    }
+
+    async fn write_media(data_dir: &Path, name: &str) -> String {
+        let path = data_dir.join("data").join(name);
+        tokio::fs::create_dir_all(path.parent().unwrap())
+            .await
+            .unwrap();
+        tokio::fs::write(&path, name.as_bytes()).await.unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    async fn record(db: &DatabaseManager, data_dir: &Path) {
+        let video = write_media(data_dir, "monitor_1_a.mp4").await;
+        db.insert_video_chunk(&video, "monitor_1").await.unwrap();
+        let frame_id = db
+            .insert_frame("monitor_1", None, None, Some("code"), Some("main.rs"), true)
+            .await
+            .unwrap();
+        db.insert_ocr_text(frame_id, "fn main() {}", "", Arc::new(OcrEngine::Tesseract))
+            .await
+            .unwrap();
+        db.add_tags(frame_id, TagContentType::Vision, vec!["work".to_string()])
+            .await
+            .unwrap();
+
+        let audio = write_media(data_dir, "mic_a.mp4").await;
+        let audio_chunk_id = db.insert_audio_chunk(&audio).await.unwrap();
+        let transcription_id = db
+            .insert_audio_transcription(
+                audio_chunk_id,
+                "hello from the archive",
+                0,
+                "whisper",
+                &AudioDevice {
+                    name: "mic".to_string(),
+                    device_type: DeviceType::Input,
+                },
+                None,
+                Some(0.0),
+                Some(2.0),
+            )
+            .await
+            .unwrap();
+        db.insert_transcription_segments(transcription_id, &[segment()])
+            .await
+            .unwrap();
+    }
+
+    fn segment() -> TranscriptionSegment {
+        TranscriptionSegment {
+            start_time: 0.0,
+            end_time: 2.0,
+            text: "hello from the archive".to_string(),
+            words: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import() {
+        let source_dir = tempdir().unwrap();
+        let target_dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let archive = out.path().join("archive.tar.zst");
+
+        let source = open_db(source_dir.path()).await;
+        record(&source, source_dir.path()).await;
+        let now = Utc::now();
+        let report = export_archive(
+            &source,
+            now - Duration::hours(1),
+            now + Duration::hours(1),
+            &archive,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.counts.video_chunks, 1);
+        assert_eq!(report.counts.frames, 1);
+        assert_eq!(report.counts.ocr_text, 1);
+        assert_eq!(report.counts.audio_chunks, 1);
+        assert_eq!(report.counts.audio_transcriptions, 1);
+        assert_eq!(report.counts.transcription_segments, 1);
+        assert_eq!(report.counts.tags, 1);
+        assert_eq!(report.counts.media_files, 2);
+        assert!(archive.exists());
+
+        let target = open_db(target_dir.path()).await;
+        let report = import_archive(&target, &archive, target_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(report.imported.video_chunks, 1);
+        assert_eq!(report.imported.frames, 1);
+        assert_eq!(report.imported.ocr_text, 1);
+        assert_eq!(report.imported.audio_transcriptions, 1);
+        assert_eq!(report.imported.transcription_segments, 1);
+        assert_eq!(report.imported.tags, 1);
+        assert_eq!(report.media_files_written, 2);
+        let transcription_id: i64 = sqlx::query_scalar("SELECT id FROM audio_transcriptions")
+            .fetch_one(&target.pool)
+            .await
+            .unwrap();
+        let segments = target
+            .get_transcription_segments(&[transcription_id])
+            .await
+            .unwrap();
+        assert_eq!(segments[&transcription_id], vec![segment()]);
+        assert_eq!(
+            tokio::fs::read(target_dir.path().join("data").join("monitor_1_a.mp4"))
+                .await
+                .unwrap(),
+            b"monitor_1_a.mp4"
+        );
+
+        let mut media = target.list_media_files().await.unwrap();
+        media.sort();
+        assert_eq!(
+            media,
+            vec![
+                target_dir
+                    .path()
+                    .join("data")
+                    .join("mic_a.mp4")
+                    .to_string_lossy()
+                    .to_string(),
+                target_dir
+                    .path()
+                    .join("data")
+                    .join("monitor_1_a.mp4")
+                    .to_string_lossy()
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_skips_existing_data() {
+        let data_dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let archive = out.path().join("archive.tar.zst");
+
+        let db = open_db(data_dir.path()).await;
+        record(&db, data_dir.path()).await;
+        let now = Utc::now();
+        export_archive(
+            &db,
+            now - Duration::hours(1),
+            now + Duration::hours(1),
+            &archive,
+            false,
+        )
+        .await
+        .unwrap();
+
+        // importing into the database the archive came from adds nothing
+        let report = import_archive(&db, &archive, data_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(report.imported.video_chunks, 0);
+        assert_eq!(report.imported.frames, 0);
+        assert_eq!(report.imported.ocr_text, 0);
+        assert_eq!(report.imported.audio_chunks, 0);
+        assert_eq!(report.imported.audio_transcriptions, 0);
+        assert_eq!(report.imported.transcription_segments, 0);
+        assert_eq!(report.imported.tags, 0);
+        assert_eq!(report.media_files_written, 0);
+        assert!(report.imported.skipped > 0);
+        assert_eq!(db.list_media_files().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_import_in_batches_completes_partial_import() {
+        let source_dir = tempdir().unwrap();
+        let target_dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let archive = out.path().join("archive.tar.zst");
+
+        // more rows than one batch holds
+        let source = open_db(source_dir.path()).await;
+        let video = write_media(source_dir.path(), "monitor_1_a.mp4").await;
+        source
+            .insert_video_chunk(&video, "monitor_1")
+            .await
+            .unwrap();
+        for i in 0..700 {
+            let frame_id = source
+                .insert_frame("monitor_1", None, None, None, None, false)
+                .await
+                .unwrap();
+            source
+                .insert_ocr_text(
+                    frame_id,
+                    &format!("frame {}", i),
+                    "",
+                    Arc::new(OcrEngine::Tesseract),
+                )
+                .await
+                .unwrap();
+        }
+        let now = Utc::now();
+        export_archive(
+            &source,
+            now - Duration::hours(1),
+            now + Duration::hours(1),
+            &archive,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let target = open_db(target_dir.path()).await;
+        let report = import_archive(&target, &archive, target_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(report.imported.frames, 700);
+        assert_eq!(report.imported.ocr_text, 700);
+
+        // frames left without their text, as by an import that failed after committing them,
+        // get it on the next import while the others aren't duplicated
+        sqlx::query("DELETE FROM ocr_text WHERE text IN ('frame 3', 'frame 650')")
+            .execute(&target.pool)
+            .await
+            .unwrap();
+        let report = import_archive(&target, &archive, target_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(report.imported.frames, 0);
+        assert_eq!(report.imported.ocr_text, 2);
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ocr_text")
+            .fetch_one(&target.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 700);
+    }
+
+    #[tokio::test]
+    async fn test_export_records_media_range() {
+        let data_dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let archive = out.path().join("archive.tar.zst");
+
+        let db = open_db(data_dir.path()).await;
+        let video = write_media(data_dir.path(), "monitor_1_a.mp4").await;
+        db.insert_video_chunk(&video, "monitor_1").await.unwrap();
+        let now = Utc::now();
+        let earlier = now - Duration::minutes(10);
+        db.insert_frame("monitor_1", Some(earlier), None, None, None, true)
+            .await
+            .unwrap();
+        db.insert_frame("monitor_1", Some(now), None, None, None, true)
+            .await
+            .unwrap();
+
+        // only the second frame is in range, but its chunk also holds the first
+        let report = export_archive(
+            &db,
+            now - Duration::minutes(1),
+            now + Duration::minutes(1),
+            &archive,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.counts.frames, 1);
+        assert_eq!(report.counts.media_files, 1);
+        assert_eq!(report.media_start, Some(earlier));
+        assert_eq!(report.media_end, Some(now));
+    }
+
+    #[tokio::test]
+    async fn test_export_rejects_empty_range() {
+        let data_dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let db = open_db(data_dir.path()).await;
+
+        let now = Utc::now();
+        assert!(
+            export_archive(&db, now, now, &out.path().join("archive.tar.zst"), false)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,135 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_db::DatabaseManager;
+    use screenpipe_server::{create_backup, find_backup, list_backups, restore_backup};
+    use sha2::{Digest, Sha256};
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    async fn record_chunk(db: &DatabaseManager, data_dir: &Path, name: &str) -> String {
+        let path = data_dir.join("data").join(name);
+        tokio::fs::create_dir_all(path.parent().unwrap())
+            .await
+            .unwrap();
+        tokio::fs::write(&path, name.as_bytes()).await.unwrap();
+
+        let path = path.to_string_lossy().to_string();
+        db.insert_video_chunk(&path, "test_device").await.unwrap();
+        db.insert_frame("test_device", None, None, None, None, false)
+            .await
+            .unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let data_dir = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        let db_path = data_dir.path().join("db.sqlite");
+        let db = DatabaseManager::new(&db_path.to_string_lossy())
+            .await
+            .unwrap();
+
+        let first_chunk = record_chunk(&db, data_dir.path(), "monitor_1_a.mp4").await;
+        let report = create_backup(&db, None, target.path(), None).await.unwrap();
+        assert_eq!(report.media_files, 1);
+        assert_eq!(report.media_files_copied, 1);
+
+        // only chunks recorded since the last snapshot are copied
+        record_chunk(&db, data_dir.path(), "monitor_1_b.mp4").await;
+        let report = create_backup(&db, None, target.path(), None).await.unwrap();
+        assert_eq!(report.media_files, 2);
+        assert_eq!(report.media_files_copied, 1);
+
+        let manifests = list_backups(target.path()).await.unwrap();
+        assert_eq!(manifests.len(), 2);
+        assert!(manifests[0].created_at <= manifests[1].created_at);
+        db.pool.close().await;
+
+        // lose the first chunk and go back to the first snapshot
+        tokio::fs::remove_file(&first_chunk).await.unwrap();
+        let manifest = find_backup(&manifests, None, Some(manifests[0].created_at)).unwrap();
+        assert_eq!(manifest.id, manifests[0].id);
+
+        let report = restore_backup(target.path(), &manifest, data_dir.path(), None)
+            .await
+            .unwrap();
+        assert_eq!(report.media_files_restored, 1);
+        assert!(Path::new(&report.previous_database.unwrap()).exists());
+        assert_eq!(
+            tokio::fs::read(&first_chunk).await.unwrap(),
+            b"monitor_1_a.mp4"
+        );
+        // verifying the restored database leaves it as it was backed up
+        let restored = tokio::fs::read(&db_path).await.unwrap();
+        assert_eq!(
+            format!("{:x}", Sha256::digest(&restored)),
+            manifest.database_sha256
+        );
+
+        let db = DatabaseManager::new(&db_path.to_string_lossy())
+            .await
+            .unwrap();
+        assert_eq!(db.list_media_files().await.unwrap(), vec![first_chunk]);
+    }
+
+    #[tokio::test]
+    async fn test_backup_prunes_old_snapshots() {
+        let data_dir = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        let db = DatabaseManager::new(&data_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+
+        let first_chunk = record_chunk(&db, data_dir.path(), "monitor_1_a.mp4").await;
+        create_backup(&db, None, target.path(), Some(1))
+            .await
+            .unwrap();
+
+        // the chunk is gone from the data directory, so only the pruned snapshot referenced it
+        tokio::fs::remove_file(&first_chunk).await.unwrap();
+        record_chunk(&db, data_dir.path(), "monitor_1_b.mp4").await;
+        let report = create_backup(&db, None, target.path(), Some(1))
+            .await
+            .unwrap();
+        assert_eq!(report.snapshots_pruned, 1);
+        assert_eq!(report.media_files_missing, 1);
+
+        let manifests = list_backups(target.path()).await.unwrap();
+        assert_eq!(manifests.len(), 1);
+        assert_eq!(manifests[0].id, report.snapshot_id);
+        assert!(!target.path().join("media").join("monitor_1_a.mp4").exists());
+        assert!(target.path().join("media").join("monitor_1_b.mp4").exists());
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_damaged_snapshot() {
+        let data_dir = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        let db = DatabaseManager::new(&data_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        record_chunk(&db, data_dir.path(), "monitor_1_a.mp4").await;
+        let report = create_backup(&db, None, target.path(), None).await.unwrap();
+
+        let snapshot_db = target
+            .path()
+            .join("snapshots")
+            .join(&report.snapshot_id)
+            .join("db.sqlite");
+        tokio::fs::write(&snapshot_db, b"not a database")
+            .await
+            .unwrap();
+
+        let manifests = list_backups(target.path()).await.unwrap();
+        let manifest = find_backup(&manifests, Some(report.snapshot_id.as_str()), None).unwrap();
+        assert!(
+            restore_backup(target.path(), &manifest, data_dir.path(), None)
+                .await
+                .is_err()
+        );
+        // the live database was not touched
+        assert!(data_dir.path().join("db.sqlite").exists());
+        assert!(db.list_media_files().await.is_ok());
+    }
+}
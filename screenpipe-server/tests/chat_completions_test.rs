@@ -0,0 +1,165 @@
+#[cfg(all(test, feature = "llm"))]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{header, Request, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use screenpipe_core::{
+        ChatMessage, ChatRequest, ChatResponse, ChatResponseChoice, ChatResponseChunk,
+        ChatResponseUsage, Model, LLM,
+    };
+    use screenpipe_server::llm::chat_endpoint::{chat_completions, set_chat_model};
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Once};
+    use tower::ServiceExt;
+
+    const STUB_MODEL: &str = "stub-echo";
+
+    /// Replies with the last message, one word per token, so no weights have to be loaded.
+    struct EchoModel;
+
+    impl Model for EchoModel {
+        fn chat_stream(
+            &self,
+            request: ChatRequest,
+            on_token: &mut dyn FnMut(&str) -> anyhow::Result<()>,
+        ) -> anyhow::Result<ChatResponse> {
+            let last = request
+                .messages
+                .last()
+                .map(|message| message.content.clone())
+                .unwrap_or_default();
+            let limit = request.max_completion_tokens.unwrap_or(usize::MAX);
+
+            let mut output = String::new();
+            let mut completion_tokens = 0;
+            for (i, word) in last.split_whitespace().take(limit).enumerate() {
+                let token = if i == 0 {
+                    word.to_string()
+                } else {
+                    format!(" {}", word)
+                };
+                on_token(&token)?;
+                output.push_str(&token);
+                completion_tokens += 1;
+            }
+
+            Ok(ChatResponse {
+                id: "chatcmpl-stub".to_string(),
+                object: "chat.completion".to_string(),
+                created: 0,
+                model: STUB_MODEL.to_string(),
+                system_fingerprint: "".to_string(),
+                choices: vec![ChatResponseChoice {
+                    index: 0,
+                    message: ChatMessage {
+                        role: "assistant".to_string(),
+                        content: output,
+                    },
+                    logprobs: None,
+                    finish_reason: "stop".to_string(),
+                }],
+                usage: ChatResponseUsage {
+                    prompt_tokens: 0,
+                    completion_tokens,
+                    total_tokens: completion_tokens,
+                    completion_tokens_details: Value::Null,
+                    tokens_per_second: 0.0,
+                },
+            })
+        }
+
+        fn model_id(&self) -> &str {
+            STUB_MODEL
+        }
+    }
+
+    // the chat model is set once per process
+    fn setup_app() -> Router {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| set_chat_model(Arc::new(LLM::from_model(EchoModel))).unwrap());
+        Router::new().route("/v1/chat/completions", post(chat_completions))
+    }
+
+    fn chat_request(stream: bool) -> Request<Body> {
+        let body = json!({
+            "messages": [{"role": "user", "content": "what did I work on today"}],
+            "stream": stream,
+            "max_completion_tokens": 4,
+        });
+        Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion() {
+        let response = setup_app().oneshot(chat_request(false)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let response: ChatResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.model, STUB_MODEL);
+        assert_eq!(response.choices[0].message.role, "assistant");
+        assert_eq!(response.choices[0].message.content, "what did I work");
+        assert_eq!(response.choices[0].finish_reason, "stop");
+        assert_eq!(response.usage.completion_tokens, 4);
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_stream() {
+        let response = setup_app().oneshot(chat_request(true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/event-stream"));
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let data: Vec<&str> = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(str::trim_start)
+            .collect();
+        assert_eq!(data.last(), Some(&"[DONE]"));
+
+        let chunks: Vec<ChatResponseChunk> = data[..data.len() - 1]
+            .iter()
+            .map(|chunk| serde_json::from_str(chunk).unwrap())
+            .collect();
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.object == "chat.completion.chunk" && chunk.model == STUB_MODEL));
+        assert!(chunks.iter().all(|chunk| chunk.id == chunks[0].id));
+        // the role comes first, the finish reason last, the content in between
+        assert_eq!(
+            chunks[0].choices[0].delta.role.as_deref(),
+            Some("assistant")
+        );
+        assert_eq!(
+            chunks.last().unwrap().choices[0].finish_reason.as_deref(),
+            Some("stop")
+        );
+        let content: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk.choices[0].delta.content.as_deref())
+            .collect();
+        assert_eq!(content, "what did I work");
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_without_messages() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({"messages": []}).to_string()))
+            .unwrap();
+        let response = setup_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use screenpipe_core::encryption::{
+        is_encrypted, read_media_file, seal_media, set_media_encryption_key, EncryptionKey,
+    };
+    use screenpipe_db::DatabaseManager;
+    use screenpipe_server::{export_archive, import_archive};
+    use tempfile::tempdir;
+
+    // the key is process wide, which is why this test lives in its own binary
+    #[tokio::test]
+    async fn test_export_of_encrypted_data_requires_decrypt() {
+        let _ = set_media_encryption_key(EncryptionKey::from_hex(&"ab".repeat(32)).unwrap());
+        let source_dir = tempdir().unwrap();
+        let target_dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let archive = out.path().join("archive.tar.zst");
+
+        let db = DatabaseManager::new(&source_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        let video = source_dir.path().join("monitor_1_a.mp4");
+        tokio::fs::write(&video, seal_media(b"frames".to_vec()).unwrap())
+            .await
+            .unwrap();
+        db.insert_video_chunk(&video.to_string_lossy(), "monitor_1")
+            .await
+            .unwrap();
+        db.insert_frame("monitor_1", None, None, None, None, true)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let (start, end) = (now - Duration::hours(1), now + Duration::hours(1));
+        assert!(export_archive(&db, start, end, &archive, false)
+            .await
+            .is_err());
+        assert!(!archive.exists());
+
+        let report = export_archive(&db, start, end, &archive, true)
+            .await
+            .unwrap();
+        assert_eq!(report.counts.media_files, 1);
+
+        // encrypted again on import
+        let target = DatabaseManager::new(&target_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        import_archive(&target, &archive, target_dir.path())
+            .await
+            .unwrap();
+        let imported = target_dir.path().join("data").join("monitor_1_a.mp4");
+        assert!(is_encrypted(&tokio::fs::read(&imported).await.unwrap()));
+        assert_eq!(
+            read_media_file(&imported.to_string_lossy()).await.unwrap(),
+            b"frames"
+        );
+    }
+}
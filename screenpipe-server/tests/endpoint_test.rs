@@ -29,6 +29,12 @@ mod tests {
     }
 
     async fn setup_test_app() -> (Router, Arc<DatabaseManager>) {
+        setup_test_app_with_admin_token(None).await
+    }
+
+    async fn setup_test_app_with_admin_token(
+        admin_token: Option<&str>,
+    ) -> (Router, Arc<DatabaseManager>) {
         let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
 
         let audio_manager = Arc::new(
@@ -48,6 +54,9 @@ mod tests {
             false,
             false,
             audio_manager,
+            admin_token.map(str::to_string),
+            None,
+            false,
         );
 
         let router = app.create_router(true).await;
@@ -780,4 +789,321 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_raw_sql_is_read_only() {
+        let (app, db) = setup_test_app().await;
+        db.insert_video_chunk("test_video_file.mp4", "test_device")
+            .await
+            .unwrap();
+        db.insert_frame("test_device", None, None, Some("code"), None, false)
+            .await
+            .unwrap();
+
+        let raw_sql = |body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/raw_sql")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(raw_sql(serde_json::json!({
+                "query": "SELECT id, app_name FROM frames WHERE app_name = ?",
+                "params": ["code"],
+                "include_columns": true
+            })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(result["rows"].as_array().unwrap().len(), 1);
+        assert_eq!(result["columns"][1]["name"], "app_name");
+        assert_eq!(result["columns"][1]["type"], "TEXT");
+        assert_eq!(result["truncated"], false);
+
+        let response = app
+            .clone()
+            .oneshot(raw_sql(serde_json::json!({ "query": "DROP TABLE frames" })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // no admin token is configured, so none is accepted
+        let response = app
+            .clone()
+            .oneshot(raw_sql(serde_json::json!({
+                "query": "DROP TABLE frames",
+                "admin_token": "guess"
+            })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM frames")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_raw_sql_from_other_origins_requires_admin_token() {
+        let (app, _db) = setup_test_app_with_admin_token(Some("secret")).await;
+        let raw_sql = |origin: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/raw_sql")
+                .header("content-type", "application/json")
+                .header("origin", origin)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let query = serde_json::json!({ "query": "SELECT 1" });
+
+        let response = app
+            .clone()
+            .oneshot(raw_sql("https://example.com", query.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .clone()
+            .oneshot(raw_sql("http://localhost:3000", query))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(raw_sql(
+                "https://example.com",
+                serde_json::json!({ "query": "SELECT 1", "admin_token": "secret" }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_delete_data_requires_admin_token() {
+        let delete = |query: &str| {
+            Request::builder()
+                .method("DELETE")
+                .uri(format!(
+                    "/data?start_time=2024-01-01T00:00:00Z&end_time=2024-01-02T00:00:00Z{}",
+                    query
+                ))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // without a configured token the endpoint is disabled
+        let (app, _db) = setup_test_app().await;
+        let response = app.clone().oneshot(delete("")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let (app, _db) = setup_test_app_with_admin_token(Some("secret")).await;
+        let response = app.clone().oneshot(delete("")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .clone()
+            .oneshot(delete("&admin_token=guess"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .clone()
+            .oneshot(delete("&admin_token=secret"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_retranscription_requires_local_admin() {
+        let request = |uri: &str, origin: Option<&str>, body: serde_json::Value| {
+            let mut request = Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json");
+            if let Some(origin) = origin {
+                request = request.header("origin", origin);
+            }
+            request.body(Body::from(body.to_string())).unwrap()
+        };
+
+        // without a configured token the endpoints are disabled
+        let (app, _db) = setup_test_app().await;
+        let response = app
+            .clone()
+            .oneshot(request(
+                "/audio/retranscribe/start",
+                None,
+                serde_json::json!({}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let (app, _db) = setup_test_app_with_admin_token(Some("secret")).await;
+        let response = app
+            .clone()
+            .oneshot(request(
+                "/audio/retranscribe/start",
+                None,
+                serde_json::json!({ "admin_token": "guess" }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .clone()
+            .oneshot(request(
+                "/audio/retranscribe/start",
+                Some("https://example.com"),
+                serde_json::json!({ "admin_token": "secret" }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app
+            .clone()
+            .oneshot(request(
+                "/audio/retranscribe/stop",
+                None,
+                serde_json::json!({}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .clone()
+            .oneshot(request(
+                "/audio/retranscribe/pause?admin_token=secret",
+                Some("http://localhost:3000"),
+                serde_json::json!({}),
+            ))
+            .await
+            .unwrap();
+        assert_ne!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_ne!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_recluster_speakers_defaults_to_dry_run() {
+        let (app, _db) = setup_test_app().await;
+        let recluster = |body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/speakers/recluster")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(recluster(serde_json::json!({})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(result["applied"], false);
+
+        // merging can't be undone, so it needs the admin token
+        let response = app
+            .clone()
+            .oneshot(recluster(serde_json::json!({"apply": true})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_audio_transcript() {
+        let (app, db) = setup_test_app().await;
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(
+            audio_chunk_id,
+            "Hello from the meeting",
+            0,
+            "",
+            &screenpipe_db::AudioDevice {
+                name: "mic".to_string(),
+                device_type: screenpipe_db::DeviceType::Input,
+            },
+            None,
+            Some(1.0),
+            Some(3.0),
+        )
+        .await
+        .unwrap();
+
+        let now = Utc::now();
+        let uri = |format: &str| {
+            format!(
+                "/audio/transcript?start={}&end={}&format={}",
+                (now - Duration::minutes(5)).format("%Y-%m-%dT%H:%M:%SZ"),
+                (now + Duration::minutes(5)).format("%Y-%m-%dT%H:%M:%SZ"),
+                format
+            )
+        };
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(uri("vtt"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/vtt; charset=utf-8"
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let vtt = String::from_utf8(body.to_vec()).unwrap();
+        assert!(vtt.starts_with("WEBVTT\n\n"));
+        assert!(vtt.contains("<v mic>Hello from the meeting"));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(uri("json"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let lines: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(lines[0]["text"], "Hello from the meeting");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "/audio/transcript?start={}&end={}",
+                        now.format("%Y-%m-%dT%H:%M:%SZ"),
+                        now.format("%Y-%m-%dT%H:%M:%SZ")
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,214 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use screenpipe_audio::audio_manager::AudioManagerBuilder;
+    use screenpipe_db::DatabaseManager;
+    use screenpipe_server::PipeManager;
+    use screenpipe_server::SCServer;
+    use screenpipe_vision::OcrEngine;
+    use serde_json::{json, Value};
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn setup_test_app() -> (Router, Arc<DatabaseManager>) {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+
+        let audio_manager = Arc::new(
+            AudioManagerBuilder::new()
+                .output_path("/tmp/screenpipe".into())
+                .build(db.clone())
+                .await
+                .unwrap(),
+        );
+
+        let app = SCServer::new(
+            db.clone(),
+            SocketAddr::from(([127, 0, 0, 1], 23949)),
+            PathBuf::from(""),
+            Arc::new(PipeManager::new(PathBuf::from(""))),
+            false,
+            false,
+            false,
+            audio_manager,
+            None,
+            None,
+            false,
+        );
+
+        (app.create_router(false).await, db)
+    }
+
+    async fn post_mcp(app: &Router, message: Value) -> (StatusCode, Option<Value>) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/mcp")
+                    .header("content-type", "application/json")
+                    .body(Body::from(message.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).ok())
+    }
+
+    #[tokio::test]
+    async fn test_mcp_initialize_and_list_tools() {
+        let (app, _db) = setup_test_app().await;
+
+        let (status, response) = post_mcp(
+            &app,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {"protocolVersion": "2024-11-05", "capabilities": {}}
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let response = response.unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(response["result"]["serverInfo"]["name"], "screenpipe");
+
+        // notifications are accepted without a response
+        let (status, _) = post_mcp(
+            &app,
+            json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let (_, response) = post_mcp(
+            &app,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+        )
+        .await;
+        let tools = response.unwrap()["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .clone();
+
+        let search = tools.iter().find(|tool| tool["name"] == "search").unwrap();
+        assert_eq!(search["inputSchema"]["type"], "object");
+        assert!(search["inputSchema"]["properties"].get("q").is_some());
+        assert!(search["inputSchema"]["properties"]
+            .get("content_type")
+            .is_some());
+
+        let frame = tools
+            .iter()
+            .find(|tool| tool["name"] == "get-frame")
+            .unwrap();
+        assert!(frame["inputSchema"]["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("frame_id")));
+
+        for name in ["semantic-search", "list-unnamed-speakers", "find-elements"] {
+            assert!(tools.iter().any(|tool| tool["name"] == name), "{}", name);
+        }
+
+        // tools that drive the machine are opt-in
+        for name in ["click-element", "type-text", "open-url", "pixel-control"] {
+            assert!(!tools.iter().any(|tool| tool["name"] == name), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mcp_rejects_foreign_origins() {
+        let (app, _db) = setup_test_app().await;
+
+        for (origin, expected) in [
+            ("https://example.com", StatusCode::FORBIDDEN),
+            ("http://localhost.example.com", StatusCode::FORBIDDEN),
+            ("http://localhost:3000", StatusCode::OK),
+            ("http://127.0.0.1:3030", StatusCode::OK),
+            ("tauri://localhost", StatusCode::OK),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/mcp")
+                        .header("content-type", "application/json")
+                        .header("origin", origin)
+                        .body(Body::from(
+                            json!({"jsonrpc": "2.0", "id": 1, "method": "ping"}).to_string(),
+                        ))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), expected, "{}", origin);
+            // the permissive cors layer of the api does not apply to /mcp
+            assert!(response
+                .headers()
+                .get("access-control-allow-origin")
+                .is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mcp_call_search() {
+        let (app, db) = setup_test_app().await;
+
+        let _ = db.insert_video_chunk("test_video.mp4", "test_device").await;
+        let frame_id = db
+            .insert_frame("test_device", None, None, None, None, false)
+            .await
+            .unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "hello from the mcp test",
+            "",
+            Arc::new(OcrEngine::Tesseract.into()),
+        )
+        .await
+        .unwrap();
+
+        let (_, response) = post_mcp(
+            &app,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "tools/call",
+                "params": {
+                    "name": "search",
+                    "arguments": {"q": "mcp", "content_type": "ocr", "limit": 5}
+                }
+            }),
+        )
+        .await;
+        let result = &response.unwrap()["result"];
+        assert_eq!(result["isError"], false);
+
+        let text = result["content"][0]["text"].as_str().unwrap();
+        let search: Value = serde_json::from_str(text).unwrap();
+        assert_eq!(search["data"].as_array().unwrap().len(), 1);
+
+        let (_, response) = post_mcp(
+            &app,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 4,
+                "method": "tools/call",
+                "params": {"name": "does-not-exist", "arguments": {}}
+            }),
+        )
+        .await;
+        assert_eq!(response.unwrap()["error"]["code"], -32602);
+    }
+}
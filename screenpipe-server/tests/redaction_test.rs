@@ -0,0 +1,85 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_core::find_ffmpeg_path;
+    use screenpipe_server::video_utils::redact_video_frames;
+    use std::path::Path;
+    use tokio::process::Command;
+
+    /// Ten frames with a gap after the fifth, like a chunk recorded at a variable rate.
+    async fn create_variable_rate_video(path: &Path) {
+        let ffmpeg = find_ffmpeg_path().expect("failed to find ffmpeg path");
+        let status = Command::new(ffmpeg)
+            .args([
+                "-f",
+                "lavfi",
+                "-i",
+                "testsrc=size=64x64:rate=10",
+                "-vf",
+                "setpts='(N+if(gte(N,5),3,0))/10/TB'",
+                "-frames:v",
+                "10",
+                "-fps_mode",
+                "passthrough",
+                "-c:v",
+                "libx264",
+                "-pix_fmt",
+                "yuv420p",
+                "-y",
+            ])
+            .arg(path)
+            .status()
+            .await
+            .unwrap();
+        assert!(status.success());
+    }
+
+    async fn frame_times(path: &Path) -> Vec<f64> {
+        let ffprobe = find_ffmpeg_path()
+            .expect("failed to find ffmpeg path")
+            .with_file_name("ffprobe");
+        let output = Command::new(ffprobe)
+            .args([
+                "-v",
+                "error",
+                "-select_streams",
+                "v:0",
+                "-show_entries",
+                "frame=pts_time",
+                "-of",
+                "csv=p=0",
+            ])
+            .arg(path)
+            .output()
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.trim().trim_end_matches(',').parse().ok())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_redaction_keeps_frames_and_timestamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor_1.mp4");
+        create_variable_rate_video(&path).await;
+        let before = frame_times(&path).await;
+        assert_eq!(before.len(), 10);
+
+        redact_video_frames(&path.to_string_lossy(), &[2, 3, 7])
+            .await
+            .unwrap();
+
+        let after = frame_times(&path).await;
+        assert_eq!(after.len(), before.len());
+        for (before, after) in before.iter().zip(&after) {
+            assert!(
+                (before - after).abs() < 0.01,
+                "{} moved to {}",
+                before,
+                after
+            );
+        }
+    }
+}
@@ -37,6 +37,9 @@ async fn setup_test_app() -> (Router, Arc<DatabaseManager>) {
         false,
         false,
         audio_manager,
+        None,
+        None,
+        false,
     );
 
     let router = app.create_router(true).await;
@@ -0,0 +1,130 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Utc};
+    use screenpipe_db::{TranscriptEntry, TranscriptionSegment};
+    use screenpipe_server::{render_transcript, stitch_transcript, TranscriptFormat};
+    use std::collections::HashMap;
+
+    fn entry(
+        id: i64,
+        chunk_timestamp: DateTime<Utc>,
+        start_time: f64,
+        end_time: f64,
+        transcription: &str,
+        device_name: &str,
+        speaker: (Option<i64>, Option<&str>),
+    ) -> TranscriptEntry {
+        TranscriptEntry {
+            audio_transcription_id: id,
+            audio_chunk_id: id,
+            chunk_timestamp,
+            timestamp: chunk_timestamp,
+            start_time: Some(start_time),
+            end_time: Some(end_time),
+            transcription: transcription.to_string(),
+            device_name: device_name.to_string(),
+            is_input_device: device_name == "mic",
+            speaker_id: speaker.0,
+            speaker_name: speaker.1.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_stitch_transcript_merges_devices_and_drops_overlap() {
+        let origin = Utc::now();
+        let entries = vec![
+            entry(
+                1,
+                origin,
+                0.0,
+                4.0,
+                "shall we look at the roadmap",
+                "mic",
+                (Some(1), Some("Ada")),
+            ),
+            entry(
+                2,
+                origin,
+                5.0,
+                8.0,
+                "Sure, go ahead.",
+                "speakers",
+                (Some(2), None),
+            ),
+            // the next chunk of the mic repeats what was said at the end of the previous one
+            entry(
+                3,
+                origin + Duration::seconds(28),
+                0.0,
+                3.0,
+                "at the roadmap for next quarter",
+                "mic",
+                (Some(1), Some("Ada")),
+            ),
+        ];
+
+        let lines = stitch_transcript(&entries, &HashMap::new());
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].speaker, "Ada");
+        assert_eq!(lines[1].speaker, "Speaker 2");
+        assert_eq!(lines[1].start, origin + Duration::seconds(5));
+        assert_eq!(lines[2].text, "for next quarter");
+
+        let srt = render_transcript(&lines, TranscriptFormat::Srt, origin);
+        assert!(srt.starts_with(
+            "1\n00:00:00,000 --> 00:00:04,000\nAda: shall we look at the roadmap\n\n"
+        ));
+        assert!(srt.contains("3\n00:00:28,000 --> 00:00:31,000\nAda: for next quarter\n"));
+
+        let vtt = render_transcript(&lines, TranscriptFormat::Vtt, origin);
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:04.000\n<v Ada>shall we"));
+
+        let txt = render_transcript(&lines, TranscriptFormat::Txt, origin);
+        assert_eq!(
+            txt,
+            "[00:00:00] Ada: shall we look at the roadmap\n\n\
+             [00:00:05] Speaker 2: Sure, go ahead.\n\n\
+             [00:00:28] Ada: for next quarter\n"
+        );
+    }
+
+    #[test]
+    fn test_stitch_transcript_uses_segments() {
+        let origin = Utc::now();
+        let entries = vec![entry(
+            1,
+            origin,
+            0.0,
+            6.0,
+            "Hello. How are you?",
+            "mic",
+            (None, None),
+        )];
+        let segments = HashMap::from([(
+            1,
+            vec![
+                TranscriptionSegment {
+                    start_time: 0.5,
+                    end_time: 1.2,
+                    text: "Hello.".to_string(),
+                    words: Vec::new(),
+                },
+                TranscriptionSegment {
+                    start_time: 3.0,
+                    end_time: 4.5,
+                    text: "How are you?".to_string(),
+                    words: Vec::new(),
+                },
+            ],
+        )]);
+
+        let lines = stitch_transcript(&entries, &segments);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].speaker, "mic");
+        assert_eq!(lines[1].start, origin + Duration::seconds(3));
+        assert_eq!(lines[1].end, origin + Duration::milliseconds(4500));
+
+        let txt = render_transcript(&lines, TranscriptFormat::Txt, origin);
+        assert_eq!(txt, "[00:00:00] mic: Hello. How are you?\n");
+    }
+}
@@ -1,13 +1,20 @@
 // cargo bench --bench vision_benchmark -- benchmark_continuous_capture
 // or
+// cargo bench --bench vision_benchmark -- frame_diff
+// or
 // cargo bench --bench vision_benchmark
-// ! not very useful bench
+// ! not very useful bench, except frame_diff
 
 use std::sync::Arc;
 
-use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use screenpipe_vision::capture_screenshot_by_window::WindowFilters;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::{DynamicImage, Rgba, RgbaImage};
+use screenpipe_vision::capture_rate::CaptureRate;
+use screenpipe_vision::capture_screenshot_by_window::{CapturedWindow, WindowFilters};
+use screenpipe_vision::frame_diff::{FrameDeduplicator, FrameDiffAlgorithm, FrameDiffConfig};
+use screenpipe_vision::incremental_ocr::IncrementalOcrConfig;
 use screenpipe_vision::monitor::get_default_monitor;
+use screenpipe_vision::privacy::PrivacyFilter;
 use screenpipe_vision::{continuous_capture, OcrEngine};
 use tokio::sync::mpsc;
 use tokio::time::Duration;
@@ -19,10 +26,13 @@ async fn benchmark_continuous_capture(duration_secs: u64) -> f64 {
     let capture_handle = tokio::spawn(async move {
         if let Err(e) = continuous_capture(
             result_tx,
-            Duration::from_millis(100),
+            Arc::new(CaptureRate::fixed(10.0)),
             OcrEngine::Tesseract,
             get_default_monitor().await.id(),
             window_filters,
+            Arc::new(PrivacyFilter::default()),
+            FrameDiffConfig::default(),
+            IncrementalOcrConfig::default(),
             vec![],
             false,
         )
@@ -67,5 +77,146 @@ fn criterion_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, criterion_benchmark);
+/// Deterministic pseudo random numbers, so that every run compares the same frames
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, max: u32) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % max.max(1) as u64) as u32
+    }
+}
+
+/// Draws lines of glyph-like dark blocks over `area` of `image`, like text on a light page.
+fn draw_text(image: &mut RgbaImage, (x, y, width, height): (u32, u32, u32, u32), rng: &mut Lcg) {
+    for py in y..y + height {
+        for px in x..x + width {
+            image.put_pixel(px, py, Rgba([250, 250, 250, 255]));
+        }
+    }
+    for line in (y..(y + height).saturating_sub(12)).step_by(20) {
+        let mut px = x;
+        while px + 10 < x + width {
+            let glyph_width = 4 + rng.next(6);
+            if rng.next(6) > 0 {
+                let shade = 20 + rng.next(60) as u8;
+                for gy in line..line + 12 {
+                    for gx in px..px + glyph_width {
+                        image.put_pixel(gx, gy, Rgba([shade, shade, shade, 255]));
+                    }
+                }
+            }
+            px += glyph_width + 2;
+        }
+    }
+}
+
+/// A window of the frame, as the capture loop hands it to the deduplicator.
+fn window_of(frame: &RgbaImage, (x, y, width, height): (u32, u32, u32, u32)) -> CapturedWindow {
+    CapturedWindow {
+        image: DynamicImage::ImageRgba8(
+            image::imageops::crop_imm(frame, x, y, width, height).to_image(),
+        ),
+        app_name: "Editor".to_string(),
+        window_name: "notes.md".to_string(),
+        process_id: 0,
+        is_focused: true,
+        frame_position: Some((x as i32, y as i32)),
+    }
+}
+
+/// Prints the share of frames with a change in the window that each algorithm takes for
+/// duplicates of the previous one, what the cheaper algorithms trade for their speed.
+fn report_missed_changes(screen: &RgbaImage, window: (u32, u32, u32, u32)) {
+    const SAMPLES: u32 = 40;
+    let changes = [
+        ("cursor", (2, 16)),
+        ("word", (60, 14)),
+        ("line", (600, 14)),
+        ("message", (500, 80)),
+    ];
+
+    println!("missed changes out of {} per change:", SAMPLES);
+    for per_window in [false, true] {
+        for algorithm in FrameDiffAlgorithm::ALL {
+            let config = FrameDiffConfig {
+                algorithm,
+                threshold: None,
+                per_window,
+            };
+            let mut deduplicator = FrameDeduplicator::new(config);
+            let previous = deduplicator.compare(
+                &DynamicImage::ImageRgba8(screen.clone()),
+                &[window_of(screen, window)],
+            );
+            deduplicator.accept(previous);
+
+            let mut report = format!(
+                "{:<14} {:<10}",
+                algorithm.to_string(),
+                if per_window { "per window" } else { "" }
+            );
+            for (name, (width, height)) in changes {
+                let mut rng = Lcg(7);
+                let mut missed = 0;
+                for _ in 0..SAMPLES {
+                    let mut changed = screen.clone();
+                    let x = window.0 + rng.next(window.2 - width);
+                    let y = window.1 + rng.next(window.3 - height);
+                    draw_text(&mut changed, (x, y, width, height), &mut rng);
+                    let comparison = deduplicator.compare(
+                        &DynamicImage::ImageRgba8(changed.clone()),
+                        &[window_of(&changed, window)],
+                    );
+                    if deduplicator.is_duplicate(&comparison) {
+                        missed += 1;
+                    }
+                }
+                report.push_str(&format!(" {} {:>2}", name, missed));
+            }
+            println!("{}", report);
+        }
+    }
+}
+
+fn frame_diff_benchmark(c: &mut Criterion) {
+    // a 2560x1600 display with an editor window, where the changes happen
+    let mut rng = Lcg(42);
+    let mut screen = RgbaImage::from_pixel(2560, 1600, Rgba([40, 44, 52, 255]));
+    let window = (400, 300, 1200, 900);
+    draw_text(&mut screen, window, &mut rng);
+    report_missed_changes(&screen, window);
+
+    let frame = DynamicImage::ImageRgba8(screen.clone());
+    let windows = [window_of(&screen, window)];
+
+    let mut group = c.benchmark_group("frame_diff");
+    group.sample_size(20);
+    for per_window in [false, true] {
+        for algorithm in FrameDiffAlgorithm::ALL {
+            let mut deduplicator = FrameDeduplicator::new(FrameDiffConfig {
+                algorithm,
+                threshold: None,
+                per_window,
+            });
+            let previous = deduplicator.compare(&frame, &windows);
+            deduplicator.accept(previous);
+
+            let name = if per_window {
+                format!("{}_per_window", algorithm)
+            } else {
+                algorithm.to_string()
+            };
+            group.bench_function(name, |b| {
+                b.iter(|| deduplicator.compare(black_box(&frame), black_box(&windows)))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark, frame_diff_benchmark);
 criterion_main!(benches);
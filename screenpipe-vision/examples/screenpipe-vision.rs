@@ -1,9 +1,11 @@
 use clap::Parser;
 use screenpipe_core::Language;
 use screenpipe_vision::{
-    capture_screenshot_by_window::WindowFilters, continuous_capture, OcrEngine,
+    capture_rate::CaptureRate, capture_screenshot_by_window::WindowFilters, continuous_capture,
+    frame_diff::FrameDiffConfig, incremental_ocr::IncrementalOcrConfig, privacy::PrivacyFilter,
+    OcrEngine,
 };
-use std::{sync::Arc, time::Duration};
+use std::sync::Arc;
 use tokio::sync::mpsc::channel;
 use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
 use xcap::Monitor;
@@ -43,10 +45,13 @@ async fn main() {
 
     let _ = continuous_capture(
         result_tx,
-        Duration::from_secs_f32(1.0 / cli.fps),
+        Arc::new(CaptureRate::fixed(cli.fps as f64)),
         OcrEngine::AppleNative,
         monitor_id.unwrap(),
         window_filters,
+        Arc::new(PrivacyFilter::default()),
+        FrameDiffConfig::default(),
+        IncrementalOcrConfig::default(),
         languages.clone(),
         false,
     )
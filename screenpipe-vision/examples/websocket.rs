@@ -3,7 +3,11 @@ use base64::{engine::general_purpose, Engine as _};
 use clap::Parser;
 use futures_util::{SinkExt, StreamExt};
 use image::ImageEncoder;
+use screenpipe_vision::capture_rate::CaptureRate;
 use screenpipe_vision::capture_screenshot_by_window::WindowFilters;
+use screenpipe_vision::frame_diff::FrameDiffConfig;
+use screenpipe_vision::incremental_ocr::IncrementalOcrConfig;
+use screenpipe_vision::privacy::PrivacyFilter;
 use screenpipe_vision::{
     continuous_capture, monitor::get_default_monitor, CaptureResult, OcrEngine,
 };
@@ -89,7 +93,7 @@ async fn main() -> Result<()> {
     tokio::spawn(async move {
         continuous_capture(
             result_tx,
-            Duration::from_secs_f64(1.0 / cli.fps),
+            Arc::new(CaptureRate::fixed(cli.fps)),
             // if apple use apple otherwise if windows use windows native otherwise use tesseract
             if cfg!(target_os = "macos") {
                 OcrEngine::AppleNative
@@ -100,6 +104,9 @@ async fn main() -> Result<()> {
             },
             id,
             window_filters,
+            Arc::new(PrivacyFilter::default()),
+            FrameDiffConfig::default(),
+            IncrementalOcrConfig::default(),
             vec![],
             false,
         )
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The rate is multiplied by this after a frame that changed, up to the ceiling
+const RAMP_UP_FACTOR: f64 = 2.0;
+/// The rate is divided by this after each frame that didn't change, down to the floor
+const BACKOFF_FACTOR: f64 = 1.25;
+/// The ceiling is divided by this while the machine runs on battery
+const BATTERY_DIVISOR: f64 = 2.0;
+/// System CPU usage, in percent, above which the ceiling is lowered
+const CPU_PRESSURE_THRESHOLD: f32 = 80.0;
+/// The ceiling is divided by this under CPU pressure
+const CPU_PRESSURE_DIVISOR: f64 = 2.0;
+
+/// Settings of the rate screens are captured at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CaptureRateConfig {
+    /// Frames per second when not adaptive, the rate the capture starts at otherwise
+    pub fps: f64,
+    /// Capture faster while the screen changes and slower while it doesn't
+    pub adaptive: bool,
+    /// Lowest rate of an adaptive capture
+    pub min_fps: f64,
+    /// Highest rate of an adaptive capture, lowered on battery and under CPU pressure
+    pub max_fps: f64,
+}
+
+impl CaptureRateConfig {
+    pub fn fixed(fps: f64) -> Self {
+        Self {
+            fps,
+            adaptive: false,
+            min_fps: fps,
+            max_fps: fps,
+        }
+    }
+}
+
+/// Rate of the screen captures, shared by the capture loops of all monitors. The power and CPU
+/// state it adapts to is updated from outside, and it keeps the rate each monitor is captured at
+/// for reporting.
+pub struct CaptureRate {
+    config: CaptureRateConfig,
+    on_battery: AtomicBool,
+    /// f32 bits
+    cpu_usage: AtomicU32,
+    effective_fps: Mutex<BTreeMap<u32, f64>>,
+}
+
+impl CaptureRate {
+    pub fn new(config: CaptureRateConfig) -> Self {
+        Self {
+            config,
+            on_battery: AtomicBool::new(false),
+            cpu_usage: AtomicU32::new(0f32.to_bits()),
+            effective_fps: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn fixed(fps: f64) -> Self {
+        Self::new(CaptureRateConfig::fixed(fps))
+    }
+
+    pub fn config(&self) -> CaptureRateConfig {
+        self.config
+    }
+
+    pub fn set_on_battery(&self, on_battery: bool) {
+        self.on_battery.store(on_battery, Ordering::Relaxed);
+    }
+
+    pub fn on_battery(&self) -> bool {
+        self.on_battery.load(Ordering::Relaxed)
+    }
+
+    /// System wide CPU usage, in percent.
+    pub fn set_cpu_usage(&self, cpu_usage: f32) {
+        self.cpu_usage.store(cpu_usage.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn cpu_usage(&self) -> f32 {
+        f32::from_bits(self.cpu_usage.load(Ordering::Relaxed))
+    }
+
+    /// Highest rate an adaptive capture may run at right now, never below the floor.
+    pub fn ceiling(&self) -> f64 {
+        let mut ceiling = self.config.max_fps;
+        if self.on_battery() {
+            ceiling /= BATTERY_DIVISOR;
+        }
+        if self.cpu_usage() > CPU_PRESSURE_THRESHOLD {
+            ceiling /= CPU_PRESSURE_DIVISOR;
+        }
+        ceiling.max(self.config.min_fps)
+    }
+
+    /// Rate each monitor is currently captured at, by monitor id.
+    pub fn effective_fps(&self) -> BTreeMap<u32, f64> {
+        self.effective_fps.lock().unwrap().clone()
+    }
+
+    /// Schedules the captures of a monitor, for as long as it lives.
+    pub fn scheduler(self: &Arc<Self>, monitor_id: u32) -> CaptureScheduler {
+        let fps = if self.config.adaptive {
+            self.config.fps.clamp(self.config.min_fps, self.ceiling())
+        } else {
+            self.config.fps
+        };
+        self.effective_fps.lock().unwrap().insert(monitor_id, fps);
+
+        CaptureScheduler {
+            rate: Arc::clone(self),
+            monitor_id,
+            fps,
+        }
+    }
+}
+
+/// Paces the capture loop of one monitor.
+pub struct CaptureScheduler {
+    rate: Arc<CaptureRate>,
+    monitor_id: u32,
+    fps: f64,
+}
+
+impl CaptureScheduler {
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    /// Adapts the rate to whether the last captured frame changed, and returns how long to wait
+    /// before the next capture. A changed frame doubles the rate, each unchanged one lowers it
+    /// a bit, so that a static screen backs off exponentially.
+    pub fn next_interval(&mut self, frame_changed: bool) -> Duration {
+        let config = self.rate.config;
+        if config.adaptive {
+            let fps = if frame_changed {
+                self.fps * RAMP_UP_FACTOR
+            } else {
+                self.fps / BACKOFF_FACTOR
+            };
+            self.fps = fps.clamp(config.min_fps, self.rate.ceiling());
+            self.rate
+                .effective_fps
+                .lock()
+                .unwrap()
+                .insert(self.monitor_id, self.fps);
+        }
+        Duration::from_secs_f64(1.0 / self.fps)
+    }
+}
+
+impl Drop for CaptureScheduler {
+    fn drop(&mut self) {
+        if let Ok(mut effective_fps) = self.rate.effective_fps.lock() {
+            effective_fps.remove(&self.monitor_id);
+        }
+    }
+}
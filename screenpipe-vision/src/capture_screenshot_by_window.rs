@@ -1,13 +1,16 @@
 use image::DynamicImage;
 use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
 use std::collections::HashSet;
 use std::error::Error;
 use std::fmt;
-use tracing::{debug, error};
+use std::str::FromStr;
+use tracing::{debug, error, warn};
 
 use xcap::{Window, XCapError};
 
 use crate::monitor::SafeMonitor;
+use crate::redaction::{redact_regions, ScreenRegion, WindowRedaction};
 
 #[derive(Debug)]
 enum CaptureError {
@@ -141,82 +144,300 @@ pub struct CapturedWindow {
     pub window_name: String,
     pub process_id: i32,
     pub is_focused: bool,
+    /// Top left corner of the window in the frame captured of its monitor, in pixels. `None`
+    /// if the window's position is unknown.
+    pub frame_position: Option<(i32, i32)>,
 }
 
+/// A pattern of the window filters, matched case insensitively against both the app name and
+/// the title of a window:
+/// - plain text matches names containing it
+/// - `glob:` followed by a glob with `*` and `?` wildcards matches whole names
+/// - `regex:` followed by a regular expression matches names it is found in
+#[derive(Clone, Debug)]
+pub enum WindowPattern {
+    Contains(String),
+    Matches(Regex),
+}
+
+impl WindowPattern {
+    /// `name` must be lowercase.
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            WindowPattern::Contains(text) => name.contains(text.as_str()),
+            WindowPattern::Matches(regex) => regex.is_match(name),
+        }
+    }
+
+    pub fn matches_window(&self, app_name: &str, title: &str) -> bool {
+        self.matches(&app_name.to_lowercase()) || self.matches(&title.to_lowercase())
+    }
+}
+
+impl FromStr for WindowPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = if let Some(glob) = s.strip_prefix("glob:") {
+            let pattern: String = glob
+                .chars()
+                .map(|c| match c {
+                    '*' => ".*".to_string(),
+                    '?' => ".".to_string(),
+                    c => regex::escape(&c.to_string()),
+                })
+                .collect();
+            format!("^{}$", pattern)
+        } else if let Some(regex) = s.strip_prefix("regex:") {
+            regex.to_string()
+        } else {
+            return Ok(WindowPattern::Contains(s.to_lowercase()));
+        };
+
+        RegexBuilder::new(&regex)
+            .case_insensitive(true)
+            .build()
+            .map(WindowPattern::Matches)
+            .map_err(|e| format!("invalid window pattern {}: {}", s, e))
+    }
+}
+
+/// Decides which windows are recorded. A window matching any ignore pattern is never
+/// recorded, even if it also matches an include pattern. Otherwise it is recorded if the
+/// include list is empty or it matches one of the include patterns.
 pub struct WindowFilters {
-    ignore_set: HashSet<String>,
-    include_set: HashSet<String>,
+    ignore: Vec<WindowPattern>,
+    include: Vec<WindowPattern>,
+    redaction: WindowRedaction,
 }
 
 impl WindowFilters {
+    /// Invalid patterns are matched as plain text, validate them with [`WindowPattern`]'s
+    /// `FromStr` beforehand to report them.
     pub fn new(ignore_list: &[String], include_list: &[String]) -> Self {
+        let parse = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    pattern.parse().unwrap_or_else(|e| {
+                        warn!("{}, matching it as plain text", e);
+                        WindowPattern::Contains(pattern.to_lowercase())
+                    })
+                })
+                .collect()
+        };
+
         Self {
-            ignore_set: ignore_list.iter().map(|s| s.to_lowercase()).collect(),
-            include_set: include_list.iter().map(|s| s.to_lowercase()).collect(),
+            ignore: parse(ignore_list),
+            include: parse(include_list),
+            redaction: WindowRedaction::Off,
         }
     }
 
-    // O(n) - we could figure out a better way to do this
+    /// Hides the windows that aren't recorded in the captured frames too, not only in OCR.
+    pub fn with_redaction(mut self, redaction: WindowRedaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    pub fn redaction(&self) -> WindowRedaction {
+        self.redaction
+    }
+
     pub fn is_valid(&self, app_name: &str, title: &str) -> bool {
         let app_name_lower = app_name.to_lowercase();
         let title_lower = title.to_lowercase();
+        let matches = |pattern: &WindowPattern| {
+            pattern.matches(&app_name_lower) || pattern.matches(&title_lower)
+        };
 
-        // If include list is empty, we're done
-        if self.include_set.is_empty() {
-            return true;
+        if self.ignore.iter().any(matches) {
+            return false;
         }
+        self.include.is_empty() || self.include.iter().any(matches)
+    }
+}
+
+/// A window that was on screen when a frame was captured, read once per frame. Properties
+/// that couldn't be read are `None`.
+#[derive(Debug, Clone, Default)]
+pub struct VisibleWindow {
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    /// Position and size in the screen coordinates monitors are positioned in
+    pub bounds: Option<(i32, i32, u32, u32)>,
+    pub is_minimized: bool,
+}
 
-        // Check include list
-        if self
-            .include_set
-            .iter()
-            .any(|include| app_name_lower.contains(include) || title_lower.contains(include))
-        {
-            return true;
+impl VisibleWindow {
+    fn read(window: &Window) -> Self {
+        let bounds = (|| {
+            Some((
+                window.x().ok()?,
+                window.y().ok()?,
+                window.width().ok()?,
+                window.height().ok()?,
+            ))
+        })();
+
+        Self {
+            app_name: window.app_name().ok(),
+            window_name: window.title().ok(),
+            bounds,
+            is_minimized: window.is_minimized().unwrap_or(false),
         }
+    }
 
-        // Check ignore list first (usually smaller)
-        if !self.ignore_set.is_empty()
-            && self
-                .ignore_set
-                .iter()
-                .any(|ignore| app_name_lower.contains(ignore) || title_lower.contains(ignore))
-        {
-            return false;
+    /// Whether the window shows on the monitor at `monitor_origin`. A window whose position is
+    /// unknown may be on any monitor.
+    pub fn is_on_monitor(&self, monitor_origin: (i32, i32), monitor_size: (u32, u32)) -> bool {
+        !self.is_minimized
+            && self.bounds.map_or(true, |bounds| {
+                ScreenRegion::from_screen_bounds(bounds, monitor_origin, monitor_size, monitor_size)
+                    .is_some()
+            })
+    }
+}
+
+/// The windows on screen when a frame was captured.
+#[derive(Debug, Clone, Default)]
+pub struct CapturedWindows {
+    /// Every window on screen, `None` if they couldn't be listed
+    pub visible: Option<Vec<VisibleWindow>>,
+    /// The windows that are recorded, with their images
+    pub captured: Vec<CapturedWindow>,
+}
+
+/// Lists the windows on screen once and captures the ones `window_filters` record. Blocking,
+/// run it with `spawn_blocking`.
+pub fn capture_windows(
+    monitor: &SafeMonitor,
+    window_filters: &WindowFilters,
+    capture_unfocused_windows: bool,
+) -> CapturedWindows {
+    let windows = match Window::all() {
+        Ok(windows) => windows,
+        Err(e) => {
+            warn!("failed to list windows: {}", e);
+            return CapturedWindows::default();
+        }
+    };
+    let visible: Vec<VisibleWindow> = windows.iter().map(VisibleWindow::read).collect();
+
+    let captured = match capture_all_visible_windows(
+        &windows,
+        &visible,
+        monitor,
+        window_filters,
+        capture_unfocused_windows,
+    ) {
+        Ok(captured) => captured,
+        Err(e) => {
+            warn!(
+                "Failed to capture window images: {}. Continuing with empty result.",
+                e
+            );
+            Vec::new()
+        }
+    };
+
+    CapturedWindows {
+        visible: Some(visible),
+        captured,
+    }
+}
+
+/// The regions of a `frame_size` capture of the monitor at `monitor_origin` that hold windows
+/// `window_filters` leave out of the recording. Windows are hidden whole, including the parts
+/// other windows cover. What can't be read is hidden rather than risking to leak it: a window
+/// whose name is unknown is hidden, and the whole frame is when the windows couldn't be listed
+/// (`windows` is `None`) or a hidden window's position is unknown.
+pub fn redaction_regions(
+    windows: Option<&[VisibleWindow]>,
+    monitor_origin: (i32, i32),
+    monitor_size: (u32, u32),
+    frame_size: (u32, u32),
+    window_filters: &WindowFilters,
+) -> Vec<ScreenRegion> {
+    let whole_frame = || vec![ScreenRegion::full(frame_size.0, frame_size.1)];
+    let Some(windows) = windows else {
+        return whole_frame();
+    };
+
+    let mut regions = Vec::new();
+    for window in windows.iter().filter(|window| !window.is_minimized) {
+        if let (Some(app_name), Some(title)) = (&window.app_name, &window.window_name) {
+            if SKIP_APPS.contains(app_name.as_str())
+                || SKIP_TITLES.contains(title.as_str())
+                || window_filters.is_valid(app_name, title)
+            {
+                continue;
+            }
+        }
+
+        match window.bounds {
+            Some(bounds) => regions.extend(ScreenRegion::from_screen_bounds(
+                bounds,
+                monitor_origin,
+                monitor_size,
+                frame_size,
+            )),
+            None => return whole_frame(),
         }
+    }
+    regions
+}
+
+/// Paints over the windows of `monitor` that `window_filters` leave out of the recording in
+/// `image`, a capture of it, as configured by [`WindowFilters::with_redaction`]. `windows` are
+/// the windows on screen, see [`redaction_regions`].
+pub fn redact_filtered_windows(
+    image: &mut DynamicImage,
+    monitor: &SafeMonitor,
+    window_filters: &WindowFilters,
+    windows: Option<&[VisibleWindow]>,
+) {
+    let redaction = window_filters.redaction();
+    if redaction == WindowRedaction::Off {
+        return;
+    }
 
-        false
+    if windows.is_none() {
+        warn!("failed to list windows to redact, redacting the whole frame");
     }
+    let regions = redaction_regions(
+        windows,
+        monitor.origin(),
+        monitor.dimensions(),
+        (image.width(), image.height()),
+        window_filters,
+    );
+    redact_regions(image, &regions, redaction);
 }
 
-pub async fn capture_all_visible_windows(
+/// Captures the images of `windows` that are recorded, `visible` holds their properties.
+fn capture_all_visible_windows(
+    windows: &[Window],
+    visible: &[VisibleWindow],
     monitor: &SafeMonitor,
     window_filters: &WindowFilters,
     capture_unfocused_windows: bool,
-) -> Result<Vec<CapturedWindow>, Box<dyn Error>> {
+) -> Result<Vec<CapturedWindow>, CaptureError> {
     let mut all_captured_images = Vec::new();
 
-    // Get windows and immediately extract the data we need
-    let windows_data = Window::all()?
-        .into_iter()
-        .filter_map(|window| {
-            // Extract all necessary data from the window while in the main thread
-            let app_name = match window.app_name() {
-                Ok(name) => name.to_string(),
-                Err(e) => {
-                    // Log warning and skip this window
-                    // mostly noise
-                    debug!("Failed to get app_name for window: {}", e);
-                    return None;
-                }
+    let windows_data = windows
+        .iter()
+        .zip(visible)
+        .filter_map(|(window, info)| {
+            let Some(app_name) = info.app_name.clone() else {
+                // mostly noise
+                debug!("Failed to get app_name for window");
+                return None;
             };
 
-            let title = match window.title() {
-                Ok(title) => title.to_string(),
-                Err(e) => {
-                    error!("Failed to get title for window {}: {}", app_name, e);
-                    return None;
-                }
+            let Some(title) = info.window_name.clone() else {
+                error!("Failed to get title for window {}", app_name);
+                return None;
             };
 
             let is_focused = match window.is_focused() {
@@ -243,7 +464,17 @@ pub async fn capture_all_visible_windows(
 
             // Capture image immediately while we have access to the window
             match window.capture_image() {
-                Ok(buffer) => Some((app_name, title, is_focused, buffer, process_id)),
+                Ok(buffer) => {
+                    let frame_position = frame_position(window, monitor, buffer.width());
+                    Some((
+                        app_name,
+                        title,
+                        is_focused,
+                        buffer,
+                        process_id,
+                        frame_position,
+                    ))
+                }
                 Err(e) => {
                     error!(
                         "Failed to capture image for window {} ({}): {}",
@@ -256,11 +487,11 @@ pub async fn capture_all_visible_windows(
         .collect::<Vec<_>>();
 
     if windows_data.is_empty() {
-        return Err(Box::new(CaptureError::NoWindows));
+        return Err(CaptureError::NoWindows);
     }
 
     // Process the captured data
-    for (app_name, window_name, is_focused, buffer, process_id) in windows_data {
+    for (app_name, window_name, is_focused, buffer, process_id, frame_position) in windows_data {
         // Convert to DynamicImage
         let image = DynamicImage::ImageRgba8(
             image::ImageBuffer::from_raw(buffer.width(), buffer.height(), buffer.into_raw())
@@ -280,9 +511,22 @@ pub async fn capture_all_visible_windows(
                 window_name,
                 process_id: process_id as i32,
                 is_focused,
+                frame_position,
             });
         }
     }
 
     Ok(all_captured_images)
 }
+
+/// Where `window` is in a capture of `monitor`. Window images are captured at the scale of the
+/// monitor frames, `image_width` is used to find it.
+fn frame_position(window: &Window, monitor: &SafeMonitor, image_width: u32) -> Option<(i32, i32)> {
+    let width = window.width().ok().filter(|width| *width > 0)?;
+    let scale = image_width as f64 / width as f64;
+    let (monitor_x, monitor_y) = monitor.origin();
+    Some((
+        ((window.x().ok()? - monitor_x) as f64 * scale).round() as i32,
+        ((window.y().ok()? - monitor_y) as f64 * scale).round() as i32,
+    ))
+}
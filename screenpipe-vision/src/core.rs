@@ -1,14 +1,20 @@
 #[cfg(target_os = "macos")]
 use crate::apple::perform_ocr_apple;
+use crate::capture_rate::CaptureRate;
 use crate::capture_screenshot_by_window::CapturedWindow;
+use crate::capture_screenshot_by_window::VisibleWindow;
 use crate::capture_screenshot_by_window::WindowFilters;
 use crate::custom_ocr::perform_ocr_custom;
+use crate::frame_diff::{FrameComparison, FrameDeduplicator, FrameDiffConfig};
+use crate::incremental_ocr::{IncrementalOcr, IncrementalOcrConfig, OcrOutput, OcrPlan};
 #[cfg(target_os = "windows")]
 use crate::microsoft::perform_ocr_windows;
 use crate::monitor::get_monitor_by_id;
+use crate::privacy::{mask_sensitive_ocr, PrivacyFilter, TextBoxUnits};
+use crate::redaction::{redact_regions, ScreenRegion, WindowRedaction};
 use crate::tesseract::perform_ocr_tesseract;
+use crate::utils::capture_screenshot;
 use crate::utils::OcrEngine;
-use crate::utils::{capture_screenshot, compare_with_previous_image};
 use anyhow::Result;
 use base64::{engine::general_purpose, Engine as _};
 use image::codecs::jpeg::JpegEncoder;
@@ -121,9 +127,12 @@ pub struct WindowOcrResult {
 pub struct OcrTaskData {
     pub image: DynamicImage,
     pub window_images: Vec<CapturedWindow>,
+    /// All windows on the monitor, recorded or not, for the privacy checks
+    pub visible_windows: Vec<VisibleWindow>,
     pub frame_number: u64,
     pub timestamp: Instant,
     pub result_tx: Sender<CaptureResult>,
+    pub privacy_filter: Arc<PrivacyFilter>,
 }
 
 const BROWSER_NAMES: [&str; 9] = [
@@ -144,17 +153,23 @@ impl std::fmt::Display for ContinuousCaptureError {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn continuous_capture(
     result_tx: Sender<CaptureResult>,
-    interval: Duration,
+    capture_rate: Arc<CaptureRate>,
     ocr_engine: OcrEngine,
     monitor_id: u32,
     window_filters: Arc<WindowFilters>,
+    privacy_filter: Arc<PrivacyFilter>,
+    frame_diff: FrameDiffConfig,
+    incremental_ocr: IncrementalOcrConfig,
     languages: Vec<Language>,
     capture_unfocused_windows: bool,
 ) -> Result<(), ContinuousCaptureError> {
     let mut frame_counter: u64 = 0;
-    let mut previous_image: Option<DynamicImage> = None;
+    let mut frame_deduplicator = FrameDeduplicator::new(frame_diff);
+    let mut incremental_ocr = IncrementalOcr::new(incremental_ocr);
+    let mut scheduler = capture_rate.scheduler(monitor_id);
     let mut max_average: Option<MaxAverageFrame> = None;
     let mut max_avg_value = 0.0;
 
@@ -185,32 +200,49 @@ pub async fn continuous_capture(
             };
 
         // 4. Process captured image
-        let (image, window_images, image_hash, _capture_duration) = capture_result;
-
+        let (image, windows, image_hash, _capture_duration) = capture_result;
+        let window_images = windows.captured;
+        // unfocused windows aren't captured by default but still show in the frame
+        let visible_windows: Vec<VisibleWindow> = windows
+            .visible
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|window| window.is_on_monitor(monitor.origin(), monitor.dimensions()))
+            .collect();
+
+        let comparison = frame_deduplicator.compare(&image, &window_images);
         let should_skip = should_skip_frame(
-            &previous_image,
+            &frame_deduplicator,
+            &comparison,
             &image,
             &mut max_average,
             frame_counter,
             &mut max_avg_value,
             &window_images,
+            &visible_windows,
             image_hash,
             result_tx.clone(),
+            &privacy_filter,
         )
         .await;
 
         if should_skip {
             frame_counter += 1;
-            tokio::time::sleep(interval).await;
+            tokio::time::sleep(scheduler.next_interval(false)).await;
             continue;
         }
 
-        previous_image = Some(image);
+        frame_deduplicator.accept(comparison);
 
         // 5. Process max average frame if available
         if let Some(max_avg_frame) = max_average.take() {
-            if let Err(e) =
-                process_max_average_frame(max_avg_frame, &ocr_engine, languages.clone()).await
+            if let Err(e) = process_max_average_frame(
+                max_avg_frame,
+                &ocr_engine,
+                languages.clone(),
+                &mut incremental_ocr,
+            )
+            .await
             {
                 error!("Error processing max average frame: {}", e);
             }
@@ -219,43 +251,34 @@ pub async fn continuous_capture(
         }
 
         frame_counter += 1;
-        tokio::time::sleep(interval).await;
+        tokio::time::sleep(scheduler.next_interval(true)).await;
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn should_skip_frame(
-    previous_image: &Option<DynamicImage>,
+    frame_deduplicator: &FrameDeduplicator,
+    comparison: &FrameComparison,
     current_image: &DynamicImage,
     max_average: &mut Option<MaxAverageFrame>,
     frame_counter: u64,
     max_avg_value: &mut f64,
     window_images: &Vec<CapturedWindow>,
+    visible_windows: &[VisibleWindow],
     image_hash: u64,
     result_tx: Sender<CaptureResult>,
+    privacy_filter: &Arc<PrivacyFilter>,
 ) -> bool {
-    let current_average = match compare_with_previous_image(
-        previous_image.as_ref(),
-        current_image,
-        max_average,
+    let current_average = comparison.difference;
+    debug!(
+        "Frame {}: difference: {:.3}, Max_avr: {:.3} Fr: {}",
         frame_counter,
-        max_avg_value,
-    )
-    .await
-    {
-        Ok(avg) => avg,
-        Err(e) => {
-            error!("Error comparing images: {}", e);
-            0.0
-        }
-    };
-
-    let current_average = if previous_image.is_none() {
-        1.0
-    } else {
-        current_average
-    };
+        current_average,
+        *max_avg_value,
+        max_average.as_ref().map_or(0, |frame| frame.frame_number)
+    );
 
-    if current_average < 0.006 {
+    if frame_deduplicator.is_duplicate(comparison) {
         debug!(
             "Skipping frame {} due to low average difference: {:.3}",
             frame_counter, current_average
@@ -266,10 +289,12 @@ async fn should_skip_frame(
             *max_average = Some(MaxAverageFrame {
                 image: current_image.clone(),
                 window_images: window_images.clone(),
+                visible_windows: visible_windows.to_vec(),
                 image_hash,
                 frame_number: frame_counter,
                 timestamp: Instant::now(),
                 result_tx: result_tx.clone(),
+                privacy_filter: privacy_filter.clone(),
                 average: current_average,
             });
             *max_avg_value = current_average;
@@ -282,16 +307,19 @@ async fn process_max_average_frame(
     max_avg_frame: MaxAverageFrame,
     ocr_engine: &OcrEngine,
     languages: Vec<Language>,
+    incremental_ocr: &mut IncrementalOcr,
 ) -> Result<(), ContinuousCaptureError> {
     let ocr_task_data = OcrTaskData {
         image: max_avg_frame.image,
         window_images: max_avg_frame.window_images,
+        visible_windows: max_avg_frame.visible_windows,
         frame_number: max_avg_frame.frame_number,
         timestamp: max_avg_frame.timestamp,
         result_tx: max_avg_frame.result_tx,
+        privacy_filter: max_avg_frame.privacy_filter,
     };
 
-    if let Err(e) = process_ocr_task(ocr_task_data, ocr_engine, languages).await {
+    if let Err(e) = process_ocr_task(ocr_task_data, ocr_engine, languages, incremental_ocr).await {
         error!("Error processing OCR task: {}", e);
         return Err(ContinuousCaptureError::ErrorProcessingOcr(e.to_string()));
     }
@@ -302,26 +330,51 @@ async fn process_max_average_frame(
 pub struct MaxAverageFrame {
     pub image: DynamicImage,
     pub window_images: Vec<CapturedWindow>,
+    pub visible_windows: Vec<VisibleWindow>,
     pub image_hash: u64,
     pub frame_number: u64,
     pub timestamp: Instant,
     pub result_tx: Sender<CaptureResult>,
+    pub privacy_filter: Arc<PrivacyFilter>,
     pub average: f64,
 }
 
+/// OCRs the windows of a frame and sends the result. With incremental OCR, only the regions of
+/// the windows that changed since they were last OCR'd are read again.
 pub async fn process_ocr_task(
     ocr_task_data: OcrTaskData,
     ocr_engine: &OcrEngine,
     languages: Vec<Language>,
+    incremental_ocr: &mut IncrementalOcr,
 ) -> Result<(), ContinuousCaptureError> {
     let OcrTaskData {
-        image,
+        mut image,
         window_images,
+        visible_windows,
         frame_number,
         timestamp,
         result_tx,
+        privacy_filter,
     } = ocr_task_data;
 
+    if let Some((app_name, window_name)) = window_images
+        .iter()
+        .map(|window| (window.app_name.as_str(), window.window_name.as_str()))
+        .chain(visible_windows.iter().map(|window| {
+            (
+                window.app_name.as_deref().unwrap_or_default(),
+                window.window_name.as_deref().unwrap_or_default(),
+            )
+        }))
+        .find(|(app_name, window_name)| privacy_filter.should_skip(app_name, window_name))
+    {
+        debug!(
+            "skipping frame {}, window {} ({}) matches a skip pattern",
+            frame_number, window_name, app_name
+        );
+        return Ok(());
+    }
+
     let start_time = Instant::now();
     debug!(
         "Performing OCR for frame number since beginning of program {}",
@@ -332,17 +385,27 @@ pub async fn process_ocr_task(
     let mut total_confidence = 0.0;
     let mut window_count = 0;
 
+    incremental_ocr.retain_windows(
+        window_images
+            .iter()
+            .map(|window| (window.app_name.as_str(), window.window_name.as_str())),
+    );
     for captured_window in window_images {
-        let ocr_result = process_window_ocr(
+        let frame_position = captured_window.frame_position;
+        let mut ocr_result = process_window_ocr(
             captured_window,
             ocr_engine,
             &languages,
+            incremental_ocr,
             &mut total_confidence,
             &mut window_count,
         )
         .await
         .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string()))?;
 
+        if privacy_filter.masks_sensitive_text() {
+            mask_sensitive_window(&mut image, &mut ocr_result, frame_position, ocr_engine);
+        }
         window_ocr_results.push(ocr_result);
     }
 
@@ -368,6 +431,7 @@ async fn process_window_ocr(
     captured_window: CapturedWindow,
     ocr_engine: &OcrEngine,
     languages: &[Language],
+    incremental_ocr: &mut IncrementalOcr,
     total_confidence: &mut f64,
     window_count: &mut u32,
 ) -> Result<WindowOcrResult, ContinuousCaptureError> {
@@ -383,10 +447,11 @@ async fn process_window_ocr(
     .await;
 
     // Perform OCR based on the selected engine
-    let (window_text, window_json_output, confidence) =
-        perform_ocr_with_engine(ocr_engine, &captured_window.image, languages.to_vec())
-            .await
-            .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string()))?;
+    let OcrOutput {
+        text: window_text,
+        text_json,
+        confidence,
+    } = perform_window_ocr(ocr_engine, &captured_window, languages, incremental_ocr).await?;
 
     // Update confidence metrics
     if let Some(conf) = confidence {
@@ -399,13 +464,159 @@ async fn process_window_ocr(
         window_name: captured_window.window_name,
         app_name: captured_window.app_name,
         text: window_text,
-        text_json: parse_json_output(&window_json_output),
+        text_json,
         focused: captured_window.is_focused,
         confidence: confidence.unwrap_or(0.0),
         browser_url,
     })
 }
 
+/// OCRs a window, or only its regions that changed since it was last OCR'd when the engine
+/// locates the text it reads.
+async fn perform_window_ocr(
+    ocr_engine: &OcrEngine,
+    window: &CapturedWindow,
+    languages: &[Language],
+    incremental_ocr: &mut IncrementalOcr,
+) -> Result<OcrOutput, ContinuousCaptureError> {
+    let units = text_box_units(ocr_engine);
+    let locates_text = matches!(ocr_engine, OcrEngine::Tesseract | OcrEngine::AppleNative);
+    let plan = if locates_text {
+        incremental_ocr.plan(&window.app_name, &window.window_name, &window.image, units)
+    } else {
+        OcrPlan::Full
+    };
+
+    match plan {
+        OcrPlan::Unchanged(output) => {
+            debug!(
+                "window {} ({}) didn't change, reusing its text",
+                window.window_name, window.app_name
+            );
+            Ok(output)
+        }
+        OcrPlan::Full => {
+            let output = perform_ocr(ocr_engine, &window.image, languages).await?;
+            if locates_text {
+                incremental_ocr.record(
+                    &window.app_name,
+                    &window.window_name,
+                    &window.image,
+                    &output,
+                );
+            }
+            Ok(output)
+        }
+        OcrPlan::Regions(regions) => {
+            debug!(
+                "OCR of {} changed regions of window {} ({})",
+                regions.len(),
+                window.window_name,
+                window.app_name
+            );
+            let mut outputs = Vec::with_capacity(regions.len());
+            for changed in regions {
+                let crop = window.image.crop_imm(
+                    changed.crop.x,
+                    changed.crop.y,
+                    changed.crop.width,
+                    changed.crop.height,
+                );
+                outputs.push((changed, perform_ocr(ocr_engine, &crop, languages).await?));
+            }
+            Ok(incremental_ocr.merge(
+                &window.app_name,
+                &window.window_name,
+                &window.image,
+                outputs,
+                units,
+            ))
+        }
+    }
+}
+
+async fn perform_ocr(
+    ocr_engine: &OcrEngine,
+    image: &DynamicImage,
+    languages: &[Language],
+) -> Result<OcrOutput, ContinuousCaptureError> {
+    let (text, json_output, confidence) =
+        perform_ocr_with_engine(ocr_engine, image, languages.to_vec()).await?;
+    Ok(OcrOutput {
+        text,
+        text_json: parse_json_output(&json_output),
+        confidence,
+    })
+}
+
+/// Where the engine positions the text blocks of its `text_json` output.
+fn text_box_units(ocr_engine: &OcrEngine) -> TextBoxUnits {
+    match ocr_engine {
+        OcrEngine::AppleNative => TextBoxUnits::NormalizedFromBottom,
+        _ => TextBoxUnits::Pixels,
+    }
+}
+
+/// Masks the secrets and PII OCR found in a window, in its text, its `text_json`, its image and
+/// the frame. If the text can't be located in the frame the whole window is masked, or the
+/// whole frame if the window can't be located either.
+fn mask_sensitive_window(
+    frame: &mut DynamicImage,
+    ocr_result: &mut WindowOcrResult,
+    frame_position: Option<(i32, i32)>,
+    ocr_engine: &OcrEngine,
+) {
+    let units = text_box_units(ocr_engine);
+    let window_size = (ocr_result.image.width(), ocr_result.image.height());
+    let masked = mask_sensitive_ocr(
+        &mut ocr_result.text,
+        &mut ocr_result.text_json,
+        window_size,
+        units,
+    );
+    if masked.is_empty() {
+        return;
+    }
+    debug!(
+        "masking {} sensitive text regions in window {}",
+        masked.regions.len().max(1),
+        ocr_result.window_name
+    );
+
+    let window_regions = if masked.unlocated {
+        vec![ScreenRegion::full(window_size.0, window_size.1)]
+    } else {
+        masked.regions
+    };
+    redact_regions(
+        &mut ocr_result.image,
+        &window_regions,
+        WindowRedaction::Black,
+    );
+
+    let frame_size = (frame.width(), frame.height());
+    let frame_regions: Vec<ScreenRegion> = match frame_position {
+        Some((x, y)) => window_regions
+            .iter()
+            .filter_map(|region| {
+                ScreenRegion::from_screen_bounds(
+                    (
+                        x + region.x as i32,
+                        y + region.y as i32,
+                        region.width,
+                        region.height,
+                    ),
+                    (0, 0),
+                    frame_size,
+                    frame_size,
+                )
+            })
+            .collect(),
+        None => vec![ScreenRegion::full(frame_size.0, frame_size.1)],
+    };
+    redact_regions(frame, &frame_regions, WindowRedaction::Black);
+}
+
 async fn get_browser_url_if_needed(
     app_name: &str,
     is_focused: bool,
@@ -578,7 +789,11 @@ impl UIFrame {
     }
 }
 
-fn get_active_browser_url_sync(app_name: &str, process_id: i32, window_title: &str) -> Result<String, std::io::Error> {
+fn get_active_browser_url_sync(
+    app_name: &str,
+    process_id: i32,
+    window_title: &str,
+) -> Result<String, std::io::Error> {
     let detector = create_url_detector();
     match detector.get_active_url(app_name, process_id, window_title) {
         Ok(Some(url)) => Ok(url),
@@ -0,0 +1,351 @@
+use image::{DynamicImage, GrayImage};
+use image_compare::{Algorithm, Metric};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::capture_screenshot_by_window::CapturedWindow;
+
+/// Side of the grid dHash compares neighbouring cells of, 256 bits per frame
+const DHASH_SIZE: u32 = 16;
+/// Side of the thumbnail pHash runs its DCT on
+const PHASH_SAMPLE_SIZE: usize = 32;
+/// Side of the lowest frequencies of the DCT pHash keeps, 64 bits per frame
+const PHASH_SIZE: usize = 8;
+/// Frames are shrunk to this width before the downscaled SSIM comparison
+const SSIM_WIDTH: u32 = 640;
+
+/// How consecutive frames are compared to skip the ones that didn't change.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrameDiffAlgorithm {
+    /// Average of the histogram distance and MSSIM of the full resolution frames
+    #[default]
+    HistogramSsim,
+    /// Difference hash, brightness gradients of a 16x16 grid
+    DHash,
+    /// Perceptual hash, lowest frequencies of the DCT of a 32x32 thumbnail
+    PHash,
+    /// MSSIM of the frames shrunk to 640 pixels wide
+    Ssim,
+}
+
+impl FrameDiffAlgorithm {
+    pub const ALL: [FrameDiffAlgorithm; 4] = [
+        FrameDiffAlgorithm::HistogramSsim,
+        FrameDiffAlgorithm::DHash,
+        FrameDiffAlgorithm::PHash,
+        FrameDiffAlgorithm::Ssim,
+    ];
+
+    pub fn differ(&self) -> Box<dyn FrameDiffer> {
+        match self {
+            FrameDiffAlgorithm::HistogramSsim => Box::new(HistogramSsimDiffer),
+            FrameDiffAlgorithm::DHash => Box::new(DHashDiffer),
+            FrameDiffAlgorithm::PHash => Box::new(PHashDiffer),
+            FrameDiffAlgorithm::Ssim => Box::new(SsimDiffer),
+        }
+    }
+
+    /// Frames differing less than this from the last recorded one are skipped. For the hashes
+    /// it is the share of bits that changed, a single flipped bit being noise.
+    pub fn default_threshold(&self) -> f64 {
+        match self {
+            FrameDiffAlgorithm::HistogramSsim => 0.006,
+            FrameDiffAlgorithm::DHash => 0.005,
+            FrameDiffAlgorithm::PHash => 0.03,
+            FrameDiffAlgorithm::Ssim => 0.006,
+        }
+    }
+}
+
+impl fmt::Display for FrameDiffAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameDiffAlgorithm::HistogramSsim => write!(f, "histogram-ssim"),
+            FrameDiffAlgorithm::DHash => write!(f, "dhash"),
+            FrameDiffAlgorithm::PHash => write!(f, "phash"),
+            FrameDiffAlgorithm::Ssim => write!(f, "ssim"),
+        }
+    }
+}
+
+impl FromStr for FrameDiffAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "histogram-ssim" => Ok(FrameDiffAlgorithm::HistogramSsim),
+            "dhash" => Ok(FrameDiffAlgorithm::DHash),
+            "phash" => Ok(FrameDiffAlgorithm::PHash),
+            "ssim" => Ok(FrameDiffAlgorithm::Ssim),
+            _ => Err(format!("unknown frame diff algorithm: {}", s)),
+        }
+    }
+}
+
+/// Settings of the frame deduplication of the capture loop.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameDiffConfig {
+    pub algorithm: FrameDiffAlgorithm,
+    /// The algorithm's default threshold when `None`
+    pub threshold: Option<f64>,
+    /// Also compare each captured window with the same window in the last recorded frame, so
+    /// that changes too small to show in the whole frame aren't missed
+    pub per_window: bool,
+}
+
+impl FrameDiffConfig {
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+            .unwrap_or_else(|| self.algorithm.default_threshold())
+    }
+}
+
+/// What a [`FrameDiffer`] keeps of a frame to compare the next ones against.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameSignature {
+    Gray(GrayImage),
+    Hash(Vec<u64>),
+}
+
+/// A way of telling how much a frame changed since another one.
+pub trait FrameDiffer: Send + Sync {
+    fn signature(&self, image: &DynamicImage) -> FrameSignature;
+
+    /// From 0.0 for identical frames to 1.0. Signatures that can't be compared, e.g. of frames
+    /// of different sizes, are entirely different.
+    fn difference(&self, previous: &FrameSignature, current: &FrameSignature) -> f64;
+}
+
+pub struct HistogramSsimDiffer;
+
+impl FrameDiffer for HistogramSsimDiffer {
+    fn signature(&self, image: &DynamicImage) -> FrameSignature {
+        FrameSignature::Gray(image.to_luma8())
+    }
+
+    fn difference(&self, previous: &FrameSignature, current: &FrameSignature) -> f64 {
+        let (FrameSignature::Gray(previous), FrameSignature::Gray(current)) = (previous, current)
+        else {
+            return 1.0;
+        };
+        let Some(ssim_diff) = ssim_difference(previous, current) else {
+            return 1.0;
+        };
+        match image_compare::gray_similarity_histogram(Metric::Hellinger, previous, current) {
+            Ok(histogram_diff) => (histogram_diff + ssim_diff) / 2.0,
+            Err(_) => 1.0,
+        }
+    }
+}
+
+pub struct DHashDiffer;
+
+impl FrameDiffer for DHashDiffer {
+    fn signature(&self, image: &DynamicImage) -> FrameSignature {
+        FrameSignature::Hash(dhash(image, DHASH_SIZE))
+    }
+
+    fn difference(&self, previous: &FrameSignature, current: &FrameSignature) -> f64 {
+        hash_difference(previous, current)
+    }
+}
+
+pub struct PHashDiffer;
+
+impl FrameDiffer for PHashDiffer {
+    fn signature(&self, image: &DynamicImage) -> FrameSignature {
+        FrameSignature::Hash(vec![phash(image)])
+    }
+
+    fn difference(&self, previous: &FrameSignature, current: &FrameSignature) -> f64 {
+        hash_difference(previous, current)
+    }
+}
+
+pub struct SsimDiffer;
+
+impl FrameDiffer for SsimDiffer {
+    fn signature(&self, image: &DynamicImage) -> FrameSignature {
+        if image.width() <= SSIM_WIDTH {
+            return FrameSignature::Gray(image.to_luma8());
+        }
+        let height = (image.height() as u64 * SSIM_WIDTH as u64 / image.width() as u64).max(1);
+        FrameSignature::Gray(image.thumbnail_exact(SSIM_WIDTH, height as u32).to_luma8())
+    }
+
+    fn difference(&self, previous: &FrameSignature, current: &FrameSignature) -> f64 {
+        match (previous, current) {
+            (FrameSignature::Gray(previous), FrameSignature::Gray(current)) => {
+                ssim_difference(previous, current).unwrap_or(1.0)
+            }
+            _ => 1.0,
+        }
+    }
+}
+
+/// How much a captured frame differs from the last recorded one, with the signatures to record
+/// it with.
+pub struct FrameComparison {
+    pub difference: f64,
+    frame: FrameSignature,
+    windows: HashMap<(String, String), FrameSignature>,
+}
+
+/// Compares each captured frame with the last recorded one. Frames only become the reference
+/// once [`accept`](FrameDeduplicator::accept)ed, so that slow changes add up until they are
+/// recorded instead of being compared away one small step at a time.
+pub struct FrameDeduplicator {
+    differ: Box<dyn FrameDiffer>,
+    threshold: f64,
+    per_window: bool,
+    previous_frame: Option<FrameSignature>,
+    previous_windows: HashMap<(String, String), FrameSignature>,
+}
+
+impl FrameDeduplicator {
+    pub fn new(config: FrameDiffConfig) -> Self {
+        Self::with_differ(
+            config.algorithm.differ(),
+            config.threshold(),
+            config.per_window,
+        )
+    }
+
+    pub fn with_differ(differ: Box<dyn FrameDiffer>, threshold: f64, per_window: bool) -> Self {
+        Self {
+            differ,
+            threshold,
+            per_window,
+            previous_frame: None,
+            previous_windows: HashMap::new(),
+        }
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Compares a frame and its windows with the last recorded ones. The difference is the
+    /// largest of the frame's and, with per window diffs, of its windows', a window missing
+    /// from the last recorded frame being entirely different. The first frame is always new.
+    pub fn compare(&self, image: &DynamicImage, windows: &[CapturedWindow]) -> FrameComparison {
+        let frame = self.differ.signature(image);
+        let mut difference = match &self.previous_frame {
+            Some(previous) => self.differ.difference(previous, &frame),
+            None => 1.0,
+        };
+
+        let mut window_signatures = HashMap::new();
+        if self.per_window {
+            for window in windows {
+                let key = (window.app_name.clone(), window.window_name.clone());
+                let signature = self.differ.signature(&window.image);
+                let window_difference = match self.previous_windows.get(&key) {
+                    Some(previous) => self.differ.difference(previous, &signature),
+                    None => 1.0,
+                };
+                difference = difference.max(window_difference);
+                window_signatures.insert(key, signature);
+            }
+        }
+
+        FrameComparison {
+            difference,
+            frame,
+            windows: window_signatures,
+        }
+    }
+
+    pub fn is_duplicate(&self, comparison: &FrameComparison) -> bool {
+        comparison.difference < self.threshold
+    }
+
+    /// Makes the compared frame the one the next frames are compared with.
+    pub fn accept(&mut self, comparison: FrameComparison) {
+        self.previous_frame = Some(comparison.frame);
+        self.previous_windows = comparison.windows;
+    }
+}
+
+/// Difference hash of `image`: whether each cell of a `size`x`size` grid of its grayscale
+/// thumbnail is brighter than the cell to its right, `size * size` bits.
+pub fn dhash(image: &DynamicImage, size: u32) -> Vec<u64> {
+    let thumbnail = image.thumbnail_exact(size + 1, size).to_luma8();
+    let mut hash = vec![0u64; (size * size).div_ceil(64) as usize];
+    for y in 0..size {
+        for x in 0..size {
+            if thumbnail.get_pixel(x, y)[0] > thumbnail.get_pixel(x + 1, y)[0] {
+                let bit = (y * size + x) as usize;
+                hash[bit / 64] |= 1u64 << (bit % 64);
+            }
+        }
+    }
+    hash
+}
+
+/// Perceptual hash of `image`: whether each of the 8x8 lowest frequencies of the DCT of its
+/// grayscale thumbnail is above their median.
+pub fn phash(image: &DynamicImage) -> u64 {
+    let n = PHASH_SAMPLE_SIZE;
+    let thumbnail = image.thumbnail_exact(n as u32, n as u32).to_luma8();
+    let pixels: Vec<f64> = thumbnail.pixels().map(|p| p[0] as f64).collect();
+
+    // only the lowest frequencies are needed, a direct DCT-II of those is cheap enough
+    let cosines: Vec<f64> = (0..PHASH_SIZE)
+        .flat_map(|u| {
+            (0..n).map(move |x| ((2 * x + 1) as f64 * u as f64 * PI / (2 * n) as f64).cos())
+        })
+        .collect();
+    let mut coefficients = [0.0; PHASH_SIZE * PHASH_SIZE];
+    for v in 0..PHASH_SIZE {
+        for u in 0..PHASH_SIZE {
+            let mut sum = 0.0;
+            for y in 0..n {
+                let row_cosine = cosines[v * n + y];
+                for x in 0..n {
+                    sum += pixels[y * n + x] * cosines[u * n + x] * row_cosine;
+                }
+            }
+            coefficients[v * PHASH_SIZE + u] = sum;
+        }
+    }
+
+    // the first coefficient is the average brightness, it would skew the median
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+
+    coefficients
+        .iter()
+        .enumerate()
+        .filter(|(_, &coefficient)| coefficient > median)
+        .fold(0u64, |hash, (bit, _)| hash | 1 << bit)
+}
+
+fn hash_difference(previous: &FrameSignature, current: &FrameSignature) -> f64 {
+    match (previous, current) {
+        (FrameSignature::Hash(previous), FrameSignature::Hash(current))
+            if previous.len() == current.len() && !current.is_empty() =>
+        {
+            let changed_bits: u32 = previous
+                .iter()
+                .zip(current)
+                .map(|(a, b)| (a ^ b).count_ones())
+                .sum();
+            changed_bits as f64 / (current.len() * 64) as f64
+        }
+        _ => 1.0,
+    }
+}
+
+/// 1 - MSSIM, `None` for images of different sizes.
+fn ssim_difference(previous: &GrayImage, current: &GrayImage) -> Option<f64> {
+    if previous.dimensions() != current.dimensions() {
+        return None;
+    }
+    image_compare::gray_similarity_structure(&Algorithm::MSSIMSimple, previous, current)
+        .ok()
+        .map(|similarity| 1.0 - similarity.score)
+}
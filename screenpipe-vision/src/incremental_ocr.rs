@@ -0,0 +1,504 @@
+use crate::privacy::{text_block_bounds, TextBoxUnits};
+use crate::redaction::ScreenRegion;
+use image::{DynamicImage, GrayImage};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Luma difference above which a pixel counts as changed
+const PIXEL_TOLERANCE: u8 = 16;
+/// Pixels of context OCR gets around a changed region, so that the text at its edges is read
+/// whole
+const REGION_PADDING: u32 = 8;
+
+/// Left, top, width and height of a text block, in pixels of the window image
+type Bounds = (f64, f64, f64, f64);
+
+/// Settings of incremental OCR, which only re-reads the regions of a window that changed since
+/// it was last OCR'd.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IncrementalOcrConfig {
+    pub enabled: bool,
+    /// Side of the square tiles windows are compared in, in pixels
+    pub tile_size: u32,
+    /// Incremental passes after which a window is OCR'd in full again, so that merge errors
+    /// don't build up
+    pub snapshot_interval: u32,
+    /// Windows whose changed regions cover more than this fraction of them are OCR'd in full
+    pub max_changed_fraction: f64,
+}
+
+impl Default for IncrementalOcrConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tile_size: 32,
+            snapshot_interval: 30,
+            max_changed_fraction: 0.5,
+        }
+    }
+}
+
+/// Text OCR read in a window image.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OcrOutput {
+    pub text: String,
+    pub text_json: Vec<HashMap<String, String>>,
+    pub confidence: Option<f64>,
+}
+
+/// A region of a window whose text has to be read again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChangedRegion {
+    /// Region whose text blocks are replaced, it covers the whole of the blocks it
+    /// touches
+    pub region: ScreenRegion,
+    /// Region of the window image to OCR, `region` with some context around it
+    pub crop: ScreenRegion,
+}
+
+/// What has to be OCR'd of a window.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OcrPlan {
+    /// Nothing changed since the window was last OCR'd, this is what was read then
+    Unchanged(OcrOutput),
+    Full,
+    Regions(Vec<ChangedRegion>),
+}
+
+struct WindowState {
+    /// The window when it was last OCR'd
+    image: GrayImage,
+    output: OcrOutput,
+    passes_since_snapshot: u32,
+}
+
+/// Text last read in each window, to plan what has to be OCR'd of the next capture of the
+/// window and merge what is read into it.
+pub struct IncrementalOcr {
+    config: IncrementalOcrConfig,
+    windows: HashMap<(String, String), WindowState>,
+}
+
+impl IncrementalOcr {
+    pub fn new(config: IncrementalOcrConfig) -> Self {
+        Self {
+            config,
+            windows: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> IncrementalOcrConfig {
+        self.config
+    }
+
+    /// Compares a window with the image of it last OCR'd. Windows seen for the first time,
+    /// resized, due for a snapshot, mostly changed or with text OCR couldn't locate are read in
+    /// full.
+    pub fn plan(
+        &self,
+        app_name: &str,
+        window_name: &str,
+        image: &DynamicImage,
+        units: TextBoxUnits,
+    ) -> OcrPlan {
+        if !self.config.enabled {
+            return OcrPlan::Full;
+        }
+        let Some(state) = self.windows.get(&window_key(app_name, window_name)) else {
+            return OcrPlan::Full;
+        };
+        let image = image.to_luma8();
+        if image.dimensions() != state.image.dimensions() {
+            return OcrPlan::Full;
+        }
+
+        let regions = changed_regions(&state.image, &image, self.config.tile_size);
+        if regions.is_empty() {
+            return OcrPlan::Unchanged(state.output.clone());
+        }
+        if state.passes_since_snapshot >= self.config.snapshot_interval {
+            return OcrPlan::Full;
+        }
+
+        let size = image.dimensions();
+        let text_json = &state.output.text_json;
+        if text_json
+            .iter()
+            .any(|block| text_block_bounds(block, size, units).is_none())
+        {
+            return OcrPlan::Full;
+        }
+        let blocks: Vec<ScreenRegion> = text_json
+            .iter()
+            .filter_map(|block| block_region(block, size, units))
+            .collect();
+        let regions = expand_to_blocks(regions, &blocks);
+
+        let changed_area: u64 = regions.iter().map(area).sum();
+        if changed_area as f64 > self.config.max_changed_fraction * area(&full(size)) as f64 {
+            return OcrPlan::Full;
+        }
+
+        OcrPlan::Regions(
+            regions
+                .into_iter()
+                .map(|region| ChangedRegion {
+                    region,
+                    crop: pad(region, REGION_PADDING, size),
+                })
+                .collect(),
+        )
+    }
+
+    /// Keeps what a full OCR of a window read, as the snapshot following captures are merged
+    /// into.
+    pub fn record(
+        &mut self,
+        app_name: &str,
+        window_name: &str,
+        image: &DynamicImage,
+        output: &OcrOutput,
+    ) {
+        if !self.config.enabled {
+            return;
+        }
+        self.windows.insert(
+            window_key(app_name, window_name),
+            WindowState {
+                image: image.to_luma8(),
+                output: output.clone(),
+                passes_since_snapshot: 0,
+            },
+        );
+    }
+
+    /// Replaces the text blocks of the changed regions of a window with what OCR read in their
+    /// crops, and returns the text of the whole window. Blocks are kept when their center is in
+    /// the region they were read for, so that the lines cut at the edges of a crop are dropped.
+    pub fn merge(
+        &mut self,
+        app_name: &str,
+        window_name: &str,
+        image: &DynamicImage,
+        regions: Vec<(ChangedRegion, OcrOutput)>,
+        units: TextBoxUnits,
+    ) -> OcrOutput {
+        let size = (image.width(), image.height());
+        let key = window_key(app_name, window_name);
+        let previous = self
+            .windows
+            .get(&key)
+            .map(|state| state.output.clone())
+            .unwrap_or_default();
+
+        let mut blocks: Vec<(Bounds, HashMap<String, String>)> = previous
+            .text_json
+            .into_iter()
+            .filter_map(|block| Some((text_block_bounds(&block, size, units)?, block)))
+            .filter(|(bounds, _)| {
+                !regions
+                    .iter()
+                    .any(|(changed, _)| contains_center(&changed.region, *bounds))
+            })
+            .collect();
+
+        let window_area = area(&full(size)) as f64;
+        let mut weighted_confidence = Vec::new();
+        let mut unchanged_fraction = 1.0;
+        for (changed, output) in regions {
+            let crop = changed.crop;
+            for mut block in output.text_json {
+                let Some((left, top, width, height)) =
+                    text_block_bounds(&block, (crop.width, crop.height), units)
+                else {
+                    continue;
+                };
+                let bounds = (left + crop.x as f64, top + crop.y as f64, width, height);
+                if contains_center(&changed.region, bounds) {
+                    set_block_bounds(&mut block, bounds, size, units);
+                    blocks.push((bounds, block));
+                }
+            }
+
+            let fraction = area(&changed.region) as f64 / window_area;
+            unchanged_fraction -= fraction;
+            if let Some(confidence) = output.confidence {
+                weighted_confidence.push((confidence, fraction));
+            }
+        }
+        if let Some(confidence) = previous.confidence {
+            weighted_confidence.push((confidence, unchanged_fraction.max(0.0)));
+        }
+
+        // one line per row of blocks, like the text engines return for a full pass
+        let lines = reading_order(blocks);
+        let text = lines
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .filter_map(|block| block.get("text"))
+                    .filter(|text| !text.is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let text_json: Vec<HashMap<String, String>> = lines.into_iter().flatten().collect();
+        let total_weight: f64 = weighted_confidence.iter().map(|(_, weight)| weight).sum();
+        let confidence = (!weighted_confidence.is_empty()).then(|| {
+            if total_weight > 0.0 {
+                weighted_confidence
+                    .iter()
+                    .map(|(confidence, weight)| confidence * weight)
+                    .sum::<f64>()
+                    / total_weight
+            } else {
+                weighted_confidence[0].0
+            }
+        });
+
+        let output = OcrOutput {
+            text,
+            text_json,
+            confidence,
+        };
+        let passes_since_snapshot = self
+            .windows
+            .get(&key)
+            .map_or(0, |state| state.passes_since_snapshot + 1);
+        self.windows.insert(
+            key,
+            WindowState {
+                image: image.to_luma8(),
+                output: output.clone(),
+                passes_since_snapshot,
+            },
+        );
+        output
+    }
+
+    /// Forgets the windows that aren't among `windows`, given as app and window names.
+    pub fn retain_windows<'a>(&mut self, windows: impl IntoIterator<Item = (&'a str, &'a str)>) {
+        let windows: HashSet<(&str, &str)> = windows.into_iter().collect();
+        self.windows.retain(|(app_name, window_name), _| {
+            windows.contains(&(app_name.as_str(), window_name.as_str()))
+        });
+    }
+}
+
+fn window_key(app_name: &str, window_name: &str) -> (String, String) {
+    (app_name.to_string(), window_name.to_string())
+}
+
+/// Groups blocks into lines, top to bottom, each left to right. A block belongs to the line
+/// above when its vertical center is within the height of that line's first block.
+fn reading_order(
+    mut blocks: Vec<(Bounds, HashMap<String, String>)>,
+) -> Vec<Vec<HashMap<String, String>>> {
+    blocks.sort_by(|(a, _), (b, _)| a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0)));
+    let mut lines: Vec<(Bounds, Vec<(Bounds, HashMap<String, String>)>)> = Vec::new();
+    for (bounds, block) in blocks {
+        let center = bounds.1 + bounds.3 / 2.0;
+        match lines.last_mut() {
+            Some(((_, top, _, height), line)) if center < *top + *height => {
+                line.push((bounds, block))
+            }
+            _ => lines.push((bounds, vec![(bounds, block)])),
+        }
+    }
+    lines
+        .into_iter()
+        .map(|(_, mut line)| {
+            line.sort_by(|(a, _), (b, _)| a.0.total_cmp(&b.0));
+            line.into_iter().map(|(_, block)| block).collect()
+        })
+        .collect()
+}
+
+/// Regions covering the `tile_size` tiles that differ between two images of the same size,
+/// one per group of touching tiles.
+pub fn changed_regions(
+    previous: &GrayImage,
+    current: &GrayImage,
+    tile_size: u32,
+) -> Vec<ScreenRegion> {
+    let (width, height) = current.dimensions();
+    if previous.dimensions() != (width, height) {
+        return vec![full((width, height))];
+    }
+    let tile_size = tile_size.max(1);
+    let columns = width.div_ceil(tile_size);
+    let rows = height.div_ceil(tile_size);
+
+    let tile_changed = |column: u32, row: u32| {
+        let (x0, y0) = (column * tile_size, row * tile_size);
+        (y0..(y0 + tile_size).min(height)).any(|y| {
+            (x0..(x0 + tile_size).min(width)).any(|x| {
+                previous.get_pixel(x, y)[0].abs_diff(current.get_pixel(x, y)[0]) > PIXEL_TOLERANCE
+            })
+        })
+    };
+    let mut changed: Vec<bool> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| tile_changed(column, row))
+        .collect();
+
+    // groups of touching changed tiles, diagonals included
+    let mut regions = Vec::new();
+    for start in 0..changed.len() {
+        if !changed[start] {
+            continue;
+        }
+        changed[start] = false;
+        let start = (start as u32 % columns, start as u32 / columns);
+        let (mut min, mut max) = (start, start);
+        let mut queue = VecDeque::from([start]);
+        while let Some((column, row)) = queue.pop_front() {
+            min = (min.0.min(column), min.1.min(row));
+            max = (max.0.max(column), max.1.max(row));
+            for next_row in row.saturating_sub(1)..=(row + 1).min(rows - 1) {
+                for next_column in column.saturating_sub(1)..=(column + 1).min(columns - 1) {
+                    let index = (next_row * columns + next_column) as usize;
+                    if changed[index] {
+                        changed[index] = false;
+                        queue.push_back((next_column, next_row));
+                    }
+                }
+            }
+        }
+
+        let (x, y) = (min.0 * tile_size, min.1 * tile_size);
+        regions.push(ScreenRegion {
+            x,
+            y,
+            width: ((max.0 + 1) * tile_size).min(width) - x,
+            height: ((max.1 + 1) * tile_size).min(height) - y,
+        });
+    }
+    merge_overlapping(regions)
+}
+
+/// Grows the regions until each text block they touch is whole in one of them, a line with
+/// one changed character is read again entirely.
+fn expand_to_blocks(mut regions: Vec<ScreenRegion>, blocks: &[ScreenRegion]) -> Vec<ScreenRegion> {
+    loop {
+        let mut expanded = false;
+        for region in regions.iter_mut() {
+            for block in blocks {
+                if intersects(region, block) && union(region, block) != *region {
+                    *region = union(region, block);
+                    expanded = true;
+                }
+            }
+        }
+        regions = merge_overlapping(regions);
+        if !expanded {
+            return regions;
+        }
+    }
+}
+
+fn merge_overlapping(mut regions: Vec<ScreenRegion>) -> Vec<ScreenRegion> {
+    let mut merged = true;
+    while merged {
+        merged = false;
+        'outer: for i in 0..regions.len() {
+            for j in i + 1..regions.len() {
+                if intersects(&regions[i], &regions[j]) {
+                    regions[i] = union(&regions[i], &regions[j]);
+                    regions.remove(j);
+                    merged = true;
+                    break 'outer;
+                }
+            }
+        }
+    }
+    regions
+}
+
+/// Pixels of the `size` window image a text block covers, `None` if it has no position or is
+/// outside of the image.
+fn block_region(
+    block: &HashMap<String, String>,
+    size: (u32, u32),
+    units: TextBoxUnits,
+) -> Option<ScreenRegion> {
+    let (left, top, width, height) = text_block_bounds(block, size, units)?;
+    let x = (left.floor().max(0.0) as u32).min(size.0);
+    let y = (top.floor().max(0.0) as u32).min(size.1);
+    let right = ((left + width).ceil().max(0.0) as u32).min(size.0);
+    let bottom = ((top + height).ceil().max(0.0) as u32).min(size.1);
+    (right > x && bottom > y).then_some(ScreenRegion {
+        x,
+        y,
+        width: right - x,
+        height: bottom - y,
+    })
+}
+
+/// Writes the bounds of a block, in pixels of the `size` window image, in the units of the
+/// engine that read it.
+fn set_block_bounds(
+    block: &mut HashMap<String, String>,
+    (left, top, width, height): Bounds,
+    (image_width, image_height): (u32, u32),
+    units: TextBoxUnits,
+) {
+    let values = match units {
+        TextBoxUnits::Pixels => [left, top, width, height].map(|value| value.round().to_string()),
+        TextBoxUnits::NormalizedFromBottom => [
+            left / image_width as f64,
+            1.0 - (top + height) / image_height as f64,
+            width / image_width as f64,
+            height / image_height as f64,
+        ]
+        .map(|value| value.to_string()),
+    };
+    for (key, value) in ["left", "top", "width", "height"].into_iter().zip(values) {
+        block.insert(key.to_string(), value);
+    }
+}
+
+fn contains_center(region: &ScreenRegion, (left, top, width, height): Bounds) -> bool {
+    let (x, y) = (left + width / 2.0, top + height / 2.0);
+    x >= region.x as f64
+        && x < (region.x + region.width) as f64
+        && y >= region.y as f64
+        && y < (region.y + region.height) as f64
+}
+
+fn intersects(a: &ScreenRegion, b: &ScreenRegion) -> bool {
+    a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+}
+
+fn union(a: &ScreenRegion, b: &ScreenRegion) -> ScreenRegion {
+    let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+    ScreenRegion {
+        x,
+        y,
+        width: (a.x + a.width).max(b.x + b.width) - x,
+        height: (a.y + a.height).max(b.y + b.height) - y,
+    }
+}
+
+fn pad(region: ScreenRegion, padding: u32, (width, height): (u32, u32)) -> ScreenRegion {
+    let (x, y) = (
+        region.x.saturating_sub(padding),
+        region.y.saturating_sub(padding),
+    );
+    ScreenRegion {
+        x,
+        y,
+        width: (region.x + region.width + padding).min(width) - x,
+        height: (region.y + region.height + padding).min(height) - y,
+    }
+}
+
+fn full((width, height): (u32, u32)) -> ScreenRegion {
+    ScreenRegion::full(width, height)
+}
+
+fn area(region: &ScreenRegion) -> u64 {
+    region.width as u64 * region.height as u64
+}
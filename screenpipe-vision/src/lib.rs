@@ -1,10 +1,15 @@
 #[cfg(target_os = "macos")]
 pub mod apple;
+pub mod capture_rate;
 pub mod core;
 pub mod custom_ocr;
+pub mod frame_diff;
+pub mod incremental_ocr;
 #[cfg(target_os = "windows")]
 pub mod microsoft;
 pub mod monitor;
+pub mod privacy;
+pub mod redaction;
 #[cfg(target_os = "macos")]
 pub mod run_ui_monitoring_macos;
 pub mod tesseract;
@@ -0,0 +1,269 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::capture_screenshot_by_window::WindowPattern;
+use crate::redaction::ScreenRegion;
+
+/// Extra pixels masked around sensitive text, so that no antialiased edge is left
+const MASK_PADDING: f64 = 2.0;
+
+/// Secrets and PII found in OCR text, with the label they are replaced with.
+static SENSITIVE_PATTERNS: Lazy<Vec<(Regex, &'static str)>> = Lazy::new(|| {
+    [
+        // a key spans several lines, whatever follows the header in the same text goes too
+        (
+            r"(?s)-----BEGIN [A-Z ]*PRIVATE KEY-----.*?(?:-----END [A-Z ]*PRIVATE KEY-----|\z)",
+            "[PRIVATE_KEY]",
+        ),
+        (
+            r"\beyJ[A-Za-z0-9_-]{5,}\.[A-Za-z0-9_-]{5,}\.[A-Za-z0-9_-]+",
+            "[JWT]",
+        ),
+        // openai, anthropic
+        (r"\bsk-(?:ant-|proj-)?[A-Za-z0-9_-]{20,}", "[API_KEY]"),
+        // stripe
+        (r"\b[rsp]k_(?:live|test)_[A-Za-z0-9]{16,}", "[API_KEY]"),
+        // aws access key ids
+        (r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b", "[API_KEY]"),
+        // github
+        (r"\bgh[pousr]_[A-Za-z0-9]{36,}", "[API_KEY]"),
+        (r"\bgithub_pat_[A-Za-z0-9_]{22,}", "[API_KEY]"),
+        // gitlab
+        (r"\bglpat-[A-Za-z0-9_-]{20,}", "[API_KEY]"),
+        // slack
+        (r"\bxox[abposr]-[A-Za-z0-9-]{10,}", "[API_KEY]"),
+        // google
+        (r"\bAIza[0-9A-Za-z_-]{35}", "[API_KEY]"),
+        (r"\b(?:\d[ -]?){12,18}\d\b", "[CREDIT_CARD]"),
+        (r"\b\d{3}-\d{2}-\d{4}\b", "[SSN]"),
+        (
+            r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b",
+            "[EMAIL]",
+        ),
+    ]
+    .into_iter()
+    .map(|(pattern, label)| (Regex::new(pattern).unwrap(), label))
+    .collect()
+});
+
+static PRIVATE_KEY_START: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"-----BEGIN [A-Z ]*PRIVATE KEY-----").unwrap());
+static PRIVATE_KEY_END: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"-----END [A-Z ]*PRIVATE KEY-----").unwrap());
+
+/// Where an OCR engine positions the text blocks of its `text_json` output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextBoxUnits {
+    /// Pixels of the window image, from its top left corner
+    Pixels,
+    /// Fractions of the window image, from its bottom left corner, as Apple Vision reports them
+    NormalizedFromBottom,
+}
+
+/// Sensitive text and where it is in the window image OCR ran on.
+#[derive(Debug, Default, PartialEq)]
+pub struct MaskedText {
+    /// Regions of the window image holding sensitive text
+    pub regions: Vec<ScreenRegion>,
+    /// Sensitive text was found in a block without a position, the whole window has to be
+    /// masked
+    pub unlocated: bool,
+}
+
+impl MaskedText {
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty() && !self.unlocated
+    }
+}
+
+/// Privacy checks run on each frame before it is stored, beyond the window filters.
+#[derive(Clone, Debug, Default)]
+pub struct PrivacyFilter {
+    mask_sensitive_text: bool,
+    skip_windows: Vec<WindowPattern>,
+}
+
+impl PrivacyFilter {
+    /// `skip_windows` are window patterns, as in the window filters, dropping the whole frame
+    /// when one of the windows on the monitor matches, e.g. "Incognito".
+    pub fn new(mask_sensitive_text: bool, skip_windows: &[String]) -> Result<Self, String> {
+        Ok(Self {
+            mask_sensitive_text,
+            skip_windows: skip_windows
+                .iter()
+                .map(|pattern| pattern.parse())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn masks_sensitive_text(&self) -> bool {
+        self.mask_sensitive_text
+    }
+
+    pub fn should_skip(&self, app_name: &str, window_name: &str) -> bool {
+        self.skip_windows
+            .iter()
+            .any(|pattern| pattern.matches_window(app_name, window_name))
+    }
+}
+
+/// `text` with the secrets and PII in it replaced by labels like `[API_KEY]`, `None` if it
+/// holds none.
+pub fn mask_sensitive_text(text: &str) -> Option<String> {
+    let mut matches: Vec<(Range<usize>, &str)> = SENSITIVE_PATTERNS
+        .iter()
+        .flat_map(|(pattern, label)| {
+            pattern
+                .find_iter(text)
+                .filter(|m| *label != "[CREDIT_CARD]" || passes_luhn(m.as_str()))
+                .map(move |m| (m.range(), *label))
+        })
+        .collect();
+    if matches.is_empty() {
+        return None;
+    }
+
+    // earliest first, the longest of overlapping matches wins
+    matches.sort_by_key(|(range, _)| (range.start, std::cmp::Reverse(range.end)));
+    let mut masked = String::with_capacity(text.len());
+    let mut position = 0;
+    for (range, label) in matches {
+        if range.start < position {
+            continue;
+        }
+        masked.push_str(&text[position..range.start]);
+        masked.push_str(label);
+        position = range.end;
+    }
+    masked.push_str(&text[position..]);
+    Some(masked)
+}
+
+/// Masks the sensitive text of a window's OCR output in place, the full `text` and each block
+/// of `text_json`, and returns where the blocks that held some are in the window image of
+/// `image_size`. Blocks between the lines of a private key are masked whole.
+pub fn mask_sensitive_ocr(
+    text: &mut String,
+    text_json: &mut [HashMap<String, String>],
+    image_size: (u32, u32),
+    units: TextBoxUnits,
+) -> MaskedText {
+    let mut masked = MaskedText::default();
+    let text_is_sensitive = match mask_sensitive_text(text) {
+        Some(masked_text) => {
+            *text = masked_text;
+            true
+        }
+        None => false,
+    };
+
+    let mut in_private_key = false;
+    for block in text_json.iter_mut() {
+        let Some(block_text) = block.get("text") else {
+            continue;
+        };
+
+        let masked_text = if in_private_key {
+            in_private_key = !PRIVATE_KEY_END.is_match(block_text);
+            Some("[PRIVATE_KEY]".to_string())
+        } else {
+            in_private_key =
+                PRIVATE_KEY_START.is_match(block_text) && !PRIVATE_KEY_END.is_match(block_text);
+            mask_sensitive_text(block_text)
+        };
+        let Some(masked_text) = masked_text else {
+            continue;
+        };
+
+        match block_region(block, image_size, units) {
+            Some(region) => masked.regions.push(region),
+            None => masked.unlocated = true,
+        }
+        block.insert("text".to_string(), masked_text);
+    }
+
+    // engines that don't split their output in blocks
+    if text_is_sensitive && masked.is_empty() {
+        masked.unlocated = true;
+    }
+    masked
+}
+
+/// Left, top, width and height of a `text_json` block in pixels of the `image_size` window
+/// image, from its top left corner. `None` if the block has no position.
+pub fn text_block_bounds(
+    block: &HashMap<String, String>,
+    (image_width, image_height): (u32, u32),
+    units: TextBoxUnits,
+) -> Option<(f64, f64, f64, f64)> {
+    let value = |key: &str| block.get(key)?.parse::<f64>().ok();
+    let (left, top, width, height) = (
+        value("left")?,
+        value("top")?,
+        value("width")?,
+        value("height")?,
+    );
+
+    Some(match units {
+        TextBoxUnits::Pixels => (left, top, width, height),
+        TextBoxUnits::NormalizedFromBottom => (
+            left * image_width as f64,
+            (1.0 - top - height) * image_height as f64,
+            width * image_width as f64,
+            height * image_height as f64,
+        ),
+    })
+}
+
+/// Region of the window image a `text_json` block covers, `None` if the block has no position.
+fn block_region(
+    block: &HashMap<String, String>,
+    (image_width, image_height): (u32, u32),
+    units: TextBoxUnits,
+) -> Option<ScreenRegion> {
+    let (x, y, width, height) = text_block_bounds(block, (image_width, image_height), units)?;
+
+    let x = (x - MASK_PADDING).floor();
+    let y = (y - MASK_PADDING).floor();
+    ScreenRegion::from_screen_bounds(
+        (
+            x as i32,
+            y as i32,
+            (width + 2.0 * MASK_PADDING).ceil() as u32 + 1,
+            (height + 2.0 * MASK_PADDING).ceil() as u32 + 1,
+        ),
+        (0, 0),
+        (image_width, image_height),
+        (image_width, image_height),
+    )
+}
+
+/// Whether the digits of a candidate card number have a valid Luhn checksum, which most
+/// digit runs of the same length don't.
+fn passes_luhn(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| {
+            if i % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .sum();
+    sum % 10 == 0
+}
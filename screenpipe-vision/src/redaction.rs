@@ -0,0 +1,150 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::fmt;
+use std::str::FromStr;
+
+/// Blurred regions are shrunk by this factor and scaled back up, enough to make text unreadable
+const BLUR_FACTOR: u32 = 24;
+
+/// How the windows left out by the window filters are hidden in the recorded frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowRedaction {
+    /// Frames are recorded as captured, filtered windows are only kept out of OCR
+    #[default]
+    Off,
+    /// Filtered windows are painted black
+    Black,
+    /// Filtered windows are blurred beyond recognition
+    Blur,
+}
+
+impl fmt::Display for WindowRedaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowRedaction::Off => write!(f, "off"),
+            WindowRedaction::Black => write!(f, "black"),
+            WindowRedaction::Blur => write!(f, "blur"),
+        }
+    }
+}
+
+impl FromStr for WindowRedaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(WindowRedaction::Off),
+            "black" => Ok(WindowRedaction::Black),
+            "blur" => Ok(WindowRedaction::Blur),
+            _ => Err(format!("unknown window redaction: {}", s)),
+        }
+    }
+}
+
+/// A rectangle of a captured frame, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScreenRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ScreenRegion {
+    /// Maps the bounds of a window, in the screen coordinates windows and monitors are
+    /// positioned in, to the pixels of a `frame_size` capture of the monitor at
+    /// `monitor_origin`. Frames of HiDPI monitors hold more pixels than the monitor has screen
+    /// coordinates, the region is rounded outwards so that no edge of the window is left.
+    /// `None` if the window is outside the monitor.
+    pub fn from_screen_bounds(
+        (x, y, width, height): (i32, i32, u32, u32),
+        monitor_origin: (i32, i32),
+        monitor_size: (u32, u32),
+        frame_size: (u32, u32),
+    ) -> Option<Self> {
+        if monitor_size.0 == 0 || monitor_size.1 == 0 {
+            return None;
+        }
+        let scale_x = frame_size.0 as f64 / monitor_size.0 as f64;
+        let scale_y = frame_size.1 as f64 / monitor_size.1 as f64;
+
+        let left = ((x - monitor_origin.0) as f64 * scale_x).floor();
+        let top = ((y - monitor_origin.1) as f64 * scale_y).floor();
+        let right = ((x - monitor_origin.0) as f64 + width as f64) * scale_x;
+        let bottom = ((y - monitor_origin.1) as f64 + height as f64) * scale_y;
+
+        let left = left.clamp(0.0, frame_size.0 as f64) as u32;
+        let top = top.clamp(0.0, frame_size.1 as f64) as u32;
+        let right = right.ceil().clamp(0.0, frame_size.0 as f64) as u32;
+        let bottom = bottom.ceil().clamp(0.0, frame_size.1 as f64) as u32;
+
+        (right > left && bottom > top).then_some(Self {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+
+    /// The whole of a `width`x`height` frame.
+    pub fn full(width: u32, height: u32) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+}
+
+/// Hides `regions` of `image` in place. Regions are clipped to the image.
+pub fn redact_regions(image: &mut DynamicImage, regions: &[ScreenRegion], mode: WindowRedaction) {
+    if mode == WindowRedaction::Off || regions.is_empty() {
+        return;
+    }
+    if !matches!(image, DynamicImage::ImageRgba8(_)) {
+        *image = DynamicImage::ImageRgba8(image.to_rgba8());
+    }
+    let DynamicImage::ImageRgba8(buffer) = image else {
+        return;
+    };
+
+    for region in regions {
+        let x = region.x.min(buffer.width());
+        let y = region.y.min(buffer.height());
+        let width = region.width.min(buffer.width() - x);
+        let height = region.height.min(buffer.height() - y);
+        if width == 0 || height == 0 {
+            continue;
+        }
+
+        match mode {
+            WindowRedaction::Black => {
+                for py in y..y + height {
+                    for px in x..x + width {
+                        buffer.put_pixel(px, py, Rgba([0, 0, 0, 255]));
+                    }
+                }
+            }
+            WindowRedaction::Blur => {
+                let blurred = blur(
+                    &imageops::crop_imm(buffer, x, y, width, height).to_image(),
+                    width,
+                    height,
+                );
+                imageops::replace(buffer, &blurred, x as i64, y as i64);
+            }
+            WindowRedaction::Off => {}
+        }
+    }
+}
+
+fn blur(region: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let small = imageops::resize(
+        region,
+        (width / BLUR_FACTOR).max(1),
+        (height / BLUR_FACTOR).max(1),
+        FilterType::Triangle,
+    );
+    imageops::resize(&small, width, height, FilterType::Triangle)
+}
@@ -1,15 +1,16 @@
 use crate::capture_screenshot_by_window::{
-    capture_all_visible_windows, CapturedWindow, WindowFilters,
+    capture_windows, redact_filtered_windows, CapturedWindows, WindowFilters,
 };
 use crate::core::MaxAverageFrame;
 use crate::custom_ocr::CustomOcrConfig;
+use crate::frame_diff::dhash;
 use crate::monitor::SafeMonitor;
 use image::DynamicImage;
 use image_compare::{Algorithm, Metric, Similarity};
-use tracing::{debug, warn};
 use screenpipe_db::CustomOcrConfig as DBCustomOcrConfig;
-use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tracing::debug;
 
 #[derive(Clone, Debug, Default)]
 pub enum OcrEngine {
@@ -47,10 +48,10 @@ impl From<screenpipe_db::OcrEngine> for OcrEngine {
     }
 }
 
+/// 64-bit difference hash of the frame, the same for frames that look the same even if some
+/// pixels moved, unlike a hash of its bytes.
 pub fn calculate_hash(image: &DynamicImage) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    image.as_bytes().hash(&mut hasher);
-    hasher.finish()
+    dhash(image, 8)[0]
 }
 
 pub fn compare_images_histogram(
@@ -74,32 +75,38 @@ pub fn compare_images_ssim(image1: &DynamicImage, image2: &DynamicImage) -> f64
 
 pub async fn capture_screenshot(
     monitor: &SafeMonitor,
-    window_filters: &WindowFilters,
+    window_filters: &Arc<WindowFilters>,
     capture_unfocused_windows: bool,
-) -> Result<(DynamicImage, Vec<CapturedWindow>, u64, Duration), anyhow::Error> {
+) -> Result<(DynamicImage, CapturedWindows, u64, Duration), anyhow::Error> {
     // info!("Starting screenshot capture for monitor: {:?}", monitor);
     let capture_start = Instant::now();
-    let image = monitor.capture_image().await.map_err(|e| {
+    let mut image = monitor.capture_image().await.map_err(|e| {
         debug!("failed to capture monitor image: {}", e);
         anyhow::anyhow!("monitor capture failed")
     })?;
+
+    // listing and capturing windows blocks, the list is shared by redaction and the privacy checks
+    let windows = {
+        let monitor = monitor.clone();
+        let window_filters = window_filters.clone();
+        tokio::task::spawn_blocking(move || {
+            capture_windows(&monitor, &window_filters, capture_unfocused_windows)
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("window capture task failed: {}", e))?
+    };
+
+    // before anything else sees the frame, while the windows are still where they were captured
+    redact_filtered_windows(
+        &mut image,
+        monitor,
+        window_filters,
+        windows.visible.as_deref(),
+    );
     let image_hash = calculate_hash(&image);
     let capture_duration = capture_start.elapsed();
 
-    let window_images =
-        match capture_all_visible_windows(monitor, window_filters, capture_unfocused_windows).await
-        {
-            Ok(images) => images,
-            Err(e) => {
-                warn!(
-                    "Failed to capture window images: {}. Continuing with empty result.",
-                    e
-                );
-                Vec::new()
-            }
-        };
-
-    Ok((image, window_images, image_hash, capture_duration))
+    Ok((image, windows, image_hash, capture_duration))
 }
 
 pub async fn compare_with_previous_image(
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_vision::capture_rate::{CaptureRate, CaptureRateConfig};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn adaptive() -> Arc<CaptureRate> {
+        Arc::new(CaptureRate::new(CaptureRateConfig {
+            fps: 1.0,
+            adaptive: true,
+            min_fps: 0.2,
+            max_fps: 4.0,
+        }))
+    }
+
+    #[test]
+    fn test_fixed_rate() {
+        let rate = Arc::new(CaptureRate::fixed(2.0));
+        let mut scheduler = rate.scheduler(1);
+        assert_eq!(scheduler.next_interval(true), Duration::from_millis(500));
+        assert_eq!(scheduler.next_interval(false), Duration::from_millis(500));
+
+        // power and CPU only matter to adaptive captures
+        rate.set_on_battery(true);
+        assert_eq!(scheduler.next_interval(false), Duration::from_millis(500));
+        assert_eq!(rate.effective_fps().get(&1), Some(&2.0));
+    }
+
+    #[test]
+    fn test_adaptive_rate_follows_activity() {
+        let rate = adaptive();
+        let mut scheduler = rate.scheduler(1);
+        assert_eq!(scheduler.fps(), 1.0);
+
+        scheduler.next_interval(true);
+        assert_eq!(scheduler.fps(), 2.0);
+        assert_eq!(scheduler.next_interval(true), Duration::from_millis(250));
+        // capped at the ceiling
+        scheduler.next_interval(true);
+        assert_eq!(scheduler.fps(), 4.0);
+
+        // a static screen backs off to the floor
+        let mut previous = scheduler.fps();
+        for _ in 0..5 {
+            scheduler.next_interval(false);
+            assert!(scheduler.fps() < previous);
+            previous = scheduler.fps();
+        }
+        for _ in 0..50 {
+            scheduler.next_interval(false);
+        }
+        assert_eq!(scheduler.fps(), 0.2);
+        assert_eq!(scheduler.next_interval(false), Duration::from_secs(5));
+        assert_eq!(rate.effective_fps().get(&1), Some(&0.2));
+
+        drop(scheduler);
+        assert!(rate.effective_fps().is_empty());
+    }
+
+    #[test]
+    fn test_ceiling_drops_on_battery_and_cpu_pressure() {
+        let rate = adaptive();
+        let mut scheduler = rate.scheduler(2);
+        for _ in 0..5 {
+            scheduler.next_interval(true);
+        }
+        assert_eq!(scheduler.fps(), 4.0);
+
+        rate.set_on_battery(true);
+        assert_eq!(rate.ceiling(), 2.0);
+        scheduler.next_interval(true);
+        assert_eq!(scheduler.fps(), 2.0);
+
+        rate.set_cpu_usage(95.0);
+        assert_eq!(rate.ceiling(), 1.0);
+        rate.set_cpu_usage(30.0);
+        rate.set_on_battery(false);
+        assert_eq!(rate.ceiling(), 4.0);
+
+        // never below the floor
+        let slow = Arc::new(CaptureRate::new(CaptureRateConfig {
+            fps: 0.5,
+            adaptive: true,
+            min_fps: 0.5,
+            max_fps: 1.0,
+        }));
+        slow.set_on_battery(true);
+        slow.set_cpu_usage(100.0);
+        assert_eq!(slow.ceiling(), 0.5);
+    }
+}
@@ -0,0 +1,161 @@
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba, RgbaImage};
+    use screenpipe_vision::capture_screenshot_by_window::CapturedWindow;
+    use screenpipe_vision::frame_diff::{
+        FrameDeduplicator, FrameDiffAlgorithm, FrameDiffConfig, FrameDiffer, FrameSignature,
+    };
+
+    const WINDOW: (u32, u32, u32, u32) = (600, 400, 200, 150);
+
+    /// White screen with a `block` of the given size painted black at the top left of the window.
+    fn screen(block: Option<(u32, u32)>) -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(1600, 1000, Rgba([255, 255, 255, 255]));
+        if let Some((width, height)) = block {
+            for y in WINDOW.1 + 20..WINDOW.1 + 20 + height {
+                for x in WINDOW.0 + 20..WINDOW.0 + 20 + width {
+                    image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+        image
+    }
+
+    fn window_of(frame: &RgbaImage) -> CapturedWindow {
+        let (x, y, width, height) = WINDOW;
+        CapturedWindow {
+            image: DynamicImage::ImageRgba8(
+                image::imageops::crop_imm(frame, x, y, width, height).to_image(),
+            ),
+            app_name: "Slack".to_string(),
+            window_name: "general".to_string(),
+            process_id: 0,
+            is_focused: true,
+            frame_position: Some((x as i32, y as i32)),
+        }
+    }
+
+    fn deduplicator(algorithm: FrameDiffAlgorithm, per_window: bool) -> FrameDeduplicator {
+        FrameDeduplicator::new(FrameDiffConfig {
+            algorithm,
+            threshold: None,
+            per_window,
+        })
+    }
+
+    #[test]
+    fn test_identical_and_changed_frames() {
+        let blank = screen(None);
+        let changed = screen(Some((180, 120)));
+
+        for algorithm in FrameDiffAlgorithm::ALL {
+            let mut deduplicator = deduplicator(algorithm, false);
+
+            let first = deduplicator.compare(&DynamicImage::ImageRgba8(blank.clone()), &[]);
+            assert!(!deduplicator.is_duplicate(&first), "{}", algorithm);
+            deduplicator.accept(first);
+
+            let same = deduplicator.compare(&DynamicImage::ImageRgba8(blank.clone()), &[]);
+            assert!(deduplicator.is_duplicate(&same), "{}", algorithm);
+            assert!(same.difference < 1e-9, "{}", algorithm);
+
+            let different = deduplicator.compare(&DynamicImage::ImageRgba8(changed.clone()), &[]);
+            assert!(!deduplicator.is_duplicate(&different), "{}", algorithm);
+        }
+
+        // the monitor resolution changed
+        let mut deduplicator = deduplicator(FrameDiffAlgorithm::HistogramSsim, false);
+        let first = deduplicator.compare(&DynamicImage::ImageRgba8(blank), &[]);
+        deduplicator.accept(first);
+        let resized = deduplicator.compare(
+            &DynamicImage::ImageRgba8(RgbaImage::from_pixel(800, 500, Rgba([255; 4]))),
+            &[],
+        );
+        assert_eq!(resized.difference, 1.0);
+    }
+
+    #[test]
+    fn test_per_window_diff_catches_small_changes() {
+        let blank = screen(None);
+        let changed = screen(Some((60, 30)));
+
+        for algorithm in [FrameDiffAlgorithm::DHash, FrameDiffAlgorithm::Ssim] {
+            let mut deduplicator = deduplicator(algorithm, true);
+            let first = deduplicator.compare(
+                &DynamicImage::ImageRgba8(blank.clone()),
+                &[window_of(&blank)],
+            );
+            deduplicator.accept(first);
+
+            let comparison = deduplicator.compare(
+                &DynamicImage::ImageRgba8(changed.clone()),
+                &[window_of(&changed)],
+            );
+            assert!(!deduplicator.is_duplicate(&comparison), "{}", algorithm);
+        }
+
+        // a window that wasn't there in the last recorded frame is a change
+        let mut deduplicator = deduplicator(FrameDiffAlgorithm::PHash, true);
+        let first = deduplicator.compare(&DynamicImage::ImageRgba8(blank.clone()), &[]);
+        deduplicator.accept(first);
+        let comparison = deduplicator.compare(
+            &DynamicImage::ImageRgba8(blank.clone()),
+            &[window_of(&blank)],
+        );
+        assert_eq!(comparison.difference, 1.0);
+    }
+
+    #[test]
+    fn test_reference_frame_only_moves_when_accepted() {
+        let blank = DynamicImage::ImageRgba8(screen(None));
+        let changed = DynamicImage::ImageRgba8(screen(Some((180, 120))));
+        let mut deduplicator = deduplicator(FrameDiffAlgorithm::Ssim, false);
+        let first = deduplicator.compare(&blank, &[]);
+        deduplicator.accept(first);
+
+        let skipped = deduplicator.compare(&changed, &[]).difference;
+        assert_eq!(deduplicator.compare(&changed, &[]).difference, skipped);
+
+        let recorded = deduplicator.compare(&changed, &[]);
+        deduplicator.accept(recorded);
+        assert!(deduplicator.compare(&changed, &[]).difference < 1e-9);
+    }
+
+    #[test]
+    fn test_custom_differ_and_thresholds() {
+        struct AlwaysChanged;
+
+        impl FrameDiffer for AlwaysChanged {
+            fn signature(&self, _image: &DynamicImage) -> FrameSignature {
+                FrameSignature::Hash(vec![0])
+            }
+
+            fn difference(&self, _previous: &FrameSignature, _current: &FrameSignature) -> f64 {
+                0.5
+            }
+        }
+
+        let frame = DynamicImage::ImageRgba8(screen(None));
+        let mut deduplicator = FrameDeduplicator::with_differ(Box::new(AlwaysChanged), 0.6, false);
+        let first = deduplicator.compare(&frame, &[]);
+        deduplicator.accept(first);
+        assert!(deduplicator.is_duplicate(&deduplicator.compare(&frame, &[])));
+
+        let config = FrameDiffConfig {
+            algorithm: FrameDiffAlgorithm::PHash,
+            threshold: None,
+            per_window: false,
+        };
+        assert_eq!(config.threshold(), 0.03);
+        let config = FrameDiffConfig {
+            threshold: Some(0.1),
+            ..config
+        };
+        assert_eq!(config.threshold(), 0.1);
+
+        for algorithm in FrameDiffAlgorithm::ALL {
+            assert_eq!(algorithm.to_string().parse(), Ok(algorithm));
+        }
+        assert!("md5".parse::<FrameDiffAlgorithm>().is_err());
+    }
+}
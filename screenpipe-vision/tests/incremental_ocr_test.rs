@@ -0,0 +1,337 @@
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, GrayImage, Luma, Rgba, RgbaImage};
+    use screenpipe_vision::incremental_ocr::{
+        changed_regions, ChangedRegion, IncrementalOcr, IncrementalOcrConfig, OcrOutput, OcrPlan,
+    };
+    use screenpipe_vision::privacy::TextBoxUnits;
+    use screenpipe_vision::redaction::ScreenRegion;
+    use std::collections::HashMap;
+
+    const CLOCK: (u32, u32, u32, u32) = (300, 10, 80, 16);
+    const FIRST_LINE: (u32, u32, u32, u32) = (10, 50, 200, 16);
+    const SECOND_LINE: (u32, u32, u32, u32) = (10, 100, 200, 16);
+
+    fn block(
+        text: &str,
+        (left, top, width, height): (u32, u32, u32, u32),
+    ) -> HashMap<String, String> {
+        HashMap::from([
+            ("text".to_string(), text.to_string()),
+            ("left".to_string(), left.to_string()),
+            ("top".to_string(), top.to_string()),
+            ("width".to_string(), width.to_string()),
+            ("height".to_string(), height.to_string()),
+        ])
+    }
+
+    fn fill(image: &mut RgbaImage, (left, top, width, height): (u32, u32, u32, u32), value: u8) {
+        for y in top..top + height {
+            for x in left..left + width {
+                image.put_pixel(x, y, Rgba([value, value, value, 255]));
+            }
+        }
+    }
+
+    /// A 400x200 window with the three lines of text drawn as dark bars, the clock ending with
+    /// `minute`.
+    fn window(minute: u8) -> DynamicImage {
+        let mut image = RgbaImage::from_pixel(400, 200, Rgba([255, 255, 255, 255]));
+        for line in [CLOCK, FIRST_LINE, SECOND_LINE] {
+            fill(&mut image, line, 0);
+        }
+        fill(&mut image, (365, 12, 10, 12), minute);
+        DynamicImage::ImageRgba8(image)
+    }
+
+    fn output() -> OcrOutput {
+        OcrOutput {
+            text: "clock 12:01\nhello world\nsecond line".to_string(),
+            text_json: vec![
+                block("clock 12:01", CLOCK),
+                block("hello world", FIRST_LINE),
+                block("second line", SECOND_LINE),
+            ],
+            confidence: Some(90.0),
+        }
+    }
+
+    fn enabled() -> IncrementalOcrConfig {
+        IncrementalOcrConfig {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    fn clock_region() -> ChangedRegion {
+        ChangedRegion {
+            region: ScreenRegion {
+                x: 300,
+                y: 0,
+                width: 84,
+                height: 32,
+            },
+            crop: ScreenRegion {
+                x: 292,
+                y: 0,
+                width: 100,
+                height: 40,
+            },
+        }
+    }
+
+    #[test]
+    fn test_changed_regions() {
+        let previous = window(0).to_luma8();
+        assert!(changed_regions(&previous, &previous, 32).is_empty());
+
+        let current = window(200).to_luma8();
+        assert_eq!(
+            changed_regions(&previous, &current, 32),
+            vec![ScreenRegion {
+                x: 352,
+                y: 0,
+                width: 32,
+                height: 32,
+            }]
+        );
+
+        // slight noise isn't a change, separate changes are separate regions
+        let mut current = previous.clone();
+        current.put_pixel(100, 150, Luma([245]));
+        assert!(changed_regions(&previous, &current, 32).is_empty());
+        current.put_pixel(5, 5, Luma([0]));
+        current.put_pixel(399, 199, Luma([0]));
+        let regions = changed_regions(&previous, &current, 32);
+        assert_eq!(regions.len(), 2);
+        assert_eq!(
+            regions[1],
+            ScreenRegion {
+                x: 384,
+                y: 192,
+                width: 16,
+                height: 8,
+            }
+        );
+
+        let resized = GrayImage::new(200, 100);
+        assert_eq!(
+            changed_regions(&previous, &resized, 32),
+            vec![ScreenRegion::full(200, 100)]
+        );
+    }
+
+    #[test]
+    fn test_plan() {
+        let mut disabled = IncrementalOcr::new(IncrementalOcrConfig::default());
+        disabled.record("Clock", "main", &window(0), &output());
+        assert_eq!(
+            disabled.plan("Clock", "main", &window(0), TextBoxUnits::Pixels),
+            OcrPlan::Full
+        );
+
+        let mut incremental_ocr = IncrementalOcr::new(enabled());
+        assert_eq!(
+            incremental_ocr.plan("Clock", "main", &window(0), TextBoxUnits::Pixels),
+            OcrPlan::Full
+        );
+        incremental_ocr.record("Clock", "main", &window(0), &output());
+        assert_eq!(
+            incremental_ocr.plan("Clock", "main", &window(0), TextBoxUnits::Pixels),
+            OcrPlan::Unchanged(output())
+        );
+
+        // the changed tile grows to the whole line of the clock
+        assert_eq!(
+            incremental_ocr.plan("Clock", "main", &window(200), TextBoxUnits::Pixels),
+            OcrPlan::Regions(vec![clock_region()])
+        );
+
+        let mut inverted = window(0).to_rgba8();
+        image::imageops::invert(&mut inverted);
+        assert_eq!(
+            incremental_ocr.plan(
+                "Clock",
+                "main",
+                &DynamicImage::ImageRgba8(inverted),
+                TextBoxUnits::Pixels
+            ),
+            OcrPlan::Full
+        );
+        assert_eq!(
+            incremental_ocr.plan(
+                "Clock",
+                "main",
+                &DynamicImage::new_rgba8(200, 100),
+                TextBoxUnits::Pixels
+            ),
+            OcrPlan::Full
+        );
+
+        // text OCR couldn't locate
+        let mut unlocated = output();
+        unlocated.text_json[1].remove("left");
+        incremental_ocr.record("Clock", "main", &window(0), &unlocated);
+        assert_eq!(
+            incremental_ocr.plan("Clock", "main", &window(200), TextBoxUnits::Pixels),
+            OcrPlan::Full
+        );
+
+        incremental_ocr.retain_windows([("Terminal", "zsh")]);
+        assert_eq!(
+            incremental_ocr.plan("Clock", "main", &window(0), TextBoxUnits::Pixels),
+            OcrPlan::Full
+        );
+    }
+
+    #[test]
+    fn test_merge_and_snapshots() {
+        let mut incremental_ocr = IncrementalOcr::new(IncrementalOcrConfig {
+            snapshot_interval: 2,
+            ..enabled()
+        });
+        incremental_ocr.record("Clock", "main", &window(0), &output());
+
+        let region_output = OcrOutput {
+            text: "clock 12:02".to_string(),
+            text_json: vec![
+                block("clock 12:02", (8, 10, 80, 16)),
+                // cut at the bottom of the crop
+                block("hel", (0, 36, 40, 4)),
+            ],
+            confidence: Some(60.0),
+        };
+        let merged = incremental_ocr.merge(
+            "Clock",
+            "main",
+            &window(200),
+            vec![(clock_region(), region_output.clone())],
+            TextBoxUnits::Pixels,
+        );
+        assert_eq!(merged.text, "clock 12:02\nhello world\nsecond line");
+        assert_eq!(
+            merged.text_json,
+            vec![
+                block("clock 12:02", CLOCK),
+                block("hello world", FIRST_LINE),
+                block("second line", SECOND_LINE),
+            ]
+        );
+        let changed = (84.0 * 32.0) / (400.0 * 200.0);
+        let confidence = merged.confidence.unwrap();
+        assert!((confidence - (60.0 * changed + 90.0 * (1.0 - changed))).abs() < 1e-9);
+
+        // merged into what was last read
+        assert_eq!(
+            incremental_ocr.plan("Clock", "main", &window(200), TextBoxUnits::Pixels),
+            OcrPlan::Unchanged(merged)
+        );
+        assert!(matches!(
+            incremental_ocr.plan("Clock", "main", &window(0), TextBoxUnits::Pixels),
+            OcrPlan::Regions(_)
+        ));
+        incremental_ocr.merge(
+            "Clock",
+            "main",
+            &window(0),
+            vec![(clock_region(), region_output)],
+            TextBoxUnits::Pixels,
+        );
+        assert_eq!(
+            incremental_ocr.plan("Clock", "main", &window(200), TextBoxUnits::Pixels),
+            OcrPlan::Full
+        );
+    }
+
+    #[test]
+    fn test_merge_keeps_lines() {
+        let mut incremental_ocr = IncrementalOcr::new(enabled());
+        incremental_ocr.record("Clock", "main", &window(0), &output());
+
+        // words of one line read a few pixels apart vertically
+        let merged = incremental_ocr.merge(
+            "Clock",
+            "main",
+            &window(200),
+            vec![(
+                clock_region(),
+                OcrOutput {
+                    text: "clock 12:02".to_string(),
+                    text_json: vec![
+                        block("12:02", (58, 12, 30, 16)),
+                        block("clock", (8, 10, 45, 16)),
+                    ],
+                    confidence: Some(90.0),
+                },
+            )],
+            TextBoxUnits::Pixels,
+        );
+        assert_eq!(merged.text, "clock 12:02\nhello world\nsecond line");
+        assert_eq!(merged.text_json[0]["text"], "clock");
+        assert_eq!(merged.text_json[1]["text"], "12:02");
+    }
+
+    #[test]
+    fn test_merge_normalized_boxes() {
+        // apple vision positions blocks in fractions of the image, from its bottom left corner
+        let normalized = |text: &str, left: f64, top: f64, width: f64, height: f64| {
+            HashMap::from([
+                ("text".to_string(), text.to_string()),
+                ("left".to_string(), left.to_string()),
+                ("top".to_string(), top.to_string()),
+                ("width".to_string(), width.to_string()),
+                ("height".to_string(), height.to_string()),
+            ])
+        };
+        let mut incremental_ocr = IncrementalOcr::new(enabled());
+        incremental_ocr.record(
+            "Clock",
+            "main",
+            &window(0),
+            &OcrOutput {
+                text: "clock 12:01\nhello world".to_string(),
+                text_json: vec![
+                    normalized("clock 12:01", 0.75, 0.87, 0.2, 0.08),
+                    normalized("hello world", 0.025, 0.67, 0.5, 0.08),
+                ],
+                confidence: Some(1.0),
+            },
+        );
+        assert_eq!(
+            incremental_ocr.plan(
+                "Clock",
+                "main",
+                &window(200),
+                TextBoxUnits::NormalizedFromBottom
+            ),
+            OcrPlan::Regions(vec![clock_region()])
+        );
+
+        let merged = incremental_ocr.merge(
+            "Clock",
+            "main",
+            &window(200),
+            vec![(
+                clock_region(),
+                OcrOutput {
+                    text: "clock 12:02".to_string(),
+                    text_json: vec![normalized("clock 12:02", 0.08, 0.35, 0.8, 0.4)],
+                    confidence: Some(1.0),
+                },
+            )],
+            TextBoxUnits::NormalizedFromBottom,
+        );
+        assert_eq!(merged.text, "clock 12:02\nhello world");
+        let clock = &merged.text_json[0];
+        for (key, expected) in [
+            ("left", 0.75),
+            ("top", 0.87),
+            ("width", 0.2),
+            ("height", 0.08),
+        ] {
+            let value: f64 = clock[key].parse().unwrap();
+            assert!((value - expected).abs() < 1e-9, "{}: {}", key, value);
+        }
+        assert_eq!(merged.confidence, Some(1.0));
+    }
+}
@@ -0,0 +1,187 @@
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba, RgbaImage};
+    use screenpipe_vision::capture_screenshot_by_window::{
+        redaction_regions, VisibleWindow, WindowFilters, WindowPattern,
+    };
+    use screenpipe_vision::redaction::{redact_regions, ScreenRegion, WindowRedaction};
+
+    fn filters(ignore: &[&str], include: &[&str]) -> WindowFilters {
+        let strings =
+            |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        WindowFilters::new(&strings(ignore), &strings(include))
+    }
+
+    #[test]
+    fn test_ignore_takes_precedence_over_include() {
+        let no_filters = filters(&[], &[]);
+        assert!(no_filters.is_valid("Safari", "Apple"));
+
+        // used to be recorded because the ignore list was never checked without an include list
+        let ignore_only = filters(&["bitwarden"], &[]);
+        assert!(!ignore_only.is_valid("Bitwarden", "Vault"));
+        assert!(ignore_only.is_valid("Safari", "Apple"));
+
+        let both = filters(&["private"], &["chrome"]);
+        assert!(both.is_valid("Google Chrome", "Inbox"));
+        assert!(!both.is_valid("Google Chrome", "New Private Window"));
+        assert!(!both.is_valid("Safari", "Inbox"));
+    }
+
+    #[test]
+    fn test_glob_and_regex_patterns() {
+        let glob = filters(&["glob:*pass?ord*"], &[]);
+        assert!(!glob.is_valid("1Password", "Vault"));
+        assert!(!glob.is_valid("Safari", "Reset your PASSWORD"));
+        assert!(glob.is_valid("Safari", "Passport renewal"));
+
+        // globs match the whole name, plain text anywhere in it
+        let anchored = filters(&["glob:code"], &[]);
+        assert!(!anchored.is_valid("Code", "main.rs"));
+        assert!(anchored.is_valid("Visual Studio Code", "main.rs"));
+
+        let regex = filters(&["regex:^(1password|bitwarden)$"], &[]);
+        assert!(!regex.is_valid("Bitwarden", ""));
+        assert!(regex.is_valid("Bitwarden Helper", ""));
+
+        // characters special to regexes are literal in plain text and globs
+        assert!(!filters(&["(1)"], &[]).is_valid("Inbox (1)", ""));
+        assert!(!filters(&["glob:inbox (?)"], &[]).is_valid("Inbox (1)", ""));
+
+        assert!("regex:(unclosed".parse::<WindowPattern>().is_err());
+        assert!("(unclosed".parse::<WindowPattern>().is_ok());
+    }
+
+    #[test]
+    fn test_screen_region_mapping() {
+        // window on a 2x HiDPI monitor placed right of the primary one
+        let region = ScreenRegion::from_screen_bounds(
+            (1540, 100, 200, 50),
+            (1440, 0),
+            (1440, 900),
+            (2880, 1800),
+        );
+        assert_eq!(
+            region,
+            Some(ScreenRegion {
+                x: 200,
+                y: 200,
+                width: 400,
+                height: 100
+            })
+        );
+
+        // clipped to the monitor
+        let partly_off =
+            ScreenRegion::from_screen_bounds((-50, -50, 100, 100), (0, 0), (100, 100), (100, 100));
+        assert_eq!(
+            partly_off,
+            Some(ScreenRegion {
+                x: 0,
+                y: 0,
+                width: 50,
+                height: 50
+            })
+        );
+
+        let other_monitor = ScreenRegion::from_screen_bounds(
+            (1540, 100, 200, 50),
+            (0, 0),
+            (1440, 900),
+            (1440, 900),
+        );
+        assert_eq!(other_monitor, None);
+    }
+
+    #[test]
+    fn test_redaction_fails_closed() {
+        let filters = filters(&["bitwarden"], &[]);
+        let window = |app_name: Option<&str>, title: Option<&str>, bounds| VisibleWindow {
+            app_name: app_name.map(str::to_string),
+            window_name: title.map(str::to_string),
+            bounds,
+            is_minimized: false,
+        };
+        let regions = |windows: Option<&[VisibleWindow]>| {
+            redaction_regions(windows, (0, 0), (100, 100), (200, 200), &filters)
+        };
+        let whole_frame = vec![ScreenRegion::full(200, 200)];
+
+        let recorded = window(Some("Safari"), Some("Inbox"), Some((0, 0, 50, 50)));
+        assert!(regions(Some(&[recorded.clone()])).is_empty());
+
+        let filtered = window(Some("Bitwarden"), Some("Vault"), Some((10, 10, 20, 20)));
+        let filtered_region = ScreenRegion {
+            x: 20,
+            y: 20,
+            width: 40,
+            height: 40,
+        };
+        assert_eq!(
+            regions(Some(&[recorded.clone(), filtered])),
+            vec![filtered_region]
+        );
+
+        // a window whose name can't be read may be a filtered one
+        let unnamed = window(Some("Bitwarden"), None, Some((10, 10, 20, 20)));
+        assert_eq!(regions(Some(&[unnamed])), vec![filtered_region]);
+        let unnamed = window(None, None, Some((10, 10, 20, 20)));
+        assert_eq!(regions(Some(&[unnamed])), vec![filtered_region]);
+
+        // nowhere to paint over, so everything is
+        let unplaced = window(Some("Bitwarden"), Some("Vault"), None);
+        assert_eq!(regions(Some(&[recorded, unplaced])), whole_frame);
+        assert_eq!(regions(None), whole_frame);
+
+        let minimized = VisibleWindow {
+            is_minimized: true,
+            ..window(Some("Bitwarden"), Some("Vault"), None)
+        };
+        assert!(regions(Some(&[minimized])).is_empty());
+    }
+
+    #[test]
+    fn test_redact_regions() {
+        // checkerboard, a blurred area of it averages to grey
+        let checkerboard = DynamicImage::ImageRgba8(RgbaImage::from_fn(200, 100, |x, y| {
+            if (x + y) % 2 == 0 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        }));
+        let region = ScreenRegion {
+            x: 50,
+            y: 20,
+            width: 100,
+            height: 60,
+        };
+
+        let mut black = checkerboard.clone();
+        redact_regions(&mut black, &[region], WindowRedaction::Black);
+        let black = black.to_rgba8();
+        assert_eq!(black.get_pixel(50, 20), &Rgba([0, 0, 0, 255]));
+        assert_eq!(black.get_pixel(149, 79), &Rgba([0, 0, 0, 255]));
+        assert_eq!(black.get_pixel(150, 80), &Rgba([255, 255, 255, 255]));
+        assert_eq!(black.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+
+        let mut blurred = checkerboard.clone();
+        redact_regions(&mut blurred, &[region], WindowRedaction::Blur);
+        let blurred = blurred.to_rgba8();
+        for (x, y) in [(60, 30), (100, 50), (140, 70)] {
+            let value = blurred.get_pixel(x, y)[0];
+            assert!(
+                (96..=160).contains(&value),
+                "pixel {},{} is {}",
+                x,
+                y,
+                value
+            );
+        }
+        assert_eq!(blurred.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+
+        let mut untouched = checkerboard.clone();
+        redact_regions(&mut untouched, &[region], WindowRedaction::Off);
+        assert_eq!(untouched, checkerboard);
+    }
+}
@@ -1,9 +1,13 @@
 #[cfg(target_os = "windows")]
 #[cfg(test)]
 mod tests {
+    use screenpipe_vision::capture_rate::CaptureRate;
     use screenpipe_vision::capture_screenshot_by_window::{CapturedWindow, WindowFilters};
     use screenpipe_vision::core::OcrTaskData;
+    use screenpipe_vision::frame_diff::FrameDiffConfig;
+    use screenpipe_vision::incremental_ocr::{IncrementalOcr, IncrementalOcrConfig};
     use screenpipe_vision::monitor::get_default_monitor;
+    use screenpipe_vision::privacy::PrivacyFilter;
     use screenpipe_vision::{process_ocr_task, OcrEngine};
     use std::sync::Arc;
     use std::{path::PathBuf, time::Instant};
@@ -35,18 +39,22 @@ mod tests {
             image,
             is_focused: true,
             process_id: 1234,
+            frame_position: None,
         }];
 
         let result = process_ocr_task(
             OcrTaskData {
                 image: image_arc,
                 window_images,
+                visible_windows: vec![],
                 frame_number,
                 timestamp,
                 result_tx: tx,
+                privacy_filter: Arc::new(PrivacyFilter::default()),
             },
             &ocr_engine,
             vec![],
+            &mut IncrementalOcr::new(IncrementalOcrConfig::default()),
         )
         .await;
 
@@ -64,7 +72,7 @@ mod tests {
         let monitor = get_default_monitor().await.id();
 
         // Set up test parameters
-        let interval = Duration::from_millis(1000);
+        let capture_rate = Arc::new(CaptureRate::fixed(1.0));
         let save_text_files_flag = false;
         let ocr_engine = OcrEngine::WindowsNative;
         let window_filters = Arc::new(WindowFilters::new(&[], &[]));
@@ -72,11 +80,14 @@ mod tests {
         // Spawn the continuous_capture function with corrected parameter order
         let capture_handle = tokio::spawn(continuous_capture(
             result_tx,
-            interval,
+            capture_rate,
             ocr_engine,
             monitor,
             window_filters, // window filters as empty vec
-            vec![],         // languages as empty vec
+            Arc::new(PrivacyFilter::default()),
+            FrameDiffConfig::default(),
+            IncrementalOcrConfig::default(),
+            vec![], // languages as empty vec
             save_text_files_flag,
         ));
 